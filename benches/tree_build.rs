@@ -0,0 +1,81 @@
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use broot::{
+    task_sync::TaskLifetime,
+    tree_build::TreeBuilder,
+    tree_options::TreeOptions,
+    tree_sources::{EntryMetadata, FsSource, SourceEntry, TreeSource},
+};
+
+// the benched tree has 3 levels of 8 subdirectories per directory,
+// each directory containing 8 files: 584 directories and 4,680 files
+static WIDTH: usize = 8;
+static DEPTH: usize = 3;
+
+// the latency added to the reading of a directory, to simulate
+// a network mount
+static LATENCY: Duration = Duration::from_millis(2);
+
+/// a file system reached through a slow network
+#[derive(Debug)]
+struct SlowSource;
+
+impl TreeSource for SlowSource {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<SourceEntry>> {
+        thread::sleep(LATENCY);
+        FsSource.read_dir(dir)
+    }
+    fn metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        FsSource.metadata(path)
+    }
+    fn symlink_metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        FsSource.symlink_metadata(path)
+    }
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        FsSource.read_link(path)
+    }
+}
+
+fn create_tree(dir: &Path, depth: usize) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for i in 0..WIDTH {
+        fs::write(dir.join(format!("file-{}.txt", i)), "")?;
+        if depth > 0 {
+            create_tree(&dir.join(format!("dir-{}", i)), depth - 1)?;
+        }
+    }
+    Ok(())
+}
+
+fn build(root: &Path, source: Arc<dyn TreeSource>) {
+    let mut options = TreeOptions::default();
+    options.show_all = true;
+    options.source = source;
+    let builder = TreeBuilder::from(root.to_path_buf(), options, 0).unwrap();
+    black_box(builder.build(&TaskLifetime::unlimited()));
+}
+
+fn tree_build_benchmark(c: &mut Criterion) {
+    let root = env::temp_dir().join("broot-bench-tree");
+    if !root.exists() {
+        create_tree(&root, DEPTH).unwrap();
+    }
+    c.bench_function("TreeBuilder::build (local)", |b| {
+        b.iter(|| build(&root, Arc::new(FsSource)));
+    });
+    c.bench_function("TreeBuilder::build (slow source)", |b| {
+        b.iter(|| build(&root, Arc::new(SlowSource)));
+    });
+}
+
+criterion_group!(benches, tree_build_benchmark);
+criterion_main!(benches);
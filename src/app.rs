@@ -7,9 +7,18 @@
 //! - an operation which keeps the state
 //! - a request to quit broot
 //! - a request to launch an executable (thus leaving broot)
+//!
+//! When broot listens on a socket, the requests of other programs
+//! come as events and are applied the same way.
 
 use {
-    std::io::Write,
+    std::{
+        fs,
+        io::Write,
+        path::{Path, PathBuf},
+        sync::{atomic::AtomicUsize, Arc},
+        thread,
+    },
     crossterm::{
         cursor,
         input::{DisableMouseCapture, EnableMouseCapture},
@@ -20,25 +29,87 @@ use {
     termimad::EventSource,
     crate::{
         app_context::AppContext,
+        app_events::{AppEvent, AppEvents},
         app_state::{AppStateCmdResult, AppState},
         browser_states::BrowserState,
         command_parsing::parse_command_sequence,
-        commands::Command,
-        errors::ProgramError,
-        external::Launchable,
+        commands::{Action, Command},
+        conf::Conf,
+        conf_watcher::{ConfWatcher, CONF_POLL_PERIOD},
+        dir_counts,
+        dir_prelisting::{PrelistRequest, Prelister},
+        errors::{ConfError, ProgramError},
+        external::{self, Launchable},
         file_sizes,
+        filesystems,
+        fs_watcher::{FsWatcher, FS_POLL_PERIOD},
+        git_ignore,
+        image_preview,
+        input_history,
         io::W,
-        screens::Screen,
+        jobs::{self, JOBS_POLL_PERIOD},
+        marks,
+        path_case,
+        project_conf::ProjectConf,
+        screens::{Screen, SIZE_POLL_PERIOD},
+        server::Request,
+        session::{Autosaver, Session, AUTOSAVE_PERIOD},
         skin::Skin,
+        state_history::{Place, StateHistory},
         status::Status,
         task_sync::TaskLifetime,
+        verb_completion,
+        verb_store::VerbStore,
+        verbs::VerbExecution,
+        xattrs,
     }
 };
 
+/// what applying a command gives, before it's drawn
+pub(crate) struct CommandOutcome {
+    pub cmd: Command, // the command to keep in the input
+    pub error: Option<String>,
+    pub message: Option<String>,
+}
+
+/// forget what was read of the disk, so that a refresh sees the changes
+fn clear_caches(con: &AppContext) {
+    file_sizes::clear_cache();
+    dir_counts::clear_cache();
+    filesystems::clear_cache();
+    git_ignore::clear_cache();
+    xattrs::clear_cache();
+    con.launch_args.tree_options.source.clear_cache();
+}
+
+/// read the configuration file again, with the project configuration
+/// merged over it, and apply it to the context as it was at launch.
+/// The configuration is returned for its skin
+fn reload_context(con: &mut AppContext, conf_path: &Path) -> Result<Conf, ConfError> {
+    let mut conf = Conf::from_file(conf_path)?;
+    // the project configuration, if any, was already trusted at launch
+    if con.launch_args.tree_options.source.is_local() {
+        if let Ok(Some(project_conf)) = ProjectConf::load(&con.launch_args.root, false) {
+            project_conf.apply(&mut conf, &mut con.launch_args.tree_options)?;
+        }
+    }
+    let mut verb_store = VerbStore::new();
+    verb_store.init(&conf);
+    con.verb_store = verb_store;
+    path_case::set_case_insensitive(conf.case_insensitive_paths);
+    con.launch_args.apply_conf(&conf);
+    Ok(conf)
+}
+
 pub struct App {
     states: Vec<Box<dyn AppState>>, // stack: the last one is current
+    forward_states: Vec<Box<dyn AppState>>, // the trees left by going back, the last one first
+    history: StateHistory, // the places left, for `:undo` and `:redo`
     quitting: bool,
+    quit_asked: bool, // whether the previous command was a quit waiting for confirmation
+    confirmation: Option<Box<VerbExecution>>, // the execution of a verb waiting for the user's `y`
     launch_at_end: Option<Launchable>, // what must be launched after end
+    event_count: Arc<AtomicUsize>, // changes with each user event, interrupting the tasks
 }
 
 impl App {
@@ -46,8 +117,13 @@ impl App {
     pub fn new() -> App {
         App {
             states: Vec::new(),
+            forward_states: Vec::new(),
+            history: StateHistory::new(),
             quitting: false,
+            quit_asked: false,
+            confirmation: None,
             launch_at_end: None,
+            event_count: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -55,13 +131,122 @@ impl App {
         self.states.push(new_state);
     }
 
-    fn mut_state(&mut self) -> &mut Box<dyn AppState> {
+    /// the number of stacked states
+    pub(crate) fn depth(&self) -> usize {
+        self.states.len()
+    }
+
+    pub(crate) fn is_quitting(&self) -> bool {
+        self.quitting
+    }
+
+    /// the program to launch on quitting, if any
+    pub(crate) fn launchable(&self) -> Option<&Launchable> {
+        self.launch_at_end.as_ref()
+    }
+
+    /// the execution of a verb waiting for the user to confirm it, if any
+    pub(crate) fn confirmation(&self) -> Option<&VerbExecution> {
+        self.confirmation.as_ref().map(|execution| execution.as_ref())
+    }
+
+    /// remove the current state, keeping it for `:forward`
+    /// if it displays a tree. Return whether it did
+    fn pop_to_forward(&mut self) -> bool {
+        if let Some(state) = self.states.pop() {
+            if state.tree().is_some() {
+                self.forward_states.push(state);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// replace the current state with one displaying the place
+    /// of the state history, and return the command showing its pattern.
+    /// The tree build is interrupted by the next user event (the
+    /// search of the pattern being, as usual, a pending task)
+    fn go_to_place(&mut self, place: Place, screen: &Screen) -> Result<Command, String> {
+        let cmd = Command::from_pattern(&place.options.pattern);
+        let tl = TaskLifetime::new(Arc::clone(&self.event_count));
+        match BrowserState::new(place.root, place.options, screen, &tl) {
+            Ok(Some(mut bs)) => {
+                if let Some(selection) = &place.selection {
+                    let page_height = BrowserState::page_height(screen, &bs.tree.options);
+                    bs.tree.try_select_path(selection);
+                    bs.tree.make_selection_visible(page_height);
+                }
+                self.states.pop();
+                self.push(Box::new(bs));
+                Ok(cmd)
+            }
+            Ok(None) => Err("tree build interrupted".to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// quit, unless a confirmation is required and this isn't
+    /// the second consecutive quit. In that case, return the
+    /// error to display
+    fn try_quit(&mut self, con: &AppContext, confirmed: bool) -> Option<String> {
+        if con.launch_args.confirm_quit && !confirmed {
+            let marked = marks::count();
+            let reason = if self.states.iter().any(|s| s.has_pending_task()) {
+                Some("A task is still running".to_string())
+            } else if jobs::running_count() > 0 {
+                Some("A job is still running".to_string())
+            } else if marked > 0 {
+                let s = if marked == 1 { "" } else { "s" };
+                Some(format!("{} file{} marked", marked, s))
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                self.quit_asked = true;
+                return Some(format!("{}: quit again to confirm", reason));
+            }
+        }
+        self.quitting = true;
+        None
+    }
+
+    pub(crate) fn mut_state(&mut self) -> &mut Box<dyn AppState> {
         self.states.last_mut().expect("No path has been pushed")
     }
-    fn state(&self) -> &Box<dyn AppState> {
+    pub(crate) fn state(&self) -> &Box<dyn AppState> {
         self.states.last().expect("No path has been pushed")
     }
 
+    /// write the status of the current state, or the question
+    /// of the confirmation waiting for an answer
+    fn write_status(
+        &self,
+        w: &mut W,
+        cmd: &Command,
+        screen: &Screen,
+        con: &AppContext,
+    ) -> Result<(), ProgramError> {
+        if let Some(execution) = &self.confirmation {
+            let command = execution.command();
+            Status::from_message(mad_inline!(
+                "Hit *y* to execute `$0`, any other key to cancel",
+                &command,
+            )).display(w, screen)
+        } else {
+            self.state().write_status(w, cmd, screen, con)
+        }
+    }
+
+    /// return the session of the topmost state having one
+    fn session(&self) -> Option<Session> {
+        self.states.iter().rev().find_map(|s| s.session())
+    }
+
+    /// return the place of the topmost state displaying a tree
+    fn place(&self) -> Option<Place> {
+        self.states.iter().rev().find_map(|s| s.place())
+    }
+
     /// execute all the pending tasks until there's none remaining or
     ///  the allowed lifetime is expired (usually when the user typed a new key)
     fn do_pending_tasks(
@@ -79,53 +264,134 @@ impl App {
             }
             self.mut_state().do_pending_task(screen, &tl);
             self.mut_state().display(w, screen, con)?;
-            self.state().write_status(w, cmd, &screen, con)?;
+            self.write_status(w, cmd, &screen, con)?;
+            verb_completion::draw_popup(w, cmd, screen, con)?;
             has_pending_task = self.state().has_pending_task();
         }
         Ok(())
     }
 
-    /// apply a command, and returns a command, which may be the same (modified or not)
-    ///  or a new one.
-    /// This normally mutates self
-    fn apply_command(
+    /// apply a command to the states, without drawing anything, and
+    /// return the command to keep in the input with the error or the
+    /// message to display.
+    /// This is the part of the application the test harness plays.
+    pub(crate) fn apply_command_to_states(
         &mut self,
-        w: &mut W,
         cmd: Command,
         screen: &mut Screen,
         con: &AppContext,
-    ) -> Result<Command, ProgramError> {
+    ) -> Result<CommandOutcome, ProgramError> {
         let mut cmd = cmd;
         debug!("action: {:?}", &cmd.action);
-        screen.read_size(con)?;
         let mut error: Option<String> = None;
-        let cmd_result = self.mut_state().apply(&mut cmd, screen, con)?;
+        let mut message: Option<String> = None;
+        let quit_asked = self.quit_asked;
+        self.quit_asked = false;
+        let place_before = self.place();
+        let mut place_left = false; // whether the command went to another tree
+        let answer = match cmd.action {
+            Action::Confirm(confirmed) => Some(confirmed),
+            _ => None,
+        };
+        let cmd_result = match (answer, self.confirmation.take()) {
+            (Some(true), Some(execution)) => {
+                if execution.may_change_files() {
+                    self.mut_state().before_confirmed_execution();
+                }
+                self.mut_state().execute_confirmed(&execution, screen, con)?
+            }
+            (Some(_), _) => AppStateCmdResult::DisplayMessage("Cancelled".to_string()),
+            (None, Some(execution)) => {
+                // the event doesn't answer the question (eg a click)
+                self.confirmation = Some(execution);
+                AppStateCmdResult::Keep
+            }
+            (None, None) => self.mut_state().apply(&mut cmd, screen, con)?,
+        };
+        let undoing = match cmd_result {
+            AppStateCmdResult::Undo | AppStateCmdResult::Redo => true,
+            _ => false,
+        };
         match cmd_result {
             AppStateCmdResult::Quit => {
                 debug!("cmd result quit");
-                self.quitting = true;
+                error = self.try_quit(con, quit_asked);
             }
             AppStateCmdResult::Launch(launchable) => {
                 self.launch_at_end = Some(*launchable);
                 self.quitting = true;
             }
             AppStateCmdResult::NewState(boxed_state, new_cmd) => {
+                if boxed_state.tree().is_some() {
+                    // as in a browser, a new tree replaces the forward history
+                    self.forward_states.clear();
+                    place_left = true;
+                }
                 self.push(boxed_state);
                 cmd = new_cmd;
             }
             AppStateCmdResult::RefreshState => {
-                file_sizes::clear_cache();
+                clear_caches(con);
                 cmd = self.mut_state().refresh(screen, con);
             }
             AppStateCmdResult::PopState => {
                 if self.states.len() == 1 {
                     debug!("quitting on last pop state");
-                    self.quitting = true;
+                    error = self.try_quit(con, quit_asked);
                 } else {
-                    self.states.pop();
+                    place_left = self.pop_to_forward();
                     cmd = self.mut_state().refresh(screen, con);
                 }
             }
+            AppStateCmdResult::Back => {
+                if self.states.len() == 1 {
+                    error = Some("no previous state".to_string());
+                } else {
+                    place_left = self.pop_to_forward();
+                    cmd = self.mut_state().refresh(screen, con);
+                }
+            }
+            AppStateCmdResult::Forward => {
+                if let Some(state) = self.forward_states.pop() {
+                    self.push(state);
+                    place_left = true;
+                    cmd = self.mut_state().refresh(screen, con);
+                } else {
+                    error = Some("no next state".to_string());
+                }
+            }
+            AppStateCmdResult::Undo => {
+                let target = place_before.clone().and_then(|current| self.history.undo(current));
+                if let Some(target) = target {
+                    match self.go_to_place(target.clone(), screen) {
+                        Ok(new_cmd) => {
+                            cmd = new_cmd;
+                        }
+                        Err(e) => {
+                            self.history.redo(target); // the history is put back as it was
+                            error = Some(e);
+                        }
+                    }
+                } else {
+                    error = Some("nothing to undo".to_string());
+                }
+            }
+            AppStateCmdResult::Redo => {
+                let target = place_before.clone().and_then(|current| self.history.redo(current));
+                if let Some(target) = target {
+                    match self.go_to_place(target.clone(), screen) {
+                        Ok(new_cmd) => {
+                            cmd = new_cmd;
+                        }
+                        Err(e) => {
+                            self.history.undo(target);
+                            error = Some(e);
+                        }
+                    }
+                } else {
+                    error = Some("nothing to redo".to_string());
+                }
+            }
             AppStateCmdResult::PopStateAndReapply => {
                 if self.states.len() == 1 {
                     debug!("quitting on last pop state");
@@ -133,20 +399,60 @@ impl App {
                 } else {
                     self.states.pop();
                     debug!("about to reapply {:?}", &cmd);
-                    return self.apply_command(w, cmd, screen, con);
+                    return self.apply_command_to_states(cmd, screen, con);
                 }
             }
             AppStateCmdResult::DisplayError(txt) => {
-                error = Some(txt.clone());
+                error = Some(txt);
             }
-            _ => {}
+            AppStateCmdResult::DisplayMessage(txt) => {
+                message = Some(txt);
+            }
+            AppStateCmdResult::AskConfirmation(execution) => {
+                self.confirmation = Some(execution);
+            }
+            AppStateCmdResult::Keep => {}
+        }
+        if self.confirmation.is_some() {
+            // the next key, whatever the command, answers the question
+            cmd.ask_confirmation();
         }
+        if let (Some(place_before), false) = (place_before, undoing) {
+            let pattern = self.place().map(|p| p.raw_pattern()).unwrap_or_default();
+            if place_left || (pattern.is_empty() && !place_before.raw_pattern().is_empty()) {
+                // clearing the pattern, for example with esc, isn't part of its edition
+                self.history.record(place_before);
+            } else if pattern != place_before.raw_pattern() {
+                self.history.record_pattern_edit(place_before);
+            } else {
+                self.history.end_pattern_edit();
+            }
+        }
+        Ok(CommandOutcome { cmd, error, message })
+    }
+
+    /// apply a command, draw the result, and return the command,
+    /// which may be the same (modified or not) or a new one
+    fn apply_command(
+        &mut self,
+        w: &mut W,
+        cmd: Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<Command, ProgramError> {
+        screen.read_size(con)?;
+        let CommandOutcome { cmd, error, message } = self.apply_command_to_states(cmd, screen, con)?;
+        // the images of a preview aren't erased by the text drawn over them
+        image_preview::erase_images(w)?;
         self.mut_state().display(w, screen, con)?;
         if let Some(text) = error {
             let status = Status::from_error(Composite::from_inline(&text));
             status.display(w, screen)?;
+        } else if let Some(text) = message {
+            Status::from_message(Composite::from_inline(&text)).display(w, screen)?;
         } else {
-            self.state().write_status(w, &cmd, screen, con)?;
+            self.write_status(w, &cmd, screen, con)?;
+            verb_completion::draw_popup(w, &cmd, screen, con)?;
         }
         screen.input_field.set_content(&cmd.raw);
         screen.input_field.display_on(w)?;
@@ -154,10 +460,231 @@ impl App {
         Ok(cmd)
     }
 
+    /// apply a request of another program, and return the reply
+    /// to send back to it
+    fn apply_request(
+        &mut self,
+        w: &mut W,
+        request: Request,
+        cmd: &mut Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<String, ProgramError> {
+        debug!("remote request: {:?}", &request);
+        if self.confirmation.is_some() {
+            return Ok("error: a confirmation is pending".to_string());
+        }
+        Ok(match request {
+            Request::GetSelection => self
+                .state()
+                .tree()
+                .map(|tree| tree.selected_line().path.to_path_buf().to_string_lossy().to_string())
+                .unwrap_or_default(),
+            Request::Command(commands) => match parse_command_sequence(
+                &commands,
+                con.launch_args.cmd_separator.as_deref(),
+                con,
+            ) {
+                Ok(commands) => {
+                    for remote_cmd in commands {
+                        *cmd = self.apply_command(w, remote_cmd, screen, con)?;
+                        self.do_pending_tasks(w, cmd, screen, con, TaskLifetime::unlimited())?;
+                        if self.quitting {
+                            break;
+                        }
+                    }
+                    "ok".to_string()
+                }
+                Err(e) => format!("error: {}", e),
+            },
+            Request::Root(path) => {
+                let options = match self.state().tree() {
+                    Some(tree) => tree.options.without_pattern(),
+                    None => con.launch_args.tree_options.clone(),
+                };
+                match BrowserState::new(path, options, screen, &TaskLifetime::unlimited()) {
+                    Ok(Some(bs)) => {
+                        self.forward_states.clear();
+                        if let Some(place) = self.place() {
+                            self.history.record(place);
+                        }
+                        self.push(Box::new(bs));
+                        *cmd = Command::new();
+                        self.mut_state().display(w, screen, con)?;
+                        self.write_status(w, cmd, screen, con)?;
+                        screen.input_field.set_content(&cmd.raw);
+                        screen.input_field.display_on(w)?;
+                        self.state().write_flags(w, screen, con)?;
+                        "ok".to_string()
+                    }
+                    Ok(None) => "error: tree build interrupted".to_string(),
+                    Err(e) => format!("error: {}", e),
+                }
+            }
+        })
+    }
+
+    /// return the directories whose changes must lead to a refresh:
+    /// the ones displayed in the current tree, if files must be watched
+    fn watched_dirs(&self, con: &AppContext) -> Vec<PathBuf> {
+        if !con.launch_args.auto_refresh
+            || con.launch_args.snapshot.is_some()
+            || !con.launch_args.tree_options.source.is_local()
+        {
+            return Vec::new();
+        }
+        match self.state().tree() {
+            Some(tree) => tree
+                .lines
+                .iter()
+                .filter(|line| line.is_dir() && !line.metadata.in_archive)
+                .map(|line| line.target())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// return the directories to list in background, the ones
+    /// the user is the most likely to open from the current tree
+    fn prelist_request(&self, con: &AppContext) -> PrelistRequest {
+        if con.launch_args.snapshot.is_some() || !con.launch_args.tree_options.source.is_local() {
+            return PrelistRequest::default();
+        }
+        match self.state().tree() {
+            Some(tree) => PrelistRequest::of(tree),
+            None => PrelistRequest::default(),
+        }
+    }
+
+    /// rebuild the current tree after files were added, removed or
+    /// renamed, the input being kept as it is
+    fn refresh_on_files_change(
+        &mut self,
+        w: &mut W,
+        cmd: &Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<(), ProgramError> {
+        if !con.launch_args.auto_refresh || self.state().tree().is_none() {
+            return Ok(());
+        }
+        debug!("refreshing after a change of the files");
+        self.refresh_and_redraw(w, cmd, screen, con)
+    }
+
+    /// called when a background job ended: the job may have changed
+    /// the files, and the jobs indicator must be updated
+    fn refresh_on_jobs_change(
+        &mut self,
+        w: &mut W,
+        cmd: &Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<(), ProgramError> {
+        debug!("refreshing after the end of a job");
+        self.refresh_and_redraw(w, cmd, screen, con)
+    }
+
+    /// wait for the end of the background jobs, refreshing the
+    /// current state when one ends (used when executing the commands
+    /// given on launch, so that the next one sees the changes)
+    fn wait_for_jobs(
+        &mut self,
+        w: &mut W,
+        cmd: &Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<(), ProgramError> {
+        while jobs::running_count() > 0 {
+            thread::sleep(JOBS_POLL_PERIOD);
+            if jobs::poll() {
+                self.refresh_on_jobs_change(w, cmd, screen, con)?;
+                self.do_pending_tasks(w, cmd, screen, con, TaskLifetime::unlimited())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// clear the caches, refresh the current state then redraw it
+    fn refresh_and_redraw(
+        &mut self,
+        w: &mut W,
+        cmd: &Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<(), ProgramError> {
+        clear_caches(con);
+        self.mut_state().refresh(screen, con);
+        self.mut_state().display(w, screen, con)?;
+        self.write_status(w, cmd, screen, con)?;
+        screen.input_field.display_on(w)?;
+        self.state().write_flags(w, screen, con)?;
+        Ok(())
+    }
+
+    /// read the new size of the terminal, then rebuild the current
+    /// state for it (a tree has as many lines as the screen can show)
+    /// and redraw everything
+    fn resize_and_redraw(
+        &mut self,
+        w: &mut W,
+        cmd: &Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<(), ProgramError> {
+        screen.read_size(con)?;
+        screen.clear(w)?;
+        image_preview::erase_images(w)?;
+        self.mut_state().refresh(screen, con);
+        self.mut_state().display(w, screen, con)?;
+        self.write_status(w, cmd, screen, con)?;
+        verb_completion::draw_popup(w, cmd, screen, con)?;
+        screen.input_field.display_on(w)?;
+        self.state().write_flags(w, screen, con)?;
+        Ok(())
+    }
+
+    /// replace the verbs and the skin with the ones of the configuration
+    /// file, then redraw everything.
+    /// If the file can't be read, the current configuration is kept.
+    fn reload_conf(
+        &mut self,
+        w: &mut W,
+        screen: &mut Screen,
+        con: &mut AppContext,
+        conf_path: &Path,
+    ) -> Result<(), ProgramError> {
+        info!("reloading configuration from {:?}", conf_path);
+        match reload_context(con, conf_path) {
+            Ok(conf) => {
+                let mut skin = Skin::create(conf.skin, conf.file_styles);
+                skin.degrade_colors(con.launch_args.color_support);
+                screen.set_skin(skin);
+                screen.clear(w)?;
+                self.mut_state().display(w, screen, con)?;
+                Status::from_message(mad_inline!("Configuration reloaded")).display(w, screen)?;
+                screen.input_field.display_on(w)?;
+                self.state().write_flags(w, screen, con)?;
+            }
+            Err(e) => {
+                warn!("configuration reload failed: {:?}", e);
+                let text = format!("Configuration not reloaded: {}", e);
+                Status::from_error(Composite::from_inline(&text)).display(w, screen)?;
+            }
+        }
+        Ok(())
+    }
+
     /// called exactly once at end of `run`, cleans the writer (which
-    /// is usually stdout or stderr)
-    fn end(&mut self, writer: &mut W) ->Result<Option<Launchable>, ProgramError> {
-        queue!(writer, DisableMouseCapture)?;
+    /// is usually stdout or stderr) and writes the marked paths if required
+    fn end(&mut self, writer: &mut W, con: &AppContext) ->Result<Option<Launchable>, ProgramError> {
+        external::write_marks(con)?;
+        if let Err(e) = input_history::save() {
+            warn!("saving the input history failed: {:?}", e);
+        }
+        if !con.launch_args.no_mouse {
+            queue!(writer, DisableMouseCapture)?;
+        }
         queue!(writer, cursor::Show)?;
         queue!(writer, LeaveAlternateScreen)?;
         writer.flush()?;
@@ -169,7 +696,7 @@ impl App {
     pub fn run(
         &mut self,
         writer: &mut W,
-        con: &AppContext,
+        con: &mut AppContext,
         skin: Skin,
     ) -> Result<Option<Launchable>, ProgramError> {
 
@@ -177,64 +704,187 @@ impl App {
         queue!(writer, cursor::Hide)?;
         debug!("we're on screen");
         let mut screen = Screen::new(con, skin)?;
+        input_history::load();
 
         // we listen for events in a separate thread so that we can go on listening
         // when a long search is running, and interrupt it if needed
-        queue!(writer, EnableMouseCapture)?;
+        if !con.launch_args.no_mouse {
+            queue!(writer, EnableMouseCapture)?;
+        }
         let event_source = EventSource::new()?;
         let rx_events = event_source.receiver();
+        self.event_count = event_source.shared_event_count();
+
+        let mut cmd = Command::new();
+
+        // on a quick start, a first frame is drawn with a tree built
+        // without the expensive features, then replaced
+        if con.launch_args.quick_start {
+            if let Some(bs) = BrowserState::new(
+                con.launch_args.root.clone(),
+                con.launch_args.tree_options.without_expensive_features(),
+                &screen,
+                &TaskLifetime::unlimited(),
+            )? {
+                self.push(Box::new(bs));
+                self.mut_state().display(writer, &screen, con)?;
+                self.write_status(writer, &cmd, &screen, con)?;
+                screen.input_field.display_on(writer)?;
+                writer.flush()?;
+                self.states.clear();
+            }
+        }
 
         // create the initial state
-        if let Some(bs) = BrowserState::new(
+        if let Some(mut bs) = BrowserState::new(
             con.launch_args.root.clone(),
             con.launch_args.tree_options.clone(),
             &screen,
             &TaskLifetime::unlimited(),
         )? {
+            if let Some(selection) = &con.launch_args.selection {
+                bs.tree.try_select_path(selection);
+            }
             self.push(Box::new(bs));
         } else {
             unreachable!();
         }
 
-        let mut cmd = Command::new();
-
         // if some commands were passed to the application
-        //  we execute them before even starting listening for events
+        //  we execute them before even starting listening for events.
+        //  Each one waits for the end of the previous one, including
+        //  its searches, computations and background jobs
         if let Some(unparsed_commands) = &con.launch_args.commands {
-            let commands = parse_command_sequence(unparsed_commands, con)?;
+            let commands = parse_command_sequence(
+                unparsed_commands,
+                con.launch_args.cmd_separator.as_deref(),
+                con,
+            )?;
             for arg_cmd in &commands {
                 cmd = (*arg_cmd).clone();
                 cmd = self.apply_command(writer, cmd, &mut screen, con)?;
                 self.do_pending_tasks(writer, &cmd, &mut screen, con, TaskLifetime::unlimited())?;
+                self.wait_for_jobs(writer, &cmd, &mut screen, con)?;
                 if self.quitting {
-                    return self.end(writer);
+                    return self.end(writer, con);
                 }
             }
         }
 
         self.mut_state().display(writer, &screen, con)?;
-        self.state().write_status(writer, &cmd, &screen, con)?;
+        self.write_status(writer, &cmd, &screen, con)?;
         self.state().write_flags(writer, &mut screen, con)?;
         screen.input_field.display_on(writer)?;
 
+        let conf_path = Conf::default_location();
+        let mut autosaver = Autosaver::new();
+
+        let app_events = AppEvents::new();
+        app_events.forward_input(rx_events);
+        app_events.watch_conf(ConfWatcher::new(conf_path.clone()), CONF_POLL_PERIOD);
+        app_events.send_ticks(AUTOSAVE_PERIOD);
+        let fs_watcher = FsWatcher::new();
+        app_events.watch_files(fs_watcher.clone(), FS_POLL_PERIOD);
+        let mut prelister = Prelister::new();
+        app_events.watch_jobs(JOBS_POLL_PERIOD);
+        app_events.watch_terminal_size(SIZE_POLL_PERIOD);
+        #[cfg(unix)]
+        {
+            if let Some(socket) = &con.launch_args.listen {
+                app_events.listen(socket)?;
+            }
+        }
+
         loop {
             let tl = TaskLifetime::new(event_source.shared_event_count());
             if !self.quitting {
                 self.do_pending_tasks(writer, &cmd, &mut screen, con, tl)?;
             }
-            let event = match rx_events.recv() {
+            autosaver.update(self.session());
+            fs_watcher.set_dirs(self.watched_dirs(con));
+            prelister.set_request(self.prelist_request(con));
+            let event = match app_events.receiver().recv() {
                 Ok(event) => event,
                 Err(_) => {
+                    break; // can't happen as we hold a sender
+                }
+            };
+            match event {
+                AppEvent::Input(event) => {
+                    cmd.add_event(&event, &mut screen.input_field, con);
+                    cmd = self.apply_command(writer, cmd, &mut screen, con)?;
+                    event_source.unblock(self.quitting);
+                }
+                AppEvent::InputClosed => {
                     // this is how we quit the application,
                     // when the input thread is properly closed
                     break;
                 }
-            };
-            cmd.add_event(&event, &mut screen.input_field, con);
-            cmd = self.apply_command(writer, cmd, &mut screen, con)?;
-            event_source.unblock(self.quitting);
+                AppEvent::ConfChanged => {
+                    self.reload_conf(writer, &mut screen, con, &conf_path)?;
+                    // the action may refer to a verb of the old store
+                    cmd.action = Action::Unparsed;
+                }
+                AppEvent::FilesChanged => {
+                    self.refresh_on_files_change(writer, &cmd, &mut screen, con)?;
+                }
+                AppEvent::JobsChanged => {
+                    self.refresh_on_jobs_change(writer, &cmd, &mut screen, con)?;
+                }
+                AppEvent::Resized => {
+                    self.resize_and_redraw(writer, &cmd, &mut screen, con)?;
+                }
+                AppEvent::Tick => {
+                    // nothing more to do: the autosave is done at each loop
+                }
+                AppEvent::Remote(request, reply_tx) => {
+                    let reply = self.apply_request(writer, request, &mut cmd, &mut screen, con)?;
+                    let _ = reply_tx.send(reply);
+                    if self.quitting {
+                        break;
+                    }
+                }
+            }
         }
 
-        self.end(writer)
+        autosaver.save();
+        if let Some(socket) = &con.launch_args.listen {
+            let _ = fs::remove_file(socket);
+        }
+        self.end(writer, con)
+    }
+}
+
+#[cfg(test)]
+mod conf_reload_tests {
+
+    use {
+        super::*,
+        crate::{file_sizes::SizeFormat, test_support, tree_options::TreeOptions},
+        std::{env, process},
+    };
+
+    #[test]
+    fn check_reloaded_settings() {
+        let dir = env::temp_dir().join(format!("broot-test-reload-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let conf_path = dir.join("conf.toml");
+        fs::write(&conf_path, "full_permissions = true\n").unwrap();
+        let conf = Conf::from_file(&conf_path).unwrap();
+        let mut con = test_support::app_context(dir.clone(), TreeOptions::default(), &conf, 20);
+        con.launch_args.size_format = Some(SizeFormat::Bytes); // as given by --size-format
+        con.launch_args.apply_conf(&conf);
+        assert!(con.launch_args.tree_options.full_permissions);
+        fs::write(&conf_path, "size_format = \"si\"\ntwo_rows = true\n").unwrap();
+        reload_context(&mut con, &conf_path).unwrap();
+        let options = &con.launch_args.tree_options;
+        assert!(!options.full_permissions);
+        assert!(options.two_rows);
+        // the launch argument still takes precedence
+        assert_eq!(options.size_format, SizeFormat::Bytes);
+        con.launch_args.size_format = None;
+        reload_context(&mut con, &conf_path).unwrap();
+        assert_eq!(con.launch_args.tree_options.size_format, SizeFormat::Si);
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
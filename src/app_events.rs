@@ -0,0 +1,136 @@
+//! All the events the application reacts to come through a single
+//! channel, whatever their source: the terminal (keys, mouse, resize),
+//! the configuration and files watchers, the end of background jobs,
+//! a periodic tick, or another program when broot listens on a socket.
+//!
+//! Each source runs in its own thread and only needs a sender, so
+//! new sources can be plugged without blocking the other ones.
+
+use std::{thread, time::Duration};
+#[cfg(unix)]
+use std::{io, path::Path};
+
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use termimad::Event;
+
+use crate::{conf_watcher::ConfWatcher, fs_watcher::FsWatcher, jobs, server::Request};
+#[cfg(unix)]
+use crate::server;
+
+#[derive(Debug)]
+pub enum AppEvent {
+    Input(Event), // an event coming from the terminal
+    InputClosed,  // the terminal input thread ended, which means we quit
+    ConfChanged,  // the configuration file was modified
+    FilesChanged, // entries were added, removed or renamed in the displayed directories
+    JobsChanged,  // a job launched by an async verb ended
+    Resized,      // the size of the terminal changed
+    Tick,         // sent periodically, for the regular tasks (eg autosave)
+    Remote(Request, Sender<String>), // a request of another program, with where to send the reply
+}
+
+pub struct AppEvents {
+    tx: Sender<AppEvent>,
+    rx: Receiver<AppEvent>,
+}
+
+impl AppEvents {
+    pub fn new() -> AppEvents {
+        let (tx, rx) = unbounded();
+        AppEvents { tx, rx }
+    }
+
+    /// return a sender, for a new source of events
+    pub fn sender(&self) -> Sender<AppEvent> {
+        self.tx.clone()
+    }
+
+    pub fn receiver(&self) -> &Receiver<AppEvent> {
+        &self.rx
+    }
+
+    /// forward the terminal events, then signal the end of the input
+    pub fn forward_input(&self, input: Receiver<Event>) {
+        let tx = self.sender();
+        thread::spawn(move || {
+            for event in input {
+                if tx.send(AppEvent::Input(event)).is_err() {
+                    return;
+                }
+            }
+            let _ = tx.send(AppEvent::InputClosed);
+        });
+    }
+
+    /// send a tick at every period, until the receiver is dropped
+    pub fn send_ticks(&self, period: Duration) {
+        let tx = self.sender();
+        thread::spawn(move || loop {
+            thread::sleep(period);
+            if tx.send(AppEvent::Tick).is_err() {
+                return;
+            }
+        });
+    }
+
+    /// check the configuration file at every period and send an
+    /// event when it changed, until the receiver is dropped
+    pub fn watch_conf(&self, mut conf_watcher: ConfWatcher, period: Duration) {
+        let tx = self.sender();
+        thread::spawn(move || loop {
+            thread::sleep(period);
+            if conf_watcher.has_changed() && tx.send(AppEvent::ConfChanged).is_err() {
+                return;
+            }
+        });
+    }
+
+    /// check the watched directories at every period and send an
+    /// event when they changed, until the receiver is dropped
+    pub fn watch_files(&self, fs_watcher: FsWatcher, period: Duration) {
+        let tx = self.sender();
+        thread::spawn(move || loop {
+            thread::sleep(period);
+            if fs_watcher.has_changed() && tx.send(AppEvent::FilesChanged).is_err() {
+                return;
+            }
+        });
+    }
+
+    /// check the running jobs at every period and send an
+    /// event when one of them ended, until the receiver is dropped
+    pub fn watch_jobs(&self, period: Duration) {
+        let tx = self.sender();
+        thread::spawn(move || loop {
+            thread::sleep(period);
+            if jobs::poll() && tx.send(AppEvent::JobsChanged).is_err() {
+                return;
+            }
+        });
+    }
+
+    /// check the size of the terminal at every period and send an
+    /// event when it changed, until the receiver is dropped
+    pub fn watch_terminal_size(&self, period: Duration) {
+        let tx = self.sender();
+        thread::spawn(move || {
+            let mut size = termimad::terminal_size();
+            loop {
+                thread::sleep(period);
+                let new_size = termimad::terminal_size();
+                if new_size != size {
+                    size = new_size;
+                    if tx.send(AppEvent::Resized).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    /// forward the requests of the programs connecting to the socket
+    #[cfg(unix)]
+    pub fn listen(&self, socket: &Path) -> io::Result<()> {
+        server::listen(socket, self.sender())
+    }
+}
@@ -5,9 +5,13 @@ use crate::{
     commands::Command,
     errors::{ProgramError, TreeBuildError},
     external::Launchable,
+    flat_tree::Tree,
     io::W,
     screens::Screen,
+    session::Session,
+    state_history::Place,
     task_sync::TaskLifetime,
+    verbs::VerbExecution,
 };
 
 /// Result of applying a command to a state
@@ -16,9 +20,15 @@ pub enum AppStateCmdResult {
     Keep,
     Launch(Box<Launchable>),
     DisplayError(String),
+    DisplayMessage(String),
+    AskConfirmation(Box<VerbExecution>), // the execution waits for the user to type `y`
     NewState(Box<dyn AppState>, Command),
     PopStateAndReapply, // the state asks the command be executed on a previous state
     PopState,
+    Back,    // like PopState, but never quitting
+    Forward, // restore the last state left by going back
+    Undo,    // go back to the previous place of the state history
+    Redo,    // go again to the place left by undoing
     RefreshState,
 }
 
@@ -57,6 +67,21 @@ pub trait AppState {
 
     fn refresh(&mut self, screen: &Screen, con: &AppContext) -> Command;
 
+    /// called just before the execution of a confirmed verb which
+    /// may change files, so that the next refresh can tell what changed
+    fn before_confirmed_execution(&mut self) {}
+
+    /// execute the verb the user just confirmed. The built-in verbs
+    /// asking a confirmation must be executed by the state
+    fn execute_confirmed(
+        &mut self,
+        execution: &VerbExecution,
+        _screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<AppStateCmdResult, ProgramError> {
+        execution.execute(con)
+    }
+
     fn do_pending_task(
         &mut self,
         screen: &mut Screen,
@@ -87,4 +112,15 @@ pub trait AppState {
         con: &AppContext,
     ) -> Result<(), ProgramError>;
 
+    /// return what's needed to restore this state, if it's
+    /// a state worth restoring
+    fn session(&self) -> Option<Session>;
+
+    /// return the place the state displays, for the state history,
+    /// if it displays a tree
+    fn place(&self) -> Option<Place>;
+
+    /// return the tree the state displays, if any
+    fn tree(&self) -> Option<&Tree>;
+
 }
@@ -0,0 +1,488 @@
+//! zip and tar archives, browsed as if they were directories.
+//!
+//! An archive is read (with the `unzip` or `tar` command) when the
+//! user opens it in the tree. The path of an entry is the path of the
+//! archive followed by the path of the entry in the archive, for
+//! example `/home/me/dl/project.zip/src/main.rs`.
+//!
+//! The entries are read-only: they must be extracted (see `extract`)
+//! before being opened or modified. They're extracted in a new directory
+//! next to the archive, never over existing files.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Component, Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use crate::{
+    errors::ProgramError,
+    tree_sources::{EntryMetadata, EntryType, SourceEntry},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar, // compressed or not
+}
+
+/// the extensions of the archives, the longest ones first
+const EXTENSIONS: &[(&str, ArchiveKind)] = &[
+    (".tar.bz2", ArchiveKind::Tar),
+    (".tar.gz", ArchiveKind::Tar),
+    (".tar.xz", ArchiveKind::Tar),
+    (".tbz2", ArchiveKind::Tar),
+    (".tar", ArchiveKind::Tar),
+    (".tgz", ArchiveKind::Tar),
+    (".txz", ArchiveKind::Tar),
+    (".jar", ArchiveKind::Zip),
+    (".zip", ArchiveKind::Zip),
+];
+
+/// return the extension of the archive name, if it's one
+fn archive_extension(name: &str) -> Option<(&'static str, ArchiveKind)> {
+    let name = name.to_lowercase();
+    EXTENSIONS.iter().find(|(ext, _)| name.ends_with(ext)).copied()
+}
+
+impl ArchiveKind {
+    /// the kind of archive the path designates, judging by its extension
+    pub fn of(path: &Path) -> Option<ArchiveKind> {
+        let name = path.file_name()?.to_string_lossy();
+        archive_extension(&name).map(|(_, kind)| kind)
+    }
+}
+
+/// the name of the directory an archive is extracted in: the
+/// name of the archive without its extension
+fn extraction_dir_name(archive_name: &str) -> String {
+    match archive_extension(archive_name) {
+        Some((ext, _)) if archive_name.len() > ext.len() => {
+            archive_name[..archive_name.len() - ext.len()].to_string()
+        }
+        _ => format!("{}.content", archive_name),
+    }
+}
+
+/// an entry of an archive
+#[derive(Debug, Clone)]
+struct ArchiveEntry {
+    raw_name: String, // the name as listed, to give back to the extraction command
+    entry_type: EntryType,
+}
+
+/// the content of an archive, as listed
+#[derive(Debug)]
+pub struct Archive {
+    kind: ArchiveKind,
+    path: PathBuf,
+    metadata: EntryMetadata, // the one of the archive file
+    entries: BTreeMap<PathBuf, ArchiveEntry>, // by path in the archive
+}
+
+lazy_static! {
+    static ref ARCHIVES_MUTEX: Mutex<HashMap<PathBuf, (Option<SystemTime>, Arc<Archive>)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// tell whether the path stays inside the directory it's relative
+/// to: it's neither absolute nor going up with `..`
+fn is_contained(path: &Path) -> bool {
+    path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// parse the output of `unzip -Z1` or `tar -tf`, which is one
+/// name per line, directories ending with a slash.
+///
+/// The entries which would be extracted outside of the extraction
+/// directory (absolute, or with `..`) are dropped.
+fn parse_listing(listing: &str) -> BTreeMap<PathBuf, ArchiveEntry> {
+    let mut entries = BTreeMap::new();
+    for raw_name in listing.lines() {
+        let name = raw_name.trim_start_matches("./");
+        let entry_type = if name.ends_with('/') {
+            EntryType::Dir
+        } else {
+            EntryType::File
+        };
+        let path = PathBuf::from(name.trim_end_matches('/'));
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        if !is_contained(&path) {
+            warn!("ignoring the archive entry {:?}", raw_name);
+            continue;
+        }
+        // the parent directories aren't always listed
+        for ancestor in path.ancestors().skip(1) {
+            if ancestor.as_os_str().is_empty() {
+                break;
+            }
+            entries.entry(ancestor.to_path_buf()).or_insert_with(|| ArchiveEntry {
+                raw_name: format!("{}/", ancestor.to_string_lossy()),
+                entry_type: EntryType::Dir,
+            });
+        }
+        entries.insert(path, ArchiveEntry {
+            raw_name: raw_name.to_string(),
+            entry_type,
+        });
+    }
+    entries
+}
+
+/// make a name given to unzip match only itself: unzip takes
+/// the names as wildcard patterns, and a leading dash as an option
+fn escape_unzip_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for (i, c) in name.chars().enumerate() {
+        match c {
+            '*' | '?' | '[' => {
+                escaped.push('[');
+                escaped.push(c);
+                escaped.push(']');
+            }
+            '-' if i == 0 => escaped.push_str("[-]"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// run the command and return its standard output, or an
+/// error made of its standard error
+fn run(command: &mut Command) -> io::Result<String> {
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+impl Archive {
+    fn read(path: &Path, kind: ArchiveKind) -> io::Result<Archive> {
+        debug!("listing the archive {:?}", path);
+        let listing = match kind {
+            ArchiveKind::Zip => run(Command::new("unzip").arg("-Z1").arg(path))?,
+            ArchiveKind::Tar => run(Command::new("tar").arg("-tf").arg(path))?,
+        };
+        Ok(Archive {
+            kind,
+            path: path.to_path_buf(),
+            metadata: EntryMetadata::from_fs(&fs::metadata(path)?),
+            entries: parse_listing(&listing),
+        })
+    }
+
+    /// return the archive, listing it if it's not yet known or if
+    /// it was modified since
+    pub fn get(path: &Path) -> io::Result<Arc<Archive>> {
+        let kind = ArchiveKind::of(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "not an archive"))?;
+        let modified = fs::metadata(path)?.modified().ok();
+        if let Some((listed_modified, archive)) = ARCHIVES_MUTEX.lock().unwrap().get(path) {
+            if *listed_modified == modified {
+                return Ok(Arc::clone(archive));
+            }
+        }
+        // the lock isn't held while the listing program runs
+        let archive = Arc::new(Archive::read(path, kind)?);
+        ARCHIVES_MUTEX
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), (modified, Arc::clone(&archive)));
+        Ok(archive)
+    }
+
+    /// the metadata of an entry, mostly taken from the archive file
+    /// (the sizes of the entries aren't known)
+    fn entry_metadata(&self, entry: &ArchiveEntry) -> EntryMetadata {
+        let mut metadata = self.metadata.clone();
+        metadata.entry_type = entry.entry_type;
+        metadata.len = 0;
+        metadata.dev = 0; // the entries aren't links of the archive file
+        metadata.ino = 0;
+        metadata.nlink = 0;
+        metadata.in_archive = true;
+        if entry.entry_type.is_dir() {
+            metadata.mode |= 0o111;
+        }
+        metadata
+    }
+
+    /// list the entries of a directory of the archive (the empty path
+    /// being the root of the archive)
+    pub fn read_dir(&self, inner: &Path) -> io::Result<Vec<SourceEntry>> {
+        if !inner.as_os_str().is_empty() {
+            match self.entries.get(inner) {
+                Some(entry) if entry.entry_type.is_dir() => {}
+                _ => {
+                    return Err(io::Error::new(io::ErrorKind::NotFound, "no such directory"));
+                }
+            }
+        }
+        Ok(self
+            .entries
+            .iter()
+            .filter(|(path, _)| path.parent() == Some(inner))
+            .map(|(path, entry)| SourceEntry {
+                path: self.path.join(path),
+                entry_type: entry.entry_type,
+            })
+            .collect())
+    }
+
+    pub fn metadata(&self, inner: &Path) -> io::Result<EntryMetadata> {
+        self.entries
+            .get(inner)
+            .map(|entry| self.entry_metadata(entry))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such entry"))
+    }
+
+    /// the directory, next to the archive, in which its entries are extracted
+    pub fn extraction_dir(&self) -> PathBuf {
+        let name = self.path.file_name().map_or_else(
+            || "archive".to_string(),
+            |name| name.to_string_lossy().to_string(),
+        );
+        self.path
+            .parent()
+            .unwrap_or_else(|| Path::new("/"))
+            .join(extraction_dir_name(&name))
+    }
+
+    /// extract the entry (a directory being extracted with its content)
+    /// in a new directory next to the archive, keeping its path in the
+    /// archive, and return the path of the extracted entry.
+    /// An empty inner path means the whole archive.
+    ///
+    /// The extraction is refused when the directory already exists,
+    /// so that no file is ever overwritten.
+    pub fn extract(&self, inner: &Path) -> Result<PathBuf, ProgramError> {
+        let dest = self.extraction_dir();
+        if dest.exists() {
+            return Err(ProgramError::Archive {
+                details: format!("{:?} already exists", dest),
+            });
+        }
+        let raw_name = if inner.as_os_str().is_empty() {
+            None
+        } else {
+            let entry = self.entries.get(inner).ok_or_else(|| ProgramError::Archive {
+                details: format!("no entry {:?} in the archive", inner),
+            })?;
+            Some(entry)
+        };
+        let mut command = match self.kind {
+            ArchiveKind::Zip => {
+                let mut command = Command::new("unzip");
+                // -n: never overwrite a file, the directory being new anyway
+                command.arg("-n").arg("-q").arg(&self.path);
+                if let Some(entry) = raw_name {
+                    let name = escape_unzip_name(&entry.raw_name);
+                    if entry.entry_type.is_dir() {
+                        command.arg(format!("{}*", name));
+                    } else {
+                        command.arg(name);
+                    }
+                }
+                command.arg("-d").arg(&dest);
+                command
+            }
+            ArchiveKind::Tar => {
+                let mut command = Command::new("tar");
+                command.arg("-xf").arg(&self.path).arg("-C").arg(&dest);
+                if let Some(entry) = raw_name {
+                    // after --, a name starting with a dash isn't an option
+                    command.arg("--").arg(entry.raw_name.trim_end_matches('/'));
+                }
+                command
+            }
+        };
+        fs::create_dir(&dest)?;
+        if let Err(e) = run(&mut command) {
+            // the directory was created for this extraction only
+            let _ = fs::remove_dir_all(&dest);
+            return Err(ProgramError::Archive {
+                details: e.to_string(),
+            });
+        }
+        Ok(dest.join(inner))
+    }
+}
+
+/// the deepest directory containing all the paths
+fn common_dir(paths: &[PathBuf]) -> PathBuf {
+    let mut common = paths[0].parent().unwrap_or(Path::new("/")).to_path_buf();
+    for path in &paths[1..] {
+        while !path.starts_with(&common) {
+            if !common.pop() {
+                break;
+            }
+        }
+    }
+    common
+}
+
+/// start packing the paths in a new archive, whose kind is given
+/// by its extension. The paths are stored relatively to the deepest
+/// directory containing them all.
+///
+/// The program (`zip` or `tar`) runs in the background, its child
+/// is returned so that it can be handled as a job.
+pub fn pack(dest: &Path, paths: &[PathBuf]) -> Result<Child, ProgramError> {
+    if paths.is_empty() {
+        return Err(ProgramError::Archive {
+            details: "nothing to pack".to_string(),
+        });
+    }
+    let base = common_dir(paths);
+    // the relative paths start with ./ so that no name can be taken for an option
+    let relative_paths = paths.iter().map(|path| {
+        Path::new(".").join(path.strip_prefix(&base).unwrap_or(path))
+    });
+    let mut command = match ArchiveKind::of(dest) {
+        Some(ArchiveKind::Zip) => {
+            let mut command = Command::new("zip");
+            command.arg("-r").arg("-q").arg(dest);
+            command
+        }
+        Some(ArchiveKind::Tar) => {
+            // with -a the compression depends on the extension
+            let mut command = Command::new("tar");
+            command.arg("-caf").arg(dest);
+            command
+        }
+        None => {
+            return Err(ProgramError::Archive {
+                details: format!("not an archive name: {:?}", dest),
+            });
+        }
+    };
+    let program = format!("{:?}", command);
+    command
+        .args(relative_paths)
+        .current_dir(&base)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|source| ProgramError::LaunchError { program, source })
+}
+
+/// if the path is in an archive (or is the archive itself), return
+/// the archive and the path of the entry in it, or the error met
+/// while reading the archive. None means the path isn't in an archive.
+///
+/// This is only called for paths which aren't found on disk.
+pub fn locate(path: &Path) -> Option<io::Result<(Arc<Archive>, PathBuf)>> {
+    for ancestor in path.ancestors() {
+        if ArchiveKind::of(ancestor).is_some() && ancestor.is_file() {
+            let inner = path.strip_prefix(ancestor).ok()?.to_path_buf();
+            return Some(Archive::get(ancestor).map(|archive| (archive, inner)));
+        }
+    }
+    None
+}
+
+/// list a directory of an archive, or None if the path isn't
+/// in an archive
+pub fn read_dir(dir: &Path) -> Option<io::Result<Vec<SourceEntry>>> {
+    locate(dir).map(|located| located.and_then(|(archive, inner)| archive.read_dir(&inner)))
+}
+
+/// return the metadata of an entry of an archive, or None if the path
+/// isn't in an archive
+pub fn metadata(path: &Path) -> Option<io::Result<EntryMetadata>> {
+    match locate(path)? {
+        Ok((archive, inner)) => {
+            if inner.as_os_str().is_empty() {
+                None // the archive itself is a normal file
+            } else {
+                Some(archive.metadata(&inner))
+            }
+        }
+        Err(e) => Some(Err(e)),
+    }
+}
+
+#[cfg(test)]
+mod archive_listing_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_archive_kinds() {
+        assert_eq!(ArchiveKind::of(Path::new("/a/b.zip")), Some(ArchiveKind::Zip));
+        assert_eq!(ArchiveKind::of(Path::new("/a/b.TAR.GZ")), Some(ArchiveKind::Tar));
+        assert_eq!(ArchiveKind::of(Path::new("/a/b.tgz")), Some(ArchiveKind::Tar));
+        assert_eq!(ArchiveKind::of(Path::new("/a/b.gz")), None);
+        assert_eq!(ArchiveKind::of(Path::new("/a/zip")), None);
+    }
+
+    #[test]
+    fn check_extraction_dir_names() {
+        assert_eq!(extraction_dir_name("project.tar.gz"), "project");
+        assert_eq!(extraction_dir_name("lib.JAR"), "lib");
+        assert_eq!(extraction_dir_name("v1.2.tgz"), "v1.2");
+        assert_eq!(extraction_dir_name(".zip"), ".zip.content");
+    }
+
+    #[test]
+    fn check_common_dir() {
+        let paths = vec![
+            PathBuf::from("/a/b/c/d.txt"),
+            PathBuf::from("/a/b/e"),
+            PathBuf::from("/a/b/c/f/g"),
+        ];
+        assert_eq!(common_dir(&paths), PathBuf::from("/a/b"));
+        assert_eq!(common_dir(&paths[..1]), PathBuf::from("/a/b/c"));
+    }
+
+    #[test]
+    fn check_listing_parsing() {
+        let entries = parse_listing("./\n./src/\n./src/main.rs\ndoc/a/b.md\nREADME.md\n");
+        let paths: Vec<&Path> = entries.keys().map(|p| p.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("README.md"),
+                Path::new("doc"),
+                Path::new("doc/a"),
+                Path::new("doc/a/b.md"),
+                Path::new("src"),
+                Path::new("src/main.rs"),
+            ],
+        );
+        assert!(entries[Path::new("doc/a")].entry_type.is_dir());
+        assert!(entries[Path::new("src/main.rs")].entry_type.is_file());
+        assert_eq!(entries[Path::new("src")].raw_name, "./src/");
+    }
+
+    #[test]
+    fn check_escaping_listing_parsing() {
+        let entries = parse_listing("a/../../b\n/etc/passwd\n../c\nd/e\n--to-command=x\n");
+        let paths: Vec<&Path> = entries.keys().map(|p| p.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("--to-command=x"),
+                Path::new("d"),
+                Path::new("d/e"),
+            ],
+        );
+    }
+
+    #[test]
+    fn check_unzip_name_escaping() {
+        assert_eq!(escape_unzip_name("src/main.rs"), "src/main.rs");
+        assert_eq!(escape_unzip_name("a*b?[c]"), "a[*]b[?][[]c]");
+        assert_eq!(escape_unzip_name("-x/a-b"), "[-]x/a-b");
+    }
+}
@@ -0,0 +1,105 @@
+//! bookmarks are named directories the user can go to with `:goto`.
+//!
+//! They're saved with `:bookmark` in a TOML file of the data
+//! directory, as a table of names to paths. A directory has only
+//! one bookmark, the last name given to it.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::PathBuf,
+};
+
+use toml::{self, value::Table, Value};
+
+use crate::{conf, fuzzy_patterns::FuzzyPattern, path_case};
+
+#[derive(Debug, Clone, Default)]
+pub struct Bookmarks {
+    entries: BTreeMap<String, PathBuf>,
+}
+
+/// return the path to the file in which the bookmarks are saved
+pub fn file_path() -> PathBuf {
+    conf::data_dir().join("bookmarks.toml")
+}
+
+impl Bookmarks {
+    /// read the saved bookmarks. A missing or unreadable file
+    /// gives no bookmark
+    pub fn load() -> Bookmarks {
+        let mut entries = BTreeMap::new();
+        let root = fs::read_to_string(file_path())
+            .ok()
+            .and_then(|data| data.parse::<Value>().ok());
+        if let Some(Value::Table(tbl)) = root {
+            for (name, path) in tbl.iter() {
+                if let Some(path) = path.as_str() {
+                    entries.insert(name.to_string(), PathBuf::from(path));
+                }
+            }
+        }
+        Bookmarks { entries }
+    }
+    pub fn save(&self) -> Result<(), io::Error> {
+        let mut tbl = Table::new();
+        for (name, path) in &self.entries {
+            tbl.insert(
+                name.to_string(),
+                Value::String(path.to_string_lossy().to_string()),
+            );
+        }
+        fs::create_dir_all(conf::data_dir())?;
+        fs::write(file_path(), Value::Table(tbl).to_string())
+    }
+    /// add a bookmark, replacing the one with the same name and the
+    /// ones of the same directory (whose path may differ by case on
+    /// case insensitive file systems)
+    pub fn set(&mut self, name: &str, path: PathBuf) {
+        let same_dir: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, p)| path_case::same_path(p, &path))
+            .map(|(n, _)| n.to_string())
+            .collect();
+        for old_name in same_dir {
+            self.entries.remove(&old_name);
+        }
+        self.entries.insert(name.to_string(), path);
+    }
+    pub fn get(&self, name: &str) -> Option<&PathBuf> {
+        self.entries.get(name)
+    }
+    /// return the name best matching the typed one, with the
+    /// fuzzy matching used for file names
+    pub fn best_match(&self, typed: &str) -> Option<&str> {
+        if typed.is_empty() {
+            return None;
+        }
+        let pattern = FuzzyPattern::from(typed);
+        self.entries
+            .keys()
+            .filter_map(|name| pattern.score_of(name).map(|score| (score, name)))
+            .max_by_key(|(score, _)| *score)
+            .map(|(_, name)| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod bookmarks_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_one_bookmark_per_directory() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.set("src", PathBuf::from("/dev/broot/src"));
+        bookmarks.set("doc", PathBuf::from("/dev/broot/website"));
+        bookmarks.set("code", PathBuf::from("/dev/broot/src"));
+        assert!(bookmarks.get("src").is_none());
+        assert_eq!(bookmarks.get("code"), Some(&PathBuf::from("/dev/broot/src")));
+        assert_eq!(bookmarks.get("doc"), Some(&PathBuf::from("/dev/broot/website")));
+        bookmarks.set("doc", PathBuf::from("/dev/broot/docs"));
+        assert_eq!(bookmarks.get("doc"), Some(&PathBuf::from("/dev/broot/docs")));
+    }
+}
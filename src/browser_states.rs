@@ -1,8 +1,8 @@
 use std::{
+    ffi::OsString,
     fs::OpenOptions,
-    io::Write,
     path::PathBuf,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use opener;
@@ -11,29 +11,58 @@ use minimad::Composite;
 use crate::{
     app_context::AppContext,
     app_state::{AppState, AppStateCmdResult},
+    archives::Archive,
+    bookmarks::Bookmarks,
     commands::{Action, Command},
     displayable_tree::DisplayableTree,
     errors::{ProgramError, TreeBuildError},
     external::Launchable,
-    flat_tree::{LineType, Tree},
+    filesystems,
+    flat_tree::{LineType, Tree, TreeLine},
     help_states::HelpState,
     io::W,
+    marks,
+    os_names,
+    output_states::OutputState,
+    path_completion,
     patterns::Pattern,
+    permissions,
     screens::{self, Screen},
+    selection_info::{self, SelectionInfo},
+    selection_type::Selection,
+    session::{Session, Toggles},
+    state_history::Place,
     status::Status,
     task_sync::TaskLifetime,
-    tree_build::TreeBuilder,
+    tree_build::{BuildProgress, TreeBuilder},
+    tree_diff,
     tree_options::{OptionBool, TreeOptions},
+    verb_invocation::VerbInvocation,
     verb_store::PrefixSearchResult,
-    verbs::VerbExecutor,
+    verbs::{Verb, VerbExecution, VerbExecutor},
 };
 
+/// the duration of a step of search, after which the lines
+/// found until then are displayed
+const SEARCH_STEP: Duration = Duration::from_millis(100);
+
+/// the error displayed when the verb doesn't apply to the selection
+fn not_applying(verb_name: &str, selection: &Selection) -> String {
+    format!("`{}` doesn't apply to {}", verb_name, selection.description())
+}
+
 /// An application state dedicated to displaying a tree.
 /// It's the first and main screen of broot.
 pub struct BrowserState {
     pub tree: Tree,
     pub filtered_tree: Option<Tree>,
     pending_pattern: Pattern, // a pattern (or not) which has not yet be applied
+    search: Option<TreeBuilder>, // the search in progress, whose partial results are displayed
+    search_selection: Option<PathBuf>, // the line the search selected, if the user didn't move since
+    git_branch: Option<String>, // the current branch of the repository of the root, if any
+    pub lines_before_verb: Option<Box<[TreeLine]>>, // the displayed lines when a verb modifying files was launched
+    change_summary: Option<String>, // what the last verb changed in the displayed lines
+    pub pending_elevation: Option<Vec<OsString>>, // the refused permission change, to retry with `:elevate`
 }
 
 impl BrowserState {
@@ -45,17 +74,53 @@ impl BrowserState {
     ) -> Result<Option<BrowserState>, TreeBuildError> {
         let pending_pattern = options.pattern;
         options.pattern = Pattern::None;
-        let builder = TreeBuilder::from(path, options, BrowserState::page_height(screen) as usize)?;
+        let git_branch = selection_info::git_branch(&path);
+        let page_height = BrowserState::page_height(screen, &options);
+        let builder = TreeBuilder::from(path, options, page_height as usize)?;
         Ok(match builder.build(tl) {
             Some(tree) => Some(BrowserState {
                 tree,
                 filtered_tree: None,
                 pending_pattern,
+                search: None,
+                search_selection: None,
+                git_branch,
+                lines_before_verb: None,
+                change_summary: None,
+                pending_elevation: None,
             }),
             None => None, // interrupted
         })
     }
 
+    /// display the tree found by the search, keeping the selection
+    /// when the user moved it since the previous step
+    fn set_search_result(&mut self, mut tree: Tree, screen: &Screen) {
+        let user_selection = match (&self.filtered_tree, &self.search_selection) {
+            (Some(previous), Some(search_selection)) => {
+                let selected = previous.selected_line().path.to_path_buf();
+                if &selected != search_selection {
+                    Some(selected)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        match user_selection {
+            Some(path) => {
+                tree.try_select_path(&path);
+            }
+            None => {
+                tree.try_select_best_match();
+                self.search_selection = Some(tree.selected_line().path.to_path_buf());
+            }
+        }
+        let page_height = BrowserState::page_height(screen, &tree.options);
+        tree.make_selection_visible(page_height);
+        self.filtered_tree = Some(tree);
+    }
+
     pub fn with_new_options(
         &self,
         screen: &Screen,
@@ -75,8 +140,78 @@ impl BrowserState {
         )
     }
 
-    pub fn page_height(screen: &Screen) -> i32 {
-        i32::from(screen.height) - 2
+    /// focus the parent of the current root, the previous root being
+    /// kept open and selected. The pattern, if any, is kept too.
+    pub fn up_tree(&self, screen: &Screen) -> AppStateCmdResult {
+        let tree = self.displayed_tree();
+        let root = tree.root().clone();
+        let parent = match root.parent() {
+            Some(parent) => parent.to_path_buf(),
+            None => {
+                return AppStateCmdResult::DisplayError("no parent found".to_string());
+            }
+        };
+        let mut options = tree.options.clone();
+        options.forced_open = Some(root.clone());
+        let state = BrowserState::new(parent, options, screen, &TaskLifetime::unlimited());
+        if let Ok(Some(mut state)) = state {
+            let page_height = BrowserState::page_height(screen, &state.tree.options);
+            state.tree.try_select_path(&root);
+            state.tree.make_selection_visible(page_height);
+            return AppStateCmdResult::NewState(
+                Box::new(state),
+                Command::from_pattern(&tree.options.pattern),
+            );
+        }
+        AppStateCmdResult::from_optional_state(state, Command::from_pattern(&tree.options.pattern))
+    }
+
+    /// list the content of the selected archive under it, as if it
+    /// were a directory. The pattern, if any, is kept and applies to
+    /// the entries of the archive too.
+    pub fn open_archive(&self, screen: &Screen) -> AppStateCmdResult {
+        let tree = self.displayed_tree();
+        let path = tree.selected_line().path.to_path_buf();
+        if let Err(e) = Archive::get(&path) {
+            return AppStateCmdResult::DisplayError(format!("can't read the archive: {}", e));
+        }
+        let mut options = tree.options.clone();
+        if !options.opened_archives.contains(&path) {
+            options.opened_archives.push(path.clone());
+        }
+        options.forced_open = Some(path.clone());
+        let state = BrowserState::new(tree.root().clone(), options, screen, &TaskLifetime::unlimited());
+        if let Ok(Some(mut state)) = state {
+            let page_height = BrowserState::page_height(screen, &state.tree.options);
+            state.tree.try_select_path(&path);
+            state.tree.make_selection_visible(page_height);
+            return AppStateCmdResult::NewState(
+                Box::new(state),
+                Command::from_pattern(&tree.options.pattern),
+            );
+        }
+        AppStateCmdResult::from_optional_state(state, Command::from_pattern(&tree.options.pattern))
+    }
+
+    /// tell whether the filesystem of the root, displayed on its line
+    /// with the sizes, must still be looked for
+    fn root_mount_missing(&self) -> bool {
+        let tree = self.displayed_tree();
+        tree.options.show_sizes
+            && tree.options.source.is_local()
+            && !tree.lines[0].metadata.in_archive
+            && !filesystems::is_mount_known(tree.root())
+    }
+
+    /// return the number of tree lines which are built and displayed,
+    /// which is less than what the screen can display when the
+    /// options limit it
+    pub fn page_height(screen: &Screen, options: &TreeOptions) -> i32 {
+        let page_height = (i32::from(screen.height) - 2) / i32::from(options.rows_per_line());
+        match options.max_lines {
+            Some(max_lines) => page_height.min(max_lines as i32),
+            None => page_height,
+        }
     }
 
     /// return a reference to the currently displayed tree, which
@@ -85,12 +220,136 @@ impl BrowserState {
         self.filtered_tree.as_ref().unwrap_or(&self.tree)
     }
 
+    /// return the pattern the user typed, which may not be the one
+    /// of the displayed tree while the search goes on
+    pub fn pattern(&self) -> &Pattern {
+        if self.pending_pattern.is_some() {
+            &self.pending_pattern
+        } else if let Some(search) = &self.search {
+            &search.options().pattern
+        } else {
+            &self.displayed_tree().options.pattern
+        }
+    }
+
     /// return a mutable reference to the currently displayed tree, which
     /// is the filtered tree if there's one, the base tree if not.
     pub fn displayed_tree_mut(&mut self) -> &mut Tree {
         self.filtered_tree.as_mut().unwrap_or(&mut self.tree)
     }
 
+    /// apply `:chmod` or `:chown` to the selection or, when there are
+    /// marked paths, ask for a confirmation before applying it to them
+    #[cfg(unix)]
+    pub fn ask_permission_change(
+        &mut self,
+        verb: &Verb,
+        invocation: &VerbInvocation,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> AppStateCmdResult {
+        let command = &verb.execution[1..];
+        let spec = invocation.args.as_ref().map_or("", |s| s.trim());
+        if let Err(e) = permissions::check_spec(command, spec) {
+            return AppStateCmdResult::DisplayError(e.to_string());
+        }
+        let paths = marks::all();
+        if paths.is_empty() {
+            let path = self.displayed_tree().selected_line().path.to_path_buf();
+            return self.change_permissions(command, spec, &[path], screen, con);
+        }
+        let execution = VerbExecution::builtin(verb, &invocation.args, paths);
+        if con.launch_args.dry_run {
+            AppStateCmdResult::DisplayMessage(format!(
+                "Dry run, not executed: `{}`",
+                execution.command(),
+            ))
+        } else {
+            AppStateCmdResult::AskConfirmation(Box::new(execution))
+        }
+    }
+
+    /// apply `:chmod` or `:chown` (given as `command`) to all the
+    /// paths, whatever the failures. When there are several paths,
+    /// the failures are listed in an output state.
+    /// When the system refuses the change and an elevation command is
+    /// configured, the change is kept for a retry with `:elevate`
+    #[cfg(unix)]
+    pub fn change_permissions(
+        &mut self,
+        command: &str,
+        spec: &str,
+        paths: &[PathBuf],
+        screen: &Screen,
+        con: &AppContext,
+    ) -> AppStateCmdResult {
+        self.lines_before_verb = Some(self.displayed_tree().lines.clone());
+        self.pending_elevation = None;
+        let failures: Vec<(&PathBuf, ProgramError)> = paths
+            .iter()
+            .filter_map(|path| {
+                let res = match command {
+                    "chown" => permissions::chown(path, spec),
+                    _ => permissions::chmod(path, spec),
+                };
+                res.err().map(|e| (path, e))
+            })
+            .collect();
+        if failures.is_empty() {
+            return AppStateCmdResult::RefreshState;
+        }
+        let refused = failures
+            .iter()
+            .any(|(_, e)| permissions::is_permission_denied(e));
+        let elevation_hint = match &con.launch_args.elevation_command {
+            Some(elevation_command) if refused => {
+                let mut tokens: Vec<OsString> = elevation_command
+                    .split_whitespace()
+                    .map(OsString::from)
+                    .collect();
+                tokens.push(command.into());
+                tokens.push(spec.into());
+                tokens.extend(paths.iter().map(|p| p.as_os_str().to_os_string()));
+                let hint = format!(
+                    "Type `:elevate` to quit broot and run `{}`",
+                    tokens
+                        .iter()
+                        .map(|t| os_names::escape(t))
+                        .collect::<Vec<String>>()
+                        .join(" "),
+                );
+                self.pending_elevation = Some(tokens);
+                Some(hint)
+            }
+            _ => None,
+        };
+        if paths.len() == 1 {
+            self.lines_before_verb = None;
+            let e = &failures[0].1;
+            return AppStateCmdResult::DisplayError(match elevation_hint {
+                Some(hint) => format!("{}. {}", e, hint),
+                None => e.to_string(),
+            });
+        }
+        // the other paths may have been changed
+        self.refresh(screen, con);
+        let mut report = format!(
+            "{} of the {} changes failed:\n",
+            failures.len(),
+            paths.len(),
+        );
+        for (path, e) in &failures {
+            report.push_str(&format!("{}: {}\n", os_names::escape(path.as_os_str()), e));
+        }
+        if let Some(hint) = elevation_hint {
+            report.push_str(&format!("\nHit esc to go back to the tree. {}\n", hint));
+        }
+        AppStateCmdResult::NewState(
+            Box::new(OutputState::new(format!("{} {}", command, spec), report)),
+            Command::new(),
+        )
+    }
+
     pub fn open_selection_stay_in_broot(
         &mut self,
         screen: &mut Screen,
@@ -99,9 +358,19 @@ impl BrowserState {
         let tree = self.displayed_tree();
         let line = tree.selected_line();
         let tl = TaskLifetime::unlimited();
+        if line.metadata.in_archive && !line.is_dir() {
+            return Ok(AppStateCmdResult::DisplayError(
+                "this file is in an archive: `:extract` it first".to_string()
+            ));
+        }
+        if !tree.options.source.is_local() && !line.is_dir() {
+            return Ok(AppStateCmdResult::DisplayError(
+                "this file is on a remote server: it can't be opened".to_string()
+            ));
+        }
         match &line.line_type {
-            LineType::File => {
-                opener::open(&line.path)?;
+            LineType::File | LineType::Archive => {
+                opener::open(&line.path.to_path_buf())?;
                 Ok(AppStateCmdResult::Keep)
             }
             LineType::Dir | LineType::SymLinkToDir(_) => {
@@ -136,13 +405,25 @@ impl BrowserState {
     ) -> Result<AppStateCmdResult, ProgramError> {
         let tree = self.displayed_tree();
         let line = tree.selected_line();
+        if line.metadata.in_archive {
+            return Ok(AppStateCmdResult::DisplayError(
+                "this entry is in an archive: `:extract` it first".to_string()
+            ));
+        }
+        if !tree.options.source.is_local() {
+            return Ok(AppStateCmdResult::DisplayError(
+                "this entry is on a remote server: it can't be opened".to_string()
+            ));
+        }
         match &line.line_type {
-            LineType::File => make_opener(line.path.clone(), line.is_exe(), con),
+            LineType::File | LineType::Archive => make_opener(line.path.to_path_buf(), line.is_exe(), con),
             LineType::Dir | LineType::SymLinkToDir(_) => {
                 Ok(if con.launch_args.cmd_export_path.is_some() {
                     let cd_idx = con.verb_store.index_of("cd");
                     con.verb_store.verbs[cd_idx].to_cmd_result(
                         &line.target(),
+                        tree.root(),
+                        1,
                         &None,
                         screen,
                         con,
@@ -166,6 +447,10 @@ impl BrowserState {
         }
     }
 
+    fn selection_info(&self) -> SelectionInfo {
+        SelectionInfo::of(self.displayed_tree(), self.git_branch.as_ref())
+    }
+
     fn normal_status_message(
         &self,
         has_pattern: bool,
@@ -205,14 +490,13 @@ fn make_opener(
     con: &AppContext,
 ) -> Result<AppStateCmdResult, ProgramError> {
     Ok(if is_exe {
-        let path = path.to_string_lossy().to_string();
         if let Some(export_path) = &con.launch_args.cmd_export_path {
             // broot was launched as br, we can launch the executable from the shell
             let f = OpenOptions::new().append(true).open(export_path)?;
-            writeln!(&f, "{}", path)?;
+            os_names::write_line(&mut &f, path.as_os_str())?;
             AppStateCmdResult::Quit
         } else {
-            AppStateCmdResult::from(Launchable::program(vec![path])?)
+            AppStateCmdResult::from(Launchable::program(vec![path.into_os_string()])?)
         }
     } else {
         AppStateCmdResult::from(Launchable::opener(path))
@@ -223,7 +507,10 @@ impl AppState for BrowserState {
 
     fn has_pending_task(&self) -> bool {
         self.pending_pattern.is_some()
+            || self.search.is_some()
+            || self.root_mount_missing()
             || self.displayed_tree().has_dir_missing_size()
+            || self.displayed_tree().has_dir_missing_count()
     }
 
     fn write_status(
@@ -233,27 +520,61 @@ impl AppState for BrowserState {
         screen: &Screen,
         con: &AppContext,
     ) -> Result<(), ProgramError> {
-        let task = if self.pending_pattern.is_some() {
+        let task = if self.pending_pattern.is_some() || self.search.is_some() {
             Some("searching")
         } else if self.displayed_tree().has_dir_missing_size() {
             Some("computing sizes")
+        } else if self.displayed_tree().has_dir_missing_count() {
+            Some("counting")
         } else {
             None
         };
+        if let Some(summary) = &self.change_summary {
+            return Status::new(task, Composite::from_inline(summary), false).display(w, screen);
+        }
+        let search_summary = match (&self.filtered_tree, task) {
+            (Some(tree), None) => Some(format!(
+                "{}. Hit *esc* to clear the filter",
+                tree.search_stats.summary(),
+            )),
+            _ => None,
+        };
+        let search_message = || match &search_summary {
+            Some(summary) => Composite::from_inline(summary),
+            None => self.normal_status_message(true),
+        };
+        let has_file_filter = !cmd.file_filter().is_empty();
         match &cmd.action {
-            Action::FuzzyPatternEdit(s) if !s.is_empty() => Status::new(
-                task, self.normal_status_message(true), false
-            ).display(w, screen),
+            Action::FuzzyPatternEdit(s) if !s.is_empty() || has_file_filter => Status::new(
+                task, search_message(), false
+            ).with_info(self.selection_info()).display(w, screen),
             Action::RegexEdit(s, _) if !s.is_empty() => Status::new(
-                task, self.normal_status_message(true), false
-            ).display(w, screen),
+                task, search_message(), false
+            ).with_info(self.selection_info()).display(w, screen),
+            Action::CompositePatternEdit(_) => Status::new(
+                task, search_message(), false
+            ).with_info(self.selection_info()).display(w, screen),
+            Action::PendingChord(first) => {
+                let hint = con.verb_store.chord_hint(*first);
+                Status::new(task, Composite::from_inline(&hint), false).display(w, screen)
+            }
             Action::VerbEdit(invocation) => match con.verb_store.search(&invocation.name) {
                 PrefixSearchResult::NoMatch => Status::new(
                     task, mad_inline!("No matching verb (*?* for the list of verbs)"), true
                 ).display(w, screen),
                 PrefixSearchResult::Match(verb) => {
-                    let line = self.displayed_tree().selected_line();
-                    verb.write_status(w, task, line.path.clone(), invocation, screen)
+                    let tree = self.displayed_tree();
+                    let line = tree.selected_line();
+                    let selection = Selection::of(line);
+                    match con.verb_store.search_for(&invocation.name, &selection) {
+                        PrefixSearchResult::Match(verb) => verb.write_status(
+                            w, task, line.path.to_path_buf(), tree.root(), 1, invocation, screen
+                        ),
+                        _ => {
+                            let text = not_applying(&verb.invocation.name, &selection);
+                            Status::new(task, Composite::from_inline(&text), true).display(w, screen)
+                        }
+                    }
                 }
                 PrefixSearchResult::TooManyMatches => Status::new(
                     task,
@@ -261,7 +582,21 @@ impl AppState for BrowserState {
                     false,
                 ).display(w, screen)
             }
-            _ => Status::new(task, self.normal_status_message(false), false).display(w, screen),
+            _ => {
+                let tree = self.displayed_tree();
+                if tree.options.show_sizes && tree.selection == 0 && task.is_none() {
+                    // the root line has no size column, so its size is given here
+                    let summary = format!(
+                        "Total size: *{}*. Hit *esc* to go back, *enter* to go up, *?* for help",
+                        tree.total_size().format(tree.options.size_format),
+                    );
+                    Status::new(task, Composite::from_inline(&summary), false).display(w, screen)
+                } else {
+                    Status::new(task, self.normal_status_message(false), false)
+                        .with_info(self.selection_info())
+                        .display(w, screen)
+                }
+            }
         }
     }
 
@@ -272,11 +607,17 @@ impl AppState for BrowserState {
         con: &AppContext,
     ) -> Result<AppStateCmdResult, ProgramError> {
         self.pending_pattern = Pattern::None;
-        let page_height = BrowserState::page_height(screen);
+        self.change_summary = None;
+        let page_height = BrowserState::page_height(screen, &self.displayed_tree().options);
         match &cmd.action {
-            Action::Back => {
-                if self.filtered_tree.is_some() {
+            Action::UpTreeFromRoot if self.displayed_tree().selection == 0 => Ok(self.up_tree(screen)),
+            Action::Back | Action::UpTreeFromRoot => {
+                // esc clears the input (pattern or verb), then brings the
+                // selection to the root, then goes back to the previous state
+                if self.filtered_tree.is_some() || self.search.is_some() || !cmd.raw.is_empty() {
                     self.filtered_tree = None;
+                    self.search = None;
+                    self.pending_pattern = Pattern::None;
                     cmd.raw.clear();
                     Ok(AppStateCmdResult::Keep)
                 } else if self.tree.selection > 0 {
@@ -287,17 +628,38 @@ impl AppState for BrowserState {
                     Ok(AppStateCmdResult::PopState)
                 }
             }
+            Action::UpTree => {
+                if self.displayed_tree().selection == 0 {
+                    Ok(self.up_tree(screen))
+                } else {
+                    let tree = self.displayed_tree_mut();
+                    tree.selection = 0;
+                    tree.make_selection_visible(page_height);
+                    Ok(AppStateCmdResult::Keep)
+                }
+            }
+            Action::Expand => {
+                let tree = self.displayed_tree();
+                let line = tree.selected_line();
+                if line.line_type == LineType::Archive {
+                    Ok(self.open_archive(screen))
+                } else {
+                    Ok(AppStateCmdResult::Keep)
+                }
+            }
             Action::MoveSelection(dy) => {
                 self.displayed_tree_mut().move_selection(*dy, page_height);
                 Ok(AppStateCmdResult::Keep)
             }
             Action::Click(_, y) => {
-                let y = *y as i32;
+                let rows = self.displayed_tree().options.rows_per_line();
+                let y = (*y / rows) as i32;
                 self.displayed_tree_mut().try_select_y(y);
                 Ok(AppStateCmdResult::Keep)
             }
             Action::DoubleClick(_, y) => {
-                if self.displayed_tree().selection == *y as usize {
+                let rows = self.displayed_tree().options.rows_per_line();
+                if self.displayed_tree().selection == (*y / rows) as usize {
                     self.open_selection_stay_in_broot(screen, con)
                 } else {
                     // A double click always come after a simple click at
@@ -309,29 +671,85 @@ impl AppState for BrowserState {
             Action::OpenSelection => self.open_selection_stay_in_broot(screen, con),
             Action::AltOpenSelection => self.open_selection_quit_broot(screen, con),
             Action::VerbIndex(index) => {
-                let verb = &con.verb_store.verbs[*index];
-                self.execute_verb(verb, &verb.invocation, screen, con)
+                let selection = Selection::of(self.displayed_tree().selected_line());
+                match con.verb_store.triggered_for(*index, &selection) {
+                    Some(verb) => self.execute_verb(verb, &verb.invocation, screen, con),
+                    None => Ok(AppStateCmdResult::DisplayError(not_applying(
+                        &con.verb_store.verbs[*index].invocation.name,
+                        &selection,
+                    ))),
+                }
             }
-            Action::VerbInvocate(invocation) => match con.verb_store.search(&invocation.name) {
-                PrefixSearchResult::Match(verb) => {
-                    self.execute_verb(verb, &invocation, screen, con)
+            Action::VerbInvocate(invocation) => {
+                let selection = Selection::of(self.displayed_tree().selected_line());
+                match con.verb_store.search_for(&invocation.name, &selection) {
+                    PrefixSearchResult::Match(verb) => {
+                        self.execute_verb(verb, &invocation, screen, con)
+                    }
+                    _ => match con.verb_store.search(&invocation.name) {
+                        PrefixSearchResult::Match(verb) => Ok(AppStateCmdResult::DisplayError(
+                            not_applying(&verb.invocation.name, &selection),
+                        )),
+                        _ => Ok(AppStateCmdResult::verb_not_found(&invocation.name)),
+                    },
                 }
-                _ => Ok(AppStateCmdResult::verb_not_found(&invocation.name)),
-            },
+            }
+            Action::CompleteVerbArg(invocation) => {
+                let invocation = invocation.clone();
+                if let PrefixSearchResult::Match(verb) = con.verb_store.search(&invocation.name) {
+                    #[cfg(unix)]
+                    {
+                        if verb.execution == ":chown" {
+                            // the argument is a user, maybe followed by a group
+                            let typed = invocation.args.as_ref().map_or("", |s| s.trim());
+                            if let Some(owner) = permissions::complete_owner(typed) {
+                                cmd.set_raw(format!("{} {}", &invocation.name, owner));
+                            }
+                            return Ok(AppStateCmdResult::Keep);
+                        }
+                    }
+                    if verb.execution == ":goto" {
+                        // the argument is the name of a bookmark
+                        let typed = invocation.args.as_ref().map_or("", |s| s.trim());
+                        if let Some(name) = Bookmarks::load().best_match(typed) {
+                            cmd.set_raw(format!("{} {}", &invocation.name, name));
+                        }
+                        return Ok(AppStateCmdResult::Keep);
+                    }
+                }
+                // relative paths are completed from the selected directory
+                // or from the directory containing the selected file
+                let line = self.displayed_tree().selected_line();
+                let base_dir = if line.is_dir() {
+                    line.target()
+                } else {
+                    let path = line.path.to_path_buf();
+                    path.parent().unwrap_or(&path).to_path_buf()
+                };
+                if let Some(raw) = path_completion::complete_last_token(&cmd.raw, &base_dir) {
+                    cmd.set_raw(raw);
+                }
+                Ok(AppStateCmdResult::Keep)
+            }
             Action::FuzzyPatternEdit(pat) => {
-                match pat.len() {
-                    0 => {
+                let pattern = match pat.len() {
+                    0 => Pattern::None,
+                    _ => Pattern::fuzzy(pat, self.tree.options.fuzzy_scoring),
+                };
+                match pattern.filtered(cmd.file_filter()) {
+                    Pattern::None => {
                         self.filtered_tree = None;
+                        self.search = None;
                     }
-                    _ => {
-                        self.pending_pattern = Pattern::fuzzy(pat);
+                    pattern => {
+                        self.pending_pattern = pattern;
                     }
                 }
                 Ok(AppStateCmdResult::Keep)
             }
             Action::RegexEdit(pat, flags) => Ok(match Pattern::regex(pat, flags) {
                 Ok(regex_pattern) => {
-                    self.pending_pattern = regex_pattern;
+                    self.pending_pattern = regex_pattern.filtered(cmd.file_filter());
                     AppStateCmdResult::Keep
                 }
                 Err(e) => {
@@ -339,8 +757,29 @@ impl AppState for BrowserState {
                     AppStateCmdResult::DisplayError(format!("{}", e))
                 }
             }),
+            Action::CompositePatternEdit(pat) => {
+                let pattern = Pattern::composite(pat, self.tree.options.fuzzy_scoring)
+                    .map(|pattern| pattern.filtered(cmd.file_filter()));
+                Ok(match pattern {
+                    Ok(Pattern::None) => {
+                        self.filtered_tree = None;
+                        self.search = None;
+                        AppStateCmdResult::Keep
+                    }
+                    Ok(pattern) => {
+                        self.pending_pattern = pattern;
+                        AppStateCmdResult::Keep
+                    }
+                    Err(e) => AppStateCmdResult::DisplayError(format!("{}", e)),
+                })
+            }
             Action::Help => Ok(AppStateCmdResult::NewState(
-                Box::new(HelpState::new(screen, con)),
+                Box::new(HelpState::new(
+                    screen,
+                    con,
+                    Selection::of(self.displayed_tree().selected_line()),
+                    self.displayed_tree().options.clone(),
+                )),
                 Command::new(),
             )),
             Action::Next => {
@@ -365,31 +804,60 @@ impl AppState for BrowserState {
     /// Stop as soon as the lifetime is expired.
     fn do_pending_task(&mut self, screen: &mut Screen, tl: &TaskLifetime) {
         if self.pending_pattern.is_some() {
-            let start = Instant::now();
+            // the new pattern replaces the search in progress, if any
             let mut options = self.tree.options.clone();
             options.pattern = self.pending_pattern.take();
             let root = self.tree.root().clone();
             let len = self.tree.lines.len() as u16;
-            let mut filtered_tree = match TreeBuilder::from(root, options, len as usize) {
-                Ok(builder) => builder.build(tl),
+            match TreeBuilder::from(root, options, len as usize) {
+                Ok(builder) => {
+                    self.search = Some(builder);
+                    self.search_selection = None;
+                }
                 Err(e) => {
                     warn!("Error while building tree: {:?}", e);
+                    self.search = None;
                     return;
                 }
-            };
-            if let Some(ref mut filtered_tree) = filtered_tree {
-                info!(
-                    "Tree search with pattern {} took {:?}",
-                    &filtered_tree.options.pattern,
-                    start.elapsed()
-                );
-                filtered_tree.try_select_best_match();
-                filtered_tree.make_selection_visible(BrowserState::page_height(screen));
-            } // if none: task was cancelled from elsewhere
-            self.filtered_tree = filtered_tree;
+            }
+        }
+        if let Some(mut builder) = self.search.take() {
+            let start = Instant::now();
+            match builder.gather(tl, Some(start + SEARCH_STEP)) {
+                BuildProgress::Complete => {
+                    let tree = builder.finish();
+                    info!(
+                        "Tree search with pattern {} took {:?}",
+                        &tree.options.pattern,
+                        start.elapsed()
+                    );
+                    self.set_search_result(tree, screen);
+                }
+                BuildProgress::Pending => {
+                    // the lines found until now are displayed while
+                    // the search goes on
+                    if let Some(tree) = builder.partial_tree() {
+                        self.set_search_result(tree, screen);
+                    }
+                    self.search = Some(builder);
+                }
+                BuildProgress::Interrupted => {
+                    // the search will go on after the event is handled,
+                    // unless it brings a new pattern
+                    self.search = Some(builder);
+                }
+            }
             return;
         }
-        self.displayed_tree_mut().fetch_some_missing_dir_size(tl);
+        if self.root_mount_missing() {
+            filesystems::fetch_mount_of(self.displayed_tree().root());
+            return;
+        }
+        if self.displayed_tree().has_dir_missing_size() {
+            self.displayed_tree_mut().fetch_some_missing_dir_size(tl);
+        } else {
+            self.displayed_tree_mut().fetch_some_missing_dir_count(tl);
+        }
     }
 
     fn display(
@@ -399,37 +867,72 @@ impl AppState for BrowserState {
         _con: &AppContext
     ) -> Result<(), ProgramError> {
         screen.goto(w, 0, 0)?;
+        let tree = self.displayed_tree();
+        let height = BrowserState::page_height(screen, &tree.options) as u16
+            * tree.options.rows_per_line();
         let dp = DisplayableTree {
-            tree: &self.displayed_tree(),
+            tree,
             skin: &screen.skin,
             area: termimad::Area {
                 left: 0,
                 top: 0,
                 width: screen.width,
-                height: screen.height - 2,
+                height,
             },
             in_app: true,
         };
-        dp.write_on(w)
+        dp.write_on(w)?;
+        // the rows below a limited tree are cleared
+        for y in height..screen.height - 2 {
+            screen.goto_clear(w, 0, y)?;
+        }
+        Ok(())
+    }
+
+    fn before_confirmed_execution(&mut self) {
+        self.lines_before_verb = Some(self.displayed_tree().lines.clone());
+    }
+
+    fn execute_confirmed(
+        &mut self,
+        execution: &VerbExecution,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<AppStateCmdResult, ProgramError> {
+        Ok(match execution.execution() {
+            #[cfg(unix)]
+            ":chmod" | ":chown" => self.change_permissions(
+                &execution.execution()[1..],
+                execution.args(),
+                execution.paths(),
+                screen,
+                con,
+            ),
+            _ => execution.execute(con)?,
+        })
     }
 
     fn refresh(&mut self, screen: &Screen, _con: &AppContext) -> Command {
-        let page_height = BrowserState::page_height(screen) as usize;
+        let page_height = BrowserState::page_height(screen, &self.tree.options) as usize;
         // refresh the base tree
         if let Err(e) = self.tree.refresh(page_height) {
             warn!("refreshing base tree failed : {:?}", e);
         }
         // refresh the filtered tree, if any
-        Command::from_pattern(
-            if let Some(ref mut tree) = self.filtered_tree {
-                if let Err(e) = tree.refresh(page_height) {
-                    warn!("refreshing filtered tree failed : {:?}", e);
-                }
-                &tree.options.pattern
-            } else {
-                &self.tree.options.pattern
+        if let Some(ref mut tree) = self.filtered_tree {
+            if let Err(e) = tree.refresh(page_height) {
+                warn!("refreshing filtered tree failed : {:?}", e);
             }
-        )
+        }
+        if let Some(before) = self.lines_before_verb.take() {
+            let tree = self.displayed_tree();
+            self.change_summary = tree_diff::summarize(
+                &before,
+                &tree.lines,
+                tree.options.size_format,
+            );
+        }
+        Command::from_pattern(&self.displayed_tree().options.pattern)
     }
 
     /// draw the flags at the bottom right of the screen
@@ -455,4 +958,33 @@ impl AppState for BrowserState {
         screen.skin.flag_value.queue(w, gi_value)?;
         Ok(())
     }
+
+    fn session(&self) -> Option<Session> {
+        let tree = self.displayed_tree();
+        if !tree.options.source.is_local() {
+            return None; // a remote root can't be restored as a local one
+        }
+        Some(Session {
+            root: self.tree.root().clone(),
+            pattern: Command::from_pattern(self.pattern()).raw,
+            selection: Some(tree.selected_line().path.to_path_buf()),
+            toggles: Toggles::of(&tree.options),
+            marked: marks::all(),
+        })
+    }
+
+    fn place(&self) -> Option<Place> {
+        let tree = self.displayed_tree();
+        let mut options = tree.options.clone();
+        options.pattern = self.pattern().clone();
+        Some(Place {
+            root: self.tree.root().clone(),
+            options,
+            selection: Some(tree.selected_line().path.to_path_buf()),
+        })
+    }
+
+    fn tree(&self) -> Option<&Tree> {
+        Some(self.displayed_tree())
+    }
 }
@@ -5,24 +5,40 @@ use directories::UserDirs;
 use crate::{
     app_state::{AppStateCmdResult},
     app_context::AppContext,
+    archives::{self, ArchiveKind},
+    bookmarks::Bookmarks,
     browser_states::BrowserState,
+    clipboard_copy,
     commands::Command,
-    errors::ProgramError,
+    date_format::DateFormat,
+    diff_states::DiffState,
+    errors::{ProgramError, TreeBuildError},
     external,
+    filesystems_states::FilesystemsState,
     flat_tree::Tree,
     help_states::HelpState,
+    jobs,
+    jobs_states::JobsState,
+    marks,
+    path_case,
+    path_completion,
+    preview_states::PreviewState,
     screens::Screen,
+    selection_type::Selection,
     task_sync::TaskLifetime,
-    tree_options::{OptionBool, TreeOptions},
+    tree_options::{FilterStrictness, FollowSymlinks, OptionBool, TreeOptions},
+    tree_sources::{DateKind, TreeSource},
     verb_invocation::VerbInvocation,
     verbs::{Verb, VerbExecutor},
 };
 
 fn focus_path(path: PathBuf, screen: &mut Screen, tree: &Tree) -> AppStateCmdResult {
+    let mut options = tree.options.clone();
+    options.forced_open = None;
     AppStateCmdResult::from_optional_state(
         BrowserState::new(
             path,
-            tree.options.clone(),
+            options,
             screen,
             &TaskLifetime::unlimited(),
         ),
@@ -30,6 +46,70 @@ fn focus_path(path: PathBuf, screen: &mut Screen, tree: &Tree) -> AppStateCmdRes
     )
 }
 
+/// the directory from which a relative path given to a verb is
+/// taken: the selected directory, or the one containing the selected file
+fn selection_dir(tree: &Tree) -> PathBuf {
+    let line = tree.selected_line();
+    if line.is_dir() {
+        if tree.options.source.is_local() {
+            line.target()
+        } else {
+            // links are resolved by the server
+            line.path.to_path_buf()
+        }
+    } else {
+        line.path.to_path_buf().parent().unwrap().to_path_buf()
+    }
+}
+
+/// check that a path typed by the user can be the root of a tree
+/// read from the source
+fn check_root(path: PathBuf, source: &dyn TreeSource) -> Result<PathBuf, TreeBuildError> {
+    let is_dir = match source.metadata(&path) {
+        Ok(md) => md.entry_type.is_dir(),
+        Err(_) => {
+            return Err(TreeBuildError::FileNotFound {
+                path: format!("{:?}", &path),
+            });
+        }
+    };
+    if !is_dir {
+        return Err(TreeBuildError::NotADirectory {
+            path: format!("{:?}", &path),
+        });
+    }
+    if source.is_local() {
+        Ok(path_case::with_real_case(&path.canonicalize().unwrap_or(path)))
+    } else {
+        Ok(path)
+    }
+}
+
+/// the internal verbs reading or changing the files themselves, or
+/// going to local paths, which can't be executed when the tree comes
+/// from a remote server
+const LOCAL_ONLY_VERBS: &[&str] = &[
+    ":bookmark",
+    ":chmod",
+    ":chown",
+    ":extract",
+    ":focus_user_home",
+    ":git_diff",
+    ":goto",
+    ":hard_links",
+    ":open_in_fm",
+    ":preview",
+    ":reveal",
+    ":tar",
+    ":zip",
+];
+
+/// tell whether the verb may be executed on a remote tree: the
+/// external verbs and some internal ones need local files
+fn works_remotely(verb: &Verb) -> bool {
+    verb.execution.starts_with(':') && !LOCAL_ONLY_VERBS.contains(&verb.execution.as_str())
+}
+
 impl VerbExecutor for BrowserState {
     fn execute_verb(
         &mut self,
@@ -41,32 +121,162 @@ impl VerbExecutor for BrowserState {
         if let Some(err) = verb.match_error(invocation) {
             return Ok(AppStateCmdResult::DisplayError(err));
         }
-        let page_height = BrowserState::page_height(screen);
+        if !self.displayed_tree().options.source.is_local() && !works_remotely(verb) {
+            return Ok(AppStateCmdResult::DisplayError(
+                "this verb can't be executed on a remote server".to_string()
+            ));
+        }
+        let page_height = BrowserState::page_height(screen, &self.displayed_tree().options);
         Ok(match verb.execution.as_ref() {
-            ":back" => AppStateCmdResult::PopState,
-            ":focus" => {
-                let tree = self.displayed_tree_mut();
-                let line = &tree.selected_line();
+            ":back" => AppStateCmdResult::Back,
+            ":bookmark" => {
+                let name = invocation.args.as_ref().map_or("", |s| s.trim());
+                let line = self.displayed_tree().selected_line();
                 let mut path = line.target();
                 if !path.is_dir() {
                     path = path.parent().unwrap().to_path_buf();
                 }
-                focus_path(path, screen, tree)
+                let mut bookmarks = Bookmarks::load();
+                bookmarks.set(name, path);
+                bookmarks.save()?;
+                AppStateCmdResult::Keep
             }
-            ":focus_root" => focus_path(PathBuf::from("/"), screen, self.displayed_tree()),
-            ":up_tree" => match self.displayed_tree().root().parent() {
-                Some(path) => focus_path(path.to_path_buf(), screen,self.displayed_tree()),
-                None => AppStateCmdResult::DisplayError("no parent found".to_string()),
+            #[cfg(unix)]
+            ":chmod" | ":chown" => self.ask_permission_change(verb, invocation, screen, con),
+            ":copy_line" => AppStateCmdResult::DisplayError(
+                "`:copy_line` copies a line of the `:preview` of a file".to_string()
+            ),
+            ":copy_name" => {
+                let path = self.displayed_tree().selected_line().path.to_path_buf();
+                match path.file_name() {
+                    Some(name) => clipboard_copy::copy_to_clipboard(&name.to_string_lossy())?,
+                    None => AppStateCmdResult::DisplayError("no name to copy".to_string()),
+                }
+            }
+            ":copy_path" => {
+                let path = self.displayed_tree().selected_line().path.to_path_buf();
+                clipboard_copy::copy_to_clipboard(&path.to_string_lossy())?
+            }
+            ":dates_format" => {
+                let arg = invocation.args.as_ref().map_or("", |s| s.trim());
+                match arg.parse::<DateFormat>() {
+                    Ok(format) => self.with_new_options(screen, &|o| o.date_format = format.clone()),
+                    Err(e) => AppStateCmdResult::DisplayError(e.to_string()),
+                }
+            }
+            ":dates_kind" => {
+                let arg = invocation.args.as_ref().map_or("", |s| s.trim());
+                match arg.parse::<DateKind>() {
+                    Ok(kind) => self.with_new_options(screen, &|o| {
+                        o.date_kind = kind;
+                        o.show_dates = true;
+                    }),
+                    Err(e) => AppStateCmdResult::DisplayError(e.to_string()),
+                }
+            }
+            ":elevate" => match self.pending_elevation.take() {
+                Some(tokens) => AppStateCmdResult::from(external::Launchable::program(tokens)?),
+                None => AppStateCmdResult::DisplayError(
+                    "no refused permission change to retry".to_string()
+                ),
+            },
+            ":extract" => match archives::locate(&self.displayed_tree().selected_line().path.to_path_buf()) {
+                Some(Ok((archive, inner))) => match archive.extract(&inner) {
+                    Ok(_) => AppStateCmdResult::RefreshState,
+                    Err(e) => AppStateCmdResult::DisplayError(e.to_string()),
+                },
+                Some(Err(e)) => AppStateCmdResult::DisplayError(format!("can't read the archive: {}", e)),
+                None => AppStateCmdResult::DisplayError(
+                    "select an archive or an entry of an archive".to_string()
+                ),
+            },
+            ":export_html" => {
+                let tree = self.displayed_tree();
+                let arg = invocation.args.as_ref().map_or("", |s| s.trim());
+                let path = path_completion::expand_path(arg, &selection_dir(tree));
+                external::export_html(tree, screen, con, &path)?
+            }
+            ":filesystems" => match FilesystemsState::new(self.displayed_tree().options.clone()) {
+                Ok(state) => AppStateCmdResult::NewState(Box::new(state), Command::new()),
+                Err(e) => AppStateCmdResult::DisplayError(format!("can't list the filesystems: {}", e)),
+            },
+            ":filter_strictness" => self.with_new_options(screen, &|o| {
+                o.filter_strictness = match o.filter_strictness {
+                    FilterStrictness::Mixed => FilterStrictness::Dimmed,
+                    FilterStrictness::Dimmed => FilterStrictness::Strict,
+                    FilterStrictness::Strict => FilterStrictness::Mixed,
+                };
+            }),
+            ":focus" => match invocation.args.as_ref().map(|s| s.trim()) {
+                Some(typed) if !typed.is_empty() => {
+                    let tree = self.displayed_tree();
+                    let path = path_completion::expand_path(typed, &selection_dir(tree));
+                    match check_root(path, tree.options.source.as_ref()) {
+                        Ok(path) => focus_path(path, screen, tree),
+                        Err(e) => AppStateCmdResult::DisplayError(e.to_string()),
+                    }
+                }
+                _ => {
+                    let tree = self.displayed_tree();
+                    focus_path(selection_dir(tree), screen, tree)
+                }
             },
+            ":focus_root" => focus_path(PathBuf::from("/"), screen, self.displayed_tree()),
+            ":up_tree" => self.up_tree(screen),
             ":focus_user_home" => match UserDirs::new() {
                 Some(ud) => focus_path(ud.home_dir().to_path_buf(), screen, self.displayed_tree()),
                 None => AppStateCmdResult::DisplayError("no user home directory found".to_string()), // does this happen ?
             },
-            ":help" => {
-                AppStateCmdResult::NewState(Box::new(HelpState::new(screen, con)), Command::new())
+            ":forward" => AppStateCmdResult::Forward,
+            ":git_diff" => match DiffState::new(self.displayed_tree().selected_line().path.to_path_buf()) {
+                Ok(state) => AppStateCmdResult::NewState(Box::new(state), Command::new()),
+                Err(e) => AppStateCmdResult::DisplayError(e),
+            },
+            ":goto" => {
+                let name = invocation.args.as_ref().map_or("", |s| s.trim());
+                match Bookmarks::load().get(name) {
+                    Some(path) if path.is_dir() => {
+                        focus_path(path.clone(), screen, self.displayed_tree())
+                    }
+                    Some(path) => AppStateCmdResult::DisplayError(
+                        format!("{:?} isn't a directory", path),
+                    ),
+                    None => AppStateCmdResult::DisplayError(
+                        format!("no bookmark named {:?}", name),
+                    ),
+                }
+            }
+            ":hard_links" => {
+                let line = self.displayed_tree().selected_line();
+                let md = &line.metadata;
+                if line.is_dir() || md.ino() == 0 {
+                    AppStateCmdResult::DisplayError("select a file to find its hard links".to_string())
+                } else if md.nlink() < 2 {
+                    AppStateCmdResult::DisplayError("this file has no other hard link".to_string())
+                } else {
+                    let inode = (md.dev(), md.ino());
+                    self.with_new_options(screen, &|o| o.same_inode = Some(inode))
+                }
             }
+            ":help" => AppStateCmdResult::NewState(
+                Box::new(HelpState::new(
+                    screen,
+                    con,
+                    Selection::of(self.displayed_tree().selected_line()),
+                    self.displayed_tree().options.clone(),
+                )),
+                Command::new(),
+            ),
+            ":jobs" => AppStateCmdResult::NewState(Box::new(JobsState::new()), Command::new()),
+            ":kill_job" => AppStateCmdResult::DisplayError(
+                "select the job to kill in the `:jobs` screen".to_string()
+            ),
             ":open_stay" => self.open_selection_stay_in_broot(screen, con)?,
             ":open_leave" => self.open_selection_quit_broot(screen, con)?,
+            ":open_in_fm" | ":reveal" => {
+                external::reveal_in_file_manager(&self.displayed_tree().selected_line().path.to_path_buf())?;
+                AppStateCmdResult::Keep
+            }
             ":line_down" => {
                 self.displayed_tree_mut().move_selection(1, page_height);
                 AppStateCmdResult::Keep
@@ -75,6 +285,19 @@ impl VerbExecutor for BrowserState {
                 self.displayed_tree_mut().move_selection(-1, page_height);
                 AppStateCmdResult::Keep
             }
+            ":newer" => {
+                let tree = self.displayed_tree();
+                let arg = invocation.args.as_ref().map_or("", |s| s.trim());
+                let path = path_completion::expand_path(arg, &selection_dir(tree));
+                match tree.options.source.metadata(&path).and_then(|md| md.modified()) {
+                    Ok(reference) => {
+                        self.with_new_options(screen, &|o| o.newer_than = Some(reference))
+                    }
+                    Err(e) => AppStateCmdResult::DisplayError(
+                        format!("can't read the date of {:?}: {}", path, e),
+                    ),
+                }
+            }
             ":page_down" => {
                 let tree = self.displayed_tree_mut();
                 if page_height < tree.lines.len() as i32 {
@@ -89,7 +312,7 @@ impl VerbExecutor for BrowserState {
                 }
                 AppStateCmdResult::Keep
             }
-            ":parent" => match &self.displayed_tree().selected_line().path.parent() {
+            ":parent" => match self.displayed_tree().selected_line().path.to_path_buf().parent() {
                 Some(path) => AppStateCmdResult::from_optional_state(
                     BrowserState::new(
                         path.to_path_buf(),
@@ -101,10 +324,33 @@ impl VerbExecutor for BrowserState {
                 ),
                 None => AppStateCmdResult::DisplayError("no parent found".to_string()),
             },
+            ":preview" => {
+                let tree = self.displayed_tree();
+                let line = tree.selected_line();
+                if line.is_dir() {
+                    AppStateCmdResult::DisplayError("select a file to preview it".to_string())
+                } else if line.metadata.in_archive {
+                    AppStateCmdResult::DisplayError(
+                        "this file is in an archive: `:extract` it first".to_string()
+                    )
+                } else {
+                    match PreviewState::new(
+                        line.target(),
+                        tree.root().to_path_buf(),
+                        tree.options.fuzzy_scoring,
+                        screen,
+                        con,
+                    ) {
+                        Ok(state) => AppStateCmdResult::NewState(Box::new(state), Command::new()),
+                        Err(e) => AppStateCmdResult::DisplayError(format!("can't read the file: {}", e)),
+                    }
+                }
+            }
             ":print_path" => {
                 external::print_path(&self.displayed_tree().selected_line().target(), con)?
             }
             ":print_tree" => external::print_tree(&self.displayed_tree(), screen, con)?,
+            ":redo" => AppStateCmdResult::Redo,
             ":refresh" => AppStateCmdResult::RefreshState,
             ":select_first" => {
                 self.displayed_tree_mut().try_select_first();
@@ -114,11 +360,22 @@ impl VerbExecutor for BrowserState {
                 self.displayed_tree_mut().try_select_last();
                 AppStateCmdResult::Keep
             }
+            ":toggle_counts" => self.with_new_options(screen, &|o| o.show_counts ^= true),
             ":toggle_dates" => self.with_new_options(screen, &|o| o.show_dates ^= true),
+            ":toggle_depth_first" => self.with_new_options(screen, &|o| o.depth_first ^= true),
             ":toggle_files" => {
                 self.with_new_options(screen, &|o: &mut TreeOptions| o.only_folders ^= true)
             }
+            ":toggle_follow_symlinks" => self.with_new_options(screen, &|o| {
+                o.follow_symlinks = match o.follow_symlinks {
+                    FollowSymlinks::Never => FollowSymlinks::WhenExplicit,
+                    FollowSymlinks::WhenExplicit => FollowSymlinks::Always,
+                    FollowSymlinks::Always => FollowSymlinks::Never,
+                };
+            }),
+            ":toggle_grouping" => self.with_new_options(screen, &|o| o.grouping ^= true),
             ":toggle_hidden" => self.with_new_options(screen, &|o| o.show_hidden ^= true),
+            ":toggle_inodes" => self.with_new_options(screen, &|o| o.show_inodes ^= true),
             ":toggle_git_ignore" => self.with_new_options(screen, &|options| {
                 options.respect_git_ignore = match options.respect_git_ignore {
                     OptionBool::Auto => {
@@ -132,16 +389,87 @@ impl VerbExecutor for BrowserState {
                     OptionBool::No => OptionBool::Yes,
                 };
             }),
+            ":toggle_link_target_sizes" => {
+                self.with_new_options(screen, &|o| o.link_target_sizes ^= true)
+            }
+            ":toggle_mark" => {
+                marks::toggle(&self.displayed_tree().selected_line().path.to_path_buf());
+                AppStateCmdResult::Keep
+            }
             ":toggle_perm" => self.with_new_options(screen, &|o| o.show_permissions ^= true),
+            ":toggle_show_all" => self.with_new_options(screen, &|o| o.show_all ^= true),
             ":toggle_sizes" => self.with_new_options(screen, &|o| o.show_sizes ^= true),
             ":toggle_trim_root" => self.with_new_options(screen, &|o| o.trim_root ^= true),
+            ":toggle_two_rows" => self.with_new_options(screen, &|o| o.two_rows ^= true),
+            ":toggle_xattr" => self.with_new_options(screen, &|o| o.show_xattrs ^= true),
             ":quit" => AppStateCmdResult::Quit,
-            _ => verb.to_cmd_result(
-                &self.displayed_tree().selected_line().path.clone(),
-                &invocation.args,
-                screen,
-                con,
-            )?,
+            ":tar" | ":zip" => {
+                let tree = self.displayed_tree();
+                let arg = invocation.args.as_ref().map_or("", |s| s.trim());
+                let mut dest = path_completion::expand_path(arg, &selection_dir(tree));
+                if ArchiveKind::of(&dest).is_none() {
+                    let extension = if verb.execution == ":zip" { "zip" } else { "tar.gz" };
+                    let mut name = dest.into_os_string();
+                    name.push(".");
+                    name.push(extension);
+                    dest = PathBuf::from(name);
+                }
+                let mut paths = marks::all();
+                if paths.is_empty() {
+                    paths.push(tree.selected_line().path.to_path_buf());
+                }
+                match archives::pack(&dest, &paths) {
+                    Ok(child) => {
+                        let s = if paths.len() == 1 { "" } else { "s" };
+                        let description = format!(
+                            "packing {} file{} in {}",
+                            paths.len(),
+                            s,
+                            dest.to_string_lossy(),
+                        );
+                        jobs::add(description, child);
+                        AppStateCmdResult::Keep
+                    }
+                    Err(e) => AppStateCmdResult::DisplayError(e.to_string()),
+                }
+            }
+            ":undo" => AppStateCmdResult::Undo,
+            ":write_tree" => {
+                let tree = self.displayed_tree();
+                let arg = invocation.args.as_ref().map_or("", |s| s.trim());
+                let path = path_completion::expand_path(arg, &selection_dir(tree));
+                external::write_tree(tree, screen, con, &path)?
+            }
+            _ => {
+                if self.displayed_tree().selected_line().metadata.in_archive {
+                    return Ok(AppStateCmdResult::DisplayError(
+                        "this entry is in an archive: `:extract` it first".to_string()
+                    ));
+                }
+                if !verb.from_shell && !verb.leave_broot {
+                    // the verb may change files, we'll tell what changed
+                    // when the tree is refreshed
+                    self.lines_before_verb = Some(self.displayed_tree().lines.clone());
+                }
+                let result = verb.to_cmd_result(
+                    &self.displayed_tree().selected_line().path.to_path_buf(),
+                    &self.displayed_tree().root().clone(),
+                    1,
+                    &invocation.args,
+                    screen,
+                    con,
+                )?;
+                // when the execution must be confirmed, the lines are
+                // taken again just before it (see before_confirmed_execution)
+                let refreshing = match result {
+                    AppStateCmdResult::RefreshState => true,
+                    _ => false,
+                };
+                if !refreshing {
+                    self.lines_before_verb = None;
+                }
+                result
+            }
         })
     }
 }
@@ -1,6 +1,6 @@
 /// this module manages reading and translating
 /// the arguments passed on launch of the application.
-use std::{env, path::PathBuf, result::Result};
+use std::{env, path::PathBuf, result::Result, sync::Arc};
 
 use crossterm::{
     input::input,
@@ -9,20 +9,86 @@ use crossterm::{
 use termimad::{Alignment, MadSkin};
 
 use crate::{
+    color_support::{self, ColorChoice, ColorSupport},
+    conf::Conf,
     errors::{ProgramError, TreeBuildError},
+    file_sizes::SizeFormat,
+    fuzzy_scoring::{CaseSensitivity, FuzzyScoring},
+    html_export::OutputFormat,
+    path_case,
+    path_filters,
+    remote::{RemoteSource, RemoteUrl},
+    server::Request,
+    snapshots::SnapshotSource,
     tree_options::{OptionBool, TreeOptions},
 };
 
 /// the parsed program launch arguments
 pub struct AppLaunchArgs {
     pub root: PathBuf,                    // what should be the initial root
+    pub root_given: bool,                 // whether the root was given, instead of being the current directory
     pub file_export_path: Option<String>, // where to write the produced path (if required with --out)
+    pub out_marks: bool,                  // whether the marked paths are written in the --out file on quitting
+    pub confirm_quit: bool,               // whether quitting must be confirmed when a task runs or files are marked
+    pub dry_run: bool,                    // whether the commands of the verbs are displayed instead of being executed
+    pub auto_refresh: bool,               // whether the tree is refreshed when the displayed directories change
+    pub elevation_command: Option<String>, // the command to retry a refused permission change with, eg "sudo"
+    pub syntax_theme: Option<String>,    // the theme highlighting the preview, None for no highlighting
+    pub max_highlighted_size: u64,       // the size of the biggest file highlighted in the preview
+    pub color_support: ColorSupport,     // the colors the terminal can display
     pub cmd_export_path: Option<String>, // where to write the produced command (if required with --outcmd or -oc)
     pub tree_options: TreeOptions,       // initial tree options
     pub commands: Option<String>,        // commands passed as cli argument, still unparsed
+    pub cmd_separator: Option<String>,   // the separator of the commands, when they're not separated by spaces
     pub install: bool,                   // installation is required
     pub height: Option<u16>,             // an optional height to replace the screen's one
-    pub no_style: bool,                  // whether to remove all styles (including colors)
+    pub color: ColorChoice,              // whether the trees printed or exported out of the app keep their colors
+    pub no_mouse: bool,                  // whether the mouse is left to the terminal (for text selection)
+    pub output_format: OutputFormat,     // how the tree is printed on leaving broot
+    pub size_format: Option<SizeFormat>, // the unit system of sizes, if given on launch
+    pub fuzzy_case: Option<CaseSensitivity>, // the case sensitivity of fuzzy patterns, if given on launch
+    pub full_permissions: bool,          // whether --full-permissions was given
+    pub two_rows: bool,                  // whether --two-rows was given
+    pub get_path: Option<String>,        // a pattern whose best matches must be printed, without running the app
+    pub limit: usize,                    // the number of paths printed with --get-path
+    pub print: bool,                     // whether the tree is written on stdout instead of running the app
+    pub quick_start: bool,               // whether expensive features are deferred after the first frame
+    pub restore: bool,                   // whether to restore the last saved session
+    pub selection: Option<PathBuf>,      // the path to select in the initial tree, if any
+    pub snapshot: Option<PathBuf>,       // the snapshot file browsed instead of the disk, if any
+    pub write_snapshot: Option<PathBuf>, // where to record a snapshot of the tree, if required
+    pub listen: Option<PathBuf>,         // the socket on which to listen for requests, if any
+    pub send: Option<(PathBuf, Request)>, // a request to send to a listening broot instead of running
+}
+
+impl AppLaunchArgs {
+    /// apply the settings of the configuration, the ones given on
+    /// launch taking precedence. This is done at launch, and again
+    /// when the configuration file is modified
+    pub fn apply_conf(&mut self, conf: &Conf) {
+        self.confirm_quit = conf.confirm_quit;
+        self.auto_refresh = conf.auto_refresh;
+        self.elevation_command = conf.elevation_command.clone();
+        self.syntax_theme = conf.syntax_theme.clone();
+        self.max_highlighted_size = conf.max_highlighted_size;
+        self.color_support = conf.color_support.unwrap_or_else(color_support::detect);
+        let options = &mut self.tree_options;
+        options.do_not_enter = conf.do_not_enter.clone();
+        options.path_filters.special_paths = conf.special_paths.clone();
+        options.column_widths = conf.column_widths.clone();
+        options.size_bar = conf.size_bar;
+        options.date_format = conf.date_format.clone().unwrap_or_default();
+        options.date_kind = conf.date_kind;
+        options.full_permissions = self.full_permissions || conf.full_permissions;
+        options.two_rows = self.two_rows || conf.two_rows;
+        options.max_lines = conf.max_tree_lines;
+        options.show_icons = conf.icons;
+        options.fuzzy_scoring = FuzzyScoring {
+            case: self.fuzzy_case.unwrap_or(conf.fuzzy_scoring.case),
+            ..conf.fuzzy_scoring
+        };
+        options.size_format = self.size_format.or(conf.size_format).unwrap_or_default();
+    }
 }
 
 /// declare the possible CLI arguments, and gets the values
@@ -31,7 +97,7 @@ fn get_cli_args<'a>() -> clap::ArgMatches<'a> {
         .version(env!("CARGO_PKG_VERSION"))
         .author("dystroy <denys.seguret@gmail.com>")
         .about("Balanced tree view + fuzzy search + BFS + customizable launcher")
-        .arg(clap::Arg::with_name("root").help("sets the root directory"))
+        .arg(clap::Arg::with_name("root").help("sets the root directory (or a sftp://[user@]host[:port][/path] url)"))
         .arg(
             clap::Arg::with_name("cmd_export_path")
                 .long("outcmd")
@@ -43,7 +109,24 @@ fn get_cli_args<'a>() -> clap::ArgMatches<'a> {
                 .short("c")
                 .long("cmd")
                 .takes_value(true)
-                .help("commands to execute (space separated, experimental)"),
+                .help("commands to execute (space separated, or separated by --cmd-separator)"),
+        )
+        .arg(
+            clap::Arg::with_name("cmd-separator")
+                .long("cmd-separator")
+                .takes_value(true)
+                .help("separator of the commands of --cmd, each one being a complete input (eg \";\")"),
+        )
+        .arg(
+            clap::Arg::with_name("color")
+                .long("color")
+                .takes_value(true)
+                .help("whether the printed or exported trees keep their colors (auto, always, never)"),
+        )
+        .arg(
+            clap::Arg::with_name("counts")
+                .long("counts")
+                .help("show the number of direct and total children of directories"),
         )
         .arg(
             clap::Arg::with_name("dates")
@@ -51,6 +134,16 @@ fn get_cli_args<'a>() -> clap::ArgMatches<'a> {
                 .long("dates")
                 .help("show the last modified date of files and directories"),
         )
+        .arg(
+            clap::Arg::with_name("depth-first")
+                .long("depth-first")
+                .help("search by exploring each directory before its siblings"),
+        )
+        .arg(
+            clap::Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("display the commands of the verbs instead of executing them"),
+        )
         .arg(
             clap::Arg::with_name("file_export_path")
                 .short("o")
@@ -58,6 +151,12 @@ fn get_cli_args<'a>() -> clap::ArgMatches<'a> {
                 .takes_value(true)
                 .help("where to write the produced path (if any)"),
         )
+        .arg(
+            clap::Arg::with_name("get-path")
+                .long("get-path")
+                .takes_value(true)
+                .help("print the path best matching this pattern, then quit"),
+        )
         .arg(
             clap::Arg::with_name("gitignore")
                 .short("g")
@@ -71,6 +170,39 @@ fn get_cli_args<'a>() -> clap::ArgMatches<'a> {
                 .long("hidden")
                 .help("show hidden files"),
         )
+        .arg(
+            clap::Arg::with_name("follow-symlinks")
+                .long("follow-symlinks")
+                .takes_value(true)
+                .help("whether to enter symlinks to directories (never, when-explicit, always)"),
+        )
+        .arg(
+            clap::Arg::with_name("full-permissions")
+                .long("full-permissions")
+                .help("always show the owner and group columns, even when they're the same for all files"),
+        )
+        .arg(
+            clap::Arg::with_name("fuzzy-case")
+                .long("fuzzy-case")
+                .takes_value(true)
+                .help("case sensitivity of fuzzy patterns (smart, sensitive, insensitive)"),
+        )
+        .arg(
+            clap::Arg::with_name("glob-exclude")
+                .long("glob-exclude")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("never show the files and directories matching this glob (repeatable)"),
+        )
+        .arg(
+            clap::Arg::with_name("glob-include")
+                .long("glob-include")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("only show the files matching one of these globs (repeatable)"),
+        )
         .arg(
             clap::Arg::with_name("height")
                 .long("height")
@@ -82,10 +214,45 @@ fn get_cli_args<'a>() -> clap::ArgMatches<'a> {
                 .long("install")
                 .help("install or reinstall the br shell function"),
         )
+        .arg(
+            clap::Arg::with_name("limit")
+                .long("limit")
+                .takes_value(true)
+                .requires("get-path")
+                .help("number of paths printed with --get-path (default: 1)"),
+        )
+        .arg(
+            clap::Arg::with_name("link-target-sizes")
+                .long("link-target-sizes")
+                .help("show the size of the targets of symlinks instead of nothing"),
+        )
+        .arg(
+            clap::Arg::with_name("listen")
+                .long("listen")
+                .takes_value(true)
+                .help("listen on this unix socket for the requests of other programs"),
+        )
+        .arg(
+            clap::Arg::with_name("max-depth")
+                .long("max-depth")
+                .takes_value(true)
+                .help("don't go deeper than this number of levels under the root"),
+        )
+        .arg(
+            clap::Arg::with_name("max-matches-per-dir")
+                .long("max-matches-per-dir")
+                .takes_value(true)
+                .help("when searching depth first, number of matches after which a directory is left (0 for no limit)"),
+        )
+        .arg(
+            clap::Arg::with_name("no-mouse")
+                .long("no-mouse")
+                .help("don't capture the mouse, so that the terminal's text selection works"),
+        )
         .arg(
             clap::Arg::with_name("no-style")
                 .long("no-style")
-                .help("whether to remove all style and colors"),
+                .help("whether to remove all style and colors (same as --color never)"),
         )
         .arg(
             clap::Arg::with_name("only-folders")
@@ -93,25 +260,104 @@ fn get_cli_args<'a>() -> clap::ArgMatches<'a> {
                 .long("only-folders")
                 .help("only show folders"),
         )
+        .arg(
+            clap::Arg::with_name("out-marks")
+                .long("out-marks")
+                .help("on quitting, write all the marked paths in the --out file, one per line"),
+        )
+        .arg(
+            clap::Arg::with_name("output-format")
+                .long("output-format")
+                .takes_value(true)
+                .help("format of the tree printed on leaving broot (text, html)"),
+        )
         .arg(
             clap::Arg::with_name("permissions")
                 .short("p")
                 .long("permissions")
                 .help("show permissions, with owner and group"),
         )
+        .arg(
+            clap::Arg::with_name("print")
+                .long("print")
+                .help("write the tree on stdout instead of running the application"),
+        )
+        .arg(
+            clap::Arg::with_name("quick-start")
+                .long("quick-start")
+                .help("display a simple tree first, then compute sizes, counts and gitignore rules"),
+        )
+        .arg(
+            clap::Arg::with_name("restore")
+                .long("restore")
+                .alias("resume")
+                .help("restore the root, pattern, selection, toggles and marked paths of the last session"),
+        )
+        .arg(
+            clap::Arg::with_name("send")
+                .long("send")
+                .takes_value(true)
+                .help("send the commands of --cmd, or the root, to the broot listening on this socket (or print its selection), then quit"),
+        )
+        .arg(
+            clap::Arg::with_name("show-all")
+                .long("show-all")
+                .help("fully expand the tree instead of fitting it to the screen"),
+        )
+        .arg(
+            clap::Arg::with_name("size-format")
+                .long("size-format")
+                .takes_value(true)
+                .help("unit system of sizes (binary, si, bytes)"),
+        )
         .arg(
             clap::Arg::with_name("sizes")
                 .short("s")
                 .long("sizes")
                 .help("show the size of files and directories"),
         )
+        .arg(
+            clap::Arg::with_name("snapshot")
+                .long("snapshot")
+                .takes_value(true)
+                .help("browse the tree recorded in a snapshot file instead of the disk"),
+        )
+        .arg(
+            clap::Arg::with_name("two-rows")
+                .long("two-rows")
+                .help("write sizes, dates and permissions on a second row under the name"),
+        )
+        .arg(
+            clap::Arg::with_name("whale")
+                .short("w")
+                .long("whale")
+                .alias("whale-spotting")
+                .help("show hidden files with their sizes relative to their parent, the biggest ones first"),
+        )
+        .arg(
+            clap::Arg::with_name("write-snapshot")
+                .long("write-snapshot")
+                .takes_value(true)
+                .help("record the tree under the root in a snapshot file, then quit"),
+        )
         .get_matches()
 }
 
-/// return the parsed launch arguments
-pub fn read_launch_args() -> Result<AppLaunchArgs, ProgramError> {
-    let cli_args = get_cli_args();
-    let mut root = match cli_args.value_of("root") {
+fn parse_globs(globs: clap::Values<'_>) -> Result<Vec<glob::Pattern>, ProgramError> {
+    globs
+        .map(|g| {
+            path_filters::parse_glob(g).ok_or_else(|| ProgramError::ArgParse {
+                bad: g.to_string(),
+                valid: "a glob, for example \"*.rs\"".to_string(),
+            })
+        })
+        .collect()
+}
+
+/// return the root given as argument (or the current directory),
+/// checking it exists, and replacing a file with its parent
+fn real_root(root_arg: Option<&str>) -> Result<PathBuf, ProgramError> {
+    let mut root = match root_arg {
         Some(path) => {
 
             /*
@@ -148,21 +394,81 @@ pub fn read_launch_args() -> Result<AppLaunchArgs, ProgramError> {
             })?;
         }
     }
-    let root = root.canonicalize()?;
+    Ok(path_case::with_real_case(&root.canonicalize()?))
+}
+
+/// return the parsed launch arguments
+pub fn read_launch_args() -> Result<AppLaunchArgs, ProgramError> {
+    let cli_args = get_cli_args();
+    let snapshot = cli_args.value_of("snapshot").map(PathBuf::from);
     let mut tree_options = TreeOptions::default();
+    let root = match &snapshot {
+        Some(snapshot) => {
+            // the root is the one of the snapshot, which may not exist on disk
+            let source = SnapshotSource::load(snapshot)?;
+            let root = source.root().to_path_buf();
+            tree_options.source = Arc::new(source);
+            root
+        }
+        None => match cli_args.value_of("root").and_then(RemoteUrl::parse) {
+            Some(url) => {
+                let source = RemoteSource::connect(url)?;
+                let root = source.root().to_path_buf();
+                tree_options.source = Arc::new(source);
+                root
+            }
+            None => real_root(cli_args.value_of("root"))?,
+        },
+    };
     tree_options.show_sizes = cli_args.is_present("sizes");
     if tree_options.show_sizes {
         // by default, if we're asked to show the size, we show all files
         tree_options.show_hidden = true;
         tree_options.respect_git_ignore = OptionBool::No;
     }
+    tree_options.link_target_sizes = cli_args.is_present("link-target-sizes");
     tree_options.only_folders = cli_args.is_present("only-folders");
     tree_options.show_hidden = cli_args.is_present("hidden");
     tree_options.show_dates = cli_args.is_present("dates");
+    tree_options.show_counts = cli_args.is_present("counts");
     tree_options.show_permissions = cli_args.is_present("permissions");
+    tree_options.show_all = cli_args.is_present("show-all");
     if let Some(respect_ignore) = cli_args.value_of("gitignore") {
         tree_options.respect_git_ignore = respect_ignore.parse()?;
     }
+    if let Some(follow_symlinks) = cli_args.value_of("follow-symlinks") {
+        tree_options.follow_symlinks = follow_symlinks.parse()?;
+    }
+    if let Some(globs) = cli_args.values_of("glob-include") {
+        tree_options.path_filters.includes = parse_globs(globs)?;
+    }
+    if let Some(globs) = cli_args.values_of("glob-exclude") {
+        tree_options.path_filters.excludes = parse_globs(globs)?;
+    }
+    tree_options.depth_first = cli_args.is_present("depth-first");
+    if cli_args.is_present("whale") {
+        tree_options.whale_spotting = true;
+        tree_options.show_sizes = true;
+        tree_options.show_hidden = true;
+        tree_options.respect_git_ignore = OptionBool::No;
+    }
+    if let Some(max) = cli_args.value_of("max-matches-per-dir") {
+        tree_options.max_matches_per_dir = max.parse().map_err(|_| ProgramError::ArgParse {
+            bad: max.to_string(),
+            valid: "a positive integer".to_string(),
+        })?;
+    }
+    if let Some(max) = cli_args.value_of("max-depth") {
+        tree_options.max_depth = match max.parse() {
+            Ok(max) if max > 0 => Some(max),
+            _ => {
+                return Err(ProgramError::ArgParse {
+                    bad: max.to_string(),
+                    valid: "a strictly positive integer".to_string(),
+                });
+            }
+        };
+    }
     let install = cli_args.is_present("install");
     let file_export_path = cli_args
         .value_of("file_export_path")
@@ -173,17 +479,86 @@ pub fn read_launch_args() -> Result<AppLaunchArgs, ProgramError> {
     let commands = cli_args
         .value_of("commands")
         .and_then(|s| Some(s.to_owned()));
-    let no_style = cli_args.is_present("no-style");
+    // --no-style is the old way to ask for no color
+    let color = if cli_args.is_present("no-style") {
+        ColorChoice::Never
+    } else {
+        cli_args
+            .value_of("color")
+            .map(|s| s.parse::<ColorChoice>())
+            .transpose()?
+            .unwrap_or_default()
+    };
+    let quick_start = cli_args.is_present("quick-start");
+    let restore = cli_args.is_present("restore");
     let height = cli_args.value_of("height").and_then(|s| s.parse().ok());
+    let size_format = cli_args
+        .value_of("size-format")
+        .map(|s| s.parse::<SizeFormat>())
+        .transpose()?;
+    let fuzzy_case = cli_args
+        .value_of("fuzzy-case")
+        .map(|s| s.parse::<CaseSensitivity>())
+        .transpose()?;
+    let limit = match cli_args.value_of("limit") {
+        Some(limit) => limit.parse().map_err(|_| ProgramError::ArgParse {
+            bad: limit.to_string(),
+            valid: "a positive integer".to_string(),
+        })?,
+        None => 1,
+    };
+    let output_format = cli_args
+        .value_of("output-format")
+        .map(|s| s.parse::<OutputFormat>())
+        .transpose()?
+        .unwrap_or(OutputFormat::Text);
+    // in client mode, the request is made of the commands, or
+    // else of the root if one is given
+    let send = cli_args.value_of("send").map(|socket| {
+        let request = if let Some(commands) = &commands {
+            Request::Command(commands.clone())
+        } else if cli_args.value_of("root").is_some() {
+            Request::Root(root.clone())
+        } else {
+            Request::GetSelection
+        };
+        (PathBuf::from(socket), request)
+    });
     Ok(AppLaunchArgs {
         root,
+        root_given: snapshot.is_some() || cli_args.value_of("root").is_some(),
         file_export_path,
+        out_marks: cli_args.is_present("out-marks"),
+        confirm_quit: false, // only set from the configuration
+        dry_run: cli_args.is_present("dry-run"),
+        auto_refresh: false, // only set from the configuration
+        elevation_command: None, // only set from the configuration
+        syntax_theme: None, // only set from the configuration
+        max_highlighted_size: 0, // only set from the configuration
+        color_support: ColorSupport::TrueColor, // only set from the configuration
         cmd_export_path,
         tree_options,
         commands,
+        cmd_separator: cli_args.value_of("cmd-separator").map(String::from),
         install,
         height,
-        no_style,
+        color,
+        no_mouse: cli_args.is_present("no-mouse"),
+        output_format,
+        size_format,
+        fuzzy_case,
+        full_permissions: cli_args.is_present("full-permissions"),
+        two_rows: cli_args.is_present("two-rows"),
+        get_path: cli_args.value_of("get-path").map(String::from),
+        limit,
+        print: cli_args.is_present("print"),
+        quick_start,
+        restore,
+        selection: None,
+        snapshot,
+        write_snapshot: cli_args.value_of("write-snapshot").map(PathBuf::from),
+        listen: cli_args.value_of("listen").map(PathBuf::from),
+        send,
     })
 }
 
@@ -197,6 +572,18 @@ pub fn ask_authorization() -> Result<bool, ProgramError> {
     })
 }
 
+/// wait for user input, return `true` only if she
+/// explicitly answered 'y' or 'yes'
+pub fn ask_explicit_authorization() -> Result<bool, ProgramError> {
+    let answer = input().read_line()?;
+    Ok(is_yes(&answer))
+}
+
+fn is_yes(answer: &str) -> bool {
+    let answer = answer.trim();
+    answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes")
+}
+
 /// build a termimad skin for cli output (mostly
 /// for the install process)
 pub fn mad_skin() -> MadSkin {
@@ -207,3 +594,19 @@ pub fn mad_skin() -> MadSkin {
     skin.code_block.align = Alignment::Center;
     skin
 }
+
+#[cfg(test)]
+mod authorization_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_explicit_answers() {
+        assert!(is_yes("y"));
+        assert!(is_yes("Yes\n"));
+        assert!(!is_yes(""));
+        assert!(!is_yes("no"));
+        assert!(!is_yes("q"));
+        assert!(!is_yes("yep"));
+    }
+}
@@ -0,0 +1,124 @@
+//! copying a text, for example the selected path, to the clipboard.
+//!
+//! The clipboard of the system is used when broot is built with the
+//! `clipboard` feature (the default) and there's one. Otherwise, and in
+//! SSH sessions where the system clipboard would be the one of the remote
+//! host, the text is given to the terminal in an OSC 52 escape sequence,
+//! which most terminal emulators understand (some need it allowed in
+//! their settings).
+
+use std::{
+    env,
+    io::{self, Write},
+};
+
+use crate::app_state::AppStateCmdResult;
+
+/// how a text was copied
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CopyMethod {
+    System,
+    Terminal, // the terminal may have refused it without telling
+}
+
+const BASE64_CHARS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_CHARS[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// build the escape sequence asking the terminal to put the text in
+/// its clipboard. Inside tmux, the sequence is wrapped so that tmux
+/// passes it to the terminal
+fn osc52_sequence(text: &str, in_tmux: bool) -> String {
+    let osc = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+    if in_tmux {
+        format!("\x1bPtmux;{}\x1b\\", osc.replace('\x1b', "\x1b\x1b"))
+    } else {
+        osc
+    }
+}
+
+fn in_ssh_session() -> bool {
+    env::var_os("SSH_CONNECTION").is_some() || env::var_os("SSH_TTY").is_some()
+}
+
+#[cfg(feature = "clipboard")]
+fn copy_with_system(text: &str) -> Result<(), String> {
+    use clipboard::{ClipboardContext, ClipboardProvider};
+    let mut ctx: ClipboardContext = ClipboardProvider::new().map_err(|e| e.to_string())?;
+    ctx.set_contents(text.to_string()).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn copy_with_system(_text: &str) -> Result<(), String> {
+    Err("broot was built without the clipboard feature".to_string())
+}
+
+fn copy_with_terminal(text: &str) -> io::Result<()> {
+    let mut w = crate::io::writer();
+    write!(w, "{}", osc52_sequence(text, env::var_os("TMUX").is_some()))?;
+    w.flush()
+}
+
+fn copy(text: &str) -> io::Result<CopyMethod> {
+    if !in_ssh_session() {
+        match copy_with_system(text) {
+            Ok(()) => {
+                return Ok(CopyMethod::System);
+            }
+            Err(e) => {
+                debug!("system clipboard not available: {}", e);
+            }
+        }
+    }
+    copy_with_terminal(text)?;
+    Ok(CopyMethod::Terminal)
+}
+
+/// copy the text to the clipboard and return the result
+/// telling the user what was copied
+pub fn copy_to_clipboard(text: &str) -> io::Result<AppStateCmdResult> {
+    let message = match copy(text)? {
+        CopyMethod::System => format!("Copied to the clipboard: `{}`", text),
+        CopyMethod::Terminal => format!("Sent to the clipboard of the terminal: `{}`", text),
+    };
+    Ok(AppStateCmdResult::DisplayMessage(message))
+}
+
+#[cfg(test)]
+mod clipboard_copy_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"/home/dys"), "L2hvbWUvZHlz");
+        assert_eq!(base64("café".as_bytes()), "Y2Fmw6k=");
+    }
+
+    #[test]
+    fn check_osc52_sequences() {
+        assert_eq!(osc52_sequence("foo", false), "\x1b]52;c;Zm9v\x07");
+        assert_eq!(osc52_sequence("foo", true), "\x1bPtmux;\x1b\x1b]52;c;Zm9v\x07\x1b\\");
+    }
+}
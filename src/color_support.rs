@@ -0,0 +1,285 @@
+//! the colors the terminal can display, and the degradation of the
+//! colors of a skin so that a skin defined with RGB values still looks
+//! right on a terminal only knowing 256 or 16 colors.
+//!
+//! This module also guesses whether the background of the terminal is
+//! dark or light, to choose between the variants of the skin.
+
+use std::{env, str::FromStr};
+
+use crossterm::style::Color;
+
+use crate::errors::ConfError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorSupport {
+    TrueColor, // any RGB color
+    Ansi256,   // the 256 colors of xterm
+    Ansi16,    // only the 16 base colors
+}
+
+impl FromStr for ColorSupport {
+    type Err = ConfError;
+    fn from_str(s: &str) -> Result<ColorSupport, ConfError> {
+        match s.to_lowercase().as_ref() {
+            "truecolor" | "24bit" => Ok(ColorSupport::TrueColor),
+            "256" | "ansi256" => Ok(ColorSupport::Ansi256),
+            "16" | "ansi16" => Ok(ColorSupport::Ansi16),
+            _ => Err(ConfError::InvalidColorSupport { raw: s.to_string() }),
+        }
+    }
+}
+
+/// whether the trees written out of the application (printed on
+/// leaving broot, written in a file or on stdout) keep the colors
+/// of the skin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    Auto,   // colors only when they're expected, eg on a terminal
+    Always, // colors even in a file or through a pipe
+    Never,  // no style at all
+}
+
+impl Default for ColorChoice {
+    fn default() -> Self {
+        ColorChoice::Auto
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = ConfError;
+    fn from_str(s: &str) -> Result<ColorChoice, ConfError> {
+        match s.to_lowercase().as_ref() {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" | "yes" => Ok(ColorChoice::Always),
+            "never" | "no" => Ok(ColorChoice::Never),
+            _ => Err(ConfError::InvalidColorChoice { raw: s.to_string() }),
+        }
+    }
+}
+
+impl ColorChoice {
+    /// tell whether the colors are kept, `auto_colors` being the
+    /// choice of the auto mode for the destination
+    pub fn keeps_colors(self, auto_colors: bool) -> bool {
+        match self {
+            ColorChoice::Auto => auto_colors,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// the brightness of the background of the terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Background {
+    Dark,
+    Light,
+}
+
+impl FromStr for Background {
+    type Err = ConfError;
+    fn from_str(s: &str) -> Result<Background, ConfError> {
+        match s.to_lowercase().as_ref() {
+            "dark" => Ok(Background::Dark),
+            "light" => Ok(Background::Light),
+            _ => Err(ConfError::InvalidBackground { raw: s.to_string() }),
+        }
+    }
+}
+
+/// read the background in the value of the COLORFGBG variable,
+/// which is like "15;0" (white on black), the last part being
+/// the background as one of the 16 base colors
+fn background_from_colorfgbg(colorfgbg: &str) -> Option<Background> {
+    let bg: u8 = colorfgbg.rsplit(';').next()?.parse().ok()?;
+    match bg {
+        7 | 9..=15 => Some(Background::Light),
+        0..=6 | 8 => Some(Background::Dark),
+        _ => None,
+    }
+}
+
+/// guess the background of the terminal, from the BROOT_BACKGROUND
+/// variable ("dark" or "light") or else from the COLORFGBG one,
+/// which some terminals set
+pub fn detect_background() -> Option<Background> {
+    if let Ok(background) = env::var("BROOT_BACKGROUND") {
+        if let Ok(background) = background.parse() {
+            return Some(background);
+        }
+    }
+    env::var("COLORFGBG")
+        .ok()
+        .and_then(|colorfgbg| background_from_colorfgbg(&colorfgbg))
+}
+
+/// guess the colors supported by a terminal from the values of
+/// the COLORTERM and TERM variables. Nearly all terminal emulators
+/// handle 256 colors, even when their TERM doesn't tell it (eg xterm,
+/// screen or tmux), so only the consoles known to be limited get
+/// the 16 base colors
+fn support_of(colorterm: Option<&str>, term: Option<&str>) -> ColorSupport {
+    if let Some(colorterm) = colorterm {
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return ColorSupport::TrueColor;
+        }
+    }
+    match term {
+        Some(term) => {
+            if term.contains("direct") {
+                ColorSupport::TrueColor
+            } else if term == "linux" || term == "dumb" || term.starts_with("vt") {
+                ColorSupport::Ansi16
+            } else {
+                ColorSupport::Ansi256
+            }
+        }
+        // on windows, TERM isn't set and the console handles RGB
+        None if cfg!(windows) => ColorSupport::TrueColor,
+        None => ColorSupport::Ansi256,
+    }
+}
+
+/// guess the colors supported by the terminal from the
+/// environment (the COLORTERM and TERM variables)
+pub fn detect() -> ColorSupport {
+    support_of(
+        env::var("COLORTERM").ok().as_deref(),
+        env::var("TERM").ok().as_deref(),
+    )
+}
+
+/// the 16 base colors, as rendered by xterm
+const BASE: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+    (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+    (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+    (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+];
+
+/// the levels of the components in the 6x6x6 cube of the 256 colors
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// return the RGB components of one of the 256 colors of an
+/// ANSI terminal, as computed by xterm
+pub fn ansi_rgb(v: u8) -> (u8, u8, u8) {
+    match v {
+        0..=15 => BASE[v as usize],
+        16..=231 => {
+            // a 6x6x6 cube
+            let v = v - 16;
+            (
+                CUBE_LEVELS[(v / 36) as usize],
+                CUBE_LEVELS[((v / 6) % 6) as usize],
+                CUBE_LEVELS[(v % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + 10 * (v - 232);
+            (level, level, level)
+        }
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (i32::from(x) - i32::from(y)).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// return the nearest of the 256 colors which aren't the base ones
+/// (those may be redefined by the terminal's theme)
+fn nearest_ansi256(rgb: (u8, u8, u8)) -> u8 {
+    let nearest_level = |c: u8| {
+        (0..6)
+            .min_by_key(|&i| (i32::from(CUBE_LEVELS[i]) - i32::from(c)).abs())
+            .unwrap_or(0) as u8
+    };
+    let cube = 16 + 36 * nearest_level(rgb.0) + 6 * nearest_level(rgb.1) + nearest_level(rgb.2);
+    let mean = ((u32::from(rgb.0) + u32::from(rgb.1) + u32::from(rgb.2)) / 3) as u8;
+    let gray = 232 + (mean.saturating_sub(3) / 10).min(23);
+    if distance(ansi_rgb(gray), rgb) < distance(ansi_rgb(cube), rgb) {
+        gray
+    } else {
+        cube
+    }
+}
+
+/// return the nearest of the 16 base colors
+fn nearest_ansi16(rgb: (u8, u8, u8)) -> Color {
+    let idx = (0..16)
+        .min_by_key(|&i| distance(BASE[i], rgb))
+        .unwrap_or(0);
+    match idx {
+        0 => Color::Black,
+        1 => Color::DarkRed,
+        2 => Color::DarkGreen,
+        3 => Color::DarkYellow,
+        4 => Color::DarkBlue,
+        5 => Color::DarkMagenta,
+        6 => Color::DarkCyan,
+        7 => Color::Grey,
+        8 => Color::DarkGrey,
+        9 => Color::Red,
+        10 => Color::Green,
+        11 => Color::Yellow,
+        12 => Color::Blue,
+        13 => Color::Magenta,
+        14 => Color::Cyan,
+        _ => Color::White,
+    }
+}
+
+/// return the color the terminal can display which is the
+/// nearest from the given one
+pub fn degrade(color: Color, support: ColorSupport) -> Color {
+    match (support, color) {
+        (ColorSupport::TrueColor, _) => color,
+        (ColorSupport::Ansi256, Color::Rgb { r, g, b }) => {
+            Color::AnsiValue(nearest_ansi256((r, g, b)))
+        }
+        (ColorSupport::Ansi16, Color::Rgb { r, g, b }) => nearest_ansi16((r, g, b)),
+        (ColorSupport::Ansi16, Color::AnsiValue(v)) => nearest_ansi16(ansi_rgb(v)),
+        _ => color,
+    }
+}
+
+#[cfg(test)]
+mod color_degradation_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_degradation() {
+        let rgb = |r, g, b| Color::Rgb { r, g, b };
+        assert_eq!(degrade(rgb(255, 0, 0), ColorSupport::TrueColor), rgb(255, 0, 0));
+        assert_eq!(degrade(rgb(255, 0, 0), ColorSupport::Ansi256), Color::AnsiValue(196));
+        assert_eq!(degrade(rgb(255, 152, 0), ColorSupport::Ansi256), Color::AnsiValue(208));
+        assert_eq!(degrade(rgb(128, 128, 128), ColorSupport::Ansi256), Color::AnsiValue(244));
+        assert_eq!(degrade(rgb(250, 10, 10), ColorSupport::Ansi16), Color::Red);
+        assert_eq!(degrade(Color::AnsiValue(196), ColorSupport::Ansi16), Color::Red);
+        assert_eq!(degrade(Color::AnsiValue(110), ColorSupport::Ansi256), Color::AnsiValue(110));
+        assert_eq!(degrade(Color::Magenta, ColorSupport::Ansi16), Color::Magenta);
+    }
+
+    #[test]
+    fn check_support_detection() {
+        assert_eq!(support_of(Some("truecolor"), Some("xterm")), ColorSupport::TrueColor);
+        assert_eq!(support_of(None, Some("xterm-direct")), ColorSupport::TrueColor);
+        assert_eq!(support_of(None, Some("xterm-256color")), ColorSupport::Ansi256);
+        assert_eq!(support_of(None, Some("xterm")), ColorSupport::Ansi256);
+        assert_eq!(support_of(None, Some("screen")), ColorSupport::Ansi256);
+        assert_eq!(support_of(None, Some("tmux")), ColorSupport::Ansi256);
+        assert_eq!(support_of(None, Some("linux")), ColorSupport::Ansi16);
+        assert_eq!(support_of(None, Some("vt100")), ColorSupport::Ansi16);
+        assert_eq!(support_of(None, Some("dumb")), ColorSupport::Ansi16);
+    }
+
+    #[test]
+    fn check_colorfgbg_parsing() {
+        assert_eq!(background_from_colorfgbg("15;0"), Some(Background::Dark));
+        assert_eq!(background_from_colorfgbg("0;15"), Some(Background::Light));
+        assert_eq!(background_from_colorfgbg("0;default;7"), Some(Background::Light));
+        assert_eq!(background_from_colorfgbg("15;default"), None);
+    }
+}
@@ -0,0 +1,158 @@
+//! constraints on the widths of the columns displayed
+//! before the names (sizes, owners and groups), and the
+//! computation of the widths of strings in the terminal.
+//!
+//! Widths are counted in terminal columns: a CJK char or an
+//! emoji takes two of them, a combining mark none.
+
+use std::str::FromStr;
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::errors::ConfError;
+
+/// the optional bounds of the width of a column
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ColumnWidth {
+    pub min: Option<usize>,
+    pub max: Option<usize>,
+}
+
+impl ColumnWidth {
+    /// return the width to use for a column whose content needs `width` chars
+    pub fn clamp(self, width: usize) -> usize {
+        let width = self.max.map_or(width, |max| width.min(max));
+        self.min.map_or(width, |min| width.max(min))
+    }
+}
+
+/// the columns whose width can be constrained
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    Size,
+    User,
+    Group,
+}
+
+impl FromStr for Column {
+    type Err = ConfError;
+    fn from_str(s: &str) -> Result<Column, ConfError> {
+        match s {
+            "size" => Ok(Column::Size),
+            "user" => Ok(Column::User),
+            "group" => Ok(Column::Group),
+            _ => Err(ConfError::InvalidColumn {
+                raw: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ColumnWidths {
+    pub size: ColumnWidth,
+    pub user: ColumnWidth,
+    pub group: ColumnWidth,
+}
+
+impl ColumnWidths {
+    pub fn set(&mut self, column: Column, width: ColumnWidth) {
+        match column {
+            Column::Size => self.size = width,
+            Column::User => self.user = width,
+            Column::Group => self.group = width,
+        }
+    }
+}
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+/// return the number of columns the char takes in the terminal
+/// (control chars are counted as taking none)
+pub fn char_width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+/// return the number of columns the string takes in the terminal
+pub fn str_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// a char with the zero width chars following it (eg combining
+/// marks, or the parts of an emoji sequence), which are displayed
+/// together and must never be separated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cluster {
+    pub start: usize, // index of the first char
+    pub end: usize,   // index after the last char
+    pub width: usize, // the number of columns
+}
+
+/// split the string in clusters of chars
+pub fn clusters(s: &str) -> Vec<Cluster> {
+    let mut clusters: Vec<Cluster> = Vec::new();
+    let mut joined = false; // whether the previous char joins the next one
+    for (i, c) in s.chars().enumerate() {
+        let width = char_width(c);
+        let extends = (width == 0 || joined) && !clusters.is_empty();
+        joined = c == ZERO_WIDTH_JOINER;
+        if extends {
+            if let Some(cluster) = clusters.last_mut() {
+                cluster.end = i + 1;
+            }
+        } else {
+            clusters.push(Cluster { start: i, end: i + 1, width });
+        }
+    }
+    clusters
+}
+
+/// return the string, truncated with an ellipsis if it's
+/// wider than the width
+pub fn fit(s: &str, width: usize) -> String {
+    if str_width(s) <= width {
+        s.to_string()
+    } else if width == 0 {
+        String::new()
+    } else {
+        let chars: Vec<char> = s.chars().collect();
+        let mut fitted = String::new();
+        let mut used = 0;
+        for cluster in clusters(s) {
+            if used + cluster.width > width - 1 {
+                break;
+            }
+            used += cluster.width;
+            fitted.extend(&chars[cluster.start..cluster.end]);
+        }
+        fitted.push('…');
+        fitted
+    }
+}
+
+/// return the string followed by the spaces needed to fill the
+/// width (format's padding counts chars, not columns)
+pub fn pad(s: &str, width: usize) -> String {
+    let mut padded = s.to_string();
+    for _ in str_width(s)..width {
+        padded.push(' ');
+    }
+    padded
+}
+
+#[cfg(test)]
+mod width_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_widths() {
+        assert_eq!(str_width("broot"), 5);
+        assert_eq!(str_width("文件"), 4);
+        assert_eq!(str_width("e\u{301}t\u{301}e\u{301}"), 3); // combining acute accents
+        assert_eq!(clusters("e\u{301}x").len(), 2);
+        assert_eq!(fit("文件名称", 5), "文件…");
+        assert_eq!(fit("e\u{301}t\u{301}e\u{301}", 2), "e\u{301}…");
+        assert_eq!(pad("文", 4), "文  ");
+    }
+}
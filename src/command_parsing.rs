@@ -1,11 +1,13 @@
 //! this mod achieves the transformation of a string containing
-//! one or several commands into a vec of parsed commands, using
-//! the verbstore to try guess what part is an argument and what
-//! part is a filter
+//! one or several commands into a vec of parsed commands.
+//!
+//! When a separator is given, each part is a complete input. Without
+//! separator, the verbstore is used to try guess what part is an
+//! argument and what part is a filter
 
 use crate::{
     app_context::AppContext,
-    commands::Command,
+    commands::{Action, Command},
     errors::ProgramError,
     verb_store::PrefixSearchResult,
 };
@@ -59,18 +61,70 @@ impl Iterator for CommandSequenceTokenizer {
     }
 }
 
-/// parse a string which is meant as a sequence of commands.
+/// parse a string which is meant as a sequence of commands,
+/// separated by the separator if there's one (see --cmd-separator)
+pub fn parse_command_sequence(
+    sequence: &str,
+    separator: Option<&str>,
+    con: &AppContext,
+) -> Result<Vec<Command>, ProgramError> {
+    match separator {
+        Some(separator) if !separator.is_empty() => {
+            parse_separated_sequence(sequence, separator, con)
+        }
+        _ => parse_spaced_sequence(sequence, con),
+    }
+}
+
+/// parse a sequence whose commands are separated by a hard separator.
+/// Each part is an input, parsed as the input of the application: a
+/// filter, then a verb after a space or a ':', the verbs being executed
+/// as if the user hit enter after typing them, so that their arguments
+/// may contain spaces (eg `main;:cp ../other dir/`).
+fn parse_separated_sequence(
+    sequence: &str,
+    separator: &str,
+    con: &AppContext,
+) -> Result<Vec<Command>, ProgramError> {
+    let mut commands: Vec<Command> = Vec::new();
+    for part in sequence.split(separator).map(str::trim) {
+        if part.is_empty() {
+            continue;
+        }
+        let mut command = Command::from(part.to_string());
+        if let Action::VerbEdit(invocation) = &command.action {
+            // the part is complete, as if the user hit enter after it
+            command.action = Action::VerbInvocate(invocation.clone());
+        }
+        if let Action::VerbInvocate(invocation) = &command.action {
+            let name = invocation.name.clone();
+            let verb = match con.verb_store.search(&name) {
+                PrefixSearchResult::NoMatch => {
+                    return Err(ProgramError::UnknownVerb { name });
+                }
+                PrefixSearchResult::TooManyMatches => {
+                    return Err(ProgramError::AmbiguousVerbName { name });
+                }
+                PrefixSearchResult::Match(verb) => verb,
+            };
+            if verb.match_error(invocation).is_some() {
+                return Err(ProgramError::UnmatchingVerbArgs { name });
+            }
+        }
+        commands.push(command);
+    }
+    Ok(commands)
+}
+
+/// parse a string whose commands are separated by spaces.
 /// Note that this is inherently flawed as packing several commands
 /// into a string without hard separator is ambiguous in the general
 /// case.
 ///
-/// In the future I might introduce a way to define a variable hard separator
-/// (for example "::sep=#:some_filter#:some command with three arguments#a_filter")
-///
-/// The current parsing try to be the least possible flawed by
+/// The parsing try to be the least possible flawed by
 /// giving verbs the biggest sequence of tokens accepted by their
 /// execution pattern.
-pub fn parse_command_sequence(
+fn parse_spaced_sequence(
     sequence: &str,
     con: &AppContext,
 ) -> Result<Vec<Command>, ProgramError> {
@@ -134,3 +188,67 @@ pub fn parse_command_sequence(
     }
     Ok(commands)
 }
+
+#[cfg(test)]
+mod command_parsing_tests {
+
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{conf::Conf, test_support, tree_options::TreeOptions};
+
+    fn context() -> AppContext {
+        test_support::app_context(
+            PathBuf::from("."),
+            TreeOptions::default(),
+            &Conf::default_conf(),
+            30,
+        )
+    }
+
+    fn error(sequence: &str, separator: Option<&str>) -> ProgramError {
+        match parse_command_sequence(sequence, separator, &context()) {
+            Ok(commands) => panic!("{:?} should not be parsed: {:?}", sequence, commands),
+            Err(e) => e,
+        }
+    }
+
+    fn raws(sequence: &str, separator: Option<&str>) -> Result<Vec<String>, ProgramError> {
+        parse_command_sequence(sequence, separator, &context())
+            .map(|commands| commands.into_iter().map(|c| c.raw).collect())
+    }
+
+    #[test]
+    fn check_separated_sequences() {
+        assert_eq!(
+            raws("main;:cp ../other dir/;:toggle_sizes;:print_tree", Some(";")).unwrap(),
+            vec!["main", ":cp ../other dir/", ":toggle_sizes", ":print_tree"],
+        );
+        assert_eq!(
+            raws(" main ; rs:cp ../other dir/ ;; ", Some(";")).unwrap(),
+            vec!["main", "rs:cp ../other dir/"],
+        );
+        // a space after the pattern starts a verb
+        match error("my dir;:print_tree", Some(";")) {
+            ProgramError::UnknownVerb { name } => assert_eq!(name, "dir"),
+            e => panic!("unexpected error: {}", e),
+        }
+        match error("main;:cp", Some(";")) {
+            ProgramError::UnmatchingVerbArgs { name } => assert_eq!(name, "cp"),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn check_spaced_sequences() {
+        assert_eq!(
+            raws("main :cp ../other :toggle_sizes", None).unwrap(),
+            vec!["main", ":cp ../other", ":toggle_sizes"],
+        );
+        assert_eq!(raws("main :print_tree", Some("")).unwrap(), vec!["main", ":print_tree"]);
+        match error(":not_a_verb", None) {
+            ProgramError::UnknownVerb { name } => assert_eq!(name, "not_a_verb"),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+}
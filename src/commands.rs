@@ -8,6 +8,11 @@ use termimad::{Event, InputField};
 
 use crate::{
     app_context::AppContext,
+    composite_patterns,
+    file_filters::FileFilter,
+    input_history::{self, HistoryCursor},
+    line_editing::{self, Yank},
+    verb_completion::{self, VerbCompletion},
     verb_invocation::VerbInvocation,
     patterns::Pattern,
 };
@@ -17,13 +22,21 @@ pub struct Command {
     pub raw: String,     // what's visible in the input
     parts: CommandParts, // the parsed parts of the visible input
     pub action: Action, // what's required, based on the last key (which may be not visible, like esc)
+    pending_chord: Option<KeyEvent>, // the first key of a chord, waiting for the second one
+    pending_confirmation: bool, // whether the next key answers a confirmation
+    pub verb_completion: Option<VerbCompletion>, // the completion of the verb name cycled with tab
+    yank: Option<Yank>, // the text inserted by the last key, if it was a yank
+    history_cursor: Option<HistoryCursor>, // where the user is in the history, if browsing it
 }
 
 /// An intermediate parsed representation of the raw string
 #[derive(Debug, Clone)]
 struct CommandParts {
-    pattern: Option<String>,     // either a fuzzy pattern or the core of a regex
+    file_filter: FileFilter,     // the filters on the type of files, typed before the pattern
+    filter_len: usize,           // the length of the filter part of the raw string
+    pattern: Option<String>,     // either a fuzzy pattern, the core of a regex or a composite pattern
     regex_flags: Option<String>, // may be Some("") if user asked for a regex but specified no flag
+    composite: bool,             // whether the pattern combines other ones with logical operators
     verb_invocation: Option<VerbInvocation>, // may be empty if user already typed the separator but no char after
 }
 
@@ -35,12 +48,19 @@ pub enum Action {
     VerbEdit(VerbInvocation),     // verb invocation, unfinished
     VerbInvocate(VerbInvocation), // verb invocation, after the user hit enter
     VerbIndex(usize),             // verb call, withtout specific argument (using a trigger key)
+    CompleteVerbArg(VerbInvocation), // the user asked for the completion of the verb's last argument
     FuzzyPatternEdit(String),     // a pattern being edited
     RegexEdit(String, String),    // a regex being edited (core & flags)
+    CompositePatternEdit(String), // a pattern with logical operators being edited
     Back,                         // back to last app state, or clear pattern
+    UpTree,                       // left at the start of the input: go towards the root, then above
+    UpTreeFromRoot,               // backspace in an empty input: go above the root when it's selected, else back
+    Expand,                       // right at the end of the input: open the selected archive
     Next,                         // goes to the next matching entry
     Previous,                     // goes to the previous matching entry
     Help,                         // goes to help state
+    PendingChord(KeyEvent),       // the first key of a chord was typed
+    Confirm(bool),                // the answer to a confirmation: whether the user typed `y`
     Click(u16, u16),              // usually a mouse click
     DoubleClick(u16, u16),        // always come after a simple click at same position
     Unparsed,                     // or unparsable
@@ -49,30 +69,63 @@ pub enum Action {
 impl CommandParts {
     fn new() -> CommandParts {
         CommandParts {
+            file_filter: FileFilter::default(),
+            filter_len: 0,
             pattern: None,
             regex_flags: None,
+            composite: false,
             verb_invocation: None,
         }
     }
     fn from(raw: &str) -> CommandParts {
         let mut cp = CommandParts::new();
         let r = regex!(
+            r"(?x)
+                ^
+                (?P<file_filter>(?:
+                    [fdxl]:
+                    |
+                    [<>]\d+(?:\.\d+)?[kKmMgGtT]?(?::|\b)
+                    |
+                    (?:mtime|changed)[<>](?:\d{4}-\d{2}-\d{2}|\d+[smhdwy])(?::|\b)
+                    |
+                    newer:[^\s:]*:?
+                    |
+                    has:[^\s:]*:?
+                )+)?
+                (?P<pattern>[^\s:]+)?
+                (?:[\s:]+(?P<verb_invocation>.*))?
+                $
+                "
+        );
+        let simple_pattern = regex!(
             r"(?x)
                 ^
                 (?P<slash_before>/)?
-                (?P<pattern>[^\s/:]+)?
+                (?P<pattern>[^/]+)?
                 (?:/(?P<regex_flags>\w*))?
-                (?:[\s:]+(?P<verb_invocation>.*))?
                 $
                 "
         );
         if let Some(c) = r.captures(raw) {
+            if let Some(file_filter) = c.name("file_filter") {
+                cp.file_filter = FileFilter::from(file_filter.as_str());
+                cp.filter_len = file_filter.end();
+            }
             if let Some(pattern) = c.name("pattern") {
-                cp.pattern = Some(String::from(pattern.as_str()));
-                if let Some(rxf) = c.name("regex_flags") {
-                    cp.regex_flags = Some(String::from(rxf.as_str()));
-                } else if c.name("slash_before").is_some() {
-                    cp.regex_flags = Some("".into());
+                let pattern = pattern.as_str();
+                if composite_patterns::is_composite(pattern) {
+                    cp.pattern = Some(String::from(pattern));
+                    cp.composite = true;
+                } else if let Some(c) = simple_pattern.captures(pattern) {
+                    if let Some(pattern) = c.name("pattern") {
+                        cp.pattern = Some(String::from(pattern.as_str()));
+                        if let Some(rxf) = c.name("regex_flags") {
+                            cp.regex_flags = Some(String::from(rxf.as_str()));
+                        } else if c.name("slash_before").is_some() {
+                            cp.regex_flags = Some("".into());
+                        }
+                    }
                 }
             }
             if let Some(verb) = c.name("verb_invocation") {
@@ -95,7 +148,9 @@ impl Action {
             Action::OpenSelection
         } else if let Some(pattern) = &cp.pattern {
             let pattern = String::from(pattern.as_str());
-            if let Some(regex_flags) = &cp.regex_flags {
+            if cp.composite {
+                Action::CompositePatternEdit(pattern)
+            } else if let Some(regex_flags) = &cp.regex_flags {
                 Action::RegexEdit(pattern, String::from(regex_flags.as_str()))
             } else {
                 Action::FuzzyPatternEdit(String::from(pattern.as_str()))
@@ -106,12 +161,28 @@ impl Action {
     }
 }
 
+/// return the string which, typed in the input, gives the pattern
+fn raw_pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Fuzzy(fp) => fp.to_string(),
+        Pattern::Regex(rp) => rp.to_string(),
+        Pattern::Composite(cp) => cp.to_string(),
+        Pattern::Filtered(fp) => format!("{}{}", fp.filter, raw_pattern(&fp.inner)),
+        Pattern::None => String::new(),
+    }
+}
+
 impl Command {
     pub fn new() -> Command {
         Command {
             raw: String::new(),
             parts: CommandParts::new(),
             action: Action::Unparsed,
+            pending_chord: None,
+            pending_confirmation: false,
+            verb_completion: None,
+            yank: None,
+            history_cursor: None,
         }
     }
 
@@ -126,22 +197,57 @@ impl Command {
     /// This specific syntax isn't definitive
     pub fn from(raw: String) -> Command {
         let parts = CommandParts::from(&raw);
-        let action = Action::from(&parts, raw.contains(':'));
-        Command { raw, parts, action }
+        // the colons of the file filter don't count
+        let action = Action::from(&parts, raw[parts.filter_len..].contains(':'));
+        Command {
+            raw,
+            parts,
+            action,
+            pending_chord: None,
+            pending_confirmation: false,
+            verb_completion: None,
+            yank: None,
+            history_cursor: None,
+        }
+    }
+
+    /// make the next key the answer to the confirmation
+    /// the application asks
+    pub fn ask_confirmation(&mut self) {
+        self.pending_confirmation = true;
+    }
+
+    /// replace the visible input (for example after a completion)
+    /// as if it had been typed by the user
+    pub fn set_raw(&mut self, raw: String) {
+        self.parts = CommandParts::from(&raw);
+        self.action = Action::from(&self.parts, false);
+        self.raw = raw;
     }
 
     pub fn from_pattern(pattern: &Pattern) -> Self {
-        Self::from(match pattern {
-            Pattern::Fuzzy(fp) => fp.to_string(),
-            Pattern::Regex(rp) => rp.to_string(),
-            Pattern::None => String::new(),
-        })
+        Self::from(raw_pattern(pattern))
+    }
+
+    /// return the filter on the type of files typed before the pattern
+    pub fn file_filter(&self) -> FileFilter {
+        self.parts.file_filter.clone()
     }
 
     /// apply an event to modify the command
     pub fn add_event(&mut self, event: &Event, input_field: &mut InputField, con: &AppContext) {
-        let mut handled_by_input_field = false;
         debug!("add_event {:?}", event);
+        if self.pending_confirmation {
+            if let Event::Key(key) = event {
+                // any other key than `y` cancels the execution
+                self.pending_confirmation = false;
+                self.action = Action::Confirm(*key == KeyEvent::Char('y'));
+            } else {
+                // a click or the wheel doesn't answer, the question stays
+                self.action = Action::Unparsed;
+            }
+            return;
+        }
         match event {
             Event::Click(x, y) => {
                 if !input_field.apply_event(&event) {
@@ -152,67 +258,215 @@ impl Command {
                 self.action = Action::DoubleClick(*x, *y);
             }
             Event::Key(key) => {
-                // we start by looking if the key is the trigger key of one of the verbs
-                if let Some(index) = con.verb_store.index_of_key(*key) {
-                    self.action = Action::VerbIndex(index);
-                    return;
-                }
-                match *key {
-                    KeyEvent::Tab => {
-                        self.action = Action::Next;
+                if let Some(first) = self.pending_chord.take() {
+                    if let Some(index) = con.verb_store.index_of_chord(first, *key) {
+                        self.action = Action::VerbIndex(index);
+                        return;
                     }
-                    KeyEvent::BackTab => {
-                        self.action = Action::Previous;
+                    if *key == KeyEvent::Esc {
+                        self.action = Action::Unparsed;
+                        return;
                     }
+                    // not a chord: the first key is handled as if typed alone
+                    self.add_key(first, input_field, con);
+                } else if con.verb_store.starts_chord(*key)
+                    && (self.raw.is_empty() || !is_char(*key))
+                {
+                    // a plain char only starts a chord when the input is empty,
+                    // so that it can still be typed in a pattern
+                    self.pending_chord = Some(*key);
+                    self.action = Action::PendingChord(*key);
+                    return;
+                }
+                self.add_key(*key, input_field, con);
+            }
+            Event::Wheel(lines_count) => {
+                self.action = Action::MoveSelection(*lines_count);
+            }
+        }
+    }
 
-                    // this may be a call to open_stay, or simply
-                    // validating the verb choice in the input
-                    KeyEvent::Enter => {
-                        self.action = Action::from(&self.parts, true);
+    /// apply a key to modify the command, chords being already handled
+    fn add_key(&mut self, key: KeyEvent, input_field: &mut InputField, con: &AppContext) {
+        let mut handled_by_input_field = false;
+        let mut yank = self.yank.take();
+        // after the verb separator, up and down browse the history
+        // of the invocations instead of moving the selection
+        let history_cursor = self.history_cursor.take();
+        if key == KeyEvent::Up || key == KeyEvent::Down {
+            let at_verb_start = self
+                .parts
+                .verb_invocation
+                .as_ref()
+                .map_or(false, |invocation| invocation.is_empty());
+            if history_cursor.is_some() || at_verb_start {
+                let dir = if key == KeyEvent::Up { 1 } else { -1 };
+                self.browse_history(dir, history_cursor, input_field);
+                return;
+            }
+        }
+        // we start by looking if the key is the trigger key of one of the verbs
+        if let Some(index) = con.verb_store.index_of_key(key) {
+            self.action = Action::VerbIndex(index);
+            return;
+        }
+        match key {
+            // the word moves, kills and yanks
+            _ if line_editing::apply_key(key, input_field, &mut yank) => {
+                handled_by_input_field = true;
+            }
+            KeyEvent::Tab | KeyEvent::BackTab if verb_completion::typed_name(self).is_some() => {
+                let dir = if key == KeyEvent::Tab { 1 } else { -1 };
+                self.cycle_verb_completion(dir, input_field, con);
+            }
+            KeyEvent::Tab => {
+                self.action = match &self.parts.verb_invocation {
+                    Some(invocation) if invocation.args.is_some() => {
+                        Action::CompleteVerbArg(invocation.clone())
                     }
+                    _ => Action::Next,
+                };
+            }
+            KeyEvent::BackTab => {
+                self.action = Action::Previous;
+            }
 
-                    // Normally redundant due to internal verb but
-                    // I'm not yet 100% sure it's Alt('\r') on all platforms
-                    KeyEvent::Alt('\r') | KeyEvent::Alt('\n') => {
-                        self.action = Action::AltOpenSelection;
+            // this may be a call to open_stay, or simply
+            // validating the verb choice in the input
+            KeyEvent::Enter => {
+                self.action = Action::from(&self.parts, true);
+                if let Action::VerbInvocate(invocation) = &self.action {
+                    if !invocation.is_empty() {
+                        input_history::add(&invocation.to_string_for_name(&invocation.name));
                     }
+                }
+            }
 
-                    KeyEvent::Char('?')
-                        if self.raw.is_empty() || self.parts.verb_invocation.is_some() =>
-                    {
-                        // a '?' opens the help when it's the first char or when it's part of the verb
-                        // invocation
-                        self.action = Action::Help;
-                    }
-                    KeyEvent::Esc => {
-                        self.action = Action::Back;
-                    }
-                    KeyEvent::Char(_)
-                    | KeyEvent::Home
-                    | KeyEvent::End
-                    | KeyEvent::Left
-                    | KeyEvent::Right
-                    | KeyEvent::Delete => {
-                        handled_by_input_field = input_field.apply_event(&event);
-                    }
-                    KeyEvent::Backspace => {
-                        handled_by_input_field = input_field.apply_event(&event);
-                        if !handled_by_input_field {
-                            self.action = Action::Back;
-                        }
-                    }
-                    _ => {}
+            // Normally redundant due to internal verb but
+            // I'm not yet 100% sure it's Alt('\r') on all platforms
+            KeyEvent::Alt('\r') | KeyEvent::Alt('\n') => {
+                self.action = Action::AltOpenSelection;
+            }
+
+            KeyEvent::Char('?')
+                if self.raw.is_empty() || self.parts.verb_invocation.is_some() =>
+            {
+                // a '?' opens the help when it's the first char or when it's part of the verb
+                // invocation
+                self.action = Action::Help;
+            }
+            KeyEvent::Esc => {
+                self.action = Action::Back;
+            }
+            KeyEvent::Char(_)
+            | KeyEvent::Home
+            | KeyEvent::End
+            | KeyEvent::Delete => {
+                handled_by_input_field = input_field.apply_event(&Event::Key(key));
+            }
+            KeyEvent::Right => {
+                handled_by_input_field = input_field.apply_event(&Event::Key(key));
+                if !handled_by_input_field {
+                    self.action = Action::Expand;
                 }
             }
-            Event::Wheel(lines_count) => {
-                self.action = Action::MoveSelection(*lines_count);
+            KeyEvent::Left => {
+                handled_by_input_field = input_field.apply_event(&Event::Key(key));
+                if !handled_by_input_field {
+                    self.action = Action::UpTree;
+                }
             }
+            KeyEvent::Backspace => {
+                handled_by_input_field = input_field.apply_event(&Event::Key(key));
+                if !handled_by_input_field {
+                    self.action = if self.raw.is_empty() {
+                        Action::UpTreeFromRoot
+                    } else {
+                        Action::Back
+                    };
+                }
+            }
+            _ => {}
         }
+        self.yank = yank;
         if handled_by_input_field {
+            self.verb_completion = None;
             self.raw = input_field.get_content();
             self.parts = CommandParts::from(&self.raw);
             self.action = Action::from(&self.parts, false);
         }
     }
+
+    /// replace the verb invocation with the one typed before (or after,
+    /// when dir is negative) the one displayed, in the history
+    fn browse_history(
+        &mut self,
+        dir: i32,
+        cursor: Option<HistoryCursor>,
+        input_field: &mut InputField,
+    ) {
+        let cursor = cursor.unwrap_or_else(|| HistoryCursor {
+            base: self.raw.clone(),
+            back: 0,
+        });
+        let back = (cursor.back as i32 + dir).max(0) as usize;
+        let raw = if back == 0 {
+            cursor.base.clone()
+        } else {
+            match input_history::recall(back) {
+                Some(invocation) => format!("{}{}", cursor.base, invocation),
+                None => {
+                    // there's no older invocation
+                    self.history_cursor = Some(cursor);
+                    return;
+                }
+            }
+        };
+        input_field.set_content(&raw);
+        self.set_raw(raw);
+        self.verb_completion = None;
+        self.history_cursor = Some(HistoryCursor { back, ..cursor });
+    }
+
+    /// replace the verb name being typed with the next (or previous,
+    /// when dir is negative) verb whose name starts with what the user
+    /// typed before the first tab
+    fn cycle_verb_completion(&mut self, dir: i32, input_field: &mut InputField, con: &AppContext) {
+        let name = match verb_completion::typed_name(self) {
+            Some(name) => name.to_string(),
+            None => {
+                return;
+            }
+        };
+        let typed = match &self.verb_completion {
+            Some(completion) => completion.typed.clone(),
+            None => name.clone(),
+        };
+        let candidates = con.verb_store.completions(&typed);
+        if candidates.is_empty() {
+            return;
+        }
+        let len = candidates.len() as i32;
+        let selection = match &self.verb_completion {
+            Some(completion) => (completion.selection as i32 + dir).rem_euclid(len),
+            None if dir > 0 => 0,
+            None => len - 1,
+        } as usize;
+        let raw = format!(
+            "{}{}",
+            &self.raw[..self.raw.len() - name.len()],
+            &candidates[selection].invocation.name,
+        );
+        input_field.set_content(&raw);
+        self.set_raw(raw);
+        self.verb_completion = Some(VerbCompletion { typed, selection });
+    }
+}
+
+fn is_char(key: KeyEvent) -> bool {
+    match key {
+        KeyEvent::Char(_) => true,
+        _ => false,
+    }
 }
 
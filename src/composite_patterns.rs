@@ -0,0 +1,369 @@
+//! a pattern combining fuzzy patterns and regular expressions
+//! with logical operators, for example `foo&!test` or `/\.rs$/|toml`.
+//!
+//! Operators are `&` (and), `|` (or) and `!` (not), `&` having a
+//! higher precedence than `|`. Parentheses can be used for grouping.
+//! An operator between the slashes of a regex is part of the regex, and
+//! a pattern which is just a regex (eg `/foo(bar)?/`) isn't composite.
+
+use std::fmt;
+
+use crate::{
+    errors::RegexError,
+    fuzzy_scoring::FuzzyScoring,
+    patterns::{Match, Pattern, Scorer},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token<'a> {
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    Leaf {
+        core: &'a str,
+        flags: Option<&'a str>, // a regex when there are flags, even empty
+    },
+}
+
+/// length of the regex flags at the start of the string
+fn flags_len(s: &str) -> usize {
+    s.find(|c: char| !c.is_alphanumeric()).unwrap_or(s.len())
+}
+
+fn tokenize(s: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        let token = match c {
+            '&' => Token::And,
+            '|' => Token::Or,
+            '!' => Token::Not,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '/' => {
+                // a regex, up to the closing slash and its flags
+                let body = &rest[1..];
+                match body.find('/') {
+                    Some(i) => {
+                        let after = &body[i + 1..];
+                        let n = flags_len(after);
+                        tokens.push(Token::Leaf {
+                            core: &body[..i],
+                            flags: Some(&after[..n]),
+                        });
+                        rest = &after[n..];
+                    }
+                    None => {
+                        tokens.push(Token::Leaf {
+                            core: body,
+                            flags: Some(""),
+                        });
+                        rest = "";
+                    }
+                }
+                continue;
+            }
+            _ => {
+                // a fuzzy pattern, or the core of a regex if followed
+                // by a slash and flags
+                let n = rest.find(|c: char| "&|!()/".contains(c)).unwrap_or(rest.len());
+                let core = &rest[..n];
+                rest = &rest[n..];
+                if rest.starts_with('/') {
+                    let after = &rest[1..];
+                    let n = flags_len(after);
+                    tokens.push(Token::Leaf {
+                        core,
+                        flags: Some(&after[..n]),
+                    });
+                    rest = &after[n..];
+                } else {
+                    tokens.push(Token::Leaf { core, flags: None });
+                }
+                continue;
+            }
+        };
+        tokens.push(token);
+        rest = &rest[1..];
+    }
+    tokens
+}
+
+/// tell whether the whole string is one regex: `/core/flags`,
+/// `core/flags`, or `/core` while the closing slash isn't typed
+fn is_single_regex(s: &str) -> bool {
+    let body = if s.starts_with('/') { &s[1..] } else { s };
+    match body.find('/') {
+        Some(i) => {
+            let after = &body[i + 1..];
+            flags_len(after) == after.len()
+        }
+        None => s.starts_with('/'),
+    }
+}
+
+/// tell whether the pattern typed by the user uses logical operators
+/// outside of the delimiters of a regex
+pub fn is_composite(s: &str) -> bool {
+    if is_single_regex(s) {
+        return false;
+    }
+    tokenize(s).iter().any(|t| match t {
+        Token::Leaf { .. } => false,
+        _ => true,
+    })
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Leaf(Pattern), // either fuzzy or regex
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+/// a recursive descent parser. As the expression is parsed while
+/// the user types it, it's lenient: a missing operand is ignored
+/// and a missing closing parenthesis is implied.
+struct Parser<'a, 't> {
+    tokens: &'t [Token<'a>],
+    idx: usize,
+    scoring: FuzzyScoring,
+}
+
+impl<'a, 't> Parser<'a, 't> {
+    fn eat(&mut self, token: &Token<'_>) -> bool {
+        if self.tokens.get(self.idx) == Some(token) {
+            self.idx += 1;
+            true
+        } else {
+            false
+        }
+    }
+    fn parse_or(&mut self) -> Result<Option<Expr>, RegexError> {
+        let mut operands = Vec::new();
+        operands.extend(self.parse_and()?);
+        while self.eat(&Token::Or) {
+            operands.extend(self.parse_and()?);
+        }
+        Ok(match operands.len() {
+            0 => None,
+            1 => operands.pop(),
+            _ => Some(Expr::Or(operands)),
+        })
+    }
+    fn parse_and(&mut self) -> Result<Option<Expr>, RegexError> {
+        let mut operands = Vec::new();
+        operands.extend(self.parse_unary()?);
+        while self.eat(&Token::And) {
+            operands.extend(self.parse_unary()?);
+        }
+        Ok(match operands.len() {
+            0 => None,
+            1 => operands.pop(),
+            _ => Some(Expr::And(operands)),
+        })
+    }
+    fn parse_unary(&mut self) -> Result<Option<Expr>, RegexError> {
+        if self.eat(&Token::Not) {
+            return Ok(self.parse_unary()?.map(|e| Expr::Not(Box::new(e))));
+        }
+        if self.eat(&Token::Open) {
+            let expr = self.parse_or()?;
+            self.eat(&Token::Close);
+            return Ok(expr);
+        }
+        match self.tokens.get(self.idx) {
+            Some(Token::Leaf { core, flags }) => {
+                self.idx += 1;
+                if core.is_empty() {
+                    return Ok(None);
+                }
+                Ok(Some(Expr::Leaf(match flags {
+                    Some(flags) => Pattern::regex(core, flags)?,
+                    None => Pattern::fuzzy(core, self.scoring),
+                })))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl Expr {
+    fn find(&self, candidate: &str) -> Option<Match> {
+        match self {
+            Expr::Leaf(pattern) => pattern.find(candidate),
+            Expr::Not(e) => match e.score_of(candidate) {
+                Some(_) => None,
+                None => Some(Match {
+                    score: 1,
+                    pos: Vec::new(),
+                }),
+            },
+            Expr::And(operands) => {
+                let mut score = 0;
+                let mut pos = Vec::new();
+                for e in operands {
+                    let m = e.find(candidate)?;
+                    score += m.score;
+                    pos.extend(m.pos);
+                }
+                pos.sort();
+                pos.dedup();
+                Some(Match { score, pos })
+            }
+            Expr::Or(operands) => operands
+                .iter()
+                .filter_map(|e| e.find(candidate))
+                .max_by_key(|m| m.score),
+        }
+    }
+    fn score_of(&self, candidate: &str) -> Option<i32> {
+        match self {
+            Expr::Leaf(pattern) => pattern.score_of(candidate),
+            Expr::Not(e) => match e.score_of(candidate) {
+                Some(_) => None,
+                None => Some(1),
+            },
+            Expr::And(operands) => {
+                let mut score = 0;
+                for e in operands {
+                    score += e.score_of(candidate)?;
+                }
+                Some(score)
+            }
+            Expr::Or(operands) => operands.iter().filter_map(|e| e.score_of(candidate)).max(),
+        }
+    }
+    fn has_ranking(&self) -> bool {
+        match self {
+            Expr::Leaf(pattern) => pattern.has_ranking(),
+            Expr::Not(_) => false,
+            Expr::And(operands) | Expr::Or(operands) => operands.iter().any(Expr::has_ranking),
+        }
+    }
+    fn best_score(&self) -> Option<i32> {
+        match self {
+            Expr::Leaf(pattern) => pattern.best_score(),
+            Expr::Not(_) => Some(1),
+            Expr::And(operands) => operands.iter().map(Expr::best_score).sum(),
+            Expr::Or(operands) => operands
+                .iter()
+                .map(Expr::best_score)
+                .collect::<Option<Vec<i32>>>()
+                .and_then(|scores| scores.into_iter().max()),
+        }
+    }
+    fn optimal_result_number(&self, targeted_size: usize) -> usize {
+        match self {
+            Expr::Leaf(pattern) => pattern.optimal_result_number(targeted_size),
+            Expr::Not(_) => targeted_size,
+            Expr::And(operands) | Expr::Or(operands) => operands
+                .iter()
+                .map(|e| e.optimal_result_number(targeted_size))
+                .max()
+                .unwrap_or(targeted_size),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompositePattern {
+    raw: String, // kept to rebuild the input
+    expr: Expr,
+}
+
+impl fmt::Display for CompositePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl CompositePattern {
+    /// parse the expression, returning None when it contains
+    /// no actual pattern (eg "!" or "&")
+    pub fn from(raw: &str, scoring: FuzzyScoring) -> Result<Option<CompositePattern>, RegexError> {
+        let tokens = tokenize(raw);
+        let mut parser = Parser {
+            tokens: &tokens,
+            idx: 0,
+            scoring,
+        };
+        Ok(parser.parse_or()?.map(|expr| CompositePattern {
+            raw: raw.to_string(),
+            expr,
+        }))
+    }
+}
+
+impl Scorer for CompositePattern {
+    fn find(&self, candidate: &str) -> Option<Match> {
+        self.expr.find(candidate)
+    }
+    fn score_of(&self, candidate: &str) -> Option<i32> {
+        self.expr.score_of(candidate)
+    }
+    fn optimal_result_number(&self, targeted_size: usize) -> usize {
+        self.expr.optimal_result_number(targeted_size)
+    }
+    fn has_ranking(&self) -> bool {
+        self.expr.has_ranking()
+    }
+    fn best_score(&self) -> Option<i32> {
+        self.expr.best_score()
+    }
+}
+
+#[cfg(test)]
+mod composite_pattern_tests {
+
+    use super::*;
+
+    fn check(pattern: &str, name: &str, matching: bool) {
+        let cp = CompositePattern::from(pattern, FuzzyScoring::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(cp.find(name).is_some(), matching, "{:?} on {:?}", pattern, name);
+        assert_eq!(cp.score_of(name).is_some(), matching, "{:?} on {:?}", pattern, name);
+    }
+
+    #[test]
+    fn check_operators() {
+        check("foo&bar", "foo_bar.rs", true);
+        check("foo&bar", "foo.rs", false);
+        check("foo|bar", "bar.rs", true);
+        check("foo|bar", "qux.rs", false);
+        check("!test", "main.rs", true);
+        check("!test", "test_main.rs", false);
+        check("rs&!(test|bench)", "main.rs", true);
+        check("rs&!(test|bench)", "bench.rs", false);
+        check("/a|b/&!c", "b", true);
+        check("/\\.rs$/&main", "main.rs", true);
+        check("/\\.rs$/&main", "main.rs.bak", false);
+        check("/foo(bar)?/|qux", "foo.rs", true);
+        check("/foo(bar)?/|qux", "fobar.rs", false);
+        check("!/a|b/", "cd", true);
+        check("!/a|b/", "ab", false);
+    }
+
+    #[test]
+    fn check_detection() {
+        assert!(is_composite("foo&bar"));
+        assert!(is_composite("!test"));
+        assert!(!is_composite("foo"));
+        assert!(!is_composite("/a|b/i"));
+        assert!(!is_composite("ab/i"));
+        assert!(is_composite("a|b"));
+        assert!(is_composite("(foo|bar)&!baz"));
+        assert!(is_composite("/a|b/|c"));
+        assert!(is_composite("!/a|b/"));
+        assert!(!is_composite("/a|b/"));
+        assert!(!is_composite("/foo(bar)?/"));
+        assert!(!is_composite("/foo(bar)?/i"));
+        assert!(!is_composite("/foo(bar"));
+        assert!(!is_composite("foo(bar)?/"));
+        assert!(!is_composite("a|b/i"));
+    }
+}
@@ -14,14 +14,68 @@ use directories::ProjectDirs;
 use toml::{self, Value};
 
 use crate::{
+    color_support::{self, Background, ColorSupport},
+    column_widths::{Column, ColumnWidth, ColumnWidths},
+    date_format::DateFormat,
     errors::ConfError,
+    file_sizes::SizeFormat,
+    fuzzy_scoring::FuzzyScoring,
+    path_filters::{self, SpecialPath},
+    selection_type::NameCondition,
+    size_bars::SizeBarStyle,
     skin_conf,
+    syntax_highlighting,
+    tree_sources::DateKind,
     verb_conf::{self, VerbConf},
 };
 
+/// the size of the biggest file whose syntax is highlighted in
+/// the preview, when it's not configured
+const DEFAULT_MAX_HIGHLIGHTED_SIZE: u64 = 200_000;
+
 pub struct Conf {
     pub verbs: Vec<VerbConf>,
     pub skin: HashMap<String, CompoundStyle>,
+    pub file_styles: Vec<(glob::Pattern, CompoundStyle)>, // styles of the files whose name matches a glob
+    pub icons: bool, // whether an icon is written before names (needs a Nerd Font)
+    pub color_support: Option<ColorSupport>, // the colors of the terminal, guessed when None
+    pub do_not_enter: Vec<PathBuf>, // directories listed but not explored
+    pub size_format: Option<SizeFormat>,
+    pub size_bar: SizeBarStyle,
+    pub date_format: Option<DateFormat>,
+    pub date_kind: DateKind, // which date is displayed
+    pub special_paths: Vec<SpecialPath>,
+    pub column_widths: ColumnWidths,
+    pub fuzzy_scoring: FuzzyScoring,
+    pub restore_session: bool, // whether to restore the last session even without --restore
+    pub full_permissions: bool, // whether to show uniform owner and group columns
+    pub two_rows: bool, // whether sizes, dates and permissions are on a second row
+    pub confirm_quit: bool, // whether quitting must be confirmed when a task runs or files are marked
+    pub max_tree_lines: Option<usize>, // the maximal number of lines of the tree, whatever the screen height
+    pub auto_refresh: bool, // whether the tree is refreshed when the displayed directories change
+    pub elevation_command: Option<String>, // eg "sudo", to retry a permission change refused by the system
+    pub case_insensitive_paths: Option<bool>, // whether the file system ignores case, guessed when None
+    pub syntax_theme: Option<String>, // the theme highlighting the preview, None for no highlighting
+    pub max_highlighted_size: u64, // the size of the biggest file highlighted in the preview
+}
+
+/// read the style entries of a skin table, the sub-tables
+/// being ignored
+fn read_skin_entries(value: &Value, skin: &mut HashMap<String, CompoundStyle>) {
+    if let Value::Table(entries_tbl) = value {
+        for (k, v) in entries_tbl.iter() {
+            if let Some(s) = v.as_str() {
+                match skin_conf::parse_object_style(s) {
+                    Ok(ske) => {
+                        skin.insert(k.to_string(), ske);
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                    }
+                }
+            }
+        }
+    }
 }
 
 fn string_field(value: &Value, field_name: &str) -> Option<String> {
@@ -34,6 +88,26 @@ fn string_field(value: &Value, field_name: &str) -> Option<String> {
     }
     None
 }
+fn string_array_field(value: &Value, field_name: &str) -> Vec<String> {
+    match value.get(field_name) {
+        Some(Value::Array(values)) => values
+            .iter()
+            .filter_map(|v| v.as_str())
+            .map(String::from)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+fn usize_field(value: &Value, field_name: &str) -> Option<usize> {
+    if let Value::Table(tbl) = value {
+        if let Some(Value::Integer(i)) = tbl.get(field_name) {
+            if *i >= 0 {
+                return Some(*i as usize);
+            }
+        }
+    }
+    None
+}
 fn bool_field(value: &Value, field_name: &str) -> Option<bool> {
     if let Value::Table(tbl) = value {
         if let Some(Value::Boolean(b)) = tbl.get(field_name) {
@@ -43,6 +117,105 @@ fn bool_field(value: &Value, field_name: &str) -> Option<bool> {
     None
 }
 
+/// read the `[[verbs]]` entries of a configuration, the invalid
+/// ones being reported on stderr and skipped
+pub fn read_verbs(root: &Value) -> Result<Vec<VerbConf>, ConfError> {
+    let mut verbs: Vec<VerbConf> = vec![];
+    if let Some(Value::Array(verbs_value)) = &root.get("verbs") {
+        for verb_value in verbs_value.iter() {
+            let invocation = string_field(verb_value, "invocation")
+                .unwrap_or("".to_string());
+            let key = string_field(verb_value, "key")
+                .map(|s| verb_conf::parse_key(&s))
+                .transpose()?;
+            let chord = string_field(verb_value, "chord")
+                .map(|s| verb_conf::parse_chord(&s))
+                .transpose()?;
+            let execution = match string_field(verb_value, "execution") {
+                Some(s) => s,
+                None => {
+                    eprintln!("Invalid [[verbs]] entry in configuration");
+                    eprintln!("Missing execution");
+                    continue;
+                }
+            };
+            let apply_to = string_field(verb_value, "apply_to")
+                .map(|s| s.parse())
+                .transpose()?;
+            let mut name_condition = NameCondition::default();
+            for extension in string_array_field(verb_value, "extensions") {
+                name_condition.extensions.push(extension.trim_start_matches('.').to_string());
+            }
+            for glob in string_array_field(verb_value, "globs") {
+                let pattern = path_filters::parse_glob(&glob)
+                    .ok_or_else(|| ConfError::InvalidGlob { raw: glob.to_string() })?;
+                name_condition.globs.push(pattern);
+            }
+            let from_shell = bool_field(verb_value, "from_shell");
+            let leave_broot = bool_field(verb_value, "leave_broot");
+            if leave_broot == Some(false) && from_shell == Some(true) {
+                eprintln!("Invalid [[verbs]] entry in configuration");
+                eprintln!(
+                    "You can't simultaneously have leave_broot=false and from_shell=true"
+                );
+                continue;
+            }
+            let capture = bool_field(verb_value, "capture");
+            if capture == Some(true) && leave_broot != Some(false) {
+                eprintln!("Invalid [[verbs]] entry in configuration");
+                eprintln!("A verb with capture=true must have leave_broot=false");
+                continue;
+            }
+            let run_async = bool_field(verb_value, "async");
+            if run_async == Some(true) && (leave_broot != Some(false) || capture == Some(true)) {
+                eprintln!("Invalid [[verbs]] entry in configuration");
+                eprintln!("A verb with async=true must have leave_broot=false and no capture");
+                continue;
+            }
+            // the values of the environment variables may contain
+            // the same placeholders as the execution
+            let env = match verb_value.get("env") {
+                Some(Value::Table(env_tbl)) => env_tbl
+                    .iter()
+                    .filter_map(|(name, value)| {
+                        value.as_str().map(|s| (name.to_string(), s.to_string()))
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            verbs.push(VerbConf {
+                invocation,
+                execution,
+                key,
+                chord,
+                shortcut: string_field(verb_value, "shortcut"),
+                description: string_field(verb_value, "description"),
+                from_shell,
+                leave_broot,
+                confirm: bool_field(verb_value, "confirm"),
+                dry_run: bool_field(verb_value, "dry_run"),
+                capture,
+                run_async,
+                apply_to,
+                name_condition,
+                env,
+            });
+        }
+    }
+    Ok(verbs)
+}
+
+/// the directories which aren't explored when the configuration
+/// doesn't say otherwise (their content is either virtual or
+/// very slow to list)
+fn default_do_not_enter() -> Vec<PathBuf> {
+    if cfg!(unix) {
+        vec![PathBuf::from("/proc"), PathBuf::from("/sys")]
+    } else {
+        Vec::new()
+    }
+}
+
 /// return the path to the config directory, based on XDG
 pub fn dir() -> PathBuf {
     if let Some(dirs) = ProjectDirs::from("org", "dystroy", "broot") {
@@ -52,6 +225,16 @@ pub fn dir() -> PathBuf {
     }
 }
 
+/// return the path to the data directory (where broot writes the
+/// session and the bookmarks), based on XDG
+pub fn data_dir() -> PathBuf {
+    if let Some(dirs) = ProjectDirs::from("org", "dystroy", "broot") {
+        dirs.data_dir().to_path_buf()
+    } else {
+        panic!("Unable to find data directories");
+    }
+}
+
 impl Conf {
     pub fn default_location() -> PathBuf {
         dir().join("conf.toml")
@@ -93,53 +276,121 @@ impl Conf {
     /// (i.e. this function is called before or after the terminal alternation)
     pub fn from_file(filepath: &Path) -> Result<Conf, ConfError> {
         let data = fs::read_to_string(filepath)?;
+        Conf::from_toml(&data)
+    }
+
+    /// return the configuration written on first launch
+    pub fn default_conf() -> Conf {
+        Conf::from_toml(DEFAULT_CONF_FILE).expect("invalid default configuration")
+    }
+
+    /// read the configuration from the content of a TOML file
+    pub fn from_toml(data: &str) -> Result<Conf, ConfError> {
         let root: Value = data.parse::<Value>()?;
-        // reading verbs
-        let mut verbs: Vec<VerbConf> = vec![];
-        if let Some(Value::Array(verbs_value)) = &root.get("verbs") {
-            for verb_value in verbs_value.iter() {
-                let invocation = string_field(verb_value, "invocation")
-                    .unwrap_or("".to_string());
-                let key = string_field(verb_value, "key")
-                    .map(|s| verb_conf::parse_key(&s))
-                    .transpose()?;
-                let execution = match string_field(verb_value, "execution") {
-                    Some(s) => s,
-                    None => {
-                        eprintln!("Invalid [[verbs]] entry in configuration");
-                        eprintln!("Missing execution");
-                        continue;
-                    }
-                };
-                let from_shell = bool_field(verb_value, "from_shell");
-                let leave_broot = bool_field(verb_value, "leave_broot");
-                if leave_broot == Some(false) && from_shell == Some(true) {
-                    eprintln!("Invalid [[verbs]] entry in configuration");
-                    eprintln!(
-                        "You can't simultaneously have leave_broot=false and from_shell=true"
-                    );
-                    continue;
+        // reading the directories broot shouldn't explore by itself
+        let do_not_enter = match &root.get("do_not_enter") {
+            Some(Value::Array(paths)) => paths
+                .iter()
+                .filter_map(|v| v.as_str())
+                .map(PathBuf::from)
+                .collect(),
+            _ => default_do_not_enter(),
+        };
+        let size_format = string_field(&root, "size_format")
+            .map(|s| s.parse())
+            .transpose()?;
+        let date_format = string_field(&root, "date_format")
+            .map(|s| s.parse())
+            .transpose()?;
+        let date_kind = string_field(&root, "date_kind")
+            .map(|s| s.parse())
+            .transpose()?
+            .unwrap_or_default();
+        let restore_session = bool_field(&root, "restore_session").unwrap_or(false);
+        let full_permissions = bool_field(&root, "full_permissions").unwrap_or(false);
+        let two_rows = bool_field(&root, "two_rows").unwrap_or(false);
+        let confirm_quit = bool_field(&root, "confirm_quit").unwrap_or(false);
+        let max_tree_lines = usize_field(&root, "max_tree_lines").filter(|&n| n > 1);
+        let auto_refresh = bool_field(&root, "auto_refresh").unwrap_or(false);
+        let elevation_command = string_field(&root, "elevation_command")
+            .filter(|s| !s.trim().is_empty());
+        let size_bar = string_field(&root, "size_bar")
+            .map(|s| s.parse())
+            .transpose()?
+            .unwrap_or_default();
+        // reading the globs of paths needing a special handling
+        let mut special_paths = Vec::new();
+        if let Some(Value::Table(entries_tbl)) = &root.get("special_paths") {
+            for (glob, v) in entries_tbl.iter() {
+                if let Some(handling) = v.as_str() {
+                    special_paths.push(SpecialPath::new(glob, handling.parse()?)?);
                 }
-                verbs.push(VerbConf {
-                    invocation,
-                    execution,
-                    key,
-                    shortcut: string_field(verb_value, "shortcut"),
-                    description: string_field(verb_value, "description"),
-                    from_shell,
-                    leave_broot,
-                    confirm: bool_field(verb_value, "confirm"),
-                });
             }
         }
-        // reading the skin
+        // reading the bounds of column widths
+        let mut column_widths = ColumnWidths::default();
+        if let Some(Value::Table(entries_tbl)) = &root.get("column_widths") {
+            for (column, v) in entries_tbl.iter() {
+                column_widths.set(
+                    column.parse::<Column>()?,
+                    ColumnWidth {
+                        min: usize_field(v, "min"),
+                        max: usize_field(v, "max"),
+                    },
+                );
+            }
+        }
+        // reading the scoring of fuzzy patterns
+        let mut fuzzy_scoring = FuzzyScoring::default();
+        if let Some(scoring_value) = root.get("fuzzy_scoring") {
+            if let Some(case) = string_field(scoring_value, "case") {
+                fuzzy_scoring.case = case.parse()?;
+            }
+            if let Some(bonus) = usize_field(scoring_value, "segment_start_bonus") {
+                fuzzy_scoring.segment_start_bonus = bonus as i32;
+            }
+            if let Some(penalty) = usize_field(scoring_value, "hole_penalty") {
+                fuzzy_scoring.hole_penalty = penalty as i32;
+            }
+        }
+        let verbs = read_verbs(&root)?;
+        verb_conf::check_chord_conflicts(&verbs)?;
+        // reading the skin, then the entries of the variant for
+        // the background of the terminal ([skin.dark] or [skin.light])
+        // which overwrite the common ones
+        let background = match string_field(&root, "background") {
+            Some(s) if s != "auto" => s.parse()?,
+            _ => color_support::detect_background().unwrap_or(Background::Dark),
+        };
+        let variant = match background {
+            Background::Dark => "dark",
+            Background::Light => "light",
+        };
+        let syntax_theme = match string_field(&root, "syntax_theme") {
+            Some(s) => syntax_highlighting::parse_theme(&s)?,
+            None => Some(syntax_highlighting::default_theme(background).to_string()),
+        };
+        let max_highlighted_size = usize_field(&root, "max_highlighted_size")
+            .map_or(DEFAULT_MAX_HIGHLIGHTED_SIZE, |size| size as u64);
         let mut skin = HashMap::new();
-        if let Some(Value::Table(entries_tbl)) = &root.get("skin") {
-            for (k, v) in entries_tbl.iter() {
+        if let Some(skin_value) = root.get("skin") {
+            read_skin_entries(skin_value, &mut skin);
+            if let Some(variant_value) = skin_value.get(variant) {
+                read_skin_entries(variant_value, &mut skin);
+            }
+        }
+        // reading the styles of files depending on their names (the
+        // table is sorted, so the globs are tried in alphabetical order)
+        let mut file_styles = Vec::new();
+        if let Some(Value::Table(entries_tbl)) = &root.get("file_styles") {
+            for (glob, v) in entries_tbl.iter() {
                 if let Some(s) = v.as_str() {
+                    let pattern = path_filters::parse_glob(glob).ok_or_else(|| {
+                        ConfError::InvalidGlob { raw: glob.to_string() }
+                    })?;
                     match skin_conf::parse_object_style(s) {
-                        Ok(ske) => {
-                            skin.insert(k.to_string(), ske);
+                        Ok(style) => {
+                            file_styles.push((pattern, style));
                         }
                         Err(e) => {
                             eprintln!("{}", e);
@@ -148,8 +399,38 @@ impl Conf {
                 }
             }
         }
+        let icons = bool_field(&root, "icons").unwrap_or(false);
+        let case_insensitive_paths = bool_field(&root, "case_insensitive_paths");
+        let color_support = match string_field(&root, "color_support") {
+            Some(s) if s != "auto" => Some(s.parse()?),
+            _ => None,
+        };
 
-        Ok(Conf { verbs, skin })
+        Ok(Conf {
+            verbs,
+            skin,
+            file_styles,
+            icons,
+            color_support,
+            do_not_enter,
+            size_format,
+            size_bar,
+            date_format,
+            date_kind,
+            special_paths,
+            column_widths,
+            fuzzy_scoring,
+            restore_session,
+            full_permissions,
+            two_rows,
+            confirm_quit,
+            max_tree_lines,
+            auto_refresh,
+            elevation_command,
+            case_insensitive_paths,
+            syntax_theme,
+            max_highlighted_size,
+        })
     }
 }
 
@@ -161,6 +442,146 @@ const DEFAULT_CONF_FILE: &str = r#"
 # Configuration documentation is available at https://dystroy.org/broot
 #
 
+#####################
+# Directories which are displayed but never explored when
+#  searching or counting children (for example network
+#  mounts). You can still focus them explicitly.
+do_not_enter = [
+    "/proc",
+    "/sys",
+]
+
+#####################
+# How sizes are written: "binary" (powers of 1024, eg 247K),
+#  "si" (powers of 1000, eg 253k) or "bytes" (eg 252,865).
+#  The --size-format launch argument overrides this setting.
+# size_format = "binary"
+
+#####################
+# How the part of each size in the total is drawn: "wide"
+#  (a 10 chars bar), "narrow" (5 chars), "braille" (2 chars)
+#  or "none".
+# size_bar = "wide"
+
+#####################
+# How dates are written: either a strftime format (see
+#  https://docs.rs/chrono/0.4/chrono/format/strftime/) or
+#  "relative" for their age, eg "3d ago".
+# date_format = "%Y/%m/%d %R"
+
+#####################
+# Which date is displayed: "modified", "created" (not known
+#  by all systems) or "accessed" (often not updated).
+# date_kind = "modified"
+
+#####################
+# Whether to reopen broot where you left it (root, pattern,
+#  selection and toggled options), as with --restore.
+# restore_session = false
+
+#####################
+# When permissions are shown, the owner and group columns
+#  are hidden when they're the same for all files (they're
+#  still in the status line). Set this to always show them,
+#  as with --full-permissions.
+# full_permissions = false
+
+#####################
+# Whether sizes, dates and permissions are written on a
+#  second row under the name instead of before it, which
+#  leaves more room for names in narrow terminals, as
+#  with --two-rows.
+# two_rows = false
+
+#####################
+# Whether quitting must be confirmed (by quitting again) when
+#  a background task, like the computation of sizes, is still
+#  running or when some files are marked.
+# confirm_quit = false
+
+#####################
+# The maximal number of lines of the tree. By default the tree
+#  fills the screen but, in very tall terminals, building and
+#  drawing fewer lines makes broot more responsive.
+# max_tree_lines = 60
+
+#####################
+# Whether the tree is refreshed when files are created, removed
+#  or renamed in the displayed directories.
+# auto_refresh = false
+
+#####################
+# The command prefixed to a `:chmod` or `:chown` the system refused,
+#  when you retry it with `:elevate`. Broot is left before, so that
+#  the command can ask for your password in the terminal.
+# elevation_command = "sudo"
+
+#####################
+# Whether an icon depending on the type of file is written
+#  before the names. This needs a Nerd Font in your terminal.
+# icons = false
+
+#####################
+# The colors the terminal can display: "truecolor", "256" or
+#  "16". With "auto", it's guessed from the COLORTERM and TERM
+#  environment variables. The colors of the skin the terminal
+#  can't display are replaced with the nearest ones.
+# color_support = "auto"
+
+#####################
+# Whether paths differing only by case designate the same file,
+#  which is assumed on Windows and macOS when not set. Paths are
+#  then compared and globs matched ignoring the case, and the
+#  root is displayed with the case stored in the file system.
+# case_insensitive_paths = true
+
+#####################
+# Whether the background of the terminal is "dark" or "light",
+#  which chooses between the [skin.dark] and [skin.light]
+#  entries. With "auto", it's read in the BROOT_BACKGROUND or
+#  COLORFGBG environment variables (dark when they're not set).
+# background = "auto"
+
+#####################
+# The theme highlighting the syntax of the files in the preview,
+#  one of "base16-eighties.dark", "base16-mocha.dark",
+#  "base16-ocean.dark", "base16-ocean.light", "InspiredGitHub",
+#  "Solarized (dark)" and "Solarized (light)", or "none". By
+#  default, it depends on the background.
+# syntax_theme = "base16-ocean.dark"
+
+#####################
+# The size, in bytes, of the biggest file whose syntax is
+#  highlighted in the preview.
+# max_highlighted_size = 200000
+
+#####################
+# Paths needing a special handling, as globs which are
+#  matched against the whole path when they contain a '/'
+#  or against the file name otherwise. The handling is
+#  either "hide" or "no-enter".
+[special_paths]
+# "node_modules" = "no-enter"
+# "target" = "hide"
+
+#####################
+# Bounds of the widths of some columns (size, user, group).
+#  Longer values are truncated.
+[column_widths]
+# user = { max = 8 }
+# group = { min = 5, max = 8 }
+
+#####################
+# Scoring of fuzzy patterns: the bonus of a match starting
+#  a word or a path segment, the penalty per hole in a match,
+#  and the case sensitivity ("smart", "sensitive" or
+#  "insensitive"). The --fuzzy-case launch argument overrides
+#  the case setting.
+[fuzzy_scoring]
+# case = "insensitive"
+# segment_start_bonus = 5
+# hole_penalty = 30
+
 #####################
 # user defined verbs:
 
@@ -169,12 +590,23 @@ const DEFAULT_CONF_FILE: &str = r#"
 #  pattern.
 # Example:
 #  execution = "/usr/bin/nvim {file}"
+# Most editors accept a line, so that the file opens at the line
+#  selected in the preview:
+#  execution = "$EDITOR +{line} {file}"
+#
+# This verb only applies to files (see apply_to, extensions and globs).
 [[verbs]]
 invocation = "edit"
 key = "F2"
 shortcut = "e"
+apply_to = "file"
 execution = "$EDITOR {file}"
 
+# A verb may also be triggered by a chord, that is two keys
+#  typed in sequence. Chords starting with a plain char only
+#  apply when the input is empty.
+# chord = "g e"
+
 [[verbs]]
 invocation = "create {subpath}"
 execution = "$EDITOR {directory}/{subpath}"
@@ -207,6 +639,9 @@ execution = "$PAGER {file}"
 # link = "Magenta none"
 # pruning = "rgb(89, 73, 101) none Italic"
 # permissions = "gray(12) none "
+# counts = "gray(14) none"
+# status_info = "gray(17) gray(5)"
+# status_git_branch = "ansi(178) gray(5)"
 # selected_line = "none gray(3)"
 # char_match = "yellow none"
 # file_error = "Red none"
@@ -222,11 +657,32 @@ execution = "$PAGER {file}"
 # status_ellipsis = "gray(19) gray(1)"
 # scrollbar_track = "rgb(80, 50, 0) none"
 # scrollbar_thumb = "rgb(255, 187, 0) none"
+# diff_added = "Green none"
+# diff_removed = "Red none"
+# diff_header = "rgb(255, 152, 0) none bold"
 # help_paragraph = "gray(20) none"
 # help_bold = "rgb(255, 187, 0) none bold"
 # help_italic = "Magenta rgb(30, 30, 40) italic"
 # help_code = "gray(21) gray(3)"
 # help_headers = "rgb(255, 187, 0) none"
+#
+# Entries for a dark or a light background of the terminal
+#  may be given in [skin.dark] and [skin.light] sections,
+#  which overwrite the ones of [skin]:
+#
+# [skin.light]
+# default = "gray(1) gray(22)"
+# file = "gray(3) none"
+# selected_line = "none gray(19)"
+
+# The files whose name matches a glob may have their own
+#  colors (when several globs match, the first one in
+#  alphabetical order applies):
+#
+# [file_styles]
+# "*.rs" = "ansi(208) none"
+# "*.md" = "Cyan none"
+# "Cargo.*" = "ansi(208) none bold"
 
 # You may find other skins on
 #  https://dystroy.org/broot/documentation/configuration/#colors
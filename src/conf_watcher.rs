@@ -0,0 +1,49 @@
+//! watch the configuration file so that verbs and skin can be
+//! reloaded without restarting broot.
+//!
+//! We don't rely on OS notifications: the modification date of the
+//! file is just checked periodically.
+
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+/// how long the watcher waits between two checks of the
+/// configuration file
+pub static CONF_POLL_PERIOD: Duration = Duration::from_millis(1000);
+
+pub struct ConfWatcher {
+    path: PathBuf,
+    last_modified: Option<SystemTime>,
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|md| md.modified()).ok()
+}
+
+impl ConfWatcher {
+    pub fn new(path: PathBuf) -> ConfWatcher {
+        let last_modified = modified(&path);
+        ConfWatcher {
+            path,
+            last_modified,
+        }
+    }
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+    /// return true when the file was modified since the last call
+    /// (or since the creation of the watcher)
+    pub fn has_changed(&mut self) -> bool {
+        let last_modified = modified(&self.path);
+        if last_modified == self.last_modified {
+            false
+        } else {
+            self.last_modified = last_modified;
+            // a removed file isn't a new configuration
+            last_modified.is_some()
+        }
+    }
+}
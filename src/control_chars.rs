@@ -0,0 +1,96 @@
+//! the text read from files or written by programs (previewed files,
+//! diffs, captured outputs) may contain control chars, some of them
+//! starting sequences the terminal would execute: moving the cursor,
+//! changing the title of the window, filling the clipboard, etc.
+//!
+//! Such text is made harmless before being displayed, its control
+//! chars being replaced with visible escapes, `^[` for ESC for example.
+
+/// write the control char in the caret notation (`^[`, `^?`) when
+/// it's a C0 control or DEL, and as `\x9B` when it's a C1 control
+fn push_escaped(s: &mut String, c: char) {
+    let code = c as u32;
+    if code < 0x20 {
+        s.push('^');
+        s.push((code as u8 + b'@') as char);
+    } else if code == 0x7F {
+        s.push_str("^?");
+    } else {
+        s.push_str(&format!("\\x{:02X}", code));
+    }
+}
+
+/// return the text with its control chars, tabs excepted,
+/// replaced with visible escapes
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c.is_control() && c != '\t' {
+            push_escaped(&mut escaped, c);
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+/// return the length, in bytes, of the SGR sequence (the one setting
+/// the colors and attributes of the text, eg `\x1b[1;31m`) at the start
+/// of the string, if there's one
+fn sgr_len(s: &str) -> Option<usize> {
+    let params = s.strip_prefix("\x1b[")?;
+    let params_len = params
+        .find(|c: char| !(c.is_ascii_digit() || c == ';' || c == ':'))
+        .unwrap_or_else(|| params.len());
+    if params[params_len..].starts_with('m') {
+        Some(2 + params_len + 1)
+    } else {
+        None
+    }
+}
+
+/// return the text without its SGR sequences, which programs write
+/// to color their output, the other control chars, tabs excepted,
+/// being replaced with visible escapes
+pub fn escape_without_sgr(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    let mut idx = 0;
+    while let Some(c) = s[idx..].chars().next() {
+        if c == '\x1b' {
+            if let Some(len) = sgr_len(&s[idx..]) {
+                idx += len;
+                continue;
+            }
+        }
+        if c.is_control() && c != '\t' {
+            push_escaped(&mut escaped, c);
+        } else {
+            escaped.push(c);
+        }
+        idx += c.len_utf8();
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod control_chars_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_escapes() {
+        assert_eq!(escape("fn main() {\t}"), "fn main() {\t}");
+        assert_eq!(escape("héhé 漢字"), "héhé 漢字");
+        assert_eq!(escape("\x1b]52;c;Y3VybA==\x07"), "^[]52;c;Y3VybA==^G");
+        assert_eq!(escape("a\rb\x7f\u{9b}2J"), "a^Mb^?\\x9B2J");
+    }
+
+    #[test]
+    fn check_sgr_removal() {
+        assert_eq!(escape_without_sgr("\x1b[33mcommit\x1b[m abc"), "commit abc");
+        assert_eq!(escape_without_sgr("\x1b[1;38;5;208mx\x1b[0m"), "x");
+        // only the SGR sequences are removed
+        assert_eq!(escape_without_sgr("\x1b[2Jx\x1b]0;title\x07"), "^[[2Jx^[]0;title^G");
+        assert_eq!(escape_without_sgr("end\x1b["), "end^[[");
+    }
+}
@@ -0,0 +1,144 @@
+//! the format of the dates written in the date column, the status
+//! line and the HTML export: either a chrono format string, like the
+//! default `%Y/%m/%d %R`, or relative to the current time, like
+//! `3d ago`.
+
+use std::{str::FromStr, time::SystemTime};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    offset::Local,
+    DateTime, NaiveDate, TimeZone,
+};
+
+use crate::{column_widths, errors::ConfError};
+
+/// the format of the dates when none is configured
+pub const DEFAULT_FORMAT: &str = "%Y/%m/%d %R";
+
+/// the width of the longest relative date, eg "364d ago"
+const RELATIVE_WIDTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateFormat {
+    Absolute(String), // a chrono format string, eg "%Y/%m/%d %R"
+    Relative,         // the age of the date, eg "2h ago"
+}
+
+impl Default for DateFormat {
+    fn default() -> Self {
+        DateFormat::Absolute(DEFAULT_FORMAT.to_string())
+    }
+}
+
+impl FromStr for DateFormat {
+    type Err = ConfError;
+    fn from_str(s: &str) -> Result<DateFormat, ConfError> {
+        if s.eq_ignore_ascii_case("relative") {
+            return Ok(DateFormat::Relative);
+        }
+        // chrono fails when writing a date with an invalid format,
+        // so it must be checked before
+        let invalid = StrftimeItems::new(s).any(|item| match item {
+            Item::Error => true,
+            _ => false,
+        });
+        if s.trim().is_empty() || invalid {
+            return Err(ConfError::InvalidDateFormat { raw: s.to_string() });
+        }
+        Ok(DateFormat::Absolute(s.to_string()))
+    }
+}
+
+impl DateFormat {
+    pub fn format(&self, time: SystemTime) -> String {
+        match self {
+            DateFormat::Absolute(format) => {
+                let date_time: DateTime<Local> = time.into();
+                date_time.format(format).to_string()
+            }
+            DateFormat::Relative => relative(time, SystemTime::now()),
+        }
+    }
+
+    /// the width, in terminal columns, of the longest formatted date,
+    /// which is the width of the date column
+    pub fn width(&self) -> usize {
+        match self {
+            DateFormat::Absolute(format) => {
+                // the names of months and days having various lengths,
+                // dates of every month and every day of the week are tried
+                (1..=12)
+                    .map(|month| (month, 28))
+                    .chain((21..=27).map(|day| (9, day)))
+                    .filter_map(|(month, day)| {
+                        let date = NaiveDate::from_ymd(2020, month, day).and_hms(23, 59, 59);
+                        Local.from_local_datetime(&date).earliest()
+                    })
+                    .map(|date_time| column_widths::str_width(&date_time.format(format).to_string()))
+                    .max()
+                    .unwrap_or(0)
+            }
+            DateFormat::Relative => RELATIVE_WIDTH,
+        }
+    }
+}
+
+/// write the age of the time, in the biggest unit in which it's
+/// at least 1
+fn relative(time: SystemTime, now: SystemTime) -> String {
+    let (secs, future) = match now.duration_since(time) {
+        Ok(age) => (age.as_secs(), false),
+        Err(e) => (e.duration().as_secs(), true),
+    };
+    let (n, unit) = if secs < 60 {
+        (secs, "s")
+    } else if secs < 60 * 60 {
+        (secs / 60, "m")
+    } else if secs < 24 * 60 * 60 {
+        (secs / (60 * 60), "h")
+    } else if secs < 365 * 24 * 60 * 60 {
+        (secs / (24 * 60 * 60), "d")
+    } else {
+        (secs / (365 * 24 * 60 * 60), "y")
+    };
+    if future {
+        format!("in {}{}", n, unit)
+    } else {
+        format!("{}{} ago", n, unit)
+    }
+}
+
+#[cfg(test)]
+mod date_format_tests {
+
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn check_relative_dates() {
+        let now = SystemTime::now();
+        let ago = |secs| relative(now - Duration::from_secs(secs), now);
+        assert_eq!(ago(0), "0s ago");
+        assert_eq!(ago(59), "59s ago");
+        assert_eq!(ago(3 * 60 + 5), "3m ago");
+        assert_eq!(ago(2 * 60 * 60), "2h ago");
+        assert_eq!(ago(3 * 24 * 60 * 60 + 1), "3d ago");
+        assert_eq!(ago(364 * 24 * 60 * 60), "364d ago");
+        assert_eq!(ago(800 * 24 * 60 * 60), "2y ago");
+        assert_eq!(relative(now + Duration::from_secs(120), now), "in 2m");
+    }
+
+    #[test]
+    fn check_parsing() {
+        assert_eq!("relative".parse::<DateFormat>().ok(), Some(DateFormat::Relative));
+        assert_eq!(
+            "%d/%m".parse::<DateFormat>().ok(),
+            Some(DateFormat::Absolute("%d/%m".to_string())),
+        );
+        assert!("%Q".parse::<DateFormat>().is_err());
+        assert!("".parse::<DateFormat>().is_err());
+        assert_eq!(DateFormat::default().width(), 16);
+        assert_eq!(DateFormat::Absolute("%B".to_string()).width(), 9); // September
+    }
+}
@@ -0,0 +1,216 @@
+use std::{io::Write, path::PathBuf, process::Command as Process};
+
+use crossterm::{
+    cursor,
+    queue,
+    style::{Color, SetBackgroundColor},
+    terminal::{Clear, ClearType},
+};
+use minimad::Composite;
+use termimad::{Area, CompoundStyle};
+
+use crate::{
+    app_context::AppContext,
+    app_state::{AppState, AppStateCmdResult},
+    column_widths,
+    commands::{Action, Command},
+    control_chars,
+    errors::ProgramError,
+    flat_tree::Tree,
+    io::W,
+    os_names,
+    screens::Screen,
+    session::Session,
+    skin::Skin,
+    state_history::Place,
+    status::Status,
+    task_sync::TaskLifetime,
+    verb_store::PrefixSearchResult,
+    verbs::VerbExecutor,
+};
+
+/// an application state displaying the changes of a file (or of
+/// the files of a directory) against the HEAD of its git repository
+pub struct DiffState {
+    pub path: PathBuf,
+    lines: Vec<String>, // the lines of the unified diff
+    scroll: usize,      // the number of lines hidden at the top
+}
+
+impl DiffState {
+    /// run `git diff` on the path and return the state displaying
+    /// its output, or the error to display when there's no diff
+    pub fn new(path: PathBuf) -> Result<DiffState, String> {
+        let dir = if path.is_dir() {
+            path.as_path()
+        } else {
+            path.parent().unwrap_or(&path)
+        };
+        let output = Process::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(&["diff", "--no-color", "HEAD", "--"])
+            .arg(&path)
+            .output()
+            .map_err(|e| format!("can't run git: {}", e))?;
+        if !output.status.success() {
+            // for example when the file isn't in a repository
+            let err = String::from_utf8_lossy(&output.stderr);
+            return Err(err.lines().next().unwrap_or("git diff failed").to_string());
+        }
+        let diff = String::from_utf8_lossy(&output.stdout).replace('\t', "    ");
+        if diff.is_empty() {
+            return Err("no change against HEAD".to_string());
+        }
+        Ok(DiffState {
+            path,
+            // the lines come from the files, and their control
+            // chars mustn't reach the terminal
+            lines: diff.lines().map(control_chars::escape).collect(),
+            scroll: 0,
+        })
+    }
+
+    /// the number of lines of the diff which can be displayed
+    fn page_height(screen: &Screen) -> usize {
+        (screen.height as usize).saturating_sub(2)
+    }
+
+    pub fn try_scroll(&mut self, dy: i32, screen: &Screen) {
+        let max_scroll = self.lines.len().saturating_sub(DiffState::page_height(screen));
+        self.scroll = (self.scroll as i32 + dy).max(0).min(max_scroll as i32) as usize;
+    }
+}
+
+/// return the style of a line of the diff, depending on its prefix
+fn line_style<'s>(line: &str, skin: &'s Skin) -> &'s CompoundStyle {
+    if line.starts_with("+++") || line.starts_with("---") || line.starts_with("@@") {
+        &skin.diff_header
+    } else if line.starts_with('+') {
+        &skin.diff_added
+    } else if line.starts_with('-') {
+        &skin.diff_removed
+    } else if line.starts_with(' ') {
+        &skin.default
+    } else {
+        // "diff --git", "index" and the other headers of git
+        &skin.diff_header
+    }
+}
+
+impl AppState for DiffState {
+    fn has_pending_task(&self) -> bool {
+        false
+    }
+
+    fn apply(
+        &mut self,
+        cmd: &mut Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<AppStateCmdResult, ProgramError> {
+        Ok(match &cmd.action {
+            Action::Back | Action::UpTree | Action::UpTreeFromRoot => AppStateCmdResult::PopState,
+            Action::MoveSelection(dy) => {
+                self.try_scroll(*dy, screen);
+                AppStateCmdResult::Keep
+            }
+            Action::VerbIndex(index) => {
+                let verb = &con.verb_store.verbs[*index];
+                self.execute_verb(verb, &verb.invocation, screen, con)?
+            }
+            Action::VerbInvocate(invocation) => match con.verb_store.search(&invocation.name) {
+                PrefixSearchResult::Match(verb) => {
+                    self.execute_verb(verb, &invocation, screen, con)?
+                }
+                _ => AppStateCmdResult::verb_not_found(&invocation.name),
+            },
+            _ => AppStateCmdResult::Keep,
+        })
+    }
+
+    fn refresh(&mut self, screen: &Screen, _con: &AppContext) -> Command {
+        // the diff is computed again so that it follows the changes of the file
+        if let Ok(state) = DiffState::new(self.path.clone()) {
+            self.lines = state.lines;
+        }
+        self.try_scroll(0, screen);
+        Command::new()
+    }
+
+    fn do_pending_task(&mut self, _screen: &mut Screen, _tl: &TaskLifetime) {
+        unreachable!();
+    }
+
+    fn display(
+        &mut self,
+        w: &mut W,
+        screen: &Screen,
+        _con: &AppContext
+    ) -> Result<(), ProgramError> {
+        let skin = &screen.skin;
+        let page_height = DiffState::page_height(screen);
+        // the last column is kept for the scrollbar
+        let width = (screen.width as usize).saturating_sub(1);
+        let scrollbar = Area::new(0, 0, screen.width - 1, page_height as u16)
+            .scrollbar(self.scroll as i32, self.lines.len() as i32);
+        for y in 0..page_height {
+            queue!(w, cursor::MoveTo(0, y as u16))?;
+            if let Some(line) = self.lines.get(self.scroll + y) {
+                line_style(line, skin).queue(w, column_widths::fit(line, width))?;
+            }
+            skin.default.queue_bg(w)?;
+            queue!(w, Clear(ClearType::UntilNewLine))?;
+            queue!(w, SetBackgroundColor(Color::Reset))?;
+            if let Some((sctop, scbottom)) = scrollbar {
+                queue!(w, cursor::MoveTo(width as u16, y as u16))?;
+                let style = if sctop <= y as u16 && y as u16 <= scbottom {
+                    &skin.scrollbar_thumb
+                } else {
+                    &skin.scrollbar_track
+                };
+                style.queue_str(w, "▐")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_status(
+        &self,
+        w: &mut W,
+        _cmd: &Command,
+        screen: &Screen,
+        _con: &AppContext,
+    ) -> Result<(), ProgramError> {
+        let text = format!(
+            "Changes of `{}` against HEAD, hit *esc* to get back to the tree",
+            os_names::escape(self.path.as_os_str()),
+        );
+        Status::from_message(Composite::from_inline(&text)).display(w, screen)
+    }
+
+    /// there's no meaningful flags here
+    fn write_flags(
+        &self,
+        w: &mut W,
+        screen: &mut Screen,
+        _con: &AppContext
+    ) -> Result<(), ProgramError> {
+        screen.skin.default.queue_bg(w)?;
+        queue!(w, Clear(ClearType::UntilNewLine))?;
+        Ok(())
+    }
+
+    /// the diff isn't restored on resume
+    fn session(&self) -> Option<Session> {
+        None
+    }
+
+    fn place(&self) -> Option<Place> {
+        None
+    }
+
+    fn tree(&self) -> Option<&Tree> {
+        None
+    }
+}
@@ -0,0 +1,50 @@
+//! this modules defines the execution of verbs on the screen
+//! displaying the git diff of a file
+
+use crate::{
+    app_context::AppContext,
+    app_state::AppStateCmdResult,
+    diff_states::DiffState,
+    errors::ProgramError,
+    screens::Screen,
+    verb_invocation::VerbInvocation,
+    verbs::{Verb, VerbExecutor},
+};
+
+impl VerbExecutor for DiffState {
+    fn execute_verb(
+        &mut self,
+        verb: &Verb,
+        invocation: &VerbInvocation,
+        screen: &mut Screen,
+        _con: &AppContext,
+    ) -> Result<AppStateCmdResult, ProgramError> {
+        if let Some(err) = verb.match_error(invocation) {
+            return Ok(AppStateCmdResult::DisplayError(err));
+        }
+        let page_height = screen.height as i32 - 2;
+        Ok(match verb.execution.as_ref() {
+            ":back" => AppStateCmdResult::PopState,
+            ":git_diff" | ":refresh" => AppStateCmdResult::RefreshState,
+            ":line_down" => {
+                self.try_scroll(1, screen);
+                AppStateCmdResult::Keep
+            }
+            ":line_up" => {
+                self.try_scroll(-1, screen);
+                AppStateCmdResult::Keep
+            }
+            ":page_down" => {
+                self.try_scroll(page_height, screen);
+                AppStateCmdResult::Keep
+            }
+            ":page_up" => {
+                self.try_scroll(-page_height, screen);
+                AppStateCmdResult::Keep
+            }
+            ":quit" => AppStateCmdResult::Quit,
+            // the other verbs apply to the tree
+            _ => AppStateCmdResult::PopStateAndReapply,
+        })
+    }
+}
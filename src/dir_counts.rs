@@ -0,0 +1,75 @@
+//! count the children of directories, both the direct ones
+//! and all the descendants.
+//!
+//! As for sizes, a cache avoids counting the same directories
+//! again and again.
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Instant,
+};
+
+use crate::task_sync::TaskLifetime;
+
+lazy_static! {
+    static ref COUNT_CACHE_MUTEX: Mutex<HashMap<PathBuf, ChildrenCount>> = Mutex::new(HashMap::new());
+}
+
+pub fn clear_cache() {
+    let mut count_cache = COUNT_CACHE_MUTEX.lock().unwrap();
+    count_cache.clear();
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ChildrenCount {
+    pub direct: usize, // number of files and directories directly in the directory
+    pub total: usize,  // number of files and directories at any depth
+}
+
+impl ChildrenCount {
+    /// Return the counts of the directory, either by computing them or by
+    ///  fetching them from cache.
+    /// If the lifetime expires before complete computation, None is returned.
+    /// The cache isn't locked during the computation, so that other
+    ///  directories can be counted meanwhile.
+    pub fn from_dir(path: &Path, tl: &TaskLifetime) -> Option<ChildrenCount> {
+        if let Some(c) = COUNT_CACHE_MUTEX.lock().unwrap().get(path) {
+            return Some(*c);
+        }
+        let start = Instant::now();
+        let direct = match fs::read_dir(path) {
+            Ok(entries) => entries.count(),
+            Err(_) => 0,
+        };
+        let total = count_descendants(path, tl)?;
+        let count = ChildrenCount { direct, total };
+        COUNT_CACHE_MUTEX.lock().unwrap().insert(PathBuf::from(path), count);
+        debug!("children count for {:?} took {:?}", path, start.elapsed());
+        Some(count)
+    }
+}
+
+/// count all the files and directories below the given one, without
+/// following symlinks. Return None if the task lifetime expired.
+fn count_descendants(path: &Path, tl: &TaskLifetime) -> Option<usize> {
+    let mut total = 0;
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        if tl.is_expired() {
+            return None;
+        }
+        if let Ok(entries) = fs::read_dir(&dir) {
+            for e in entries.filter_map(|e| e.ok()) {
+                total += 1;
+                if let Ok(ft) = e.file_type() {
+                    if ft.is_dir() {
+                        dirs.push(e.path());
+                    }
+                }
+            }
+        }
+    }
+    Some(total)
+}
@@ -0,0 +1,292 @@
+//! the background listing of the directories the user is likely to
+//! open next (the selected one, its subdirectories, the siblings of
+//! the selection), so that entering them is instant even when reading
+//! a directory is slow, as on network mounts.
+//!
+//! The listings are kept in a LRU cache the file system source looks
+//! into before reading a directory. As for the files watcher, a listing
+//! is only used as long as the modification date of the directory,
+//! which changes when an entry is added, removed or renamed, is the
+//! one read before listing it.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+    time::SystemTime,
+};
+
+use crossbeam::channel::{unbounded, Receiver, Sender, TryRecvError};
+
+use crate::{
+    flat_tree::{Tree, TreeLine},
+    path_case,
+    tree_sources::{self, SourceEntry},
+};
+
+/// the number of listings kept in the cache
+const CACHE_CAPACITY: usize = 500;
+
+/// the maximal number of directories listed for a selection
+const MAX_LISTINGS_PER_REQUEST: usize = 40;
+
+struct Listing {
+    modified: SystemTime, // the date of the directory before it was listed
+    entries: Vec<SourceEntry>,
+    last_use: u64,
+}
+
+struct ListingCache {
+    listings: HashMap<PathBuf, Listing>,
+    capacity: usize,
+    uses: u64, // incremented at each use, to find the least recently used listing
+}
+
+impl ListingCache {
+    fn new(capacity: usize) -> ListingCache {
+        ListingCache {
+            listings: HashMap::new(),
+            capacity,
+            uses: 0,
+        }
+    }
+    fn listed_date(&self, dir: &Path) -> Option<SystemTime> {
+        self.listings.get(dir).map(|listing| listing.modified)
+    }
+    fn get(&mut self, dir: &Path) -> Option<Vec<SourceEntry>> {
+        self.uses += 1;
+        let uses = self.uses;
+        self.listings.get_mut(dir).map(|listing| {
+            listing.last_use = uses;
+            listing.entries.clone()
+        })
+    }
+    /// store the listing, removing the least recently used one
+    /// when the cache is full
+    fn insert(&mut self, dir: PathBuf, modified: SystemTime, entries: Vec<SourceEntry>) {
+        if self.listings.len() >= self.capacity && !self.listings.contains_key(&dir) {
+            let lru = self
+                .listings
+                .iter()
+                .min_by_key(|(_, listing)| listing.last_use)
+                .map(|(path, _)| path.clone());
+            if let Some(lru) = lru {
+                self.listings.remove(&lru);
+            }
+        }
+        self.uses += 1;
+        self.listings.insert(
+            dir,
+            Listing {
+                modified,
+                entries,
+                last_use: self.uses,
+            },
+        );
+    }
+}
+
+lazy_static! {
+    static ref LISTING_CACHE_MUTEX: Mutex<ListingCache> = Mutex::new(ListingCache::new(CACHE_CAPACITY));
+}
+
+pub fn clear_cache() {
+    let mut cache = LISTING_CACHE_MUTEX.lock().unwrap();
+    cache.listings.clear();
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|md| md.modified()).ok()
+}
+
+/// tell whether the cache has a listing of the directory which is
+/// still valid (the directory is read outside of the lock)
+fn has_fresh_listing(dir: &Path) -> bool {
+    let listed_date = LISTING_CACHE_MUTEX.lock().unwrap().listed_date(dir);
+    listed_date.is_some() && modified(dir) == listed_date
+}
+
+/// return the entries of the directory if it was pre-listed and
+/// didn't change since
+pub fn cached(dir: &Path) -> Option<Vec<SourceEntry>> {
+    if !has_fresh_listing(dir) {
+        return None;
+    }
+    LISTING_CACHE_MUTEX.lock().unwrap().get(dir)
+}
+
+fn subdirs(entries: &[SourceEntry]) -> Vec<PathBuf> {
+    entries
+        .iter()
+        .filter(|e| e.entry_type.is_dir())
+        .map(|e| e.path.clone())
+        .collect()
+}
+
+/// list the directory and store its entries in the cache. Return
+/// the subdirectories
+fn prelist(dir: &Path) -> Vec<PathBuf> {
+    let modified = match modified(dir) {
+        Some(modified) => modified,
+        None => {
+            return Vec::new(); // such listing couldn't be checked
+        }
+    };
+    let entries = match tree_sources::read_fs_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => {
+            return Vec::new();
+        }
+    };
+    let subdirs = subdirs(&entries);
+    let mut cache = LISTING_CACHE_MUTEX.lock().unwrap();
+    cache.insert(dir.to_path_buf(), modified, entries);
+    subdirs
+}
+
+/// the directories to pre-list for a selection
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrelistRequest {
+    selected: Option<PathBuf>, // the selected directory, whose subdirectories are pre-listed too
+    others: Vec<PathBuf>,      // the sibling directories and the parent of the root
+}
+
+impl PrelistRequest {
+    pub fn of(tree: &Tree) -> PrelistRequest {
+        let enterable = |line: &TreeLine| {
+            if !line.is_dir() || line.metadata.in_archive {
+                return false;
+            }
+            let path = line.path.to_path_buf();
+            !tree
+                .options
+                .do_not_enter
+                .iter()
+                .any(|p| path_case::same_path(p, &path))
+                && tree.options.path_filters.may_enter(&path, &line.name)
+        };
+        let selected_line = tree.selected_line();
+        let selected = if enterable(selected_line) {
+            Some(selected_line.path.to_path_buf())
+        } else {
+            None
+        };
+        let parent = selected_line.path.parent();
+        let mut others: Vec<PathBuf> = tree
+            .lines
+            .iter()
+            .skip(1)
+            .filter(|line| line.path != selected_line.path && line.path.parent() == parent)
+            .filter(|line| enterable(line))
+            .map(|line| line.path.to_path_buf())
+            .collect();
+        if let Some(root_parent) = tree.root().parent() {
+            others.push(root_parent.to_path_buf());
+        }
+        PrelistRequest { selected, others }
+    }
+}
+
+/// the handle to the thread listing the directories in background
+pub struct Prelister {
+    tx: Sender<PrelistRequest>,
+    last_request: PrelistRequest,
+}
+
+impl Prelister {
+    pub fn new() -> Prelister {
+        let (tx, rx) = unbounded();
+        thread::spawn(move || prelist_requested_dirs(rx));
+        Prelister {
+            tx,
+            last_request: PrelistRequest::default(),
+        }
+    }
+
+    /// ask for the directories of the request to be listed. The
+    /// directories of the previous request which weren't listed
+    /// yet are forgotten
+    pub fn set_request(&mut self, request: PrelistRequest) {
+        if request == self.last_request {
+            return;
+        }
+        self.last_request = request.clone();
+        let _ = self.tx.send(request);
+    }
+}
+
+/// list the directories of the requests, a new request replacing
+/// the current one, until the prelister is dropped
+fn prelist_requested_dirs(rx: Receiver<PrelistRequest>) {
+    let mut next_request = None;
+    loop {
+        let request = match next_request.take() {
+            Some(request) => request,
+            None => match rx.recv() {
+                Ok(request) => request,
+                Err(_) => return,
+            },
+        };
+        let mut queue: VecDeque<PathBuf> = request.others.iter().cloned().collect();
+        if let Some(selected) = &request.selected {
+            queue.push_front(selected.clone());
+        }
+        let mut listings = 0;
+        while listings < MAX_LISTINGS_PER_REQUEST {
+            match rx.try_recv() {
+                Ok(new_request) => {
+                    next_request = Some(new_request);
+                    break;
+                }
+                Err(TryRecvError::Disconnected) => return,
+                Err(TryRecvError::Empty) => {}
+            }
+            let dir = match queue.pop_front() {
+                Some(dir) => dir,
+                None => break,
+            };
+            let subdirs = match cached(&dir) {
+                Some(entries) => subdirs(&entries),
+                None => {
+                    listings += 1;
+                    prelist(&dir)
+                }
+            };
+            if request.selected.as_ref() == Some(&dir) {
+                // the subdirectories of the selection come before its siblings
+                for subdir in subdirs.into_iter().rev() {
+                    queue.push_front(subdir);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod dir_prelisting_tests {
+
+    use super::*;
+    use crate::tree_sources::EntryType;
+
+    fn entries(name: &str) -> Vec<SourceEntry> {
+        vec![SourceEntry {
+            path: PathBuf::from(name),
+            entry_type: EntryType::File,
+        }]
+    }
+
+    #[test]
+    fn check_lru_eviction() {
+        let now = SystemTime::now();
+        let mut cache = ListingCache::new(2);
+        cache.insert(PathBuf::from("a"), now, entries("a/f"));
+        cache.insert(PathBuf::from("b"), now, entries("b/f"));
+        assert!(cache.get(Path::new("a")).is_some()); // b is now the least recently used
+        cache.insert(PathBuf::from("c"), now, entries("c/f"));
+        assert!(cache.listed_date(Path::new("a")).is_some());
+        assert!(cache.listed_date(Path::new("b")).is_none());
+        assert!(cache.listed_date(Path::new("c")).is_some());
+    }
+}
@@ -1,31 +1,41 @@
 use std::{time::SystemTime};
 
-#[cfg(unix)]
-use std::os::unix::fs::MetadataExt;
-
-use chrono::{offset::Local, DateTime};
 use crossterm::{
     cursor,
     queue,
     style::{Color, SetBackgroundColor},
     terminal::{Clear, ClearType},
 };
-use termimad::{
-    CompoundStyle,
-    ProgressBar,
-};
+use termimad::CompoundStyle;
 
 #[cfg(unix)]
 use crate::permissions;
 
 use crate::{
+    column_widths,
     file_sizes::Size,
+    filesystems,
     flat_tree::{LineType, Tree, TreeLine},
     errors::ProgramError,
+    icons,
+    marks,
+    os_names,
     patterns::Pattern,
     skin::Skin,
+    tree_options::FilterStrictness,
+    xattrs,
 };
 
+/// the width of the column of the children counts
+const COUNT_COLUMN_WIDTH: usize = 14;
+
+/// the maximal width of the security context in the xattr column
+const MAX_CONTEXT_WIDTH: usize = 32;
+
+/// the columns are dropped when they'd leave less than this
+/// width to the names
+const MIN_NAME_WIDTH: usize = 12;
+
 /// A tree wrapper which can be used either
 /// - to write on the screen in the application,
 /// - or to write in a file or an exported string.
@@ -52,17 +62,37 @@ impl<'s, 't> DisplayableTree<'s, 't> {
                 left: 0,
                 top: 0,
                 width,
-                height: tree.lines.len() as u16,
+                // out of the app, all the lines are written, even when
+                // they're more than an area can hold
+                height: (tree.lines.len() * tree.options.rows_per_line() as usize)
+                    .min(u16::max_value() as usize) as u16,
             },
             in_app: false,
         }
     }
 
+    /// tell whether the line is only there because it leads to
+    /// matches of the search, and must be dimmed
+    fn is_dimmed(&self, line: &TreeLine) -> bool {
+        let options = &self.tree.options;
+        options.filter_strictness == FilterStrictness::Dimmed
+            && options.pattern.is_some()
+            && line.depth > 0
+            && line.line_type != LineType::Pruning
+            && line.group_count.is_none()
+            && !self.tree.is_match(line)
+    }
+
     fn name_style(&self, line: &TreeLine) -> &CompoundStyle {
+        if self.is_dimmed(line) {
+            return &self.skin.dimmed;
+        }
         match &line.line_type {
             LineType::Dir => &self.skin.directory,
-            LineType::File => {
-                if line.is_exe() {
+            LineType::File | LineType::Archive => {
+                if let Some(style) = self.skin.file_style(&line.name) {
+                    style
+                } else if line.is_exe() {
                     &self.skin.exe
                 } else {
                     &self.skin.file
@@ -77,19 +107,57 @@ impl<'s, 't> DisplayableTree<'s, 't> {
         &self,
         f: &mut F,
         line: &TreeLine,
+        line_index: usize,
         total_size: Size,
+        size_width: usize,
         selected: bool,
     ) -> Result<(), termimad::Error> where F: std::io::Write {
+        let bar_style = self.tree.options.size_bar;
+        let whale_spotting = self.tree.options.whale_spotting;
         if let Some(s) = line.size {
-            let pb = ProgressBar::new(s.part_of(total_size), 10);
+            // in whale spotting mode, the size is compared to the one of the parent
+            let part = if whale_spotting {
+                s.part_of(self.tree.parent_size(line_index))
+            } else {
+                s.part_of(total_size)
+            };
+            let bar = bar_style.render(part);
             if selected {
                 self.skin.selected_line.queue_bg(f)?;
             }
             let style = self.name_style(line);
             style.queue_fg(f)?;
-            Ok(write!(f, "{:>5} {:<10} ", s.to_string(), pb)?)
+            // an arrow tells the size is the one of the link's target
+            let hint = if line.is_symlink() { '→' } else { ' ' };
+            let size = s.format(self.tree.options.size_format);
+            let size = column_widths::fit(&size, size_width);
+            let percent = if whale_spotting {
+                format!("{:>3.0}% ", part * 100.0)
+            } else {
+                String::new()
+            };
+            Ok(write!(f, "{:>w$}{}{}{} ", size, hint, percent, bar, w = size_width)?)
+        } else {
+            let percent_width = if whale_spotting { 5 } else { 0 };
+            let filler = format!(
+                "{} ",
+                "─".repeat(size_width + 1 + percent_width + bar_style.width()),
+            );
+            self.skin.tree.queue_str(f, &filler)
+        }
+    }
+
+    fn write_line_count<F>(
+        &self,
+        f: &mut F,
+        line: &TreeLine,
+    ) -> Result<(), termimad::Error> where F: std::io::Write {
+        if let Some(c) = line.children_count {
+            self.skin.counts.queue(f, format!("{:>5}/{:<7} ", c.direct, c.total))
+        } else if line.line_type == LineType::Dir {
+            self.skin.tree.queue_str(f, "───────────── ")
         } else {
-            self.skin.tree.queue_str(f, "──────────────── ")
+            self.skin.tree.queue_str(f, "              ")
         }
     }
 
@@ -97,11 +165,16 @@ impl<'s, 't> DisplayableTree<'s, 't> {
         &self,
         f: &mut F,
         system_time: SystemTime,
+        width: usize,
     ) -> Result<(), termimad::Error> where F: std::io::Write {
-        let date_time: DateTime<Local> = system_time.into();
-        self.skin.dates.queue(f, date_time.format("%Y/%m/%d %R ").to_string())
+        let date = self.tree.options.date_format.format(system_time);
+        let date = column_widths::fit(&date, width);
+        self.skin.dates.queue(f, format!("{} ", column_widths::pad(&date, width)))
     }
 
+    /// write the name of the line and what follows it (count of matches,
+    /// link target, etc.) in at most `width` chars, the name being
+    /// truncated with an ellipsis when it doesn't fit
     fn write_line_name<F>(
         &self,
         f: &mut F,
@@ -109,20 +182,9 @@ impl<'s, 't> DisplayableTree<'s, 't> {
         idx: usize,
         pattern: &Pattern,
         selected: bool,
+        width: usize,
     ) -> Result<(), ProgramError> where F: std::io::Write {
-        let style = match &line.line_type {
-            LineType::Dir => &self.skin.directory,
-            LineType::File => {
-                if line.is_exe() {
-                    &self.skin.exe
-                } else {
-                    &self.skin.file
-                }
-            }
-            LineType::SymLinkToFile(_) | LineType::SymLinkToDir(_) => &self.skin.link,
-            LineType::Pruning => &self.skin.pruning,
-        };
-        let mut style = style.clone();
+        let mut style = self.name_style(line).clone();
         let mut char_match_style = self.skin.char_match.clone();
         if selected {
             if let Some(c) = self.skin.selected_line.get_bg() {
@@ -130,41 +192,186 @@ impl<'s, 't> DisplayableTree<'s, 't> {
                 char_match_style.set_bg(c);
             }
         }
+        let mut remaining = width;
+        if self.in_app && marks::is_marked(&line.path.to_path_buf()) && remaining >= 2 {
+            char_match_style.queue_str(f, "• ")?;
+            remaining -= 2;
+        }
+        if self.tree.options.show_icons && idx > 0 && line.group_count.is_none() {
+            if let Some(icon) = icons::icon(line) {
+                let icon = format!("{} ", icon);
+                let icon_width = column_widths::str_width(&icon);
+                if icon_width <= remaining {
+                    style.queue(f, icon)?;
+                    remaining -= icon_width;
+                }
+            }
+        }
         if idx == 0 {
-            style.queue_str(f, &line.path.to_string_lossy())?;
+            let root = column_widths::fit(&os_names::escape(self.tree.root().as_os_str()), remaining);
+            remaining -= column_widths::str_width(&root);
+            style.queue_str(f, &root)?;
+        } else if line.group_count.is_some() {
+            // the header of a group, whose name is a path
+            let header = column_widths::fit(&line.name, remaining);
+            remaining -= column_widths::str_width(&header);
+            style.queue_str(f, &header)?;
         } else {
-            pattern.style(&line.name, &style, &char_match_style).write_on(f)?;
+            remaining -= pattern
+                .style(&line.name, &style, &char_match_style)
+                .write_fit_on(f, remaining)?;
+        }
+        if let Some(count) = line.group_count {
+            let s = if count == 1 { "" } else { "es" };
+            let count = column_widths::fit(&format!(" ({} match{})", count, s), remaining);
+            remaining -= column_widths::str_width(&count);
+            self.skin.counts.queue(f, count)?;
         }
         match &line.line_type {
-            LineType::Dir => {
-                if line.unlisted > 0 {
+            LineType::Dir | LineType::Archive => {
+                if line.unlisted > 0 && remaining >= 2 {
                     style.queue_str(f, " …")?;
+                    remaining -= 2;
                 }
             }
             LineType::SymLinkToFile(target) | LineType::SymLinkToDir(target) => {
-                style.queue_str(f, " -> ")?;
-                if line.has_error {
-                    self.skin.file_error.queue_str(f, &target)?;
-                } else {
-                    let target_style = if line.is_dir() {
-                        &self.skin.directory
+                // the arrow is only written when there's room for some of the target
+                if remaining > 4 {
+                    style.queue_str(f, " -> ")?;
+                    let target = column_widths::fit(target, remaining - 4);
+                    remaining -= 4 + column_widths::str_width(&target);
+                    if line.has_error {
+                        self.skin.file_error.queue_str(f, &target)?;
                     } else {
-                        &self.skin.file
-                    };
-                    let mut target_style = target_style.clone();
-                    if selected {
-                        if let Some(c) = self.skin.selected_line.get_bg() {
-                            target_style.set_bg(c);
+                        let target_style = if line.is_dir() {
+                            &self.skin.directory
+                        } else {
+                            &self.skin.file
+                        };
+                        let mut target_style = target_style.clone();
+                        if selected {
+                            if let Some(c) = self.skin.selected_line.get_bg() {
+                                target_style.set_bg(c);
+                            }
                         }
+                        target_style.queue(f, &target)?;
                     }
-                    target_style.queue(f, &target)?;
                 }
             }
             _ => {}
         }
+        if idx == 0 && self.in_app && self.tree.options.show_sizes {
+            self.write_root_mount(f, line, selected, remaining)?;
+        }
+        Ok(())
+    }
+
+    /// write the device and the space of the filesystem containing
+    /// the root, giving some context to the sizes of the tree. Nothing
+    /// is written when it doesn't fit in the width.
+    fn write_root_mount<F>(
+        &self,
+        f: &mut F,
+        root: &TreeLine,
+        selected: bool,
+        width: usize,
+    ) -> Result<(), ProgramError> where F: std::io::Write {
+        if root.metadata.in_archive || !self.tree.options.source.is_local() {
+            return Ok(());
+        }
+        if let Some(mount) = filesystems::mount_of(self.tree.root()) {
+            let size_format = self.tree.options.size_format;
+            let part = mount.used_part();
+            let text = format!(
+                "  {} {}/{} {:>3.0}% {}",
+                mount.device,
+                Size::from(mount.used).format(size_format),
+                Size::from(mount.size).format(size_format),
+                part * 100.0,
+                self.tree.options.size_bar.render(part),
+            );
+            if column_widths::str_width(&text) > width {
+                return Ok(());
+            }
+            let mut style = self.skin.counts.clone();
+            if selected {
+                if let Some(c) = self.skin.selected_line.get_bg() {
+                    style.set_bg(c);
+                }
+            }
+            style.queue(f, text)?;
+        }
         Ok(())
     }
 
+    /// write the inode column: the inode number and the number
+    /// of hard links of the file
+    fn write_line_inode<F>(
+        &self,
+        f: &mut F,
+        line: &TreeLine,
+        (ino_width, nlink_width): (usize, usize),
+    ) -> Result<(), termimad::Error> where F: std::io::Write {
+        if line.is_selectable() && line.metadata.ino() > 0 {
+            self.skin.counts.queue(
+                f,
+                format!(
+                    "{:>iw$} {:>lw$} ",
+                    line.metadata.ino(),
+                    line.metadata.nlink(),
+                    iw = ino_width,
+                    lw = nlink_width,
+                ),
+            )
+        } else {
+            let filler = format!("{} ", "─".repeat(ino_width + 1 + nlink_width));
+            self.skin.tree.queue_str(f, &filler)
+        }
+    }
+
+    /// return the width of the widest security context among the
+    /// lines which are displayed
+    fn context_width(&self) -> usize {
+        let tree = self.tree;
+        let (start, nb_lines) = if self.in_app {
+            let nb_lines = self.area.height / tree.options.rows_per_line();
+            (tree.scroll as usize + 1, nb_lines as usize)
+        } else {
+            (1, tree.lines.len())
+        };
+        tree.lines
+            .iter()
+            .skip(start)
+            .take(nb_lines)
+            .filter(|line| line.is_selectable())
+            .filter_map(|line| xattrs::of(&line.path.to_path_buf()).context)
+            .map(|context| column_widths::str_width(&context))
+            .max()
+            .unwrap_or(0)
+            .min(MAX_CONTEXT_WIDTH)
+    }
+
+    /// write the xattr column: a `@` when the file has extended
+    /// attributes, then its security context, if any
+    fn write_line_xattrs<F>(
+        &self,
+        f: &mut F,
+        line: &TreeLine,
+        context_width: usize,
+    ) -> Result<(), termimad::Error> where F: std::io::Write {
+        if !line.is_selectable() {
+            let filler = format!("{} ", "─".repeat(context_width + 2));
+            return self.skin.tree.queue_str(f, &filler);
+        }
+        let xattrs = xattrs::of(&line.path.to_path_buf());
+        let marker = if xattrs.count > 0 { '@' } else { ' ' };
+        let context = column_widths::fit(xattrs.context.as_deref().unwrap_or(""), context_width);
+        self.skin.permissions.queue(
+            f,
+            format!("{} {} ", marker, column_widths::pad(&context, context_width)),
+        )
+    }
+
     pub fn write_on<F>(
         &self,
         f: &mut F,
@@ -172,67 +379,200 @@ impl<'s, 't> DisplayableTree<'s, 't> {
         let tree = self.tree;
         #[cfg(unix)]
         let user_group_max_lengths = user_group_max_lengths(&tree);
+        #[cfg(unix)]
+        let permissions_width = {
+            let (user_width, group_width) = user_group_max_lengths;
+            // the mode, the widths of the not pruned columns with
+            // their leading space, and a trailing space
+            10 + if user_width > 0 { user_width + 1 } else { 0 }
+                + if group_width > 0 { group_width + 1 } else { 0 }
+        };
+        #[cfg(not(unix))]
+        let permissions_width = 0;
         let total_size = tree.total_size();
+        // the total is the biggest size, it gives the width of the column
+        let size_width = tree.options.column_widths.size.clamp(
+            total_size
+                .format(tree.options.size_format)
+                .chars()
+                .count()
+                .max(5),
+        );
+        // the extended attributes are only read for the displayed lines
+        let xattrs_shown = tree.options.show_xattrs
+            && xattrs::supported()
+            && tree.options.source.is_local();
+        let xattr_width = if xattrs_shown {
+            self.context_width()
+        } else {
+            0
+        };
+        let inode_widths = inode_widths(tree);
+        let inode_column_width = inode_widths.0 + 1 + inode_widths.1 + 1;
+        let percent_width = if tree.options.whale_spotting { 5 } else { 0 };
+        let size_column_width = size_width + 1 + percent_width + tree.options.size_bar.width() + 1;
+        let date_width = if tree.options.shows_dates() {
+            tree.options.date_format.width()
+        } else {
+            0
+        };
+        let date_kind = tree.options.displayed_date_kind();
+        // in the application, nothing is written after the width of a row,
+        // the last char being kept for the scrollbar
+        let row_width = if self.in_app {
+            (self.area.width as usize).saturating_sub(1)
+        } else {
+            usize::max_value()
+        };
+        // the columns which would leave too little room to the names
+        // are dropped, the last ones first
+        let mut columns = [
+            (tree.options.show_sizes, size_column_width),
+            (tree.options.show_counts, COUNT_COLUMN_WIDTH),
+            (cfg!(unix) && tree.options.show_permissions, permissions_width),
+            (cfg!(unix) && tree.options.show_inodes, inode_column_width),
+            (xattrs_shown, xattr_width + 3),
+            (tree.options.shows_dates(), date_width + 1),
+        ];
+        for i in (0..columns.len()).rev() {
+            let used: usize = columns.iter().filter(|c| c.0).map(|c| c.1).sum();
+            if used + MIN_NAME_WIDTH <= row_width {
+                break;
+            }
+            columns[i].0 = false;
+        }
+        let [
+            (show_sizes, _),
+            (show_counts, _),
+            (show_permissions, _),
+            (show_inodes, _),
+            (show_xattrs, _),
+            (show_dates, _),
+        ] = columns;
+        // in the two rows layout, the columns are written on a second
+        // row, under the name
+        let rows = tree.options.rows_per_line();
+        let two_rows = rows > 1;
         let scrollbar = if self.in_app {
-            self.area.scrollbar(tree.scroll, tree.lines.len() as i32)
+            // computed in tree lines, then scaled to rows
+            let lines_area = termimad::Area {
+                height: self.area.height / rows,
+                ..self.area
+            };
+            lines_area
+                .scrollbar(tree.scroll, tree.lines.len() as i32)
+                .map(|(top, bottom)| (top * rows, bottom * rows + rows - 1))
         } else {
             None
         };
-        for y in 0..self.area.height {
+        let nb_rows = if self.in_app {
+            self.area.height as usize
+        } else {
+            tree.lines.len() * rows as usize
+        };
+        for row in 0..nb_rows {
+            let y = row as u16; // only used in the app, where it fits
             if self.in_app {
                 queue!(f, cursor::MoveTo(0, y))?;
             }
-            let mut line_index = y as usize;
+            let mut line_index = row / rows as usize;
             if line_index > 0 {
                 line_index += tree.scroll as usize;
             }
+            let columns_row = two_rows && row % rows as usize == 1;
             let mut selected = false;
+            let mut x = 0; // the width already written in the row
             if line_index < tree.lines.len() {
                 let line = &tree.lines[line_index];
                 selected = self.in_app && line_index == tree.selection;
-                for depth in 0..line.depth {
-                    self.skin.tree.queue_str(
-                        f,
-                        if line.left_branchs[depth as usize] {
+                let show_columns = line_index > 0 && columns_row == two_rows;
+                if columns_row {
+                    // the branchs continue down to the next line
+                    x += 3 * (line.depth as usize + 1);
+                    for depth in 0..=line.depth {
+                        self.skin.tree.queue_str(
+                            f,
                             if self.tree.has_branch(line_index + 1, depth as usize) {
-                                if depth == line.depth - 1 {
-                                    "├──"
+                                "│  "
+                            } else {
+                                "   "
+                            },
+                        )?;
+                    }
+                } else {
+                    x += 3 * line.depth as usize;
+                    for depth in 0..line.depth {
+                        self.skin.tree.queue_str(
+                            f,
+                            if line.left_branchs[depth as usize] {
+                                if self.tree.has_branch(line_index + 1, depth as usize) {
+                                    if depth == line.depth - 1 {
+                                        "├──"
+                                    } else {
+                                        "│  "
+                                    }
                                 } else {
-                                    "│  "
+                                    "└──"
                                 }
                             } else {
-                                "└──"
-                            }
-                        } else {
-                            "   "
-                        },
-                    )?;
+                                "   "
+                            },
+                        )?;
+                    }
                 }
-                if tree.options.show_sizes && line_index > 0 {
-                    self.write_line_size(f, line, total_size, selected)?;
+                if show_sizes && show_columns {
+                    self.write_line_size(f, line, line_index, total_size, size_width, selected)?;
+                    x += size_column_width;
+                }
+                if show_counts && show_columns {
+                    self.write_line_count(f, line)?;
+                    x += COUNT_COLUMN_WIDTH;
                 }
                 #[cfg(unix)]
                 {
-                    if tree.options.show_permissions && line_index > 0 {
+                    if show_permissions && show_columns {
+                        x += permissions_width;
                         if line.is_selectable() {
                             self.skin.permissions.queue(f, line.mode())?;
-                            let user = permissions::user_name(line.metadata.uid());
-                            write!(f, " {:w$}", &user, w = user_group_max_lengths.0,)?;
-                            let group = permissions::group_name(line.metadata.gid());
-                            write!(f, " {:w$} ", &group, w = user_group_max_lengths.1,)?;
+                            if user_group_max_lengths.0 > 0 {
+                                let user = permissions::user_name(line.metadata.uid());
+                                let width = user_group_max_lengths.0;
+                                let user = column_widths::fit(&user, width);
+                                write!(f, " {}", column_widths::pad(&user, width))?;
+                            }
+                            if user_group_max_lengths.1 > 0 {
+                                let group = permissions::group_name(line.metadata.gid());
+                                let width = user_group_max_lengths.1;
+                                let group = column_widths::fit(&group, width);
+                                write!(f, " {}", column_widths::pad(&group, width))?;
+                            }
+                            write!(f, " ")?;
                         } else {
-                            self.skin.tree.queue_str(f, "──────────────")?;
+                            let filler = format!("{} ", "─".repeat(permissions_width - 1));
+                            self.skin.tree.queue_str(f, &filler)?;
                         }
                     }
                 }
-                if tree.options.show_dates && line_index > 0 {
-                    if let Ok(date) = line.metadata.modified() {
-                        self.write_date(f, date)?;
+                if show_inodes && show_columns {
+                    x += inode_column_width;
+                    self.write_line_inode(f, line, inode_widths)?;
+                }
+                if show_xattrs && show_columns {
+                    x += xattr_width + 3;
+                    self.write_line_xattrs(f, line, xattr_width)?;
+                }
+                if show_dates && show_columns {
+                    x += date_width + 1;
+                    if let Ok(date) = line.metadata.date(date_kind) {
+                        self.write_date(f, date, date_width)?;
                     } else {
-                        self.skin.tree.queue_str(f, "──────────────── ")?;
+                        self.skin.tree.queue_str(f, &format!("{} ", "─".repeat(date_width)))?;
                     }
                 }
-                self.write_line_name(f, line, line_index, &tree.options.pattern, selected)?;
+                if !columns_row {
+                    let name_width = row_width.saturating_sub(x);
+                    self.write_line_name(f, line, line_index, &tree.options.pattern, selected, name_width)?;
+                }
             }
             if selected {
                 self.skin.selected_line.queue_bg(f)?;
@@ -258,19 +598,52 @@ impl<'s, 't> DisplayableTree<'s, 't> {
     }
 }
 
+/// return the widths of the inode numbers and of the numbers
+/// of hard links of the tree
+fn inode_widths(tree: &Tree) -> (usize, usize) {
+    if !tree.options.show_inodes {
+        return (0, 0);
+    }
+    let lines = tree.lines.iter().skip(1);
+    let ino_width = lines.clone().map(|l| l.metadata.ino().to_string().len()).max();
+    let nlink_width = lines.map(|l| l.metadata.nlink().to_string().len()).max();
+    (ino_width.unwrap_or(1), nlink_width.unwrap_or(1))
+}
+
+/// return the widths of the user and group columns, a width of 0
+/// meaning the column is pruned because all lines have the same
+/// value (unless the full_permissions option is set)
 #[cfg(unix)]
-fn user_group_max_lengths(tree: &Tree) -> (usize, usize) {
+pub fn user_group_max_lengths(tree: &Tree) -> (usize, usize) {
     let mut max_user_len = 0;
     let mut max_group_len = 0;
+    let mut uids = None;
+    let mut gids = None;
+    let mut uniform_user = true;
+    let mut uniform_group = true;
     if tree.options.show_permissions {
-        for i in 1..tree.lines.len() {
-            let line = &tree.lines[i];
-            let user = permissions::user_name(line.metadata.uid());
-            max_user_len = max_user_len.max(user.len());
-            let group = permissions::group_name(line.metadata.gid());
-            max_group_len = max_group_len.max(group.len());
+        for line in tree.lines.iter().skip(1).filter(|line| line.is_selectable()) {
+            let (uid, gid) = (line.metadata.uid(), line.metadata.gid());
+            uniform_user &= *uids.get_or_insert(uid) == uid;
+            uniform_group &= *gids.get_or_insert(gid) == gid;
+            let user = permissions::user_name(uid);
+            max_user_len = max_user_len.max(column_widths::str_width(&user));
+            let group = permissions::group_name(gid);
+            max_group_len = max_group_len.max(column_widths::str_width(&group));
+        }
+    }
+    if !tree.options.full_permissions {
+        if uniform_user {
+            max_user_len = 0;
+        }
+        if uniform_group {
+            max_group_len = 0;
         }
     }
-    (max_user_len, max_group_len)
+    let widths = &tree.options.column_widths;
+    (
+        if max_user_len > 0 { widths.user.clamp(max_user_len) } else { 0 },
+        if max_group_len > 0 { widths.group.clamp(max_group_len) } else { 0 },
+    )
 }
 
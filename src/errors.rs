@@ -17,6 +17,25 @@ custom_error! {pub ProgramError
     TreeBuild {source: TreeBuildError} = "{}",
     OpenError {source: opener::OpenError} = "Open Error : {:?}",
     LaunchError {program: String, source: io::Error} = "Unable to launch {program}: {source}",
+    Snapshot {source: SnapshotError} = "Bad snapshot: {}",
+    InvalidMode {raw: String} = "Not a valid mode: {:?} (expected eg 755 or u+x)",
+    UnknownOwner {name: String} = "Unknown user or group: {:?}",
+    Archive {details: String} = "Archive error: {}",
+    Remote {details: String} = "Remote error: {}",
+}
+
+impl ProgramError {
+    /// tell whether the error comes from writing in a pipe whose reader
+    /// is gone (eg in `broot -s | head`), which isn't a failure of broot
+    pub fn is_broken_pipe(&self) -> bool {
+        match self {
+            ProgramError::Io { source } => source.kind() == io::ErrorKind::BrokenPipe,
+            ProgramError::Crossterm {
+                source: crossterm::ErrorKind::IoError(source),
+            } => source.kind() == io::ErrorKind::BrokenPipe,
+            _ => false,
+        }
+    }
 }
 
 custom_error! {pub TreeBuildError
@@ -30,6 +49,28 @@ custom_error! {pub ConfError
     MissingField {txt: String}                      = "missing field in conf",
     InvalidVerbInvocation {invocation: String}      = "invalid verb invocation: {}",
     InvalidKey {raw: String}                        = "not a valid key: {}",
+    InvalidChord {raw: String}                      = "not a valid chord: {} (expected two keys, eg \"g s\")",
+    ConflictingChord {raw: String}                  = "conflicting chord: {} (its keys already trigger another verb)",
+    InvalidSelectionType {raw: String}              = "not a valid selection type: {} (valid values: file, directory, link, any)",
+    InvalidSizeFormat {raw: String}                 = "not a valid size format: {} (valid values: binary, si, bytes)",
+    InvalidDateFormat {raw: String}                 = "not a valid date format: {:?} (expected \"relative\" or a strftime format like \"%Y/%m/%d %R\")",
+    InvalidDateKind {raw: String}                   = "not a valid kind of date: {} (valid values: modified, created, accessed)",
+    InvalidGlob {raw: String}                       = "not a valid glob: {}",
+    InvalidCaseSensitivity {raw: String}            = "not a valid case sensitivity: {} (valid values: smart, sensitive, insensitive)",
+    InvalidSizeBar {raw: String}                    = "not a valid size bar style: {} (valid values: wide, narrow, braille, none)",
+    InvalidBackground {raw: String}                 = "not a valid background: {} (valid values: auto, dark, light)",
+    InvalidColorSupport {raw: String}               = "not a valid color support: {} (valid values: auto, truecolor, 256, 16)",
+    InvalidColorChoice {raw: String}                = "not a valid color choice: {} (valid values: auto, always, never)",
+    InvalidColumn {raw: String}                     = "not a column with a configurable width: {} (valid values: size, user, group)",
+    InvalidSpecialHandling {raw: String}            = "not a valid special path handling: {} (valid values: hide, no-enter)",
+    InvalidSyntaxTheme {raw: String}                = "not a valid syntax theme: {} (valid values: none, base16-eighties.dark, base16-mocha.dark, base16-ocean.dark, base16-ocean.light, InspiredGitHub, Solarized (dark), Solarized (light))",
+}
+
+custom_error! {pub SnapshotError
+    Io {source: io::Error}                          = "unable to read or write the file",
+    Json {source: serde_json::Error}                = "unable to parse JSON",
+    MissingField {txt: String}                      = "missing field: {}",
+    InvalidEntryType {raw: String}                  = "not a valid entry type: {} (valid values: dir, file, link)",
 }
 
 // error which can be raised when parsing a regex the
@@ -1,9 +1,10 @@
 use std::{
     env,
-    fs::OpenOptions,
-    io::{self, Write},
+    ffi::OsString,
+    fs::{File, OpenOptions},
+    io,
     path::{Path, PathBuf},
-    process::Command,
+    process::{Child, Command, Stdio},
 };
 
 use opener;
@@ -12,11 +13,16 @@ use regex::Regex;
 use crate::{
     app_context::AppContext,
     app_state::AppStateCmdResult,
+    color_support::ColorChoice,
     displayable_tree::DisplayableTree,
     errors::ProgramError,
     flat_tree::Tree,
+    html_export::{OutputFormat, TreeHtmlRenderer},
+    marks,
+    os_names,
     screens::Screen,
     skin::Skin,
+    stdout_export,
 };
 
 /// description of a possible launch of an external program
@@ -25,18 +31,22 @@ use crate::{
 pub enum Launchable {
     Printer {
         // just print something on stdout on end of broot
-        to_print: String,
+        to_print: OsString,
     },
     TreePrinter {
         // print the tree on end of broot
         tree: Box<Tree>,
         skin: Box<Skin>,
         width: u16,
+        format: OutputFormat,
     },
     Program {
-        // execute an external program
-        exe: String,
-        args: Vec<String>,
+        // execute an external program. The arguments are kept
+        // raw so that the paths which aren't valid UTF-8 are passed
+        // unchanged
+        exe: OsString,
+        args: Vec<OsString>,
+        env: Vec<(String, String)>, // added to the environment of the program
     },
     SystemOpen {
         // open a path
@@ -45,67 +55,137 @@ pub enum Launchable {
 }
 
 /// If s starts by a '$', replace it by the environment variable of the same name
-fn resolve_env_variable(s: String) -> String {
-    if s.starts_with('$') {
-        env::var(&s[1..]).unwrap_or(s)
-    } else {
-        s
-    }
+fn resolve_env_variable(s: OsString) -> OsString {
+    let value = s
+        .to_str()
+        .filter(|s| s.starts_with('$'))
+        .and_then(|s| env::var_os(&s[1..]));
+    value.unwrap_or(s)
 }
 
 impl Launchable {
     pub fn opener(path: PathBuf) -> Launchable {
         Launchable::SystemOpen { path }
     }
-    pub fn printer(to_print: String) -> Launchable {
+    pub fn printer(to_print: OsString) -> Launchable {
         Launchable::Printer { to_print }
     }
     pub fn tree_printer(
         tree: &Tree,
         screen: &Screen,
-        no_style: bool,
+        styled: bool,
+        format: OutputFormat,
     ) -> Launchable {
         Launchable::TreePrinter {
             tree: Box::new(tree.clone()),
             skin: Box::new(
-                if no_style {
-                    Skin::no_term()
-                } else {
+                if styled {
                     screen.skin.clone()
+                } else {
+                    Skin::no_term()
                 }
             ),
             width: screen.width,
+            format,
         }
     }
 
-    pub fn program(mut parts: Vec<String>) -> io::Result<Launchable> {
+    pub fn program(parts: Vec<OsString>) -> io::Result<Launchable> {
+        Launchable::program_with_env(parts, Vec::new())
+    }
+
+    /// build a launchable for an external program whose environment
+    /// is completed with the given variables
+    pub fn program_with_env(
+        mut parts: Vec<OsString>,
+        env: Vec<(String, String)>,
+    ) -> io::Result<Launchable> {
         let mut parts = parts.drain(0..).map(resolve_env_variable);
         match parts.next() {
             Some(exe) => Ok(Launchable::Program {
                 exe,
                 args: parts.collect(),
+                env,
             }),
             None => Err(io::Error::new(io::ErrorKind::Other, "Empty launch string")),
         }
     }
 
+    /// run the program and return what it wrote on stdout and stderr.
+    /// Only a program has an output: the other launchables are executed
+    /// and give an empty one
+    pub fn output(&self) -> Result<String, ProgramError> {
+        match self {
+            Launchable::Program { exe, args, env } => {
+                let output = Command::new(&exe)
+                    .args(args.iter())
+                    .envs(env.iter().map(|(name, value)| (name, value)))
+                    .stdin(Stdio::null())
+                    .output()
+                    .map_err(|source| ProgramError::LaunchError {
+                        program: exe.to_string_lossy().to_string(),
+                        source,
+                    })?;
+                let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+                text.push_str(&String::from_utf8_lossy(&output.stderr));
+                Ok(text)
+            }
+            _ => {
+                self.execute()?;
+                Ok(String::new())
+            }
+        }
+    }
+
+    /// start the program without waiting for its end. Its output is
+    /// discarded. The other launchables are executed immediately, so
+    /// there's no child to return
+    pub fn spawn(&self) -> Result<Option<Child>, ProgramError> {
+        match self {
+            Launchable::Program { exe, args, env } => {
+                let child = Command::new(&exe)
+                    .args(args.iter())
+                    .envs(env.iter().map(|(name, value)| (name, value)))
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .map_err(|source| ProgramError::LaunchError {
+                        program: exe.to_string_lossy().to_string(),
+                        source,
+                    })?;
+                Ok(Some(child))
+            }
+            _ => {
+                self.execute()?;
+                Ok(None)
+            }
+        }
+    }
+
     pub fn execute(&self) -> Result<(), ProgramError> {
         match self {
             Launchable::Printer { to_print } => {
-                println!("{}", to_print);
+                os_names::write_line(&mut io::stdout(), to_print)?;
                 Ok(())
             }
-            Launchable::TreePrinter { tree, skin, width } => {
-                let dp = DisplayableTree::out_of_app(&tree, &skin, *width);
-                dp.write_on(&mut std::io::stdout())
-            }
-            Launchable::Program { exe, args } => {
+            Launchable::TreePrinter { tree, skin, width, format } => match format {
+                OutputFormat::Text => {
+                    let dp = DisplayableTree::out_of_app(&tree, &skin, *width);
+                    dp.write_on(&mut std::io::stdout())
+                }
+                OutputFormat::Html => {
+                    TreeHtmlRenderer::new(&tree, &skin).write_on(&mut std::io::stdout())
+                }
+            },
+            Launchable::Program { exe, args, env } => {
                 Command::new(&exe)
                     .args(args.iter())
+                    .envs(env.iter().map(|(name, value)| (name, value)))
                     .spawn()
                     .and_then(|mut p| p.wait())
                     .map_err(|source| ProgramError::LaunchError {
-                        program: exe.clone(),
+                        program: exe.to_string_lossy().to_string(),
                         source,
                     })?;
                 Ok(())
@@ -122,49 +202,296 @@ impl Launchable {
 //  it in quotes if necessary (and then escaping internal quotes).
 // Don't do unnecessary transformation, so that the produced string
 //  is prettier on screen.
+#[cfg(not(windows))]
 pub fn escape_for_shell(path: &Path) -> String {
-    let path = path.to_string_lossy();
+    let path = os_names::escape(path.as_os_str());
     if regex!(r"^[\w/.-]*$").is_match(&path) {
-        path.to_string()
+        path
     } else {
         format!("'{}'", &path.replace('\'', r"'\''"))
     }
 }
 
+// from a path, build a string usable in a command of the shell which
+//  will execute it: cmd when the br script of cmd launched broot (it
+//  sets BROOT_SHELL), PowerShell otherwise
+#[cfg(windows)]
+pub fn escape_for_shell(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if regex!(r"^[\w/\\.:-]*$").is_match(&path) {
+        path.to_string()
+    } else if env::var_os("BROOT_SHELL").map_or(false, |shell| shell == "cmd") {
+        escape_for_cmd(&path)
+    } else {
+        escape_for_powershell(&path)
+    }
+}
+
+// wrap the path in single quotes, in which PowerShell expands nothing.
+//  The quotes of the path are doubled, including the typographic
+//  ones which PowerShell also takes as single quotes
+#[cfg(windows)]
+fn escape_for_powershell(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len() + 2);
+    escaped.push('\'');
+    for c in path.chars() {
+        if let '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' = c {
+            escaped.push(c);
+        }
+        escaped.push(c);
+    }
+    escaped.push('\'');
+    escaped
+}
+
+// wrap the path in double quotes, out of which the % and ^ are put
+//  and escaped with a caret, as cmd would otherwise expand the %
+//  even between double quotes, and takes carets literally there
+#[cfg(windows)]
+fn escape_for_cmd(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len() + 2);
+    escaped.push('"');
+    for c in path.chars() {
+        if c == '%' || c == '^' {
+            escaped.push('"');
+            escaped.push('^');
+            escaped.push(c);
+            escaped.push('"');
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// like escape_for_shell, but keeping the bytes of a path which
+/// isn't valid UTF-8: it's wrapped in single quotes, which all the
+/// supported shells take literally
+#[cfg(unix)]
+pub fn escape_os_for_shell(path: &Path) -> OsString {
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    if path.to_str().is_some() {
+        return escape_for_shell(path).into();
+    }
+    let mut bytes = vec![b'\''];
+    for &b in path.as_os_str().as_bytes() {
+        if b == b'\'' {
+            bytes.extend_from_slice(br"'\''");
+        } else {
+            bytes.push(b);
+        }
+    }
+    bytes.push(b'\'');
+    OsString::from_vec(bytes)
+}
+
+#[cfg(not(unix))]
+pub fn escape_os_for_shell(path: &Path) -> OsString {
+    escape_for_shell(path).into()
+}
+
+/// show the given path in the system file manager, with the file
+/// selected when the file manager allows it (Finder, Explorer, and
+/// the linux ones implementing the freedesktop FileManager1 interface).
+///
+/// Contrary to opener::open, this never opens the file itself.
+pub fn reveal_in_file_manager(path: &Path) -> Result<(), ProgramError> {
+    #[cfg(target_os = "macos")]
+    {
+        launch_silently("open", &["-R".to_string(), path.to_string_lossy().to_string()])
+    }
+    #[cfg(windows)]
+    {
+        // explorer returns an error code even on success, so we don't wait
+        Command::new("explorer")
+            .arg(format!("/select,{}", path.to_string_lossy()))
+            .spawn()
+            .map_err(|source| ProgramError::LaunchError {
+                program: "explorer".to_string(),
+                source,
+            })?;
+        Ok(())
+    }
+    #[cfg(not(any(target_os = "macos", windows)))]
+    {
+        let args = vec![
+            "--session".to_string(),
+            "--dest=org.freedesktop.FileManager1".to_string(),
+            "--type=method_call".to_string(),
+            "/org/freedesktop/FileManager1".to_string(),
+            "org.freedesktop.FileManager1.ShowItems".to_string(),
+            format!("array:string:{}", file_uri(path)),
+            "string:".to_string(),
+        ];
+        if launch_silently("dbus-send", &args).is_ok() {
+            return Ok(());
+        }
+        // no file manager answered: we just open the parent directory
+        let dir = path.parent().unwrap_or(path);
+        opener::open(dir)?;
+        Ok(())
+    }
+}
+
+/// execute a program without letting it write on the terminal and
+/// wait for it to end, returning an error if it failed
+#[cfg(not(windows))]
+fn launch_silently(exe: &str, args: &[String]) -> Result<(), ProgramError> {
+    let status = Command::new(exe)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|source| ProgramError::LaunchError {
+            program: exe.to_string(),
+            source,
+        })?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(ProgramError::LaunchError {
+            program: exe.to_string(),
+            source: io::Error::new(io::ErrorKind::Other, format!("{}", status)),
+        })
+    }
+}
+
+/// build the file:// URI of a path, percent-encoding the bytes
+/// which aren't allowed in an URI path
+#[cfg(not(any(target_os = "macos", windows)))]
+fn file_uri(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+    let mut uri = "file://".to_string();
+    for &b in path.as_os_str().as_bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(b as char);
+            }
+            _ => {
+                uri.push_str(&format!("%{:02X}", b));
+            }
+        }
+    }
+    uri
+}
+
 pub fn print_path(path: &Path, con: &AppContext) -> io::Result<AppStateCmdResult> {
-    let path = path.to_string_lossy().to_string();
     Ok(
-        if let Some(ref output_path) = con.launch_args.file_export_path {
+        if con.launch_args.out_marks && marks::count() > 0 {
+            // the marked paths are written instead, on quitting
+            AppStateCmdResult::Quit
+        } else if let Some(ref output_path) = con.launch_args.file_export_path {
             // an output path was provided, we write to it
             let f = OpenOptions::new()
                 .create(true)
                 .append(true)
                 .open(output_path)?;
-            writeln!(&f, "{}", path)?;
+            os_names::write_line(&mut &f, path.as_os_str())?;
             AppStateCmdResult::Quit
         } else {
             // no output path provided. We write on stdout, but we must
             // do it after app closing to have the normal terminal
-            AppStateCmdResult::from(Launchable::printer(path))
+            AppStateCmdResult::from(Launchable::printer(path.as_os_str().to_os_string()))
         },
     )
 }
 
+/// write the marked paths in the output file, if broot was
+/// launched with both `--out` and `--out-marks`
+pub fn write_marks(con: &AppContext) -> io::Result<()> {
+    if !con.launch_args.out_marks {
+        return Ok(());
+    }
+    if let Some(ref output_path) = con.launch_args.file_export_path {
+        let marked = marks::all();
+        if !marked.is_empty() {
+            let f = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(output_path)?;
+            for path in marked {
+                os_names::write_line(&mut &f, path.as_os_str())?;
+            }
+        }
+    }
+    Ok(())
+}
+
 fn print_tree_to_file(
     tree: &Tree,
     screen: &mut Screen,
     file_path: &str,
+    format: OutputFormat,
+    color: ColorChoice,
 ) -> Result<AppStateCmdResult, ProgramError> {
-    let no_style_skin = Skin::no_term();
-    let dp = DisplayableTree::out_of_app(tree, &no_style_skin, screen.width);
+    // a text --out file is written without style unless asked otherwise
+    let skin = if color.keeps_colors(format == OutputFormat::Html) {
+        screen.skin.clone()
+    } else {
+        Skin::no_term()
+    };
     let mut f = OpenOptions::new()
         .create(true)
         .append(true)
         .open(file_path)?;
-    dp.write_on(&mut f)?;
+    match format {
+        OutputFormat::Text => {
+            let dp = DisplayableTree::out_of_app(tree, &skin, screen.width);
+            dp.write_on(&mut f)?;
+        }
+        OutputFormat::Html => {
+            TreeHtmlRenderer::new(tree, &skin).write_on(&mut f)?;
+        }
+    }
     Ok(AppStateCmdResult::Quit)
 }
 
+/// write the tree, as it would be printed on leaving broot, in a
+/// file (replacing its content) and stay in broot
+pub fn write_tree(
+    tree: &Tree,
+    screen: &mut Screen,
+    con: &AppContext,
+    file_path: &Path,
+) -> Result<AppStateCmdResult, ProgramError> {
+    let skin = if con.launch_args.color.keeps_colors(true) {
+        screen.skin.clone()
+    } else {
+        Skin::no_term()
+    };
+    let dp = DisplayableTree::out_of_app(tree, &skin, screen.width);
+    Ok(match File::create(file_path) {
+        Ok(mut f) => {
+            dp.write_on(&mut f)?;
+            AppStateCmdResult::Keep
+        }
+        Err(e) => AppStateCmdResult::DisplayError(format!("can't write {:?}: {}", file_path, e)),
+    })
+}
+
+/// write the tree as a HTML page in a file (replacing its content)
+/// and stay in broot
+pub fn export_html(
+    tree: &Tree,
+    screen: &mut Screen,
+    con: &AppContext,
+    file_path: &Path,
+) -> Result<AppStateCmdResult, ProgramError> {
+    let skin = if con.launch_args.color.keeps_colors(true) {
+        screen.skin.clone()
+    } else {
+        Skin::no_term()
+    };
+    Ok(match File::create(file_path) {
+        Ok(mut f) => {
+            TreeHtmlRenderer::new(tree, &skin).write_on(&mut f)?;
+            AppStateCmdResult::Keep
+        }
+        Err(e) => AppStateCmdResult::DisplayError(format!("can't write {:?}: {}", file_path, e)),
+    })
+}
+
 pub fn print_tree(
     tree: &Tree,
     screen: &mut Screen,
@@ -172,14 +499,53 @@ pub fn print_tree(
 ) -> Result<AppStateCmdResult, ProgramError> {
     if let Some(ref output_path) = con.launch_args.file_export_path {
         // an output path was provided, we write to it
-        print_tree_to_file(tree, screen, output_path)
+        print_tree_to_file(
+            tree,
+            screen,
+            output_path,
+            con.launch_args.output_format,
+            con.launch_args.color,
+        )
     } else {
         // no output path provided. We write on stdout, but we must
         // do it after app closing to have the normal terminal
         Ok(AppStateCmdResult::from(Launchable::tree_printer(
             tree,
             screen,
-            con.launch_args.no_style,
+            // by default, the colors are kept on a terminal, or as the
+            // CSS classes of a HTML page
+            con.launch_args.color.keeps_colors(
+                !stdout_export::is_redirected()
+                    || con.launch_args.output_format == OutputFormat::Html
+            ),
+            con.launch_args.output_format,
         )))
     }
 }
+
+#[cfg(all(test, windows))]
+mod shell_escaping_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_powershell_escaping() {
+        assert_eq!(escape_for_powershell(r"C:\Program Files"), r"'C:\Program Files'");
+        assert_eq!(escape_for_powershell(r"C:\$env:TEMP `x`"), r"'C:\$env:TEMP `x`'");
+        assert_eq!(escape_for_powershell(r"C:\it's"), r"'C:\it''s'");
+        assert_eq!(escape_for_powershell("C:\\it\u{2019}s"), "'C:\\it\u{2019}\u{2019}s'");
+    }
+
+    #[test]
+    fn check_cmd_escaping() {
+        assert_eq!(escape_for_cmd(r"C:\Program Files"), r#""C:\Program Files""#);
+        assert_eq!(escape_for_cmd(r"C:\a & b"), r#""C:\a & b""#);
+        assert_eq!(escape_for_cmd(r"C:\%PATH%"), r#""C:\"^%"PATH"^%"""#);
+        assert_eq!(escape_for_cmd(r"C:\a^b"), r#""C:\a"^^"b""#);
+    }
+
+    #[test]
+    fn check_plain_paths_are_kept() {
+        assert_eq!(escape_for_shell(Path::new(r"C:\dev\broot")), r"C:\dev\broot");
+    }
+}
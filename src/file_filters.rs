@@ -0,0 +1,517 @@
+//! the filters on the kind of file which may be typed before the
+//! pattern in the input, for example `x:deploy` for the executable
+//! files whose name matches "deploy", `d:` for the directories,
+//! `>10M` for the files bigger than 10MB, `changed<7d` for the
+//! entries modified during the last week, `atime>30d` for the
+//! entries which weren't read for a month, `newer:target/debug/broot`
+//! for the entries modified after that file, or `has:TODO` for the
+//! files containing "TODO".
+//!
+//! Unlike the pattern, which only looks at names, they're checked on
+//! the entry itself (its type, its metadata or its content when needed).
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use chrono::{offset::Local, NaiveDate, TimeZone};
+
+use crate::{
+    path_completion,
+    patterns::{Match, Pattern, Scorer},
+    tree_sources::{DateKind, EntryType, TreeSource},
+};
+
+/// a requirement on the type of the entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TypeFilter {
+    Files,       // f: regular files
+    Dirs,        // d: directories
+    Executables, // x: files with an execution permission (unix only)
+    Symlinks,    // l: symbolic links, not followed
+}
+
+impl TypeFilter {
+    fn from_char(c: char) -> Option<TypeFilter> {
+        match c {
+            'f' => Some(TypeFilter::Files),
+            'd' => Some(TypeFilter::Dirs),
+            'x' => Some(TypeFilter::Executables),
+            'l' => Some(TypeFilter::Symlinks),
+            _ => None,
+        }
+    }
+    fn as_char(self) -> char {
+        match self {
+            TypeFilter::Files => 'f',
+            TypeFilter::Dirs => 'd',
+            TypeFilter::Executables => 'x',
+            TypeFilter::Symlinks => 'l',
+        }
+    }
+    fn accepts(self, path: &Path, entry_type: EntryType, source: &dyn TreeSource) -> bool {
+        match self {
+            TypeFilter::Files => entry_type.is_file(),
+            TypeFilter::Dirs => entry_type.is_dir(),
+            TypeFilter::Symlinks => entry_type.is_symlink(),
+            TypeFilter::Executables => {
+                // the permissions of a link are the ones of its target
+                !entry_type.is_dir()
+                    && source.metadata(path).map_or(false, |md| {
+                        md.entry_type.is_file() && md.mode() & 0o111 != 0
+                    })
+            }
+        }
+    }
+}
+
+/// a requirement on the size of a file, in bytes. Directories
+/// never pass it, as their size isn't known while building the tree
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeFilter {
+    Greater(u64), // eg >10M
+    Smaller(u64), // eg <4k
+}
+
+impl SizeFilter {
+    /// parse a size filter like `>10M` or `<1.5G`. The units are
+    /// powers of 1024, like in the size column, and their case
+    /// doesn't matter
+    fn from(s: &str) -> Option<SizeFilter> {
+        let (op, rest) = (s.chars().next()?, &s[1..]);
+        let (number, multiplier) = match rest.chars().last()?.to_ascii_lowercase() {
+            'k' => (&rest[..rest.len() - 1], 1u64 << 10),
+            'm' => (&rest[..rest.len() - 1], 1 << 20),
+            'g' => (&rest[..rest.len() - 1], 1 << 30),
+            't' => (&rest[..rest.len() - 1], 1 << 40),
+            _ => (rest, 1),
+        };
+        let bytes = (number.parse::<f64>().ok()? * multiplier as f64) as u64;
+        match op {
+            '>' => Some(SizeFilter::Greater(bytes)),
+            '<' => Some(SizeFilter::Smaller(bytes)),
+            _ => None,
+        }
+    }
+    fn accepts(self, path: &Path, entry_type: EntryType, source: &dyn TreeSource) -> bool {
+        if entry_type.is_dir() {
+            return false;
+        }
+        let len = match source.symlink_metadata(path) {
+            Ok(md) => md.len(),
+            Err(_) => {
+                return false;
+            }
+        };
+        match self {
+            SizeFilter::Greater(bytes) => len > bytes,
+            SizeFilter::Smaller(bytes) => len < bytes,
+        }
+    }
+}
+
+impl fmt::Display for SizeFilter {
+    /// write the filter with the biggest unit giving an exact number
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (op, mut bytes) = match self {
+            SizeFilter::Greater(bytes) => ('>', *bytes),
+            SizeFilter::Smaller(bytes) => ('<', *bytes),
+        };
+        let mut unit = 0;
+        while bytes > 0 && bytes % 1024 == 0 && unit < 4 {
+            bytes /= 1024;
+            unit += 1;
+        }
+        write!(f, "{}{}{}", op, bytes, ["", "K", "M", "G", "T"][unit])
+    }
+}
+
+/// what the date of an entry is compared to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DateLimit {
+    Day(SystemTime), // the start of a day, eg 2024-01-01
+    Age(Duration),   // an age, eg 7d, the limit moving with the current time
+}
+
+/// a requirement on a date of an entry, either compared to a day
+/// (`mtime>2024-01-01` for the entries modified since that day) or
+/// to an age (`changed<7d` for the entries modified less than 7 days
+/// ago). The keyword tells which date is compared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DateFilter {
+    raw: String, // kept to rebuild the input
+    kind: DateKind,
+    newer: bool, // whether the entries must be more recent than the limit
+    limit: DateLimit,
+}
+
+impl DateFilter {
+    /// parse a filter like `mtime>2024-01-01` or `changed<7d`. The
+    /// keyword is `mtime` or `changed` for the modification date,
+    /// `btime` or `created` for the creation date, and `atime` or
+    /// `accessed` for the date of the last access
+    fn from(s: &str) -> Option<DateFilter> {
+        let op_idx = s.find(|c| c == '<' || c == '>')?;
+        let kind = match &s[..op_idx] {
+            "mtime" | "changed" => DateKind::Modified,
+            "btime" | "created" => DateKind::Created,
+            "atime" | "accessed" => DateKind::Accessed,
+            _ => {
+                return None;
+            }
+        };
+        let greater = s[op_idx..].starts_with('>');
+        let value = &s[op_idx + 1..];
+        let (newer, limit) = if let Ok(day) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            let start = Local.from_local_datetime(&day.and_hms(0, 0, 0)).earliest()?;
+            (greater, DateLimit::Day(SystemTime::from(start)))
+        } else {
+            // an age: the smaller it is, the more recent the entries
+            let unit = value.chars().last()?;
+            let n: u64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+            let secs = match unit {
+                's' => 1,
+                'm' => 60,
+                'h' => 60 * 60,
+                'd' => 24 * 60 * 60,
+                'w' => 7 * 24 * 60 * 60,
+                'y' => 365 * 24 * 60 * 60,
+                _ => {
+                    return None;
+                }
+            };
+            (!greater, DateLimit::Age(Duration::from_secs(n.checked_mul(secs)?)))
+        };
+        Some(DateFilter {
+            raw: s.to_string(),
+            kind,
+            newer,
+            limit,
+        })
+    }
+    fn accepts(&self, path: &Path, source: &dyn TreeSource) -> bool {
+        let limit = match self.limit {
+            DateLimit::Day(start) => start,
+            DateLimit::Age(age) => match SystemTime::now().checked_sub(age) {
+                Some(limit) => limit,
+                None => SystemTime::UNIX_EPOCH,
+            },
+        };
+        match source.metadata(path).and_then(|md| md.date(self.kind)) {
+            Ok(modified) if self.newer => modified >= limit,
+            Ok(modified) => modified < limit,
+            Err(_) => false,
+        }
+    }
+}
+
+/// a requirement on the modification date of an entry, which must be
+/// more recent than the one of a reference file, like `find -newer`.
+/// The date of the reference is read when the tree is built, a relative
+/// path starting from the root of the tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewerFilter {
+    path: String, // as typed
+    reference: Option<SystemTime>, // None until resolved, or when the file can't be read
+}
+
+impl NewerFilter {
+    fn from(path: &str) -> Option<NewerFilter> {
+        if path.is_empty() {
+            None
+        } else {
+            Some(NewerFilter {
+                path: path.to_string(),
+                reference: None,
+            })
+        }
+    }
+    fn reference_path(&self, root: &Path) -> PathBuf {
+        path_completion::expand_path(&self.path, root)
+    }
+    fn resolve(&mut self, root: &Path, source: &dyn TreeSource) {
+        let path = self.reference_path(root);
+        self.reference = source.metadata(&path).and_then(|md| md.modified()).ok();
+        if self.reference.is_none() {
+            debug!("no date for the reference file {:?}", path);
+        }
+    }
+    /// tell whether the entry was modified after the reference. No
+    /// entry passes the filter when the reference can't be read
+    fn accepts(&self, path: &Path, source: &dyn TreeSource) -> bool {
+        match self.reference {
+            Some(reference) => source
+                .metadata(path)
+                .and_then(|md| md.modified())
+                .map_or(false, |modified| modified > reference),
+            None => false,
+        }
+    }
+}
+
+/// the files bigger than that aren't read by a content search
+const MAX_SEARCHED_CONTENT_SIZE: u64 = 10 * 1024 * 1024;
+
+/// a text the content of the file must contain, with the exact
+/// same case. Only the local files are read, the directories
+/// never pass it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentFilter {
+    needle: String,
+}
+
+impl ContentFilter {
+    fn from(needle: &str) -> Option<ContentFilter> {
+        if needle.is_empty() {
+            None
+        } else {
+            Some(ContentFilter {
+                needle: needle.to_string(),
+            })
+        }
+    }
+    fn accepts(&self, path: &Path, source: &dyn TreeSource) -> bool {
+        if !source.is_local() {
+            return false;
+        }
+        let searchable = source.metadata(path).map_or(false, |md| {
+            md.entry_type.is_file() && md.len() <= MAX_SEARCHED_CONTENT_SIZE
+        });
+        if !searchable {
+            return false;
+        }
+        let needle = self.needle.as_bytes();
+        match fs::read(path) {
+            Ok(content) => content.windows(needle.len()).any(|w| w == needle),
+            Err(e) => {
+                debug!("content of {:?} not searched: {:?}", path, e);
+                false
+            }
+        }
+    }
+}
+
+/// one of the filters which can be typed before the pattern
+#[derive(Debug, Clone, PartialEq)]
+enum Predicate {
+    Type(TypeFilter),
+    Size(SizeFilter),
+    Date(DateFilter),
+    Newer(NewerFilter),
+    Content(ContentFilter),
+}
+
+impl Predicate {
+    fn from(s: &str) -> Option<Predicate> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => TypeFilter::from_char(c).map(Predicate::Type),
+            (Some('>'), _) | (Some('<'), _) => SizeFilter::from(s).map(Predicate::Size),
+            _ => DateFilter::from(s).map(Predicate::Date),
+        }
+    }
+    fn accepts(&self, path: &Path, entry_type: EntryType, source: &dyn TreeSource) -> bool {
+        match self {
+            Predicate::Type(t) => t.accepts(path, entry_type, source),
+            Predicate::Size(s) => s.accepts(path, entry_type, source),
+            Predicate::Date(d) => d.accepts(path, source),
+            Predicate::Newer(n) => n.accepts(path, source),
+            Predicate::Content(c) => c.accepts(path, source),
+        }
+    }
+    /// tell whether checking the predicate means reading the file
+    fn reads_content(&self) -> bool {
+        match self {
+            Predicate::Content(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// the filters typed before the pattern. An entry must pass all
+/// of them to be a match.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileFilter {
+    predicates: Vec<Predicate>,
+}
+
+impl fmt::Display for FileFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for predicate in &self.predicates {
+            match predicate {
+                Predicate::Type(t) => write!(f, "{}:", t.as_char())?,
+                Predicate::Size(s) => write!(f, "{}:", s)?,
+                Predicate::Date(d) => write!(f, "{}:", d.raw)?,
+                Predicate::Newer(n) => write!(f, "newer:{}:", n.path)?,
+                Predicate::Content(c) => write!(f, "has:{}:", c.needle)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FileFilter {
+    /// parse the filter part of the input (eg `f:x:` or `>10M:`),
+    /// as isolated by the parsing of the command. The `newer` keyword
+    /// takes the next part as path of the reference file, and the `has`
+    /// one as the text the files must contain
+    pub fn from(s: &str) -> FileFilter {
+        let mut predicates = Vec::new();
+        let mut parts = s.split(':');
+        while let Some(part) = parts.next() {
+            let predicate = if part == "newer" {
+                parts.next().and_then(NewerFilter::from).map(Predicate::Newer)
+            } else if part == "has" {
+                parts.next().and_then(ContentFilter::from).map(Predicate::Content)
+            } else {
+                Predicate::from(part)
+            };
+            if let Some(predicate) = predicate {
+                if !predicates.contains(&predicate) {
+                    predicates.push(predicate);
+                }
+            }
+        }
+        FileFilter { predicates }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.predicates.is_empty()
+    }
+    /// tell whether the entries are filtered on their dates, in
+    /// which case the date column is displayed
+    pub fn has_date_filter(&self) -> bool {
+        self.date_kind().is_some()
+    }
+    /// return the kind of date of the first date filter, which is
+    /// the one displayed in the date column
+    pub fn date_kind(&self) -> Option<DateKind> {
+        self.predicates.iter().find_map(|p| match p {
+            Predicate::Date(d) => Some(d.kind),
+            Predicate::Newer(_) => Some(DateKind::Modified),
+            _ => None,
+        })
+    }
+    /// read the dates of the reference files of the `newer` filters,
+    /// their relative paths starting from the root of the tree
+    pub fn resolve(&mut self, root: &Path, source: &dyn TreeSource) {
+        for predicate in &mut self.predicates {
+            if let Predicate::Newer(n) = predicate {
+                n.resolve(root, source);
+            }
+        }
+    }
+    /// tell whether the entry passes all the filters. The entry
+    /// type is the one of the entry itself, not of a link target.
+    ///
+    /// The content of the file is only read when the other
+    /// filters accept the entry.
+    pub fn accepts(&self, path: &Path, entry_type: EntryType, source: &dyn TreeSource) -> bool {
+        self.predicates
+            .iter()
+            .filter(|p| !p.reads_content())
+            .chain(self.predicates.iter().filter(|p| p.reads_content()))
+            .all(|p| p.accepts(path, entry_type, source))
+    }
+}
+
+/// a pattern whose matches must also pass a file filter
+#[derive(Debug, Clone)]
+pub struct FilteredPattern {
+    pub filter: FileFilter,
+    pub inner: Box<Pattern>, // Pattern::None when only the filter was typed
+}
+
+impl fmt::Display for FilteredPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.filter, self.inner)
+    }
+}
+
+impl Scorer for FilteredPattern {
+    fn find(&self, candidate: &str) -> Option<Match> {
+        self.inner.find(candidate)
+    }
+    fn score_of(&self, candidate: &str) -> Option<i32> {
+        if self.inner.is_some() {
+            self.inner.score_of(candidate)
+        } else {
+            Some(1)
+        }
+    }
+    fn optimal_result_number(&self, targeted_size: usize) -> usize {
+        self.inner.optimal_result_number(targeted_size)
+    }
+    fn has_ranking(&self) -> bool {
+        self.inner.has_ranking()
+    }
+    fn best_score(&self) -> Option<i32> {
+        if self.inner.is_some() {
+            self.inner.best_score()
+        } else {
+            Some(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod file_filter_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_parsing() {
+        assert_eq!(FileFilter::from("x:").to_string(), "x:");
+        assert_eq!(FileFilter::from("f:x:f:").to_string(), "f:x:");
+        assert!(FileFilter::from("").is_empty());
+        assert!(FileFilter::from("q:").is_empty());
+        assert_eq!(FileFilter::from(">10m").to_string(), ">10M:");
+        assert_eq!(FileFilter::from("f:<1.5k:").to_string(), "f:<1536:");
+        assert_eq!(FileFilter::from("<2048").to_string(), "<2K:");
+        assert_eq!(FileFilter::from("f:changed<7d").to_string(), "f:changed<7d:");
+        assert!(FileFilter::from("mtime>2024-01-01").has_date_filter());
+        assert!(FileFilter::from("mtime>2024-13-01").is_empty());
+        assert!(FileFilter::from("ctime<7d").is_empty());
+        assert_eq!(FileFilter::from("f:atime>30d").date_kind(), Some(DateKind::Accessed));
+        assert_eq!(FileFilter::from("created<2020-01-01").date_kind(), Some(DateKind::Created));
+        assert!(FileFilter::from(">M").is_empty());
+    }
+
+    #[test]
+    fn check_newer_parsing() {
+        assert_eq!(FileFilter::from("newer:Cargo.lock").to_string(), "newer:Cargo.lock:");
+        assert_eq!(
+            FileFilter::from("f:newer:target/debug/broot:").to_string(),
+            "f:newer:target/debug/broot:",
+        );
+        assert_eq!(FileFilter::from("newer:a:newer:a:").to_string(), "newer:a:");
+        assert!(FileFilter::from("newer:").is_empty());
+        assert_eq!(FileFilter::from("newer::x:").to_string(), "x:");
+        assert_eq!(FileFilter::from("newer:a").date_kind(), Some(DateKind::Modified));
+        let filter = NewerFilter::from("~/notes.md").unwrap();
+        assert!(filter.reference_path(Path::new("/tmp")).ends_with("notes.md"));
+        let filter = NewerFilter::from("target/broot").unwrap();
+        assert_eq!(filter.reference_path(Path::new("/src")), Path::new("/src/target/broot"));
+    }
+
+    #[test]
+    fn check_content_filter() {
+        use crate::tree_sources::FsSource;
+        use std::{env, process};
+        assert_eq!(FileFilter::from("f:has:TODO").to_string(), "f:has:TODO:");
+        assert!(FileFilter::from("has:").is_empty());
+        let dir = env::temp_dir().join(format!("broot-test-content-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("main.rs");
+        fs::write(&file, "fn main() {\n    // TODO\n}\n").unwrap();
+        let accepts = |filter: &str, path: &Path, entry_type| {
+            FileFilter::from(filter).accepts(path, entry_type, &FsSource)
+        };
+        assert!(accepts("has:TODO", &file, EntryType::File));
+        assert!(!accepts("has:todo", &file, EntryType::File));
+        assert!(!accepts("d:has:TODO", &file, EntryType::File));
+        assert!(!accepts("has:TODO", &dir, EntryType::Directory));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
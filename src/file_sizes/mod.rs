@@ -8,13 +8,59 @@ use std::{
     fmt,
     ops::AddAssign,
     path::{Path, PathBuf},
+    str::FromStr,
     sync::Mutex,
     time::Instant,
 };
 
-use crate::task_sync::TaskLifetime;
+use crate::{
+    errors::ConfError,
+    task_sync::TaskLifetime,
+};
 
 const SIZE_NAMES: &[&str] = &["", "K", "M", "G", "T", "P", "E", "Z", "Y"]; // Y: for when your disk is bigger than 1024 ZB
+const SI_SIZE_NAMES: &[&str] = &["", "k", "M", "G", "T", "P", "E", "Z", "Y"];
+
+/// the way sizes are written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeFormat {
+    Binary, // powers of 1024, for example 247K
+    Si,     // powers of 1000, for example 253k
+    Bytes,  // the exact number of bytes, for example 252,865
+}
+
+impl Default for SizeFormat {
+    fn default() -> Self {
+        SizeFormat::Binary
+    }
+}
+
+impl FromStr for SizeFormat {
+    type Err = ConfError;
+    fn from_str(s: &str) -> Result<SizeFormat, ConfError> {
+        match s {
+            "binary" => Ok(SizeFormat::Binary),
+            "si" => Ok(SizeFormat::Si),
+            "bytes" => Ok(SizeFormat::Bytes),
+            _ => Err(ConfError::InvalidSizeFormat {
+                raw: s.to_string(),
+            }),
+        }
+    }
+}
+
+/// write a number with a comma between each group of three digits
+pub(crate) fn with_thousands_separators(n: u64) -> String {
+    let digits = n.to_string();
+    let mut s = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            s.push(',');
+        }
+        s.push(c);
+    }
+    s
+}
 
 lazy_static! {
     static ref SIZE_CACHE_MUTEX: Mutex<HashMap<PathBuf, Size>> = Mutex::new(HashMap::new());
@@ -54,6 +100,22 @@ impl Size {
             None
         }
     }
+    /// format the size according to the wanted unit system
+    pub fn format(self, format: SizeFormat) -> String {
+        match format {
+            SizeFormat::Binary => self.to_string(),
+            SizeFormat::Si => {
+                let mut v = self.0;
+                let mut i = 0;
+                while v >= 5000 && i < SI_SIZE_NAMES.len() - 1 {
+                    v /= 1000;
+                    i += 1;
+                }
+                format!("{}{}", v, &SI_SIZE_NAMES[i])
+            }
+            SizeFormat::Bytes => with_thousands_separators(self.0),
+        }
+    }
     pub fn part_of(self, total: Size) -> f32 {
         if total.0 == 0 {
             0.0
@@ -106,3 +168,16 @@ mod file_sizes_default;
 #[cfg(not(unix))]
 use file_sizes_default::*;
 
+#[cfg(test)]
+mod file_sizes_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_thousands_separators() {
+        assert_eq!(with_thousands_separators(0), "0");
+        assert_eq!(with_thousands_separators(999), "999");
+        assert_eq!(with_thousands_separators(12502), "12,502");
+        assert_eq!(with_thousands_separators(1234567), "1,234,567");
+    }
+}
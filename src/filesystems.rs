@@ -0,0 +1,179 @@
+//! the mounted filesystems with their space, as given by the
+//! portable output of the `df` command.
+//!
+//! The filesystem containing a directory is cached, as it's
+//! displayed on the root line at every redraw. It's looked for as
+//! a pending task of the state, never while drawing.
+
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// how long `df` may take to give the filesystem of a directory,
+/// as it may hang on an unreachable network filesystem
+const DF_TIMEOUT: Duration = Duration::from_secs(2);
+
+lazy_static! {
+    static ref MOUNT_CACHE_MUTEX: Mutex<HashMap<PathBuf, Option<Mount>>> = Mutex::new(HashMap::new());
+}
+
+pub fn clear_cache() {
+    let mut mount_cache = MOUNT_CACHE_MUTEX.lock().unwrap();
+    mount_cache.clear();
+}
+
+/// a mounted filesystem
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mount {
+    pub device: String,
+    pub mount_point: PathBuf,
+    pub size: u64, // in bytes
+    pub used: u64,
+    pub available: u64,
+}
+
+impl Mount {
+    /// the used part of the filesystem, between 0 and 1
+    pub fn used_part(&self) -> f32 {
+        if self.size == 0 {
+            0.0
+        } else {
+            self.used as f32 / self.size as f32
+        }
+    }
+}
+
+/// parse a line of the output of `df -kP`, for example
+/// `/dev/sda1  245084444 114427372 118150016  50% /`
+fn parse_df_line(line: &str) -> Option<Mount> {
+    let mut tokens = line.split_whitespace();
+    let device = tokens.next()?.to_string();
+    let size: u64 = tokens.next()?.parse().ok()?;
+    let used: u64 = tokens.next()?.parse().ok()?;
+    let available: u64 = tokens.next()?.parse().ok()?;
+    tokens.next()?; // the capacity, a percentage we compute ourselves
+    // the mount point may contain spaces
+    let mount_point = tokens.collect::<Vec<&str>>().join(" ");
+    if mount_point.is_empty() {
+        return None;
+    }
+    Some(Mount {
+        device,
+        mount_point: PathBuf::from(mount_point),
+        size: size * 1024,
+        used: used * 1024,
+        available: available * 1024,
+    })
+}
+
+/// run `df -kP` with the given paths (all filesystems when there's
+/// none) and parse its output
+fn df(paths: &[&Path]) -> io::Result<Vec<Mount>> {
+    let output = Command::new("df").arg("-kP").args(paths).output()?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+    let output = String::from_utf8_lossy(&output.stdout);
+    Ok(output
+        .lines()
+        .skip(1) // the headers
+        .filter_map(parse_df_line)
+        .collect())
+}
+
+/// list the mounted filesystems with a size (the pseudo filesystems,
+/// like /proc, are skipped)
+pub fn read_mounts() -> io::Result<Vec<Mount>> {
+    Ok(df(&[])?
+        .into_iter()
+        .filter(|mount| mount.size > 0)
+        .collect())
+}
+
+/// run `df -kP` for one path, killing it if it doesn't answer in time
+fn df_of(path: &Path) -> io::Result<Vec<Mount>> {
+    let mut child = Command::new("df")
+        .arg("-kP")
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let start = Instant::now();
+    // the output for one path is too small to fill the pipe
+    while child.try_wait()?.is_none() {
+        if start.elapsed() > DF_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "df didn't answer"));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .skip(1) // the headers
+        .filter_map(parse_df_line)
+        .collect())
+}
+
+/// tell whether the filesystem of the path was looked for (even if
+/// none was found)
+pub fn is_mount_known(path: &Path) -> bool {
+    MOUNT_CACHE_MUTEX.lock().unwrap().contains_key(path)
+}
+
+/// return the filesystem containing the path if it was looked for
+/// and found with a size. This never runs `df`
+pub fn mount_of(path: &Path) -> Option<Mount> {
+    MOUNT_CACHE_MUTEX.lock().unwrap().get(path).cloned().flatten()
+}
+
+/// look for the filesystem containing the path with `df` and cache
+/// it. The cache isn't locked while `df` runs
+pub fn fetch_mount_of(path: &Path) {
+    let mount = match df_of(path) {
+        Ok(mounts) => mounts.into_iter().next().filter(|mount| mount.size > 0),
+        Err(e) => {
+            warn!("no filesystem found for {:?}: {:?}", path, e);
+            None
+        }
+    };
+    MOUNT_CACHE_MUTEX.lock().unwrap().insert(path.to_path_buf(), mount);
+}
+
+#[cfg(test)]
+mod df_parsing_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_df_line_parsing() {
+        assert_eq!(
+            parse_df_line("/dev/sda1  245084444 114427372 118150016  50% /mnt/my disk"),
+            Some(Mount {
+                device: "/dev/sda1".to_string(),
+                mount_point: PathBuf::from("/mnt/my disk"),
+                size: 245084444 * 1024,
+                used: 114427372 * 1024,
+                available: 118150016 * 1024,
+            }),
+        );
+        assert_eq!(parse_df_line("Filesystem 1024-blocks Used Available Capacity Mounted on"), None);
+    }
+}
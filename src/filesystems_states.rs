@@ -0,0 +1,290 @@
+use std::io::Write;
+
+use crossterm::{
+    cursor,
+    queue,
+    style::{Color, SetBackgroundColor},
+    terminal::{Clear, ClearType},
+};
+use minimad::Composite;
+
+use crate::{
+    app_context::AppContext,
+    app_state::{AppState, AppStateCmdResult},
+    browser_states::BrowserState,
+    commands::{Action, Command},
+    conf::{self, Conf},
+    errors::ProgramError,
+    file_sizes::{Size, SizeFormat},
+    filesystems::{self, Mount},
+    flat_tree::Tree,
+    io::W,
+    screens::Screen,
+    session::Session,
+    size_bars::SizeBarStyle,
+    state_history::Place,
+    status::Status,
+    task_sync::TaskLifetime,
+    tree_options::TreeOptions,
+    verb_store::PrefixSearchResult,
+    verbs::VerbExecutor,
+};
+
+/// an application state listing the mounted filesystems, like `df`,
+/// from which a filesystem can be opened as a tree
+pub struct FilesystemsState {
+    pub mounts: Vec<Mount>,
+    pub selection: usize,
+    scroll: usize, // the number of mounts hidden at the top
+    pub options: TreeOptions, // the options of the trees opened from this state
+}
+
+impl FilesystemsState {
+    pub fn new(options: TreeOptions) -> Result<FilesystemsState, ProgramError> {
+        Ok(FilesystemsState {
+            mounts: filesystems::read_mounts()?,
+            selection: 0,
+            scroll: 0,
+            options: options.without_pattern(),
+        })
+    }
+
+    /// the number of mounts which can be displayed, below the header
+    fn page_height(screen: &Screen) -> usize {
+        (screen.height as usize).saturating_sub(3)
+    }
+
+    pub fn move_selection(&mut self, dy: i32, screen: &Screen) {
+        if self.mounts.is_empty() {
+            return;
+        }
+        let len = self.mounts.len() as i32;
+        self.selection = (self.selection as i32 + dy).max(0).min(len - 1) as usize;
+        let page_height = FilesystemsState::page_height(screen);
+        if self.selection < self.scroll {
+            self.scroll = self.selection;
+        } else if page_height > 0 && self.selection >= self.scroll + page_height {
+            self.scroll = self.selection + 1 - page_height;
+        }
+    }
+
+    /// open the selected filesystem as a new tree
+    pub fn open_selection(&self, screen: &Screen) -> AppStateCmdResult {
+        match self.mounts.get(self.selection) {
+            Some(mount) => AppStateCmdResult::from_optional_state(
+                BrowserState::new(
+                    mount.mount_point.clone(),
+                    self.options.clone(),
+                    screen,
+                    &TaskLifetime::unlimited(),
+                ),
+                Command::new(),
+            ),
+            None => AppStateCmdResult::DisplayError("no filesystem found".to_string()),
+        }
+    }
+
+    fn size_format(&self) -> SizeFormat {
+        self.options.size_format
+    }
+}
+
+impl AppState for FilesystemsState {
+
+    fn has_pending_task(&self) -> bool {
+        false
+    }
+
+    fn apply(
+        &mut self,
+        cmd: &mut Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<AppStateCmdResult, ProgramError> {
+        Ok(match &cmd.action {
+            Action::Back | Action::UpTree | Action::UpTreeFromRoot => AppStateCmdResult::PopState,
+            Action::MoveSelection(dy) => {
+                self.move_selection(*dy, screen);
+                AppStateCmdResult::Keep
+            }
+            Action::Click(_, y) | Action::DoubleClick(_, y) => {
+                // the first line is the header
+                let idx = self.scroll + *y as usize;
+                if *y > 0 && idx <= self.mounts.len() {
+                    self.selection = idx - 1;
+                }
+                if let Action::DoubleClick(..) = &cmd.action {
+                    self.open_selection(screen)
+                } else {
+                    AppStateCmdResult::Keep
+                }
+            }
+            Action::OpenSelection => self.open_selection(screen),
+            Action::VerbIndex(index) => {
+                let verb = &con.verb_store.verbs[*index];
+                self.execute_verb(verb, &verb.invocation, screen, con)?
+            }
+            Action::VerbInvocate(invocation) => match con.verb_store.search(&invocation.name) {
+                PrefixSearchResult::Match(verb) => {
+                    self.execute_verb(verb, &invocation, screen, con)?
+                }
+                _ => AppStateCmdResult::verb_not_found(&invocation.name),
+            },
+            _ => AppStateCmdResult::Keep,
+        })
+    }
+
+    fn refresh(&mut self, screen: &Screen, _con: &AppContext) -> Command {
+        match filesystems::read_mounts() {
+            Ok(mounts) => {
+                self.mounts = mounts;
+                self.move_selection(0, screen);
+            }
+            Err(e) => {
+                warn!("refreshing the filesystems failed: {:?}", e);
+            }
+        }
+        Command::new()
+    }
+
+    fn do_pending_task(&mut self, _screen: &mut Screen, _tl: &TaskLifetime) {
+        unreachable!();
+    }
+
+    fn display(
+        &mut self,
+        w: &mut W,
+        screen: &Screen,
+        _con: &AppContext
+    ) -> Result<(), ProgramError> {
+        let skin = &screen.skin;
+        let size_format = self.size_format();
+        let size_width = 6;
+        let bar_style = SizeBarStyle::Wide;
+        // the device column is as wide as needed, within reason
+        let device_width = self.mounts
+            .iter()
+            .map(|mount| mount.device.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(10)
+            .min(30);
+        queue!(w, cursor::MoveTo(0, 0))?;
+        skin.tree.queue(w, format!(
+            "{:<dw$} {:>sw$} {:>sw$} {:>sw$} {:<bw$} mount point",
+            "filesystem", "size", "used", "free", " use",
+            dw = device_width,
+            sw = size_width,
+            bw = bar_style.width() + 5,
+        ))?;
+        queue!(w, Clear(ClearType::UntilNewLine))?;
+        let page_height = FilesystemsState::page_height(screen);
+        for y in 0..page_height {
+            queue!(w, cursor::MoveTo(0, y as u16 + 1))?;
+            let idx = self.scroll + y;
+            let selected = idx == self.selection;
+            if let Some(mount) = self.mounts.get(idx) {
+                if selected {
+                    skin.selected_line.queue_bg(w)?;
+                }
+                let format_size = |bytes: u64| Size::from(bytes).format(size_format);
+                let device: String = mount.device.chars().take(device_width).collect();
+                let mut device_style = skin.file.clone();
+                let mut counts_style = skin.counts.clone();
+                let mut mount_style = skin.directory.clone();
+                if selected {
+                    if let Some(c) = skin.selected_line.get_bg() {
+                        device_style.set_bg(c);
+                        counts_style.set_bg(c);
+                        mount_style.set_bg(c);
+                    }
+                }
+                device_style.queue(w, format!("{:<dw$} ", device, dw = device_width))?;
+                counts_style.queue(w, format!(
+                    "{:>sw$} {:>sw$} {:>sw$} ",
+                    format_size(mount.size),
+                    format_size(mount.used),
+                    format_size(mount.available),
+                    sw = size_width,
+                ))?;
+                let part = mount.used_part();
+                counts_style.queue(w, format!(
+                    "{:>3.0}% {} ",
+                    part * 100.0,
+                    bar_style.render(part),
+                ))?;
+                mount_style.queue(w, mount.mount_point.to_string_lossy())?;
+            }
+            if selected {
+                skin.selected_line.queue_bg(w)?;
+            } else {
+                skin.default.queue_bg(w)?;
+            }
+            queue!(w, Clear(ClearType::UntilNewLine))?;
+            queue!(w, SetBackgroundColor(Color::Reset))?;
+        }
+        Ok(())
+    }
+
+    fn write_status(
+        &self,
+        w: &mut W,
+        cmd: &Command,
+        screen: &Screen,
+        con: &AppContext,
+    ) -> Result<(), ProgramError> {
+        match &cmd.action {
+            Action::VerbEdit(invocation) => match con.verb_store.search(&invocation.name) {
+                PrefixSearchResult::NoMatch => {
+                    Status::from_error(mad_inline!("No matching verb")).display(w, screen)
+                }
+                PrefixSearchResult::Match(verb) => {
+                    verb.write_status(
+                        w,
+                        None,
+                        Conf::default_location(),
+                        &conf::dir(),
+                        1,
+                        invocation,
+                        screen,
+                    )
+                }
+                PrefixSearchResult::TooManyMatches => Status::from_message(mad_inline!(
+                    "Type a verb then *enter* to execute it"
+                )).display(w, screen),
+            }
+            Action::PendingChord(first) => {
+                let hint = con.verb_store.chord_hint(*first);
+                Status::from_message(Composite::from_inline(&hint)).display(w, screen)
+            }
+            _ => Status::from_message(mad_inline!(
+                "Hit *enter* to open the selected filesystem, *esc* to get back to the tree"
+            )).display(w, screen),
+        }
+    }
+
+    /// there's no meaningful flags here
+    fn write_flags(
+        &self,
+        w: &mut W,
+        screen: &mut Screen,
+        _con: &AppContext
+    ) -> Result<(), ProgramError> {
+        screen.skin.default.queue_bg(w)?;
+        queue!(w, Clear(ClearType::UntilNewLine))?;
+        Ok(())
+    }
+
+    /// the list of filesystems isn't restored on resume
+    fn session(&self) -> Option<Session> {
+        None
+    }
+
+    fn place(&self) -> Option<Place> {
+        None
+    }
+
+    fn tree(&self) -> Option<&Tree> {
+        None
+    }
+}
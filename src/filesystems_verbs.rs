@@ -0,0 +1,76 @@
+//! this modules defines the execution of verbs on the filesystems screen
+
+use crate::{
+    app_context::AppContext,
+    app_state::AppStateCmdResult,
+    clipboard_copy,
+    commands::Command,
+    errors::ProgramError,
+    external,
+    filesystems_states::FilesystemsState,
+    help_states::HelpState,
+    screens::Screen,
+    selection_type::{Selection, SelectionType},
+    verb_invocation::VerbInvocation,
+    verbs::{Verb, VerbExecutor},
+};
+
+impl VerbExecutor for FilesystemsState {
+    fn execute_verb(
+        &mut self,
+        verb: &Verb,
+        invocation: &VerbInvocation,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<AppStateCmdResult, ProgramError> {
+        if let Some(err) = verb.match_error(invocation) {
+            return Ok(AppStateCmdResult::DisplayError(err));
+        }
+        let page_height = screen.height as i32 - 3;
+        let selected_path = self.mounts.get(self.selection).map(|m| m.mount_point.clone());
+        Ok(match verb.execution.as_ref() {
+            ":back" => AppStateCmdResult::PopState,
+            ":copy_path" => match selected_path {
+                Some(path) => clipboard_copy::copy_to_clipboard(&path.to_string_lossy())?,
+                None => AppStateCmdResult::Keep,
+            },
+            ":filesystems" => AppStateCmdResult::Keep,
+            ":focus" | ":open_stay" => self.open_selection(screen),
+            ":help" => AppStateCmdResult::NewState(
+                Box::new(HelpState::new(
+                    screen,
+                    con,
+                    Selection::of_type(SelectionType::Directory),
+                    self.options.clone(),
+                )),
+                Command::new(),
+            ),
+            ":line_down" => {
+                self.move_selection(1, screen);
+                AppStateCmdResult::Keep
+            }
+            ":line_up" => {
+                self.move_selection(-1, screen);
+                AppStateCmdResult::Keep
+            }
+            ":page_down" => {
+                self.move_selection(page_height, screen);
+                AppStateCmdResult::Keep
+            }
+            ":page_up" => {
+                self.move_selection(-page_height, screen);
+                AppStateCmdResult::Keep
+            }
+            ":print_path" => match selected_path {
+                Some(path) => external::print_path(&path, con)?,
+                None => AppStateCmdResult::Keep,
+            },
+            ":quit" => AppStateCmdResult::Quit,
+            _ if verb.execution.starts_with(':') => AppStateCmdResult::PopStateAndReapply,
+            _ => match selected_path {
+                Some(path) => verb.to_cmd_result(&path, &path, 1, &invocation.args, screen, con)?,
+                None => AppStateCmdResult::Keep,
+            },
+        })
+    }
+}
@@ -2,60 +2,166 @@
 ///  no link from a child to its parent or from a parent to its children.
 use std::{
     cmp::{self, Ord, Ordering, PartialOrd},
-    fs,
+    ffi::OsStr,
+    fmt, fs,
+    hash::{Hash, Hasher},
     mem,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 #[cfg(unix)]
-use {
-    std::os::unix::fs::MetadataExt,
-    umask::Mode,
-};
+use umask::Mode;
 
 #[cfg(windows)]
 use is_executable::IsExecutable;
 
 use crate::{
     errors,
+    dir_counts::ChildrenCount,
     file_sizes::Size,
+    os_names,
     task_sync::TaskLifetime,
-    tree_build::TreeBuilder,
+    tree_build::{SearchStats, TreeBuilder},
     tree_options::TreeOptions,
+    tree_sources::EntryMetadata,
 };
 
+/// in whale spotting mode, the part of its parent under which
+/// a line is pruned
+const WHALE_MIN_PART: f32 = 0.01;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum LineType {
     File,
     Dir,
     SymLinkToDir(String),
     SymLinkToFile(String), // (to file or to symlink)
+    Archive,               // a zip or tar file, which can be browsed as a directory
     Pruning,               // a "xxx unlisted" line
 }
 
+/// the path of a line, stored as the name of the file and the path of
+/// its parent, which is shared by all the lines of the directory. This
+/// way a big tree doesn't store, for every file, the whole path of the
+/// directories leading to it.
+#[derive(Clone)]
+pub struct LinePath(Arc<PathNode>);
+
+struct PathNode {
+    parent: Option<LinePath>, // None for the root, whose name is its whole path
+    name: Box<OsStr>,
+}
+
+impl LinePath {
+    pub fn root(path: &Path) -> LinePath {
+        LinePath(Arc::new(PathNode {
+            parent: None,
+            name: path.as_os_str().to_os_string().into_boxed_os_str(),
+        }))
+    }
+    pub fn child(&self, name: &OsStr) -> LinePath {
+        LinePath(Arc::new(PathNode {
+            parent: Some(self.clone()),
+            name: name.to_os_string().into_boxed_os_str(),
+        }))
+    }
+    /// return the path of the parent, None for the root of the tree
+    pub fn parent(&self) -> Option<&LinePath> {
+        self.0.parent.as_ref()
+    }
+    pub fn file_name(&self) -> Option<&OsStr> {
+        match self.0.parent {
+            Some(_) => Some(&self.0.name),
+            None => Path::new(&self.0.name).file_name(),
+        }
+    }
+    /// return the names leading to the file, starting with
+    /// the whole path of the root
+    fn names(&self) -> Vec<&OsStr> {
+        let mut names = Vec::new();
+        let mut node = Some(self);
+        while let Some(path) = node {
+            names.push(&*path.0.name);
+            node = path.parent();
+        }
+        names.reverse();
+        names
+    }
+    pub fn to_path_buf(&self) -> PathBuf {
+        let names = self.names();
+        let mut path = PathBuf::with_capacity(names.iter().map(|n| n.len() + 1).sum());
+        for name in names {
+            path.push(name);
+        }
+        path
+    }
+}
+
+impl PartialEq for LinePath {
+    fn eq(&self, other: &LinePath) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+            || (self.0.name == other.0.name && self.0.parent == other.0.parent)
+    }
+}
+
+impl Eq for LinePath {}
+
+impl Hash for LinePath {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut node = Some(self);
+        while let Some(path) = node {
+            path.0.name.hash(state);
+            node = path.parent();
+        }
+    }
+}
+
+impl PartialEq<Path> for LinePath {
+    fn eq(&self, other: &Path) -> bool {
+        match self.parent() {
+            Some(parent) => {
+                other.file_name() == Some(&*self.0.name)
+                    && other.parent().map_or(false, |other_parent| parent == other_parent)
+            }
+            None => Path::new(&self.0.name) == other,
+        }
+    }
+}
+
+impl fmt::Debug for LinePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_path_buf().fmt(f)
+    }
+}
+
 /// a line in the representation of the file hierarchy
 #[derive(Debug, Clone)]
 pub struct TreeLine {
     pub left_branchs: Box<[bool]>, // a depth-sized array telling whether a branch pass
     pub depth: u16,
     pub name: String,
-    pub path: PathBuf,
+    pub path: LinePath,
     pub line_type: LineType,
     pub has_error: bool,
     pub nb_kept_children: usize,
     pub unlisted: usize, // number of not listed children (Dir) or brothers (Pruning)
     pub score: i32,      // 0 if there's no pattern
     pub size: Option<Size>, // None when not measured
-    pub metadata: fs::Metadata,
+    pub children_count: Option<ChildrenCount>, // None when not counted or not a directory
+    pub group_count: Option<usize>, // number of matches, for the header lines of grouped searches
+    pub metadata: EntryMetadata,
 }
 
 #[derive(Debug, Clone)]
 pub struct Tree {
+    pub root: PathBuf, // the path of the first line
     pub lines: Box<[TreeLine]>,
     pub selection: usize, // there's always a selection (starts with root, which is 0)
     pub options: TreeOptions,
     pub scroll: i32, // the number of lines at the top hidden because of scrolling
     pub nb_gitignored: u32, // number of times a gitignore pattern excluded a file
+    pub search_stats: SearchStats, // what was searched to build the tree
 }
 
 impl TreeLine {
@@ -72,9 +178,15 @@ impl TreeLine {
             _ => false,
         }
     }
+    pub fn is_symlink(&self) -> bool {
+        match &self.line_type {
+            LineType::SymLinkToFile(_) | LineType::SymLinkToDir(_) => true,
+            _ => false,
+        }
+    }
     pub fn is_file(&self) -> bool {
         match &self.line_type {
-            LineType::File => true,
+            LineType::File | LineType::Archive => true,
             _ => false,
         }
     }
@@ -87,7 +199,7 @@ impl TreeLine {
         return self.mode().is_exe();
 
         #[cfg(windows)]
-        return self.path.is_executable();
+        return self.path.to_path_buf().is_executable();
     }
     // build and return the absolute targeted path: either self.path or the
     //  solved canonicalized symlink
@@ -97,14 +209,16 @@ impl TreeLine {
             LineType::SymLinkToFile(target) | LineType::SymLinkToDir(target) => {
                 let mut target_path = PathBuf::from(target);
                 if target_path.is_relative() {
-                    target_path = self.path.parent().unwrap().join(target_path);
+                    if let Some(parent) = self.path.parent() {
+                        target_path = parent.to_path_buf().join(target_path);
+                    }
                 }
                 if let Ok(canonic) = fs::canonicalize(&target_path) {
                     target_path = canonic;
                 }
                 target_path
             }
-            _ => self.path.clone(),
+            _ => self.path.to_path_buf(),
         }
     }
 }
@@ -120,38 +234,21 @@ impl Ord for TreeLine {
     // paths are sorted in a complete ignore case way
     // (A<a<B<b)
     fn cmp(&self, other: &TreeLine) -> Ordering {
-        let mut sci = self.path.components();
-        let mut oci = other.path.components();
-        loop {
-            match sci.next() {
-                Some(sc) => {
-                    match oci.next() {
-                        Some(oc) => {
-                            let scs = sc.as_os_str().to_string_lossy();
-                            let ocs = oc.as_os_str().to_string_lossy();
-                            let lower_ordering = scs.to_lowercase().cmp(&ocs.to_lowercase());
-                            if lower_ordering != Ordering::Equal {
-                                return lower_ordering;
-                            }
-                            let ordering = scs.cmp(&ocs);
-                            if ordering != Ordering::Equal {
-                                return ordering;
-                            }
-                        }
-                        None => {
-                            return Ordering::Greater;
-                        }
-                    };
-                }
-                None => {
-                    if oci.next().is_some() {
-                        return Ordering::Less;
-                    } else {
-                        return Ordering::Equal;
-                    }
-                }
-            };
+        let snames = self.path.names();
+        let onames = other.path.names();
+        for (sn, on) in snames.iter().zip(onames.iter()) {
+            let scs = sn.to_string_lossy();
+            let ocs = on.to_string_lossy();
+            let lower_ordering = scs.to_lowercase().cmp(&ocs.to_lowercase());
+            if lower_ordering != Ordering::Equal {
+                return lower_ordering;
+            }
+            let ordering = scs.cmp(&ocs);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
         }
+        snames.len().cmp(&onames.len())
     }
 }
 
@@ -172,6 +269,7 @@ impl Tree {
                                                                            // we save the old selection to try restore it
         let selected_path = self.selected_line().path.to_path_buf();
         mem::swap(&mut self.lines, &mut tree.lines);
+        self.search_stats = tree.search_stats;
         self.selection = 0; // so that there's no error if we can't find the selection after refresh
         self.scroll = 0;
         self.try_select_path(&selected_path);
@@ -202,13 +300,13 @@ impl Tree {
             let depth = (self.lines[end_index].depth - 1) as usize;
             let start_index = {
                 let parent_index = {
-                    let parent_path = &self.lines[end_index].path.parent();
+                    let parent_path = self.lines[end_index].path.parent().cloned();
                     match parent_path {
                         Some(parent_path) => {
                             let mut index = end_index;
                             loop {
                                 index -= 1;
-                                if self.lines[index].path == *parent_path {
+                                if self.lines[index].path == parent_path {
                                     break;
                                 }
                                 if index == 0 {
@@ -314,7 +412,7 @@ impl Tree {
         &self.lines[self.selection]
     }
     pub fn root(&self) -> &PathBuf {
-        &self.lines[0].path
+        &self.root
     }
     // select the line with the best matching score
     pub fn try_select_best_match(&mut self) {
@@ -341,7 +439,7 @@ impl Tree {
             if !line.is_selectable() {
                 continue;
             }
-            if path == line.path {
+            if line.path == *path {
                 self.selection = idx;
                 return;
             }
@@ -396,20 +494,40 @@ impl Tree {
         false
     }
 
+    /// tell whether the size of the line must be computed as the one
+    /// of a directory (possibly the target of a symlink)
+    fn has_dir_size(&self, line: &TreeLine) -> bool {
+        if line.metadata.in_archive || !self.options.source.is_local() {
+            return false; // the sizes of the archive and remote entries aren't computed
+        }
+        match &line.line_type {
+            LineType::Dir => true,
+            LineType::SymLinkToDir(_) => self.options.link_target_sizes,
+            _ => false,
+        }
+    }
+
     pub fn has_dir_missing_size(&self) -> bool {
         self.options.show_sizes
             && self.lines.iter().skip(1).any(|line|
-                line.line_type == LineType::Dir && line.size.is_none()
+                self.has_dir_size(line) && line.size.is_none()
             )
     }
 
     pub fn fetch_file_sizes(&mut self) {
+        let local = self.options.source.is_local();
         for i in 1..self.lines.len() {
-            if self.lines[i].is_file() {
-                self.lines[i].size = Some(Size::from_file(&self.lines[i].path));
+            if self.lines[i].metadata.in_archive || !local {
+                self.lines[i].size = Some(Size::from(self.lines[i].metadata.len()));
+            } else if self.lines[i].is_file() {
+                self.lines[i].size = Some(Size::from_file(&self.lines[i].path.to_path_buf()));
+            } else if self.options.link_target_sizes {
+                if let LineType::SymLinkToFile(_) = self.lines[i].line_type {
+                    self.lines[i].size = Some(Size::from_file(&self.lines[i].target()));
+                }
             }
         }
-        self.sort_siblings_by_size();
+        self.sort_by_size();
     }
 
     /// compute the size of one directory
@@ -418,18 +536,59 @@ impl Tree {
     ///  has_dir_missing_size returns false
     pub fn fetch_some_missing_dir_size(&mut self, tl: &TaskLifetime) {
         for i in 1..self.lines.len() {
-            if self.lines[i].size.is_none() && self.lines[i].line_type == LineType::Dir {
-                self.lines[i].size = Size::from_dir(&self.lines[i].path, tl);
-                self.sort_siblings_by_size();
+            if self.lines[i].size.is_none() && self.has_dir_size(&self.lines[i]) {
+                self.lines[i].size = Size::from_dir(&self.lines[i].target(), tl);
+                self.sort_by_size();
                 return;
             }
         }
     }
+    pub fn has_dir_missing_count(&self) -> bool {
+        self.options.show_counts
+            && self.options.source.is_local()
+            && self.lines.iter().skip(1).any(|line|
+                line.line_type == LineType::Dir
+                    && line.children_count.is_none()
+                    && !line.metadata.in_archive
+            )
+    }
+
+    /// count the children of one directory
+    ///
+    /// To count them in all directories, this should be called until
+    ///  has_dir_missing_count returns false
+    pub fn fetch_some_missing_dir_count(&mut self, tl: &TaskLifetime) {
+        for i in 1..self.lines.len() {
+            if self.lines[i].children_count.is_none()
+                && self.lines[i].line_type == LineType::Dir
+                && !self.lines[i].metadata.in_archive
+            {
+                self.lines[i].children_count = ChildrenCount::from_dir(&self.lines[i].path.to_path_buf(), tl);
+                return;
+            }
+        }
+    }
+
+    /// sort the lines according to their size, and in whale spotting
+    /// mode prune the small ones when all sizes are known
+    fn sort_by_size(&mut self) {
+        if self.options.whale_spotting {
+            let selected_path = self.selected_line().path.to_path_buf();
+            self.sort_tree_by_size();
+            if !self.has_dir_missing_size() {
+                self.prune_small_lines();
+            }
+            self.selection = 0;
+            self.try_select_path(&selected_path);
+        } else {
+            self.sort_siblings_by_size();
+        }
+    }
+
     /// Sort files according to their size
     ///
     /// Warning: must not be called if there's more than one level displayed!
-    /// (a better sort should be devised but it's unsure whether it would be
-    /// readable enough)
+    /// (see sort_tree_by_size for deeper trees)
     fn sort_siblings_by_size(&mut self) {
         self.lines[1..].sort_by(|a, b| {
             let asize = a.size.map_or(0, |s| s.into());
@@ -437,6 +596,240 @@ impl Tree {
             bsize.cmp(&asize)
         });
     }
+    /// return the index of the parent of the line (0 for the root)
+    ///
+    /// This relies on the lines being in tree order, each line
+    /// coming after its parent.
+    pub fn parent_index(&self, line_index: usize) -> usize {
+        let depth = self.lines[line_index].depth;
+        (0..line_index)
+            .rev()
+            .find(|&i| self.lines[i].depth < depth)
+            .unwrap_or(0)
+    }
+
+    /// return the size of the directory containing the line
+    pub fn parent_size(&self, line_index: usize) -> Size {
+        match self.parent_index(line_index) {
+            0 => self.total_size(),
+            i => self.lines[i].size.unwrap_or_else(|| Size::from(0)),
+        }
+    }
+
+    /// return the index of the parent of every line, in one pass
+    /// (the root being its own parent)
+    fn parent_indexes(&self) -> Vec<usize> {
+        let mut parents = vec![0; self.lines.len()];
+        let mut ancestors: Vec<usize> = vec![0]; // the last line seen at each depth
+        for i in 1..self.lines.len() {
+            let depth = self.lines[i].depth as usize;
+            ancestors.truncate(depth);
+            parents[i] = ancestors.last().copied().unwrap_or(0);
+            ancestors.push(i);
+        }
+        parents
+    }
+
+    /// sort the lines so that, at every level, siblings are sorted
+    /// by decreasing size, each directory being followed by its children
+    fn sort_tree_by_size(&mut self) {
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); self.lines.len()];
+        for (i, &parent_index) in self.parent_indexes().iter().enumerate().skip(1) {
+            children[parent_index].push(i);
+        }
+        for siblings in &mut children {
+            let lines = &self.lines;
+            siblings.sort_by_key(|&i| {
+                let size: u64 = lines[i].size.map_or(0, |s| s.into());
+                cmp::Reverse(size)
+            });
+        }
+        let mut order = Vec::with_capacity(self.lines.len());
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            order.push(i);
+            stack.extend(children[i].iter().rev());
+        }
+        let mut lines: Vec<Option<TreeLine>> = self.lines.iter().cloned().map(Some).collect();
+        self.lines = order
+            .into_iter()
+            .filter_map(|i| lines[i].take())
+            .collect::<Vec<TreeLine>>()
+            .into_boxed_slice();
+        self.update_left_branchs();
+    }
+
+    /// replace the lines weighing less than WHALE_MIN_PART of their
+    /// parent with a pruning line giving their number and total size.
+    /// The lines must be sorted with sort_tree_by_size.
+    fn prune_small_lines(&mut self) {
+        let len = self.lines.len();
+        let parents = self.parent_indexes();
+        let mut removed = vec![false; len];
+        // for each parent: the index of the first small child, the
+        // number of small children and their total size
+        let mut small: Vec<Option<(usize, usize, Size)>> = vec![None; len];
+        for i in 1..len {
+            let parent_index = parents[i];
+            if removed[parent_index] {
+                removed[i] = true;
+                continue;
+            }
+            if self.lines[i].line_type == LineType::Pruning {
+                continue;
+            }
+            let size = self.lines[i].size.unwrap_or_else(|| Size::from(0));
+            let parent_size = match parent_index {
+                0 => self.total_size(),
+                _ => self.lines[parent_index].size.unwrap_or_else(|| Size::from(0)),
+            };
+            if size.part_of(parent_size) < WHALE_MIN_PART {
+                removed[i] = true;
+                let entry = small[parent_index].get_or_insert((i, 0, Size::from(0)));
+                entry.1 += 1;
+                entry.2 += size;
+            }
+        }
+        for (first, count, size) in small.into_iter().flatten() {
+            if count < 2 {
+                // a pruning line would take as much room as the line
+                let depth = self.lines[first].depth;
+                for i in first..len {
+                    if i > first && self.lines[i].depth <= depth {
+                        break;
+                    }
+                    removed[i] = false;
+                }
+                continue;
+            }
+            let line = &mut self.lines[first];
+            line.line_type = LineType::Pruning;
+            line.name = format!("{} unlisted", count);
+            line.unlisted = count;
+            line.size = Some(size);
+            line.children_count = None;
+            removed[first] = false;
+        }
+        let mut index = 0;
+        let mut lines = mem::replace(&mut self.lines, Vec::new().into_boxed_slice()).into_vec();
+        lines.retain(|_| {
+            index += 1;
+            !removed[index - 1]
+        });
+        self.lines = lines.into_boxed_slice();
+        self.update_left_branchs();
+    }
+
+    /// tell whether the line matches the search itself, rather than
+    /// being there because it leads to matches
+    pub fn is_match(&self, line: &TreeLine) -> bool {
+        let options = &self.options;
+        options.pattern.score_of(&line.name).is_some()
+            && options.pattern.file_filter().map_or(true, |filter| {
+                filter.accepts(&line.path.to_path_buf(), line.metadata.entry_type, &*options.source)
+            })
+    }
+
+    /// rebuild the lines of a search result so that every match is
+    /// listed under a header line for its parent directory, which
+    /// gives the path relative to the root and the number of matches.
+    /// The directories without direct match disappear.
+    pub fn group_matches(&mut self) {
+        let len = self.lines.len();
+        let parents = self.parent_indexes();
+        // the lines of every group (pruning lines included) and the
+        // number of matches among them, by index of the parent
+        let mut groups: Vec<(Vec<usize>, usize)> = vec![(Vec::new(), 0); len];
+        for i in 1..len {
+            let line = &self.lines[i];
+            if line.line_type == LineType::Pruning {
+                groups[parents[i]].0.push(i);
+            } else if self.is_match(line) {
+                groups[parents[i]].0.push(i);
+                groups[parents[i]].1 += 1;
+            }
+        }
+        let root = self.root.clone();
+        let mut lines = Vec::with_capacity(len);
+        lines.push(self.lines[0].clone());
+        // the root being the first parent, the matches it directly
+        // contains come just after it, before the other groups
+        for (parent_index, (members, nb_matches)) in groups.into_iter().enumerate() {
+            if nb_matches == 0 {
+                continue;
+            }
+            if parent_index == 0 {
+                lines[0].group_count = Some(nb_matches);
+            } else {
+                let mut header = self.lines[parent_index].clone();
+                header.group_count = Some(nb_matches);
+                header.depth = 1;
+                header.left_branchs = vec![false; 1].into_boxed_slice();
+                if let Ok(relative) = header.path.to_path_buf().strip_prefix(&root) {
+                    header.name = os_names::escape(relative.as_os_str());
+                }
+                lines.push(header);
+            }
+            let depth = if parent_index == 0 { 1 } else { 2 };
+            for i in members {
+                let mut line = self.lines[i].clone();
+                line.depth = depth;
+                line.left_branchs = vec![false; depth as usize].into_boxed_slice();
+                lines.push(line);
+            }
+        }
+        self.lines = lines.into_boxed_slice();
+        self.update_left_branchs();
+    }
+
+    /// keep only the lines matching the pattern themselves, each one
+    /// directly under the root with its path relative to the root as
+    /// name, so that the branches leading to the matches disappear
+    pub fn keep_only_matches(&mut self) {
+        let root = self.root.clone();
+        let mut lines = Vec::with_capacity(self.lines.len());
+        lines.push(self.lines[0].clone());
+        for line in self.lines.iter().skip(1) {
+            if line.line_type == LineType::Pruning || !self.is_match(line) {
+                continue;
+            }
+            let mut line = line.clone();
+            line.depth = 1;
+            line.left_branchs = vec![false; 1].into_boxed_slice();
+            if let Ok(relative) = line.path.to_path_buf().strip_prefix(&root) {
+                line.name = os_names::escape(relative.as_os_str());
+            }
+            lines.push(line);
+        }
+        self.lines = lines.into_boxed_slice();
+        self.update_left_branchs();
+    }
+
+    /// compute the branches drawn on the left of the lines, which
+    /// must be in tree order
+    fn update_left_branchs(&mut self) {
+        for line in self.lines.iter_mut() {
+            for b in line.left_branchs.iter_mut() {
+                *b = false;
+            }
+        }
+        // a branch goes from a directory to its last child
+        let parents = self.parent_indexes();
+        let mut last_children = vec![0; self.lines.len()];
+        for i in 1..self.lines.len() {
+            last_children[parents[i]] = i;
+        }
+        for (parent_index, &last_child) in last_children.iter().enumerate() {
+            if last_child == 0 {
+                continue;
+            }
+            let depth = self.lines[parent_index].depth as usize;
+            for i in parent_index + 1..=last_child {
+                self.lines[i].left_branchs[depth] = true;
+            }
+        }
+    }
+
     /// compute and return the size of the root
     pub fn total_size(&self) -> Size {
         if let Some(size) = self.lines[0].size {
@@ -457,3 +850,26 @@ impl Tree {
         }
     }
 }
+
+#[cfg(test)]
+mod line_path_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_line_paths() {
+        let root = LinePath::root(Path::new("/home/dys"));
+        let src = root.child(OsStr::new("src"));
+        let main = src.child(OsStr::new("main.rs"));
+        assert_eq!(main.to_path_buf(), PathBuf::from("/home/dys/src/main.rs"));
+        assert_eq!(main.file_name(), Some(OsStr::new("main.rs")));
+        assert_eq!(root.file_name(), Some(OsStr::new("dys")));
+        assert!(main == *Path::new("/home/dys/src/main.rs"));
+        assert!(main != *Path::new("/home/dys/main.rs"));
+        assert!(root == *Path::new("/home/dys"));
+        assert_eq!(main.parent(), Some(&src));
+        assert_eq!(root.parent(), None);
+        // paths built separately are equal
+        assert_eq!(root.child(OsStr::new("src")), src);
+    }
+}
@@ -0,0 +1,105 @@
+//! watch the directories of the displayed tree so that it can be
+//! refreshed when files are created, removed or renamed.
+//!
+//! As for the configuration, we don't rely on OS notifications: the
+//! modification dates of the directories, which change when one of
+//! their entries is added, removed or renamed, are checked periodically.
+//! A change is only reported once the directories are quiet again, so
+//! that a bulk operation leads to one refresh instead of a lot of them.
+
+use std::{
+    fs,
+    mem,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// how long the watcher waits between two checks of the directories
+pub static FS_POLL_PERIOD: Duration = Duration::from_millis(400);
+
+/// the number of checks without any change needed before a change
+/// is reported
+const QUIET_CHECKS: usize = 2;
+
+struct WatchedDirs {
+    dirs: Vec<(PathBuf, Option<SystemTime>)>,
+    pending_change: bool, // whether a change wasn't reported yet
+    quiet_checks: usize,  // the number of checks since the last change
+}
+
+/// the watcher, shared between the thread checking the directories
+/// and the application, which tells what directories are displayed
+#[derive(Clone)]
+pub struct FsWatcher {
+    watched: Arc<Mutex<WatchedDirs>>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|md| md.modified()).ok()
+}
+
+impl FsWatcher {
+    pub fn new() -> FsWatcher {
+        FsWatcher {
+            watched: Arc::new(Mutex::new(WatchedDirs {
+                dirs: Vec::new(),
+                pending_change: false,
+                quiet_checks: 0,
+            })),
+        }
+    }
+
+    /// set the directories to watch. The modification dates of the
+    /// directories which were already watched are kept, so that a change
+    /// happening while the tree was rebuilt isn't missed
+    pub fn set_dirs(&self, dirs: Vec<PathBuf>) {
+        let mut watched = self.watched.lock().unwrap();
+        if watched.dirs.len() == dirs.len()
+            && watched.dirs.iter().zip(dirs.iter()).all(|(w, d)| &w.0 == d)
+        {
+            return;
+        }
+        let old_dirs = mem::replace(&mut watched.dirs, Vec::new());
+        watched.dirs = dirs
+            .into_iter()
+            .map(|dir| {
+                let date = match old_dirs.iter().find(|(path, _)| path == &dir) {
+                    Some((_, date)) => *date,
+                    None => modified(&dir),
+                };
+                (dir, date)
+            })
+            .collect();
+        watched.pending_change = false;
+    }
+
+    /// check the directories and return true when a change was
+    /// seen and no other one happened since
+    pub fn has_changed(&self) -> bool {
+        let mut watched = self.watched.lock().unwrap();
+        let mut changed = false;
+        for (path, date) in watched.dirs.iter_mut() {
+            let new_date = modified(path);
+            if new_date != *date {
+                *date = new_date;
+                changed = true;
+            }
+        }
+        if changed {
+            watched.pending_change = true;
+            watched.quiet_checks = 0;
+            false
+        } else if watched.pending_change {
+            watched.quiet_checks += 1;
+            if watched.quiet_checks >= QUIET_CHECKS {
+                watched.pending_change = false;
+                true
+            } else {
+                false
+            }
+        } else {
+            false
+        }
+    }
+}
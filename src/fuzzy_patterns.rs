@@ -4,27 +4,31 @@
 
 use std::fmt::{self, Write};
 
-use crate::patterns::Match;
+use crate::{
+    fuzzy_scoring::FuzzyScoring,
+    patterns::{Match, Scorer},
+};
 
-// weights used in match score computing
+// weights used in match score computing (the other ones
+// are in the FuzzyScoring, as they can be configured)
 const BONUS_MATCH: i32 = 50_000;
 const BONUS_EXACT: i32 = 1_000;
 const BONUS_START: i32 = 10;
-const BONUS_START_WORD: i32 = 5;
 const BONUS_CANDIDATE_LENGTH: i32 = -1; // per char
 const BONUS_LENGTH: i32 = -10; // per char of length of the match
-const BONUS_NB_HOLES: i32 = -30; // there's also a max on that number
 
 #[derive(Debug, Clone)]
 pub struct FuzzyPattern {
-    lc_bytes: Box<[u8]>,
-    lc_chars: Box<[char]>, // lowercase characters
+    bytes: Box<[u8]>,
+    chars: Box<[char]>, // lowercase characters unless the pattern is case sensitive
+    case_sensitive: bool,
     max_nb_holes: usize,
+    scoring: FuzzyScoring,
 }
 
 impl fmt::Display for FuzzyPattern {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for &c in self.lc_chars.iter() {
+        for &c in self.chars.iter() {
             f.write_char(c)?
         }
         Ok(())
@@ -45,11 +49,22 @@ enum ScoreSearchResult {
 impl FuzzyPattern {
 
     pub fn from(pat: &str) -> FuzzyPattern {
-        let lc_bytes = pat.to_lowercase().as_bytes().to_vec();
-        let lc_bytes = lc_bytes.into_boxed_slice();
-        let lc_chars: Vec<char> = pat.chars().map(|c| c.to_ascii_lowercase()).collect();
-        let lc_chars = lc_chars.into_boxed_slice();
-        let max_nb_holes = match lc_chars.len() {
+        FuzzyPattern::with_scoring(pat, FuzzyScoring::default())
+    }
+
+    pub fn with_scoring(pat: &str, scoring: FuzzyScoring) -> FuzzyPattern {
+        let case_sensitive = scoring.case.is_sensitive_for(pat);
+        let (bytes, chars): (Vec<u8>, Vec<char>) = if case_sensitive {
+            (pat.as_bytes().to_vec(), pat.chars().collect())
+        } else {
+            (
+                pat.to_lowercase().as_bytes().to_vec(),
+                pat.chars().map(|c| c.to_ascii_lowercase()).collect(),
+            )
+        };
+        let bytes = bytes.into_boxed_slice();
+        let chars = chars.into_boxed_slice();
+        let max_nb_holes = match chars.len() {
             1 => 0,
             2 => 1,
             3 => 2,
@@ -58,26 +73,50 @@ impl FuzzyPattern {
             6 => 4,
             7 => 4,
             8 => 4,
-            _ => lc_chars.len() * 4 / 7,
+            _ => chars.len() * 4 / 7,
         };
         FuzzyPattern {
-            lc_bytes,
-            lc_chars,
+            bytes,
+            chars,
+            case_sensitive,
             max_nb_holes,
+            scoring,
         }
     }
 
+    fn normalized_byte(&self, b: u8) -> u8 {
+        if self.case_sensitive {
+            b
+        } else {
+            b.to_ascii_lowercase()
+        }
+    }
+
+    fn normalized_char(&self, c: char) -> char {
+        if self.case_sensitive {
+            c
+        } else {
+            c.to_ascii_lowercase()
+        }
+    }
+
+    /// tell whether a char ends a word or a path segment, so that
+    /// a match starting just after gets a bonus
+    fn is_separator(c: char) -> bool {
+        c == '_' || c == ' ' || c == '-' || c == '/'
+    }
+
     fn score_starting_at(
         &self,
         cand: &[u8],
         start_idx: usize, // start index in candidate, in bytes
     ) -> ScoreSearchResult {
-        if cand[start_idx].to_ascii_lowercase() != self.lc_bytes[0] {
+        if self.normalized_byte(cand[start_idx]) != self.bytes[0] {
             return ScoreSearchResult::None;
         }
         let mut d = 1;
         let mut nb_holes = 0;
-        for pat_idx in 1..self.lc_bytes.len() {
+        for pat_idx in 1..self.bytes.len() {
             let hole_start = d;
             loop {
                 let cand_idx = start_idx + d;
@@ -85,7 +124,7 @@ impl FuzzyPattern {
                     return ScoreSearchResult::None;
                 }
                 d += 1;
-                if cand[cand_idx].to_ascii_lowercase() == self.lc_bytes[pat_idx] {
+                if self.normalized_byte(cand[cand_idx]) == self.bytes[pat_idx] {
                     break;
                 }
             }
@@ -101,19 +140,18 @@ impl FuzzyPattern {
         let match_len = (d as i32) - 1;
         let mut score = BONUS_MATCH
             + BONUS_CANDIDATE_LENGTH * (cand.len() as i32)
-            + BONUS_NB_HOLES * (nb_holes as i32)
+            - self.scoring.hole_penalty * (nb_holes as i32)
             + match_len * BONUS_LENGTH;
         if start_idx == 0 {
             score += BONUS_START;
-            if cand.len() == self.lc_bytes.len() {
+            if cand.len() == self.bytes.len() {
                 score += BONUS_EXACT;
                 return ScoreSearchResult::Perfect(score);
             }
         } else {
-            let previous = cand[start_idx - 1];
-            if previous == b'_' || previous == b' ' || previous == b'-' {
-                score += BONUS_START_WORD;
-                if cand.len()-start_idx == self.lc_bytes.len() {
+            if FuzzyPattern::is_separator(cand[start_idx - 1] as char) {
+                score += self.scoring.segment_start_bonus;
+                if cand.len()-start_idx == self.bytes.len() {
                     return ScoreSearchResult::Perfect(score);
                 }
             }
@@ -126,14 +164,14 @@ impl FuzzyPattern {
         cand_chars: &[char],
         start_idx: usize, // start index in candidate, in chars
     ) -> MatchSearchResult {
-        if cand_chars[start_idx] != self.lc_chars[0] {
+        if cand_chars[start_idx] != self.chars[0] {
             return MatchSearchResult::None;
         }
         let mut pos: Vec<usize> = vec![]; // positions of matching chars in candidate
         pos.push(start_idx);
         let mut d = 1;
         let mut nb_holes = 0;
-        for pat_idx in 1..self.lc_chars.len() {
+        for pat_idx in 1..self.chars.len() {
             let hole_start = d;
             loop {
                 let cand_idx = start_idx + d;
@@ -141,7 +179,7 @@ impl FuzzyPattern {
                     return MatchSearchResult::None;
                 }
                 d += 1;
-                if cand_chars[cand_idx] == self.lc_chars[pat_idx] {
+                if cand_chars[cand_idx] == self.chars[pat_idx] {
                     pos.push(cand_idx);
                     break;
                 }
@@ -157,20 +195,19 @@ impl FuzzyPattern {
         }
         let mut score = BONUS_MATCH;
         score += BONUS_CANDIDATE_LENGTH * (cand_chars.len() as i32);
-        score += BONUS_NB_HOLES * (nb_holes as i32);
+        score -= self.scoring.hole_penalty * (nb_holes as i32);
         let match_len = (d as i32) - 1;
         score += match_len * BONUS_LENGTH;
         if start_idx == 0 {
             score += BONUS_START;
-            if cand_chars.len() == self.lc_chars.len() {
+            if cand_chars.len() == self.chars.len() {
                 score += BONUS_EXACT;
                 return MatchSearchResult::Perfect(Match { score, pos });
             }
         } else {
-            let previous = cand_chars[start_idx - 1];
-            if previous == '_' || previous == ' ' || previous == '-' {
-                score += BONUS_START_WORD;
-                if cand_chars.len()-start_idx == self.lc_chars.len() {
+            if FuzzyPattern::is_separator(cand_chars[start_idx - 1]) {
+                score += self.scoring.segment_start_bonus;
+                if cand_chars.len()-start_idx == self.chars.len() {
                     return MatchSearchResult::Perfect(Match { score, pos });
                 }
             }
@@ -183,13 +220,13 @@ impl FuzzyPattern {
     /// "abc" in "ababca-abc", the returned match would be at the end.
     pub fn find(&self, candidate: &str) -> Option<Match> {
         let mut cand_chars: Vec<char> = Vec::with_capacity(candidate.len());
-        cand_chars.extend(candidate.chars().map(|c| c.to_ascii_lowercase()));
-        if cand_chars.len() < self.lc_chars.len() {
+        cand_chars.extend(candidate.chars().map(|c| self.normalized_char(c)));
+        if cand_chars.len() < self.chars.len() {
             return None;
         }
         let mut best_score = 0;
         let mut best_match: Option<Match> = None;
-        let n = cand_chars.len() - self.lc_chars.len();
+        let n = cand_chars.len() - self.chars.len();
         for start_idx in 0..=n {
             match self.match_starting_at_index(&cand_chars, start_idx) {
                 MatchSearchResult::Perfect(m) => {
@@ -217,11 +254,11 @@ impl FuzzyPattern {
     /// there's no big bug) and that orderings are consistent (what matters for the
     /// app is scores orderings).
     pub fn score_of(&self, candidate: &str) -> Option<i32> {
-        if candidate.len() < self.lc_bytes.len() {
+        if candidate.len() < self.bytes.len() {
             return None;
         }
         let mut best_score = 0;
-        let n = candidate.len() - self.lc_bytes.len();
+        let n = candidate.len() - self.bytes.len();
         for start_idx in 0..=n {
             match self.score_starting_at(candidate.as_bytes(), start_idx) {
                 ScoreSearchResult::Perfect(s) => {
@@ -242,6 +279,16 @@ impl FuzzyPattern {
         }
     }
 
+    /// an upper bound of the scores: the one of a candidate made of the
+    /// pattern only, or of the pattern just after a separator, with
+    /// the holes counted in only if they're rewarded
+    pub fn best_score(&self) -> i32 {
+        let len = self.chars.len() as i32;
+        let start_bonus = (BONUS_START + BONUS_EXACT).max(self.scoring.segment_start_bonus);
+        let holes_bonus = (-self.scoring.hole_penalty).max(0) * (self.max_nb_holes as i32);
+        BONUS_MATCH + (BONUS_CANDIDATE_LENGTH + BONUS_LENGTH) * len + start_bonus + holes_bonus
+    }
+
     // return the number of results we should find before starting to
     //  sort them (unless time is runing out).
     pub const fn optimal_result_number(&self, targeted_size: usize) -> usize {
@@ -249,6 +296,24 @@ impl FuzzyPattern {
     }
 }
 
+impl Scorer for FuzzyPattern {
+    fn find(&self, candidate: &str) -> Option<Match> {
+        self.find(candidate)
+    }
+    fn score_of(&self, candidate: &str) -> Option<i32> {
+        self.score_of(candidate)
+    }
+    fn optimal_result_number(&self, targeted_size: usize) -> usize {
+        self.optimal_result_number(targeted_size)
+    }
+    fn has_ranking(&self) -> bool {
+        true
+    }
+    fn best_score(&self) -> Option<i32> {
+        Some(self.best_score())
+    }
+}
+
 #[cfg(test)]
 mod fuzzy_pattern_tests {
 
@@ -310,5 +375,38 @@ mod fuzzy_pattern_tests {
             &["Réveillon", "Réveillons", " réveils", "πréveil", "déréveil", " rêves"],
         );
     }
+
+    #[test]
+    fn check_case_sensitivity() {
+        use crate::fuzzy_scoring::CaseSensitivity;
+        let with_case = |case| FuzzyScoring {
+            case,
+            ..FuzzyScoring::default()
+        };
+        let fp = FuzzyPattern::with_scoring("Abc", with_case(CaseSensitivity::Sensitive));
+        assert!(fp.find("abc").is_none());
+        assert!(fp.find("xAbc").is_some());
+        let fp = FuzzyPattern::with_scoring("Abc", with_case(CaseSensitivity::Smart));
+        assert!(fp.score_of("abc").is_none());
+        let fp = FuzzyPattern::with_scoring("abc", with_case(CaseSensitivity::Smart));
+        assert!(fp.score_of("ABC").is_some());
+    }
+
+    #[test]
+    fn check_best_score() {
+        static NAMES: &[&str] = &["broot", "a broot", "src/broot", "brxoot", "Broot", "abroot"];
+        let fp = FuzzyPattern::from("broot");
+        for name in NAMES {
+            assert!(fp.score_of(name).unwrap() <= fp.best_score());
+            assert!(fp.find(name).unwrap().score <= fp.best_score());
+        }
+        assert_eq!(fp.score_of("broot"), Some(fp.best_score()));
+    }
+
+    #[test]
+    fn check_segment_start_bonus() {
+        let fp = FuzzyPattern::from("broot");
+        assert!(fp.score_of("src/broot") > fp.score_of("srcxbroot"));
+    }
 }
 
@@ -0,0 +1,62 @@
+//! the configurable parts of the scoring of fuzzy matches
+
+use std::str::FromStr;
+
+use crate::errors::ConfError;
+
+/// how the case of the letters is taken into account when matching
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaseSensitivity {
+    Smart,       // sensitive only when the pattern contains an uppercase letter
+    Sensitive,   // 'a' doesn't match 'A'
+    Insensitive, // 'a' matches 'A' and 'A' matches 'a'
+}
+
+impl Default for CaseSensitivity {
+    fn default() -> Self {
+        CaseSensitivity::Insensitive
+    }
+}
+
+impl FromStr for CaseSensitivity {
+    type Err = ConfError;
+    fn from_str(s: &str) -> Result<CaseSensitivity, ConfError> {
+        match s {
+            "smart" => Ok(CaseSensitivity::Smart),
+            "sensitive" => Ok(CaseSensitivity::Sensitive),
+            "insensitive" => Ok(CaseSensitivity::Insensitive),
+            _ => Err(ConfError::InvalidCaseSensitivity {
+                raw: s.to_string(),
+            }),
+        }
+    }
+}
+
+impl CaseSensitivity {
+    /// tell whether a pattern must be matched with its exact case
+    pub fn is_sensitive_for(self, pattern: &str) -> bool {
+        match self {
+            CaseSensitivity::Smart => pattern.chars().any(char::is_uppercase),
+            CaseSensitivity::Sensitive => true,
+            CaseSensitivity::Insensitive => false,
+        }
+    }
+}
+
+/// the weights which can be tuned in the configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FuzzyScoring {
+    pub segment_start_bonus: i32, // when the match starts a word or a path segment
+    pub hole_penalty: i32,        // per hole (sequence of unmatched chars) in the match
+    pub case: CaseSensitivity,
+}
+
+impl Default for FuzzyScoring {
+    fn default() -> Self {
+        FuzzyScoring {
+            segment_start_bonus: 5,
+            hole_penalty: 30,
+            case: CaseSensitivity::default(),
+        }
+    }
+}
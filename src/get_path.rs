@@ -0,0 +1,80 @@
+//! the headless query mode (`--get-path`): the pattern is searched
+//! like in the application, but without entering the alternate screen,
+//! and the best matching paths are printed on stdout.
+
+use std::path::PathBuf;
+
+use crate::{
+    commands::{Action, Command},
+    errors::ProgramError,
+    flat_tree::TreeLine,
+    patterns::Pattern,
+    task_sync::TaskLifetime,
+    tree_build::TreeBuilder,
+    tree_options::TreeOptions,
+};
+
+/// the number of tree lines allowed per wanted path, as the
+/// ancestors of the matching files take lines too
+const LINES_PER_PATH: usize = 10;
+
+/// parse the pattern with the syntax of the input (so that eg
+/// `/re/i` is a regex and `x:deploy` only matches executables)
+fn parse_pattern(raw: &str, options: &TreeOptions) -> Result<Pattern, ProgramError> {
+    let bad_pattern = || ProgramError::ArgParse {
+        bad: raw.to_string(),
+        valid: "a fuzzy pattern, a /regex/ or a composite pattern".to_string(),
+    };
+    let cmd = Command::from(raw.to_string());
+    let pattern = match cmd.action {
+        Action::FuzzyPatternEdit(pat) if pat.is_empty() => Pattern::None,
+        Action::FuzzyPatternEdit(pat) => Pattern::fuzzy(&pat, options.fuzzy_scoring),
+        Action::RegexEdit(pat, flags) => {
+            Pattern::regex(&pat, &flags).map_err(|_| bad_pattern())?
+        }
+        Action::CompositePatternEdit(pat) => {
+            Pattern::composite(&pat, options.fuzzy_scoring).map_err(|_| bad_pattern())?
+        }
+        _ => {
+            return Err(bad_pattern());
+        }
+    };
+    match pattern.filtered(cmd.file_filter()) {
+        Pattern::None => Err(bad_pattern()),
+        pattern => Ok(pattern),
+    }
+}
+
+/// search the pattern under the root and return the paths of the
+/// `limit` best matches, the best one first
+pub fn best_paths(
+    root: PathBuf,
+    mut options: TreeOptions,
+    raw_pattern: &str,
+    limit: usize,
+) -> Result<Vec<PathBuf>, ProgramError> {
+    options.pattern = parse_pattern(raw_pattern, &options)?;
+    let builder = TreeBuilder::from(root, options, limit.max(1) * LINES_PER_PATH)?;
+    let tree = match builder.build(&TaskLifetime::unlimited()) {
+        Some(tree) => tree,
+        None => {
+            return Ok(Vec::new());
+        }
+    };
+    // the tree also contains the ancestors of the matches, which
+    // we don't want (all lines have a positive score)
+    let mut matches: Vec<&TreeLine> = tree
+        .lines
+        .iter()
+        .skip(1) // the root
+        .filter(|line| line.is_selectable() && tree.is_match(line))
+        .collect();
+    // like when selecting the best match in the application, the
+    // shortest path wins in case of equal scores
+    matches.sort_by(|a, b| b.score.cmp(&a.score).then(a.depth.cmp(&b.depth)));
+    Ok(matches
+        .into_iter()
+        .take(limit)
+        .map(|line| line.path.to_path_buf())
+        .collect())
+}
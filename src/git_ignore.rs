@@ -2,16 +2,35 @@
 //! Also manages a stack of such files, because more than one
 //!  can apply for a dir (i.e when entering a directory we
 //!  may add a gitignore file to the stack)
+//! In a git repository, the stack starts with the `.git/info/exclude`
+//!  file of the repository and doesn't go above its root.
 
 use std::{
+    collections::HashMap,
     fs::File,
-    io::{BufRead, BufReader, Result},
+    io::{BufRead, BufReader, ErrorKind, Result},
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 
 use glob;
 use regex::Regex;
 
+use crate::selection_info;
+
+lazy_static! {
+    // the parsed .gitignore files, shared by all the tree builds and
+    // threads. None means there's no readable file at this path
+    static ref GITIGNORE_CACHE_MUTEX: Mutex<HashMap<PathBuf, Option<Arc<GitIgnoreFile>>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// forget the parsed .gitignore files, so that they're read again
+pub fn clear_cache() {
+    let mut cache = GITIGNORE_CACHE_MUTEX.lock().unwrap();
+    cache.clear();
+}
+
 /// a simple rule of a gitignore file
 #[derive(Clone)]
 struct GitIgnoreRule {
@@ -71,10 +90,10 @@ pub struct GitIgnoreFile {
 impl GitIgnoreFile {
     pub fn new(path: &Path) -> Result<GitIgnoreFile> {
         let f = File::open(path)?;
-        let parent = path.parent().unwrap();
+        let anchor = anchor_dir(path);
         let mut rules: Vec<GitIgnoreRule> = Vec::new();
         for line in BufReader::new(f).lines() {
-            if let Some(rule) = GitIgnoreRule::from(&line?, &parent) {
+            if let Some(rule) = GitIgnoreRule::from(&line?, anchor) {
                 rules.push(rule);
             }
         }
@@ -88,37 +107,82 @@ impl GitIgnoreFile {
         );
         Ok(GitIgnoreFile { rules })
     }
+    /// return the parsed file at this path, only parsing it when it
+    /// isn't already in cache
+    pub fn cached(path: &Path) -> Option<Arc<GitIgnoreFile>> {
+        if let Some(gif) = GITIGNORE_CACHE_MUTEX.lock().unwrap().get(path) {
+            return gif.clone();
+        }
+        // the file is parsed without holding the lock, so that other
+        // threads aren't blocked
+        let gif = match GitIgnoreFile::new(path) {
+            Ok(gif) => Some(Arc::new(gif)),
+            Err(e) => {
+                if e.kind() != ErrorKind::NotFound {
+                    info!("reading git ignore file failed: {:?}", e);
+                }
+                None
+            }
+        };
+        let mut cache = GITIGNORE_CACHE_MUTEX.lock().unwrap();
+        cache.insert(path.to_path_buf(), gif.clone());
+        gif
+    }
+}
+
+/// return the directory the rules of an ignore file are relative
+/// to: the directory of a .gitignore file, or the root of the
+/// repository for its .git/info/exclude file
+fn anchor_dir(path: &Path) -> &Path {
+    if path.ends_with(".git/info/exclude") {
+        path.ancestors().nth(3).unwrap()
+    } else {
+        path.parent().unwrap()
+    }
 }
 
 /// A stack of the gitignore files applying to a directory.
+/// The files are shared with the cache, so that extending a filter
+/// doesn't copy their rules.
 pub struct GitIgnoreFilter {
-    pub files: Vec<GitIgnoreFile>, // the last one is the deepest one
+    pub files: Vec<Arc<GitIgnoreFile>>, // the last one is the deepest one
 }
 impl GitIgnoreFilter {
+    /// build the stack of the files applying to a directory: in a git
+    /// repository, the exclude file of the repository then the .gitignore
+    /// files from its root down to the directory. Outside of a repository,
+    /// all the .gitignore files of the ancestors apply.
     pub fn applicable_to(path: &Path) -> GitIgnoreFilter {
-        let mut filter = GitIgnoreFilter { files: Vec::new() };
-        for ignore_file in GitIgnoreFilesFinder::for_dir(path) {
-            match GitIgnoreFile::new(&ignore_file) {
-                Ok(gif) => {
-                    filter.files.push(gif);
-                }
-                Err(e) => {
-                    info!("reading git ignore file failed: {:?}", e);
-                }
-            }
+        let git_root = selection_info::git_root(path);
+        let mut ignore_files: Vec<PathBuf> =
+            GitIgnoreFilesFinder::for_dir(path, git_root.as_deref()).collect();
+        if let Some(git_root) = &git_root {
+            ignore_files.push(git_root.join(".git").join("info").join("exclude"));
+        }
+        ignore_files.reverse(); // the finder starts with the deepest file
+        GitIgnoreFilter {
+            files: ignore_files
+                .iter()
+                .filter_map(|ignore_file| GitIgnoreFile::cached(ignore_file))
+                .collect(),
         }
-        filter
     }
     pub fn extended_to(&self, dir: &Path) -> GitIgnoreFilter {
+        if dir.join(".git").exists() {
+            // a nested repository, or a submodule, doesn't
+            // inherit the rules of the enclosing one
+            return GitIgnoreFilter::applicable_to(dir);
+        }
         let mut files = self.files.clone();
         let ignore_file = dir.join(".gitignore");
-        if let Ok(gif) = GitIgnoreFile::new(&ignore_file) {
+        if let Some(gif) = GitIgnoreFile::cached(&ignore_file) {
             files.push(gif);
         }
         GitIgnoreFilter { files }
     }
     pub fn accepts(&self, path: &Path, filename: &str, directory: bool) -> bool {
-        for file in &self.files {
+        // the rules of the deepest files have precedence
+        for file in self.files.iter().rev() {
             for rule in &file.rules {
                 if rule.directory && !directory {
                     continue;
@@ -137,31 +201,34 @@ impl GitIgnoreFilter {
     }
 }
 
-/// an iterator to find all applicable git_ignore files
+/// an iterator to find all applicable git_ignore files, from the
+/// directory up to the root of its repository (or of the disk)
 pub struct GitIgnoreFilesFinder<'a> {
-    dir: &'a Path,
+    dir: Option<&'a Path>,
+    git_root: Option<&'a Path>, // where to stop, when in a repository
 }
 impl<'a> GitIgnoreFilesFinder<'a> {
-    fn for_dir(dir: &'a Path) -> GitIgnoreFilesFinder<'a> {
-        GitIgnoreFilesFinder { dir }
+    fn for_dir(dir: &'a Path, git_root: Option<&'a Path>) -> GitIgnoreFilesFinder<'a> {
+        GitIgnoreFilesFinder {
+            dir: Some(dir),
+            git_root,
+        }
     }
 }
 impl<'a> Iterator for GitIgnoreFilesFinder<'a> {
     type Item = PathBuf; // I don't really see a way to deal with only &'a Path as join makes a PathBuf
     fn next(&mut self) -> Option<PathBuf> {
-        loop {
-            let ignore_file = self.dir.join(".gitignore");
-            match self.dir.parent() {
-                Some(parent) => {
-                    self.dir = parent;
-                    if ignore_file.exists() {
-                        return Some(ignore_file);
-                    }
-                }
-                None => {
-                    return None;
-                }
+        while let Some(dir) = self.dir {
+            self.dir = if Some(dir) == self.git_root {
+                None
+            } else {
+                dir.parent()
+            };
+            let ignore_file = dir.join(".gitignore");
+            if ignore_file.exists() {
+                return Some(ignore_file);
             }
         }
+        None
     }
 }
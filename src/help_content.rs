@@ -5,6 +5,10 @@ use minimad::{
 
 use crate::{
     app_context::AppContext,
+    patterns::Pattern,
+    selection_type::Selection,
+    tree_options::{FilterStrictness, FollowSymlinks, OptionBool, TreeOptions},
+    verbs::Verb,
 };
 
 static MD: &str = r#"
@@ -24,6 +28,10 @@ The mouse can be used to select (on click) or open (on double-click).
 ## Verbs
 
 To execute a verb, type a space or `:` then start of its name or shortcut.
+Typing some letters here searches the verbs and toggles.
+${filter
+Only the verbs and toggles matching the typed pattern are listed, hit `<esc>` to list them all.
+}
 |:-:|:-:|:-:|:-:
 |**name**|**shortcut**|**key**|**description**
 |-:|:-:|:-:|:-
@@ -31,6 +39,23 @@ ${verb-rows
 |${name}|${shortcut}|${key}|${description}`${execution}`
 }
 |-:
+${hidden-verbs
+Verbs not applying to ${selection}, thus not listed above:
+}
+${hidden-verb-rows
+* ${name}
+}
+
+## Toggles
+
+The values of the toggles in the tree you come from:
+|:-:|:-:|:-:
+|**name**|**shortcut**|**value**
+|-:|:-:|:-:
+${toggle-rows
+|${name}|${shortcut}|${value}
+}
+|-:
 
 ## Configuration
 
@@ -43,6 +68,7 @@ Some options can be set on launch:
 * `-f` or `--only-folders` : only show folders
 * `-s` or `--sizes` : display sizes
 * `-d` or `--dates` : display last modified dates
+* `--depth-first` : search by exploring each directory before its siblings
  (for the complete list, run `broot --help`)
 
 ## Flags
@@ -53,8 +79,76 @@ Flags are displayed at bottom right:
  When gitignore is auto, .gitignore rules are respected if the displayed root is a git repository or in one.
 "#;
 
-/// build the markdown which will be displayed in the help page
-pub fn build_text(con: &AppContext) -> Text<'_> {
+fn yes_no(b: bool) -> &'static str {
+    if b {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// return the current value of the option changed by a toggle verb,
+/// or None if the verb isn't a toggle
+fn toggle_value(verb: &Verb, options: &TreeOptions) -> Option<&'static str> {
+    Some(match verb.execution.as_ref() {
+        ":filter_strictness" => match options.filter_strictness {
+            FilterStrictness::Mixed => "mixed",
+            FilterStrictness::Dimmed => "dimmed",
+            FilterStrictness::Strict => "strict",
+        },
+        ":toggle_counts" => yes_no(options.show_counts),
+        ":toggle_dates" => yes_no(options.show_dates),
+        ":toggle_depth_first" => yes_no(options.depth_first),
+        ":toggle_files" => yes_no(!options.only_folders),
+        ":toggle_follow_symlinks" => match options.follow_symlinks {
+            FollowSymlinks::Never => "never",
+            FollowSymlinks::WhenExplicit => "when explicit",
+            FollowSymlinks::Always => "always",
+        },
+        ":toggle_git_ignore" => match options.respect_git_ignore {
+            OptionBool::Auto => "auto",
+            OptionBool::Yes => "yes",
+            OptionBool::No => "no",
+        },
+        ":toggle_grouping" => yes_no(options.grouping),
+        ":toggle_hidden" => yes_no(options.show_hidden),
+        ":toggle_inodes" => yes_no(options.show_inodes),
+        ":toggle_link_target_sizes" => yes_no(options.link_target_sizes),
+        ":toggle_perm" => yes_no(options.show_permissions),
+        ":toggle_show_all" => yes_no(options.show_all),
+        ":toggle_sizes" => yes_no(options.show_sizes),
+        ":toggle_trim_root" => yes_no(options.trim_root),
+        ":toggle_two_rows" => yes_no(options.two_rows),
+        ":toggle_xattr" => yes_no(options.show_xattrs),
+        _ => {
+            return None;
+        }
+    })
+}
+
+/// tell whether the verb must be listed when the user typed
+/// a pattern (its name, shortcut or description must match)
+fn matches(verb: &Verb, pattern: &Pattern) -> bool {
+    if !pattern.is_some() {
+        return true;
+    }
+    pattern.score_of(&verb.invocation.name).is_some()
+        || verb.shortcut.as_ref().map_or(false, |sk| pattern.score_of(sk).is_some())
+        || verb.description.as_ref().map_or(false, |d| pattern.score_of(d).is_some())
+}
+
+/// build the markdown which will be displayed in the help page.
+///
+/// Only the verbs applying to the selection and matching
+/// the pattern are listed in the table. The toggles are listed
+/// with their value in the given options.
+pub fn build_text<'s>(
+    con: &'s AppContext,
+    selection: &Selection,
+    selection_description: &'s str,
+    pattern: &Pattern,
+    options: &TreeOptions,
+) -> Text<'s> {
     lazy_static! {
         // this doesn't really matter, only half a ms is spared
         static ref TEMPLATE: TextTemplate<'static> = TextTemplate::from(MD);
@@ -63,7 +157,26 @@ pub fn build_text(con: &AppContext) -> Text<'_> {
     expander
         .set("version", env!("CARGO_PKG_VERSION"))
         .set("config-path", &con.config_path);
+    if pattern.is_some() {
+        expander.sub("filter");
+    }
+    let mut has_hidden_verbs = false;
     for verb in &con.verb_store.verbs {
+        if !matches(verb, pattern) {
+            continue;
+        }
+        if let Some(value) = toggle_value(verb, options) {
+            expander.sub("toggle-rows")
+                .set("name", &verb.invocation.name)
+                .set("shortcut", if let Some(sk) = &verb.shortcut { &sk } else { "" })
+                .set("value", value);
+        }
+        if !verb.applies_to(selection) {
+            has_hidden_verbs = true;
+            expander.sub("hidden-verb-rows")
+                .set("name", &verb.invocation.name);
+            continue;
+        }
         let sub = expander.sub("verb-rows")
             .set("name", &verb.invocation.name)
             .set(
@@ -79,5 +192,9 @@ pub fn build_text(con: &AppContext) -> Text<'_> {
             sub.set("execution", &verb.execution);
         }
     }
+    if has_hidden_verbs {
+        expander.sub("hidden-verbs")
+            .set("selection", selection_description);
+    }
     expander.expand()
 }
@@ -4,6 +4,7 @@ use crossterm::{
     queue,
     terminal::{Clear, ClearType},
 };
+use minimad::Composite;
 use termimad::{
     Area,
     FmtText,
@@ -14,13 +15,19 @@ use crate::{
     app_state::{AppState, AppStateCmdResult},
     app_context::AppContext,
     commands::{Action, Command},
-    conf::Conf,
+    conf::{self, Conf},
     errors::ProgramError,
+    flat_tree::Tree,
     help_content,
     io::W,
+    patterns::Pattern,
     screens::Screen,
+    selection_type::Selection,
+    session::Session,
+    state_history::Place,
     status::Status,
     task_sync::TaskLifetime,
+    tree_options::TreeOptions,
     verb_store::PrefixSearchResult,
     verbs::VerbExecutor,
 };
@@ -30,18 +37,37 @@ pub struct HelpState {
     pub scroll: i32, // scroll position
     pub area: Area,
     screen_size: (u16, u16), // kept to detect when the background should be cleared
+    selection: Selection, // the verbs not applying to it aren't listed
+    pattern: Pattern, // when set, only the matching verbs are listed
+    options: TreeOptions, // the options of the tree we come from, whose toggles are shown
 }
 
 impl HelpState {
-    pub fn new(_screen: &Screen, _con: & AppContext) -> HelpState {
+    pub fn new(
+        _screen: &Screen,
+        _con: &AppContext,
+        selection: Selection,
+        options: TreeOptions,
+    ) -> HelpState {
         let area = Area::uninitialized(); // will be fixed at drawing time
         HelpState {
             area,
             scroll: 0,
             screen_size: (0, 0),
+            selection,
+            pattern: Pattern::None,
+            options,
         }
     }
 
+    /// change the pattern filtering the verbs, the text being scrolled
+    /// back to its top
+    fn set_pattern(&mut self, pattern: Pattern) -> AppStateCmdResult {
+        self.pattern = pattern;
+        self.scroll = 0;
+        AppStateCmdResult::Keep
+    }
+
     /// return true when the screen area changed
     fn resize_area(&mut self, screen: &Screen) -> bool {
         if self.screen_size == (screen.width, screen.height) {
@@ -68,7 +94,31 @@ impl AppState for HelpState {
         con: &AppContext,
     ) -> Result<AppStateCmdResult, ProgramError> {
         Ok(match &cmd.action {
-            Action::Back => AppStateCmdResult::PopState,
+            Action::Back | Action::UpTree | Action::UpTreeFromRoot => {
+                if self.pattern.is_some() {
+                    cmd.raw.clear();
+                    self.set_pattern(Pattern::None)
+                } else {
+                    AppStateCmdResult::PopState
+                }
+            }
+            Action::FuzzyPatternEdit(pat) => {
+                if pat.is_empty() {
+                    self.set_pattern(Pattern::None)
+                } else {
+                    self.set_pattern(Pattern::fuzzy(pat, self.options.fuzzy_scoring))
+                }
+            }
+            Action::RegexEdit(pat, flags) => match Pattern::regex(pat, flags) {
+                Ok(pattern) => self.set_pattern(pattern),
+                Err(e) => AppStateCmdResult::DisplayError(format!("{}", e)),
+            },
+            Action::CompositePatternEdit(pat) => {
+                match Pattern::composite(pat, self.options.fuzzy_scoring) {
+                    Ok(pattern) => self.set_pattern(pattern),
+                    Err(e) => AppStateCmdResult::DisplayError(format!("{}", e)),
+                }
+            }
             Action::VerbIndex(index) => {
                 let verb = &con.verb_store.verbs[*index];
                 self.execute_verb(verb, &verb.invocation, screen, con)?
@@ -104,7 +154,14 @@ impl AppState for HelpState {
         if self.resize_area(screen) {
             screen.clear(w)?;
         }
-        let text = help_content::build_text(con);
+        let selection_description = self.selection.description();
+        let text = help_content::build_text(
+            con,
+            &self.selection,
+            &selection_description,
+            &self.pattern,
+            &self.options,
+        );
         let fmt_text = FmtText::from_text(&screen.help_skin, text, Some((self.area.width - 1) as usize));
         let mut text_view = TextView::from(&self.area, &fmt_text);
         self.scroll = text_view.set_scroll(self.scroll);
@@ -124,12 +181,24 @@ impl AppState for HelpState {
                     Status::from_error(mad_inline!("No matching verb")).display(w, screen)
                 }
                 PrefixSearchResult::Match(verb) => {
-                    verb.write_status(w, None, Conf::default_location(), invocation, screen)
+                    verb.write_status(
+                        w,
+                        None,
+                        Conf::default_location(),
+                        &conf::dir(),
+                        1,
+                        invocation,
+                        screen,
+                    )
                 }
                 PrefixSearchResult::TooManyMatches => Status::from_message(mad_inline!(
                     "Type a verb then *enter* to execute it"
                 )).display(w, screen),
             }
+            Action::PendingChord(first) => {
+                let hint = con.verb_store.chord_hint(*first);
+                Status::from_message(Composite::from_inline(&hint)).display(w, screen)
+            }
             _ => Status::from_message(mad_inline!(
                 "Hit *esc* to get back to the tree, or a space to start a verb"
             )).display(w, screen),
@@ -147,4 +216,17 @@ impl AppState for HelpState {
         queue!(w, Clear(ClearType::UntilNewLine))?;
         Ok(())
     }
+
+    /// the help isn't restored on resume
+    fn session(&self) -> Option<Session> {
+        None
+    }
+
+    fn place(&self) -> Option<Place> {
+        None
+    }
+
+    fn tree(&self) -> Option<&Tree> {
+        None
+    }
 }
@@ -4,6 +4,7 @@ use crate::{
     app_context::AppContext,
     app_state::AppStateCmdResult,
     browser_states::BrowserState,
+    clipboard_copy,
     commands::Command,
     conf::{self, Conf},
     errors::ProgramError,
@@ -29,6 +30,9 @@ impl VerbExecutor for HelpState {
         }
         Ok(match verb.execution.as_ref() {
             ":back" => AppStateCmdResult::PopState,
+            ":copy_path" => {
+                clipboard_copy::copy_to_clipboard(&Conf::default_location().to_string_lossy())?
+            }
             ":focus" | ":parent" => AppStateCmdResult::from_optional_state(
                 BrowserState::new(
                     conf::dir(),
@@ -58,11 +62,16 @@ impl VerbExecutor for HelpState {
             }
             ":print_path" => external::print_path(&Conf::default_location(), con)?,
             ":quit" => AppStateCmdResult::Quit,
-            ":focus_user_home" | ":focus_root" => AppStateCmdResult::PopStateAndReapply,
+            ":export_html" | ":filesystems" | ":focus_user_home" | ":focus_root" | ":goto"
+            | ":forward" | ":jobs" | ":newer" | ":preview" | ":redo" | ":undo" | ":write_tree" => {
+                AppStateCmdResult::PopStateAndReapply
+            }
             _ if verb.execution.starts_with(":toggle") => AppStateCmdResult::PopStateAndReapply,
             _ if verb.execution.starts_with(':') => AppStateCmdResult::Keep, // other internal verbs do nothing
             _ => verb.to_cmd_result(
                 &Conf::default_location(),
+                &conf::dir(),
+                1,
                 &invocation.args,
                 screen,
                 con,
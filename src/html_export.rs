@@ -0,0 +1,251 @@
+//! An export of the tree as a HTML page, where the skin is converted
+//! to CSS classes and the directories can be collapsed.
+
+use std::{io::Write, str::FromStr};
+
+use crossterm::style::{Attribute, Color};
+use termimad::CompoundStyle;
+
+use crate::{
+    color_support,
+    errors::ProgramError,
+    flat_tree::{LineType, Tree, TreeLine},
+    skin::Skin,
+};
+
+/// the format in which the tree is printed when leaving broot
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Html,
+}
+
+impl FromStr for OutputFormat {
+    type Err = ProgramError;
+    fn from_str(s: &str) -> Result<OutputFormat, ProgramError> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "html" => Ok(OutputFormat::Html),
+            _ => Err(ProgramError::ArgParse {
+                bad: s.to_string(),
+                valid: "text, html".to_string(),
+            }),
+        }
+    }
+}
+
+/// return the CSS value of a terminal color
+fn css_color(color: Color) -> String {
+    match color {
+        Color::Rgb { r, g, b } => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        Color::AnsiValue(v) => ansi_css_color(v),
+        Color::Black => ansi_css_color(0),
+        Color::DarkRed => ansi_css_color(1),
+        Color::DarkGreen => ansi_css_color(2),
+        Color::DarkYellow => ansi_css_color(3),
+        Color::DarkBlue => ansi_css_color(4),
+        Color::DarkMagenta => ansi_css_color(5),
+        Color::DarkCyan => ansi_css_color(6),
+        Color::Grey => ansi_css_color(7),
+        Color::DarkGrey => ansi_css_color(8),
+        Color::Red => ansi_css_color(9),
+        Color::Green => ansi_css_color(10),
+        Color::Yellow => ansi_css_color(11),
+        Color::Blue => ansi_css_color(12),
+        Color::Magenta => ansi_css_color(13),
+        Color::Cyan => ansi_css_color(14),
+        Color::White => ansi_css_color(15),
+        Color::Reset => "inherit".to_string(),
+    }
+}
+
+/// return the CSS value of one of the 256 colors of an ANSI terminal,
+/// as computed by xterm
+fn ansi_css_color(v: u8) -> String {
+    let (r, g, b) = color_support::ansi_rgb(v);
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// return the CSS declarations equivalent to a skin entry
+fn css_declarations(style: &CompoundStyle) -> String {
+    let mut css = String::new();
+    if let Some(fg) = style.get_fg() {
+        css.push_str(&format!("color: {}; ", css_color(fg)));
+    }
+    if let Some(bg) = style.get_bg() {
+        css.push_str(&format!("background-color: {}; ", css_color(bg)));
+    }
+    for attribute in &style.object_style.attributes {
+        css.push_str(match attribute {
+            Attribute::Bold => "font-weight: bold; ",
+            Attribute::Italic => "font-style: italic; ",
+            Attribute::Underlined => "text-decoration: underline; ",
+            Attribute::CrossedOut => "text-decoration: line-through; ",
+            _ => "",
+        });
+    }
+    css
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// writes a tree as a HTML page, each directory being a collapsible
+/// list of its children
+pub struct TreeHtmlRenderer<'s, 't> {
+    pub tree: &'t Tree,
+    pub skin: &'s Skin,
+}
+
+impl<'s, 't> TreeHtmlRenderer<'s, 't> {
+    pub fn new(tree: &'t Tree, skin: &'s Skin) -> TreeHtmlRenderer<'s, 't> {
+        TreeHtmlRenderer { tree, skin }
+    }
+
+    /// the skin entries used in the page, with their CSS class name
+    fn classes(&self) -> Vec<(&'static str, &CompoundStyle)> {
+        let skin = self.skin;
+        vec![
+            ("default", &skin.default),
+            ("tree", &skin.tree),
+            ("file", &skin.file),
+            ("directory", &skin.directory),
+            ("exe", &skin.exe),
+            ("link", &skin.link),
+            ("pruning", &skin.pruning),
+            ("permissions", &skin.permissions),
+            ("dates", &skin.dates),
+            ("counts", &skin.counts),
+            ("file_error", &skin.file_error),
+        ]
+    }
+
+    fn name_class(line: &TreeLine) -> &'static str {
+        match &line.line_type {
+            LineType::Dir => "directory",
+            LineType::File | LineType::Archive => {
+                if line.is_exe() {
+                    "exe"
+                } else {
+                    "file"
+                }
+            }
+            LineType::SymLinkToFile(_) | LineType::SymLinkToDir(_) => "link",
+            LineType::Pruning => "pruning",
+        }
+    }
+
+    fn write_style<F>(&self, f: &mut F) -> Result<(), ProgramError> where F: Write {
+        writeln!(f, "<style>")?;
+        for (name, style) in self.classes() {
+            writeln!(f, ".{} {{ {}}}", name, css_declarations(style))?;
+        }
+        writeln!(f, "body {{ font-family: monospace; }}")?;
+        writeln!(f, "ul {{ list-style: none; padding-left: 1.5em; margin: 0; }}")?;
+        writeln!(f, "summary {{ cursor: pointer; }}")?;
+        writeln!(f, "</style>")?;
+        Ok(())
+    }
+
+    /// write the columns before the name, as chosen in the tree options
+    fn write_columns<F>(&self, f: &mut F, line: &TreeLine) -> Result<(), ProgramError> where F: Write {
+        let options = &self.tree.options;
+        if options.show_sizes {
+            if let Some(size) = line.size {
+                write!(f, "<span class=\"tree\">{}</span> ", size.format(options.size_format))?;
+            }
+        }
+        if options.show_counts {
+            if let Some(c) = line.children_count {
+                write!(f, "<span class=\"counts\">{}/{}</span> ", c.direct, c.total)?;
+            }
+        }
+        #[cfg(unix)]
+        {
+            if options.show_permissions && line.is_selectable() {
+                write!(f, "<span class=\"permissions\">{}</span> ", line.mode())?;
+            }
+        }
+        if options.shows_dates() {
+            if let Ok(date) = line.metadata.date(options.displayed_date_kind()) {
+                write!(
+                    f,
+                    "<span class=\"dates\">{}</span> ",
+                    options.date_format.format(date),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_name<F>(&self, f: &mut F, line: &TreeLine) -> Result<(), ProgramError> where F: Write {
+        write!(
+            f,
+            "<span class=\"{}\">{}</span>",
+            TreeHtmlRenderer::name_class(line),
+            escape(&line.name),
+        )?;
+        match &line.line_type {
+            LineType::SymLinkToFile(target) | LineType::SymLinkToDir(target) => {
+                let class = if line.has_error {
+                    "file_error"
+                } else if line.is_dir() {
+                    "directory"
+                } else {
+                    "file"
+                };
+                write!(f, " -&gt; <span class=\"{}\">{}</span>", class, escape(target))?;
+            }
+            LineType::Dir if line.unlisted > 0 => {
+                write!(f, " …")?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn write_on<F>(&self, f: &mut F) -> Result<(), ProgramError> where F: Write {
+        let lines = &self.tree.lines;
+        let root = self.tree.root().to_string_lossy();
+        writeln!(f, "<!DOCTYPE html>")?;
+        writeln!(f, "<html>")?;
+        writeln!(f, "<head>")?;
+        writeln!(f, "<meta charset=\"utf-8\">")?;
+        writeln!(f, "<title>{}</title>", escape(&root))?;
+        self.write_style(f)?;
+        writeln!(f, "</head>")?;
+        writeln!(f, "<body class=\"default\">")?;
+        writeln!(f, "<div class=\"directory\">{}</div>", escape(&root))?;
+        writeln!(f, "<ul>")?;
+        for i in 1..lines.len() {
+            let line = &lines[i];
+            let next_depth = lines.get(i + 1).map_or(1, |next| next.depth);
+            write!(f, "<li>")?;
+            if next_depth > line.depth {
+                // the line has children, which can be hidden
+                write!(f, "<details open><summary>")?;
+                self.write_columns(f, line)?;
+                self.write_name(f, line)?;
+                writeln!(f, "</summary>")?;
+                writeln!(f, "<ul>")?;
+            } else {
+                self.write_columns(f, line)?;
+                self.write_name(f, line)?;
+                writeln!(f, "</li>")?;
+                // closing the lists of the directories we leave
+                for _ in next_depth..line.depth {
+                    writeln!(f, "</ul>")?;
+                    writeln!(f, "</details></li>")?;
+                }
+            }
+        }
+        writeln!(f, "</ul>")?;
+        writeln!(f, "</body>")?;
+        writeln!(f, "</html>")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+//! the icons written before the names when the `icons` option
+//! is set. They're glyphs of the Nerd Fonts, so the terminal must
+//! use one of those fonts.
+
+use crate::flat_tree::{LineType, TreeLine};
+
+const ICON_DIRECTORY: &str = "\u{f07b}";
+const ICON_LINK: &str = "\u{f0c1}";
+const ICON_FILE: &str = "\u{f15b}";
+
+/// return the icon of a file, depending on its extension
+fn file_icon(name: &str) -> &'static str {
+    let extension = match name.rfind('.') {
+        Some(idx) if idx > 0 => name[idx + 1..].to_ascii_lowercase(),
+        _ => return ICON_FILE,
+    };
+    match extension.as_ref() {
+        "rs" => "\u{e7a8}",
+        "md" => "\u{e73e}",
+        "py" => "\u{e73c}",
+        "js" | "mjs" => "\u{e74e}",
+        "ts" => "\u{e628}",
+        "go" => "\u{e626}",
+        "java" => "\u{e738}",
+        "c" | "h" => "\u{e61e}",
+        "cpp" | "cc" | "hpp" => "\u{e61d}",
+        "html" | "htm" => "\u{e736}",
+        "css" | "scss" => "\u{e749}",
+        "json" => "\u{e60b}",
+        "toml" | "yaml" | "yml" | "ini" | "conf" => "\u{e615}",
+        "sh" | "bash" | "zsh" | "fish" => "\u{f489}",
+        "lock" => "\u{f023}",
+        "txt" | "log" => "\u{f0f6}",
+        "pdf" => "\u{f1c1}",
+        "png" | "jpg" | "jpeg" | "gif" | "svg" | "bmp" | "webp" => "\u{f1c5}",
+        "zip" | "tar" | "gz" | "xz" | "bz2" | "7z" | "rar" => "\u{f1c6}",
+        "mp3" | "ogg" | "flac" | "wav" => "\u{f1c7}",
+        "mp4" | "mkv" | "avi" | "webm" => "\u{f1c8}",
+        _ => ICON_FILE,
+    }
+}
+
+/// return the icon of the line, which is followed by a space
+/// when written
+pub fn icon(line: &TreeLine) -> Option<&'static str> {
+    match &line.line_type {
+        LineType::Dir => Some(ICON_DIRECTORY),
+        LineType::File | LineType::Archive => Some(file_icon(&line.name)),
+        LineType::SymLinkToFile(_) | LineType::SymLinkToDir(_) => Some(ICON_LINK),
+        LineType::Pruning => None,
+    }
+}
@@ -0,0 +1,481 @@
+//! display of the images in the preview.
+//!
+//! Terminals displaying images are detected from the environment:
+//! kitty (its graphics protocol, for PNG files), iTerm2 and WezTerm
+//! (the iTerm2 inline images, for all formats they know), and the
+//! terminals known to understand sixel, the image being then converted
+//! by `img2sixel` (from libsixel) when it's installed.
+//! Everywhere else, and inside tmux or screen which don't pass those
+//! sequences, the preview describes the image: its format, its size
+//! and, for JPEG files, the camera and date found in the EXIF data.
+
+use std::{
+    env,
+    io::{self, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::clipboard_copy::base64;
+
+/// the size of the base64 chunks of the kitty graphics protocol
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+/// how the terminal may display images
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageProtocol {
+    Kitty,
+    Iterm,
+    Sixel,
+}
+
+lazy_static! {
+    static ref PROTOCOL: Option<ImageProtocol> = ImageProtocol::from_env(|name| env::var(name).ok());
+}
+
+impl ImageProtocol {
+    /// the protocol of the terminal broot runs in, if there's one
+    pub fn detect() -> Option<ImageProtocol> {
+        *PROTOCOL
+    }
+    fn from_env<F>(var: F) -> Option<ImageProtocol>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if var("TMUX").is_some() {
+            return None;
+        }
+        let term = var("TERM").unwrap_or_default();
+        if term.starts_with("screen") {
+            return None;
+        }
+        if term == "xterm-kitty" || var("KITTY_WINDOW_ID").is_some() {
+            return Some(ImageProtocol::Kitty);
+        }
+        match var("TERM_PROGRAM").as_deref() {
+            Some("iTerm.app") | Some("WezTerm") => return Some(ImageProtocol::Iterm),
+            _ => {}
+        }
+        if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm") {
+            return Some(ImageProtocol::Sixel);
+        }
+        None
+    }
+}
+
+/// the formats whose header is read
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Gif,
+    Jpeg,
+    Bmp,
+}
+
+impl ImageFormat {
+    fn of(bytes: &[u8]) -> Option<ImageFormat> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some(ImageFormat::Gif)
+        } else if bytes.starts_with(b"\xff\xd8\xff") {
+            Some(ImageFormat::Jpeg)
+        } else if bytes.starts_with(b"BM") && bytes.len() > 26 {
+            Some(ImageFormat::Bmp)
+        } else {
+            None
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "PNG",
+            ImageFormat::Gif => "GIF",
+            ImageFormat::Jpeg => "JPEG",
+            ImageFormat::Bmp => "BMP",
+        }
+    }
+}
+
+/// what's known of an image without decoding it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    pub dimensions: Option<(u32, u32)>, // width and height, in pixels
+    pub exif: Vec<(&'static str, String)>, // the EXIF fields worth displaying
+}
+
+fn be16(bytes: &[u8], idx: usize) -> Option<u32> {
+    bytes.get(idx..idx + 2).map(|b| u32::from(b[0]) << 8 | u32::from(b[1]))
+}
+
+fn le16(bytes: &[u8], idx: usize) -> Option<u32> {
+    bytes.get(idx..idx + 2).map(|b| u32::from(b[1]) << 8 | u32::from(b[0]))
+}
+
+fn be32(bytes: &[u8], idx: usize) -> Option<u32> {
+    Some(be16(bytes, idx)? << 16 | be16(bytes, idx + 2)?)
+}
+
+fn le32(bytes: &[u8], idx: usize) -> Option<u32> {
+    Some(le16(bytes, idx + 2)? << 16 | le16(bytes, idx)?)
+}
+
+impl ImageInfo {
+    /// read the header of an image, return None if it's not
+    /// an image of a known format
+    pub fn of(bytes: &[u8]) -> Option<ImageInfo> {
+        let format = ImageFormat::of(bytes)?;
+        let mut exif = Vec::new();
+        let dimensions = match format {
+            ImageFormat::Png => match (be32(bytes, 16), be32(bytes, 20)) {
+                (Some(w), Some(h)) => Some((w, h)),
+                _ => None,
+            },
+            ImageFormat::Gif => match (le16(bytes, 6), le16(bytes, 8)) {
+                (Some(w), Some(h)) => Some((w, h)),
+                _ => None,
+            },
+            ImageFormat::Bmp => match (le32(bytes, 18), le32(bytes, 22)) {
+                // the height is negative for top-down bitmaps
+                (Some(w), Some(h)) => Some((w, (h as i32).unsigned_abs())),
+                _ => None,
+            },
+            ImageFormat::Jpeg => read_jpeg(bytes, &mut exif),
+        };
+        Some(ImageInfo {
+            format,
+            dimensions,
+            exif,
+        })
+    }
+
+    /// the lines describing the image, when it's not displayed
+    pub fn description(&self, file_size: u64) -> Vec<String> {
+        let mut lines = Vec::new();
+        lines.push(match self.dimensions {
+            Some((w, h)) => format!("{} image, {}x{} pixels, {} bytes", self.format.name(), w, h, file_size),
+            None => format!("{} image, {} bytes", self.format.name(), file_size),
+        });
+        for (name, value) in &self.exif {
+            lines.push(format!("{}: {}", name, value));
+        }
+        lines
+    }
+}
+
+/// go through the segments of a JPEG file up to the frame header,
+/// which gives the dimensions, reading the EXIF segment on the way
+fn read_jpeg(bytes: &[u8], exif: &mut Vec<(&'static str, String)>) -> Option<(u32, u32)> {
+    let mut idx = 2;
+    loop {
+        if *bytes.get(idx)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(idx + 1)?;
+        let len = be16(bytes, idx + 2)? as usize;
+        match marker {
+            // the start of frame markers, except DHT, JPG and DAC
+            0xC0..=0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => {
+                let h = be16(bytes, idx + 5)?;
+                let w = be16(bytes, idx + 7)?;
+                return Some((w, h));
+            }
+            0xE1 => {
+                if let Some(segment) = bytes.get(idx + 4..idx + 2 + len) {
+                    if segment.starts_with(b"Exif\0\0") {
+                        read_exif(&segment[6..], exif);
+                    }
+                }
+            }
+            0xD9 | 0xDA => return None, // end of image or start of the scan
+            _ => {}
+        }
+        idx += 2 + len;
+    }
+}
+
+/// the EXIF tags which are displayed
+const EXIF_TAGS: &[(u32, &str)] = &[
+    (0x010F, "Make"),
+    (0x0110, "Model"),
+    (0x0132, "Date"),
+    (0x9003, "Taken"),
+];
+
+/// the tag of the pointer to the sub-IFD holding the shooting data
+const EXIF_IFD_POINTER: u32 = 0x8769;
+
+/// read the interesting ASCII fields of an EXIF block (a TIFF structure)
+fn read_exif(tiff: &[u8], exif: &mut Vec<(&'static str, String)>) {
+    let little_endian = match tiff.get(0..2) {
+        Some(b"II") => true,
+        Some(b"MM") => false,
+        _ => return,
+    };
+    let u16_at = |idx| if little_endian { le16(tiff, idx) } else { be16(tiff, idx) };
+    let u32_at = |idx| if little_endian { le32(tiff, idx) } else { be32(tiff, idx) };
+    let mut ifd = u32_at(4);
+    let mut visited_ifds = 0;
+    while let Some(ifd_offset) = ifd.take() {
+        visited_ifds += 1;
+        if visited_ifds > 2 {
+            break; // IFD0 and the EXIF sub-IFD, and no loop
+        }
+        let ifd_offset = ifd_offset as usize;
+        let count = match u16_at(ifd_offset) {
+            Some(count) => count as usize,
+            None => return,
+        };
+        for i in 0..count {
+            let entry = ifd_offset + 2 + 12 * i;
+            let (tag, kind, len) = match (u16_at(entry), u16_at(entry + 2), u32_at(entry + 4)) {
+                (Some(tag), Some(kind), Some(len)) => (tag, kind, len as usize),
+                _ => return,
+            };
+            if tag == EXIF_IFD_POINTER {
+                ifd = u32_at(entry + 8);
+                continue;
+            }
+            let name = match EXIF_TAGS.iter().find(|(t, _)| *t == tag) {
+                Some((_, name)) if kind == 2 => name,
+                _ => continue,
+            };
+            // the value is in the entry when it fits in 4 bytes
+            let start = if len <= 4 {
+                Some(entry + 8)
+            } else {
+                u32_at(entry + 8).map(|offset| offset as usize)
+            };
+            if let Some(value) = start.and_then(|start| tiff.get(start..start + len)) {
+                let value = String::from_utf8_lossy(value);
+                let value = value.trim_end_matches('\0').trim();
+                if !value.is_empty() {
+                    exif.push((name, value.to_string()));
+                }
+            }
+        }
+    }
+}
+
+/// the size in pixels of a cell of the terminal, if the terminal tells it
+#[cfg(unix)]
+fn cell_size() -> Option<(u32, u32)> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let res = unsafe { libc::ioctl(libc::STDERR_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if res != 0 || size.ws_col == 0 || size.ws_row == 0 || size.ws_xpixel == 0 {
+        return None;
+    }
+    Some((
+        u32::from(size.ws_xpixel) / u32::from(size.ws_col),
+        u32::from(size.ws_ypixel) / u32::from(size.ws_row),
+    ))
+}
+
+#[cfg(not(unix))]
+fn cell_size() -> Option<(u32, u32)> {
+    None
+}
+
+/// the cells (columns and rows) an image of the given dimensions takes
+/// when it's fit in the area, keeping its proportions and never
+/// enlarging it
+pub fn fit(dimensions: (u32, u32), max_cols: u16, max_rows: u16) -> (u16, u16) {
+    let (cell_width, cell_height) = cell_size().unwrap_or((8, 16));
+    fit_with_cells(dimensions, (cell_width, cell_height), max_cols, max_rows)
+}
+
+fn fit_with_cells(
+    (width, height): (u32, u32),
+    (cell_width, cell_height): (u32, u32),
+    max_cols: u16,
+    max_rows: u16,
+) -> (u16, u16) {
+    let (width, height) = (u64::from(width.max(1)), u64::from(height.max(1)));
+    let (cell_width, cell_height) = (u64::from(cell_width.max(1)), u64::from(cell_height.max(1)));
+    let (max_cols, max_rows) = (u64::from(max_cols.max(1)), u64::from(max_rows.max(1)));
+    // the scale is the smallest of the ones fitting the width and the height
+    let mut cols = ((width + cell_width - 1) / cell_width).min(max_cols);
+    let mut rows = (cols * cell_width * height / width + cell_height - 1) / cell_height;
+    if rows > max_rows {
+        rows = max_rows;
+        cols = (rows * cell_height * width / height / cell_width).max(1);
+    }
+    (cols as u16, rows.max(1) as u16)
+}
+
+/// build the sequences displaying a PNG image with the kitty graphics
+/// protocol, in the given number of cells
+pub fn kitty_sequence(png: &[u8], cols: u16, rows: u16) -> String {
+    let encoded = base64(png);
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+    let mut seq = String::with_capacity(encoded.len() + chunks.len() * 16 + 32);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        seq.push_str("\x1b_G");
+        if i == 0 {
+            // q=2 so that the terminal doesn't answer on our input
+            seq.push_str(&format!("a=T,f=100,q=2,C=1,c={},r={},", cols, rows));
+        }
+        seq.push_str(&format!("m={};", more));
+        // base64 is ASCII, so the chunks are valid strings
+        seq.push_str(std::str::from_utf8(chunk).unwrap());
+        seq.push_str("\x1b\\");
+    }
+    seq
+}
+
+/// the sequence removing all the images displayed with the kitty protocol
+pub const KITTY_ERASE: &str = "\x1b_Ga=d,q=2\x1b\\";
+
+/// build the sequence displaying an image with the iTerm2 protocol
+pub fn iterm_sequence(bytes: &[u8], cols: u16, rows: u16) -> String {
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=1:{}\x07",
+        bytes.len(),
+        cols,
+        rows,
+        base64(bytes),
+    )
+}
+
+/// convert the image to sixel with img2sixel, fitting the given cells
+pub fn sixel_sequence(path: &Path, cols: u16, rows: u16) -> io::Result<Vec<u8>> {
+    let (cell_width, cell_height) = cell_size().unwrap_or((8, 16));
+    let output = Command::new("img2sixel")
+        .arg("-w")
+        .arg((u32::from(cols) * cell_width).to_string())
+        .arg("-h")
+        .arg((u32::from(rows) * cell_height).to_string())
+        .arg(path)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::Other, "img2sixel failed"));
+    }
+    Ok(output.stdout)
+}
+
+/// build the bytes displaying the image in the given cells with the
+/// protocol, or None when the protocol can't display this image
+pub fn sequence(
+    protocol: ImageProtocol,
+    format: ImageFormat,
+    bytes: &[u8],
+    path: &Path,
+    cols: u16,
+    rows: u16,
+) -> Option<Vec<u8>> {
+    match protocol {
+        // kitty would need the image to be converted to anything else than PNG
+        ImageProtocol::Kitty if format == ImageFormat::Png => {
+            Some(kitty_sequence(bytes, cols, rows).into_bytes())
+        }
+        ImageProtocol::Kitty => None,
+        ImageProtocol::Iterm => Some(iterm_sequence(bytes, cols, rows).into_bytes()),
+        ImageProtocol::Sixel => match sixel_sequence(path, cols, rows) {
+            Ok(seq) => Some(seq),
+            Err(e) => {
+                debug!("no sixel conversion of {:?}: {:?}", path, e);
+                None
+            }
+        },
+    }
+}
+
+/// erase the images displayed by the preview, when the terminal
+/// keeps them apart from the text
+pub fn erase_images<W: Write>(w: &mut W) -> io::Result<()> {
+    if ImageProtocol::detect() == Some(ImageProtocol::Kitty) {
+        w.write_all(KITTY_ERASE.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod image_preview_tests {
+
+    use super::*;
+
+    fn env_of(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |name| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+        }
+    }
+
+    #[test]
+    fn check_protocol_detection() {
+        let detect = |vars| ImageProtocol::from_env(env_of(vars));
+        assert_eq!(detect(&[("TERM", "xterm-kitty")]), Some(ImageProtocol::Kitty));
+        assert_eq!(detect(&[("TERM", "xterm-256color"), ("TERM_PROGRAM", "iTerm.app")]), Some(ImageProtocol::Iterm));
+        assert_eq!(detect(&[("TERM", "foot")]), Some(ImageProtocol::Sixel));
+        assert_eq!(detect(&[("TERM", "xterm-256color")]), None);
+        assert_eq!(detect(&[("TERM", "xterm-kitty"), ("TMUX", "/tmp/tmux-1000/default,1,0")]), None);
+        assert_eq!(detect(&[("TERM", "screen-256color"), ("KITTY_WINDOW_ID", "1")]), None);
+    }
+
+    #[test]
+    fn check_png_and_gif_dimensions() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&[0, 0, 3, 32, 0, 0, 2, 88]); // 800x600
+        let info = ImageInfo::of(&png).unwrap();
+        assert_eq!(info.format, ImageFormat::Png);
+        assert_eq!(info.dimensions, Some((800, 600)));
+        let gif = b"GIF89a\x40\x01\xf0\x00";
+        assert_eq!(ImageInfo::of(gif).unwrap().dimensions, Some((320, 240)));
+        assert_eq!(ImageInfo::of(b"hello"), None);
+    }
+
+    #[test]
+    fn check_jpeg_exif() {
+        // a big endian TIFF structure with the Model in IFD0, then
+        // the EXIF sub-IFD with the date the picture was taken
+        let mut tiff = b"MM\0\x2a\0\0\0\x08".to_vec();
+        tiff.extend_from_slice(&[0, 2]); // 2 entries
+        tiff.extend_from_slice(&[0x01, 0x10, 0, 2, 0, 0, 0, 4]); // Model, ASCII, 4 bytes
+        tiff.extend_from_slice(b"X10\0");
+        tiff.extend_from_slice(&[0x87, 0x69, 0, 4, 0, 0, 0, 1, 0, 0, 0, 38]); // the sub-IFD
+        tiff.extend_from_slice(&[0, 0, 0, 0]); // no next IFD
+        assert_eq!(tiff.len(), 38);
+        tiff.extend_from_slice(&[0, 1]);
+        tiff.extend_from_slice(&[0x90, 0x03, 0, 2, 0, 0, 0, 20, 0, 0, 0, 56]);
+        tiff.extend_from_slice(&[0, 0, 0, 0]);
+        assert_eq!(tiff.len(), 56);
+        tiff.extend_from_slice(b"2020:02:29 10:20:30\0");
+        let mut jpeg = b"\xff\xd8\xff\xe1".to_vec();
+        let len = 2 + 6 + tiff.len();
+        jpeg.extend_from_slice(&[(len >> 8) as u8, len as u8]);
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(&tiff);
+        jpeg.extend_from_slice(&[0xff, 0xc0, 0, 17, 8, 0, 120, 0, 160]); // 160x120
+        let info = ImageInfo::of(&jpeg).unwrap();
+        assert_eq!(info.format, ImageFormat::Jpeg);
+        assert_eq!(info.dimensions, Some((160, 120)));
+        assert_eq!(
+            info.exif,
+            vec![("Model", "X10".to_string()), ("Taken", "2020:02:29 10:20:30".to_string())],
+        );
+    }
+
+    #[test]
+    fn check_fit() {
+        // a small image isn't enlarged
+        assert_eq!(fit_with_cells((80, 32), (8, 16), 100, 40), (10, 2));
+        // a wide one takes the whole width
+        assert_eq!(fit_with_cells((1600, 400), (8, 16), 100, 40), (100, 13));
+        // a tall one takes the whole height
+        assert_eq!(fit_with_cells((400, 1600), (8, 16), 100, 40), (20, 40));
+    }
+
+    #[test]
+    fn check_kitty_chunks() {
+        let seq = kitty_sequence(&[0; 6000], 10, 5);
+        // 6000 bytes are 8000 base64 chars, sent in two chunks
+        assert!(seq.starts_with("\x1b_Ga=T,f=100,q=2,C=1,c=10,r=5,m=1;AAAA"));
+        assert_eq!(seq.matches("\x1b_G").count(), 2);
+        assert!(seq.contains("\x1b\\\x1b_Gm=0;AAAA"));
+        assert!(seq.ends_with("\x1b\\"));
+    }
+}
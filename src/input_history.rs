@@ -0,0 +1,83 @@
+//! the history of the verb invocations typed in the input.
+//!
+//! After the verb separator (`:` or a space), the up and down keys
+//! recall the previous invocations instead of moving the selection.
+//!
+//! The history is kept in memory, read from a file of the data
+//! directory when broot starts and written there when it quits,
+//! one invocation per line.
+
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use crate::conf;
+
+/// the number of invocations kept
+const CAPACITY: usize = 500;
+
+lazy_static! {
+    // the oldest invocation first
+    static ref HISTORY_MUTEX: Mutex<Vec<String>> = Mutex::new(Vec::new());
+}
+
+/// return the path to the file in which the history is saved
+pub fn file_path() -> PathBuf {
+    conf::data_dir().join("history.txt")
+}
+
+/// read the saved history. A missing or unreadable file gives
+/// an empty history
+pub fn load() {
+    let entries: Vec<String> = fs::read_to_string(file_path())
+        .map(|data| data.lines().map(String::from).collect())
+        .unwrap_or_default();
+    *HISTORY_MUTEX.lock().unwrap() = entries;
+}
+
+pub fn save() -> Result<(), io::Error> {
+    let history = HISTORY_MUTEX.lock().unwrap();
+    let mut data = String::new();
+    for entry in history.iter() {
+        data.push_str(entry);
+        data.push('\n');
+    }
+    fs::create_dir_all(conf::data_dir())?;
+    fs::write(file_path(), data)
+}
+
+/// add an invocation as the most recent one, removing
+/// its previous occurence if any
+pub fn add(entry: &str) {
+    let entry = entry.trim();
+    if entry.is_empty() || entry.contains('\n') {
+        return;
+    }
+    let mut history = HISTORY_MUTEX.lock().unwrap();
+    history.retain(|e| e != entry);
+    history.push(entry.to_string());
+    if history.len() > CAPACITY {
+        let excess = history.len() - CAPACITY;
+        history.drain(..excess);
+    }
+}
+
+/// return the invocation typed `back` invocations ago, 1
+/// being the most recent one
+pub fn recall(back: usize) -> Option<String> {
+    let history = HISTORY_MUTEX.lock().unwrap();
+    if back == 0 || back > history.len() {
+        None
+    } else {
+        Some(history[history.len() - back].clone())
+    }
+}
+
+/// where the user is in the history, while browsing it
+#[derive(Debug, Clone)]
+pub struct HistoryCursor {
+    pub base: String, // the input before the recalled invocation (pattern and separator)
+    pub back: usize,  // 0 when the input is back to what was typed
+}
@@ -0,0 +1,126 @@
+//! the programs launched by the verbs with `async = true`: they run
+//! in the background while the user goes on browsing, and they can be
+//! listed and killed in the jobs screen.
+//!
+//! The output of those programs is discarded, as it would mess the screen.
+use std::{
+    io,
+    process::Child,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// how long the watcher waits between two checks of the
+/// running jobs
+pub static JOBS_POLL_PERIOD: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobStatus {
+    Running,
+    Finished(Option<i32>), // the exit code, if the program wasn't killed by a signal
+    Killed,                // killed from broot
+}
+
+/// what's displayed of a job
+#[derive(Debug, Clone)]
+pub struct JobInfo {
+    pub id: usize,
+    pub command: String, // the executed command, as it would be typed in a shell
+    pub status: JobStatus,
+    pub started: Instant,
+}
+
+struct Job {
+    info: JobInfo,
+    child: Child,
+}
+
+struct Jobs {
+    list: Vec<Job>,
+    next_id: usize,
+}
+
+lazy_static! {
+    static ref JOBS_MUTEX: Mutex<Jobs> = Mutex::new(Jobs {
+        list: Vec::new(),
+        next_id: 1,
+    });
+}
+
+/// register a started program, and return the id of the new job
+pub fn add(command: String, child: Child) -> usize {
+    let mut jobs = JOBS_MUTEX.lock().unwrap();
+    let id = jobs.next_id;
+    jobs.next_id += 1;
+    jobs.list.push(Job {
+        info: JobInfo {
+            id,
+            command,
+            status: JobStatus::Running,
+            started: Instant::now(),
+        },
+        child,
+    });
+    id
+}
+
+/// check whether the running jobs ended, and return true when
+/// at least one of them did
+pub fn poll() -> bool {
+    let mut jobs = JOBS_MUTEX.lock().unwrap();
+    let mut changed = false;
+    for job in jobs.list.iter_mut() {
+        if job.info.status != JobStatus::Running {
+            continue;
+        }
+        match job.child.try_wait() {
+            Ok(Some(exit_status)) => {
+                debug!("job {} finished with {:?}", job.info.id, exit_status);
+                job.info.status = JobStatus::Finished(exit_status.code());
+                changed = true;
+            }
+            Ok(None) => {} // still running
+            Err(e) => {
+                warn!("can't check job {}: {:?}", job.info.id, e);
+                job.info.status = JobStatus::Finished(None);
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/// kill the job if it's running. Return true when it was
+pub fn kill(id: usize) -> io::Result<bool> {
+    let mut jobs = JOBS_MUTEX.lock().unwrap();
+    for job in jobs.list.iter_mut() {
+        if job.info.id == id && job.info.status == JobStatus::Running {
+            job.child.kill()?;
+            let _ = job.child.wait(); // so that it's not left as a zombie
+            job.info.status = JobStatus::Killed;
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+pub fn running_count() -> usize {
+    JOBS_MUTEX
+        .lock()
+        .unwrap()
+        .list
+        .iter()
+        .filter(|job| job.info.status == JobStatus::Running)
+        .count()
+}
+
+/// return the jobs, the oldest first
+pub fn all() -> Vec<JobInfo> {
+    JOBS_MUTEX
+        .lock()
+        .unwrap()
+        .list
+        .iter()
+        .map(|job| job.info.clone())
+        .collect()
+}
@@ -0,0 +1,250 @@
+use std::io::Write;
+
+use crossterm::{
+    cursor,
+    queue,
+    style::{Color, SetBackgroundColor},
+    terminal::{Clear, ClearType},
+};
+use minimad::Composite;
+
+use crate::{
+    app_context::AppContext,
+    app_state::{AppState, AppStateCmdResult},
+    commands::{Action, Command},
+    conf::{self, Conf},
+    errors::ProgramError,
+    flat_tree::Tree,
+    io::W,
+    jobs::{self, JobStatus},
+    screens::Screen,
+    session::Session,
+    state_history::Place,
+    status::Status,
+    task_sync::TaskLifetime,
+    verb_store::PrefixSearchResult,
+    verbs::VerbExecutor,
+};
+
+/// an application state listing the programs launched in the
+/// background by the verbs with `async = true`
+pub struct JobsState {
+    pub selection: usize, // the index of the selected job
+    scroll: usize,        // the number of jobs hidden at the top
+}
+
+impl JobsState {
+    pub fn new() -> JobsState {
+        // the last launched job is selected
+        JobsState {
+            selection: jobs::all().len().saturating_sub(1),
+            scroll: 0,
+        }
+    }
+
+    /// the number of jobs which can be displayed, below the header
+    fn page_height(screen: &Screen) -> usize {
+        (screen.height as usize).saturating_sub(3)
+    }
+
+    pub fn move_selection(&mut self, dy: i32, screen: &Screen) {
+        let len = jobs::all().len() as i32;
+        if len == 0 {
+            return;
+        }
+        self.selection = (self.selection as i32 + dy).max(0).min(len - 1) as usize;
+        let page_height = JobsState::page_height(screen);
+        if self.selection < self.scroll {
+            self.scroll = self.selection;
+        } else if page_height > 0 && self.selection >= self.scroll + page_height {
+            self.scroll = self.selection + 1 - page_height;
+        }
+    }
+
+    /// kill the selected job, if it's still running
+    pub fn kill_selection(&self) -> AppStateCmdResult {
+        match jobs::all().get(self.selection) {
+            Some(job) => match jobs::kill(job.id) {
+                Ok(true) => AppStateCmdResult::Keep,
+                Ok(false) => AppStateCmdResult::DisplayError("this job isn't running".to_string()),
+                Err(e) => AppStateCmdResult::DisplayError(format!("can't kill the job: {}", e)),
+            },
+            None => AppStateCmdResult::DisplayError("no job selected".to_string()),
+        }
+    }
+}
+
+fn status_desc(status: JobStatus) -> String {
+    match status {
+        JobStatus::Running => "running".to_string(),
+        JobStatus::Finished(Some(0)) => "done".to_string(),
+        JobStatus::Finished(Some(code)) => format!("failed ({})", code),
+        JobStatus::Finished(None) => "ended".to_string(),
+        JobStatus::Killed => "killed".to_string(),
+    }
+}
+
+impl AppState for JobsState {
+
+    fn has_pending_task(&self) -> bool {
+        false
+    }
+
+    fn apply(
+        &mut self,
+        cmd: &mut Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<AppStateCmdResult, ProgramError> {
+        Ok(match &cmd.action {
+            Action::Back | Action::UpTree | Action::UpTreeFromRoot => AppStateCmdResult::PopState,
+            Action::MoveSelection(dy) => {
+                self.move_selection(*dy, screen);
+                AppStateCmdResult::Keep
+            }
+            Action::Click(_, y) | Action::DoubleClick(_, y) => {
+                // the first line is the header
+                let idx = self.scroll + *y as usize;
+                if *y > 0 && idx <= jobs::all().len() {
+                    self.selection = idx - 1;
+                }
+                AppStateCmdResult::Keep
+            }
+            Action::VerbIndex(index) => {
+                let verb = &con.verb_store.verbs[*index];
+                self.execute_verb(verb, &verb.invocation, screen, con)?
+            }
+            Action::VerbInvocate(invocation) => match con.verb_store.search(&invocation.name) {
+                PrefixSearchResult::Match(verb) => {
+                    self.execute_verb(verb, &invocation, screen, con)?
+                }
+                _ => AppStateCmdResult::verb_not_found(&invocation.name),
+            },
+            _ => AppStateCmdResult::Keep,
+        })
+    }
+
+    fn refresh(&mut self, screen: &Screen, _con: &AppContext) -> Command {
+        self.move_selection(0, screen);
+        Command::new()
+    }
+
+    fn do_pending_task(&mut self, _screen: &mut Screen, _tl: &TaskLifetime) {
+        unreachable!();
+    }
+
+    fn display(
+        &mut self,
+        w: &mut W,
+        screen: &Screen,
+        _con: &AppContext
+    ) -> Result<(), ProgramError> {
+        let skin = &screen.skin;
+        let jobs = jobs::all();
+        let status_width = 11;
+        queue!(w, cursor::MoveTo(0, 0))?;
+        skin.tree.queue(w, format!(
+            "{:>4} {:<sw$} {:>7} command",
+            "job", "status", "time",
+            sw = status_width,
+        ))?;
+        queue!(w, Clear(ClearType::UntilNewLine))?;
+        let page_height = JobsState::page_height(screen);
+        for y in 0..page_height {
+            queue!(w, cursor::MoveTo(0, y as u16 + 1))?;
+            let idx = self.scroll + y;
+            let selected = idx == self.selection;
+            if let Some(job) = jobs.get(idx) {
+                if selected {
+                    skin.selected_line.queue_bg(w)?;
+                }
+                let mut counts_style = skin.counts.clone();
+                let mut command_style = skin.file.clone();
+                if selected {
+                    if let Some(c) = skin.selected_line.get_bg() {
+                        counts_style.set_bg(c);
+                        command_style.set_bg(c);
+                    }
+                }
+                counts_style.queue(w, format!(
+                    "{:>4} {:<sw$} {:>6}s ",
+                    job.id,
+                    status_desc(job.status),
+                    job.started.elapsed().as_secs(),
+                    sw = status_width,
+                ))?;
+                command_style.queue(w, &job.command)?;
+            }
+            if selected {
+                skin.selected_line.queue_bg(w)?;
+            } else {
+                skin.default.queue_bg(w)?;
+            }
+            queue!(w, Clear(ClearType::UntilNewLine))?;
+            queue!(w, SetBackgroundColor(Color::Reset))?;
+        }
+        Ok(())
+    }
+
+    fn write_status(
+        &self,
+        w: &mut W,
+        cmd: &Command,
+        screen: &Screen,
+        con: &AppContext,
+    ) -> Result<(), ProgramError> {
+        match &cmd.action {
+            Action::VerbEdit(invocation) => match con.verb_store.search(&invocation.name) {
+                PrefixSearchResult::NoMatch => {
+                    Status::from_error(mad_inline!("No matching verb")).display(w, screen)
+                }
+                PrefixSearchResult::Match(verb) => {
+                    verb.write_status(
+                        w,
+                        None,
+                        Conf::default_location(),
+                        &conf::dir(),
+                        1,
+                        invocation,
+                        screen,
+                    )
+                }
+                PrefixSearchResult::TooManyMatches => Status::from_message(mad_inline!(
+                    "Type a verb then *enter* to execute it"
+                )).display(w, screen),
+            }
+            Action::PendingChord(first) => {
+                let hint = con.verb_store.chord_hint(*first);
+                Status::from_message(Composite::from_inline(&hint)).display(w, screen)
+            }
+            _ => Status::from_message(mad_inline!(
+                "Type *:kill_job* to kill the selected job, *esc* to get back to the tree"
+            )).display(w, screen),
+        }
+    }
+
+    /// there's no meaningful flags here
+    fn write_flags(
+        &self,
+        w: &mut W,
+        screen: &mut Screen,
+        _con: &AppContext
+    ) -> Result<(), ProgramError> {
+        screen.skin.default.queue_bg(w)?;
+        queue!(w, Clear(ClearType::UntilNewLine))?;
+        Ok(())
+    }
+
+    /// the list of jobs isn't restored on resume
+    fn session(&self) -> Option<Session> {
+        None
+    }
+
+    fn place(&self) -> Option<Place> {
+        None
+    }
+
+    fn tree(&self) -> Option<&Tree> {
+        None
+    }
+}
@@ -0,0 +1,62 @@
+//! this modules defines the execution of verbs on the jobs screen
+
+use crate::{
+    app_context::AppContext,
+    app_state::AppStateCmdResult,
+    commands::Command,
+    errors::ProgramError,
+    help_states::HelpState,
+    jobs_states::JobsState,
+    screens::Screen,
+    selection_type::{Selection, SelectionType},
+    tree_options::TreeOptions,
+    verb_invocation::VerbInvocation,
+    verbs::{Verb, VerbExecutor},
+};
+
+impl VerbExecutor for JobsState {
+    fn execute_verb(
+        &mut self,
+        verb: &Verb,
+        invocation: &VerbInvocation,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<AppStateCmdResult, ProgramError> {
+        if let Some(err) = verb.match_error(invocation) {
+            return Ok(AppStateCmdResult::DisplayError(err));
+        }
+        Ok(match verb.execution.as_ref() {
+            ":back" => AppStateCmdResult::PopState,
+            ":help" => AppStateCmdResult::NewState(
+                Box::new(HelpState::new(
+                    screen,
+                    con,
+                    Selection::of_type(SelectionType::Any),
+                    TreeOptions::default(),
+                )),
+                Command::new(),
+            ),
+            ":jobs" | ":refresh" => AppStateCmdResult::Keep,
+            ":kill_job" => self.kill_selection(),
+            ":line_down" => {
+                self.move_selection(1, screen);
+                AppStateCmdResult::Keep
+            }
+            ":line_up" => {
+                self.move_selection(-1, screen);
+                AppStateCmdResult::Keep
+            }
+            ":page_down" => {
+                self.move_selection(screen.height as i32 - 3, screen);
+                AppStateCmdResult::Keep
+            }
+            ":page_up" => {
+                self.move_selection(3 - screen.height as i32, screen);
+                AppStateCmdResult::Keep
+            }
+            ":quit" => AppStateCmdResult::Quit,
+            // the other verbs apply to the tree
+            _ => AppStateCmdResult::PopStateAndReapply,
+        })
+    }
+}
@@ -9,40 +9,98 @@ extern crate lazy_regex;
 
 pub mod app;
 pub mod app_context;
+pub mod app_events;
 pub mod app_state;
+pub mod archives;
+pub mod bookmarks;
 pub mod browser_states;
 pub mod browser_verbs;
 pub mod cli;
+pub mod clipboard_copy;
+pub mod color_support;
+pub mod column_widths;
 pub mod command_parsing;
 pub mod commands;
+pub mod composite_patterns;
 pub mod conf;
+pub mod conf_watcher;
+pub mod control_chars;
+pub mod date_format;
+pub mod diff_states;
+pub mod diff_verbs;
+pub mod dir_counts;
+pub mod dir_prelisting;
 pub mod displayable_tree;
 pub mod external;
+pub mod file_filters;
 pub mod file_sizes;
+pub mod filesystems;
+pub mod filesystems_states;
+pub mod filesystems_verbs;
 pub mod flat_tree;
+pub mod fs_watcher;
+pub mod get_path;
 pub mod git_ignore;
 pub mod help_content;
 pub mod help_states;
 pub mod help_verbs;
+pub mod html_export;
+pub mod icons;
+pub mod image_preview;
+pub mod input_history;
 pub mod io;
+pub mod jobs;
+pub mod jobs_states;
+pub mod jobs_verbs;
+pub mod line_editing;
 pub mod mad_skin;
+pub mod marks;
 pub mod matched_string;
+pub mod os_names;
+pub mod output_states;
+pub mod output_verbs;
+pub mod path_case;
+pub mod path_completion;
+pub mod path_filters;
 pub mod permissions;
+pub mod preview_states;
+pub mod preview_verbs;
+pub mod project_conf;
 pub mod screens;
+pub mod selection_info;
+pub mod selection_type;
+pub mod server;
+pub mod session;
 pub mod shell_bash;
+pub mod shell_cmd;
 pub mod shell_fish;
 pub mod shell_install;
+pub mod shell_nushell;
+pub mod shell_powershell;
+pub mod size_bars;
 pub mod skin;
 pub mod skin_conf;
+pub mod snapshots;
+pub mod state_history;
 pub mod status;
+pub mod stdout_export;
+pub mod syntax_highlighting;
 pub mod task_sync;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
 pub mod tree_build;
+pub mod tree_diff;
 pub mod tree_options;
+pub mod tree_sources;
+pub mod verb_completion;
 pub mod verb_conf;
 pub mod verb_invocation;
 pub mod verb_store;
 pub mod verbs;
+pub mod xattrs;
 pub mod errors;
 pub mod fuzzy_patterns;
+pub mod fuzzy_scoring;
 pub mod patterns;
 pub mod regex_patterns;
+pub mod remote;
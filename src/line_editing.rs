@@ -0,0 +1,246 @@
+//! the line editing keys of the input, on top of the ones the input
+//! field of termimad handles (chars, arrows, home, end, delete):
+//!
+//! * alt-b and alt-f move the cursor to the previous or next word
+//! * ctrl-w kills the word before the cursor, ctrl-u the start of
+//!   the line and ctrl-k its end
+//! * ctrl-y yanks the last killed text, and alt-y, just after, replaces
+//!   it with the text killed before
+//!
+//! The killed texts are kept in a ring shared by all the inputs.
+
+use std::{collections::VecDeque, sync::Mutex};
+
+use crossterm::input::KeyEvent;
+use termimad::{Event, InputField};
+
+/// the number of killed texts kept
+const KILL_RING_CAPACITY: usize = 30;
+
+/// the keys handled here, the other ones being left to the input field
+const EDITING_KEYS: [KeyEvent; 7] = [
+    KeyEvent::Alt('b'),
+    KeyEvent::Alt('f'),
+    KeyEvent::Ctrl('w'),
+    KeyEvent::Ctrl('u'),
+    KeyEvent::Ctrl('k'),
+    KeyEvent::Ctrl('y'),
+    KeyEvent::Alt('y'),
+];
+
+lazy_static! {
+    // the most recently killed text first
+    static ref KILL_RING_MUTEX: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+}
+
+fn kill(text: String) {
+    if text.is_empty() {
+        return;
+    }
+    let mut ring = KILL_RING_MUTEX.lock().unwrap();
+    ring.push_front(text);
+    ring.truncate(KILL_RING_CAPACITY);
+}
+
+fn killed(index: usize) -> Option<String> {
+    KILL_RING_MUTEX.lock().unwrap().get(index).cloned()
+}
+
+/// the text inserted by the last yank, which alt-y may replace
+#[derive(Debug, Clone, Copy)]
+pub struct Yank {
+    start: usize, // in chars
+    len: usize,
+    index: usize, // in the kill ring
+}
+
+/// the content of the input, with the position of the cursor in chars
+#[derive(Debug, Clone, PartialEq)]
+struct Line {
+    chars: Vec<char>,
+    cursor: usize,
+}
+
+impl Line {
+    fn of(input_field: &mut InputField) -> Line {
+        // the input field doesn't tell where its cursor is, so it's
+        // found by counting the moves to the end of the content
+        let chars: Vec<char> = input_field.get_content().chars().collect();
+        let right = Event::Key(KeyEvent::Right);
+        let mut moves = 0;
+        while moves < chars.len() && input_field.apply_event(&right) {
+            moves += 1;
+        }
+        Line {
+            cursor: chars.len() - moves,
+            chars,
+        }
+    }
+    fn write_to(&self, input_field: &mut InputField) {
+        input_field.set_content(&self.chars.iter().collect::<String>());
+        input_field.apply_event(&Event::Key(KeyEvent::End));
+        let left = Event::Key(KeyEvent::Left);
+        for _ in self.cursor..self.chars.len() {
+            input_field.apply_event(&left);
+        }
+    }
+    /// the start of the word before the cursor, words being made
+    /// of alphanumeric chars
+    fn word_start(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && !self.chars[i - 1].is_alphanumeric() {
+            i -= 1;
+        }
+        while i > 0 && self.chars[i - 1].is_alphanumeric() {
+            i -= 1;
+        }
+        i
+    }
+    /// the end of the word after the cursor
+    fn word_end(&self) -> usize {
+        let mut i = self.cursor;
+        while i < self.chars.len() && !self.chars[i].is_alphanumeric() {
+            i += 1;
+        }
+        while i < self.chars.len() && self.chars[i].is_alphanumeric() {
+            i += 1;
+        }
+        i
+    }
+    /// the start of the blank separated word before the cursor, so
+    /// that ctrl-w removes a whole path
+    fn blank_word_start(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && self.chars[i - 1].is_whitespace() {
+            i -= 1;
+        }
+        while i > 0 && !self.chars[i - 1].is_whitespace() {
+            i -= 1;
+        }
+        i
+    }
+    /// remove the chars from start to end, the cursor
+    /// being between them, and return them
+    fn cut(&mut self, start: usize, end: usize) -> String {
+        self.cursor = start;
+        self.chars.drain(start..end).collect()
+    }
+    fn insert(&mut self, text: &str) {
+        for c in text.chars() {
+            self.chars.insert(self.cursor, c);
+            self.cursor += 1;
+        }
+    }
+}
+
+/// apply the key to the line, return false if it's not a line editing key
+fn apply_key_to_line(key: KeyEvent, line: &mut Line, yank: &mut Option<Yank>) -> bool {
+    let last_yank = yank.take();
+    match key {
+        KeyEvent::Alt('b') => {
+            line.cursor = line.word_start();
+        }
+        KeyEvent::Alt('f') => {
+            line.cursor = line.word_end();
+        }
+        KeyEvent::Ctrl('w') => {
+            let start = line.blank_word_start();
+            kill(line.cut(start, line.cursor));
+        }
+        KeyEvent::Ctrl('u') => {
+            kill(line.cut(0, line.cursor));
+        }
+        KeyEvent::Ctrl('k') => {
+            kill(line.cut(line.cursor, line.chars.len()));
+        }
+        KeyEvent::Ctrl('y') => {
+            if let Some(text) = killed(0) {
+                let start = line.cursor;
+                line.insert(&text);
+                *yank = Some(Yank {
+                    start,
+                    len: line.cursor - start,
+                    index: 0,
+                });
+            }
+        }
+        KeyEvent::Alt('y') => {
+            if let Some(last_yank) = last_yank {
+                let index = last_yank.index + 1;
+                if let Some(text) = killed(index) {
+                    line.cut(last_yank.start, last_yank.start + last_yank.len);
+                    line.insert(&text);
+                    *yank = Some(Yank {
+                        start: last_yank.start,
+                        len: line.cursor - last_yank.start,
+                        index,
+                    });
+                } else {
+                    *yank = Some(last_yank); // no older text: nothing changes
+                }
+            }
+        }
+        _ => {
+            return false;
+        }
+    }
+    true
+}
+
+/// apply the key to the input if it's a line editing key, and return
+/// whether it was one. The yank is the one of the previous key, and
+/// is replaced with the one of this key.
+pub fn apply_key(key: KeyEvent, input_field: &mut InputField, yank: &mut Option<Yank>) -> bool {
+    if !EDITING_KEYS.contains(&key) {
+        *yank = None;
+        return false;
+    }
+    let mut line = Line::of(input_field);
+    apply_key_to_line(key, &mut line, yank);
+    line.write_to(input_field);
+    true
+}
+
+#[cfg(test)]
+mod line_editing_tests {
+
+    use super::*;
+
+    fn line(s: &str, cursor: usize) -> Line {
+        Line {
+            chars: s.chars().collect(),
+            cursor,
+        }
+    }
+
+    fn check(before: (&str, usize), key: KeyEvent, after: (&str, usize)) {
+        let mut edited = line(before.0, before.1);
+        assert!(apply_key_to_line(key, &mut edited, &mut None));
+        assert_eq!(edited, line(after.0, after.1));
+    }
+
+    #[test]
+    fn check_word_moves() {
+        check(("ab :cp ../dir", 13), KeyEvent::Alt('b'), ("ab :cp ../dir", 10));
+        check(("ab :cp ../dir", 10), KeyEvent::Alt('b'), ("ab :cp ../dir", 4));
+        check(("ab :cp ../dir", 0), KeyEvent::Alt('f'), ("ab :cp ../dir", 2));
+        check(("ab :cp ../dir", 2), KeyEvent::Alt('f'), ("ab :cp ../dir", 6));
+    }
+
+    #[test]
+    fn check_kills_and_yanks() {
+        check(("ab :cp ../dir", 13), KeyEvent::Ctrl('w'), ("ab :cp ", 7));
+        check(("ab :cp ../dir", 6), KeyEvent::Ctrl('u'), (" ../dir", 0));
+        check(("ab :cp ../dir", 6), KeyEvent::Ctrl('k'), ("ab :cp", 6));
+        // the kill ring is shared by the tests, which are run in
+        // parallel, so the yanked text is only checked in this one
+        let mut edited = line("some text", 4);
+        let mut yank = None;
+        apply_key_to_line(KeyEvent::Ctrl('y'), &mut edited, &mut yank);
+        let yanked = killed(yank.unwrap().index).unwrap();
+        assert_eq!(edited.chars.len(), 9 + yanked.chars().count());
+        assert_eq!(edited.cursor, 4 + yanked.chars().count());
+        assert!(!apply_key_to_line(KeyEvent::Char('y'), &mut edited, &mut yank));
+        assert!(yank.is_none());
+    }
+}
@@ -16,9 +16,19 @@ use {
         conf::Conf,
         errors::ProgramError,
         external::Launchable,
+        get_path,
+        html_export::OutputFormat,
         io,
+        marks,
+        os_names,
+        path_case,
+        project_conf::ProjectConf,
+        server,
+        session::{self, Session},
         shell_install,
         skin,
+        snapshots,
+        stdout_export,
         verb_store::VerbStore,
     },
 };
@@ -54,22 +64,107 @@ fn configure_log() {
 /// which must be run after broot
 fn run() -> Result<Option<Launchable>, ProgramError> {
     configure_log();
-    let launch_args = cli::read_launch_args()?;
+    let mut launch_args = cli::read_launch_args()?;
     let should_quit = shell_install::init(&launch_args)?;
     if should_quit {
         return Ok(None);
     }
+    if let Some(snapshot_path) = &launch_args.write_snapshot {
+        snapshots::write(&launch_args.root, snapshot_path)?;
+        return Ok(None);
+    }
+    #[cfg(unix)]
+    {
+        if let Some((socket, request)) = &launch_args.send {
+            println!("{}", server::send(socket, request)?);
+            return Ok(None);
+        }
+    }
+    let mut config = Conf::from_default_location()?;
+    path_case::set_case_insensitive(config.case_insensitive_paths);
+    // the session is only restored when no root was given, and the
+    // toggles of the launch arguments are kept
+    if (launch_args.restore || config.restore_session)
+        && !launch_args.root_given
+        && launch_args.get_path.is_none()
+        && launch_args.tree_options.source.is_local()
+    {
+        if let Some(session) = Session::load() {
+            info!("restoring session {:?}", &session);
+            launch_args.root = session.root;
+            launch_args.selection = session.selection;
+            session.toggles.add_to(&mut launch_args.tree_options);
+            marks::add_all(session.marked);
+            if launch_args.commands.is_none() && !session.pattern.is_empty() {
+                launch_args.commands = Some(session.pattern);
+            }
+        }
+    }
+    if launch_args.tree_options.source.is_local() {
+        // the same directory typed with another case is the same root
+        launch_args.root = path_case::with_real_case(&launch_args.root);
+        // the terminal can't be asked whether to trust the project
+        // configuration when the tree is directly printed
+        let interactive = !stdout_export::is_redirected() && launch_args.get_path.is_none();
+        if let Some(project_conf) = ProjectConf::load(&launch_args.root, interactive)? {
+            info!("using the project configuration {:?}", &project_conf.path);
+            project_conf.apply(&mut config, &mut launch_args.tree_options)?;
+        }
+    }
     let mut verb_store = VerbStore::new();
-    let config = Conf::from_default_location()?;
     verb_store.init(&config);
-    let context = AppContext::from(launch_args, verb_store);
-    let skin = skin::Skin::create(config.skin);
-    App::new().run(&mut io::writer(), &context, skin)
+    launch_args.apply_conf(&config);
+    if let Some(pattern) = &launch_args.get_path {
+        let paths = get_path::best_paths(
+            launch_args.root.clone(),
+            launch_args.tree_options.clone(),
+            pattern,
+            launch_args.limit,
+        )?;
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        for path in paths {
+            os_names::write_line(&mut stdout, path.as_os_str())?;
+        }
+        return Ok(None);
+    }
+    let mut skin = skin::Skin::create(config.skin, config.file_styles);
+    skin.degrade_colors(launch_args.color_support);
+    if (launch_args.print || stdout_export::is_headless())
+        && launch_args.commands.is_none()
+        && launch_args.file_export_path.is_none()
+        && launch_args.cmd_export_path.is_none()
+        && launch_args.listen.is_none()
+    {
+        // the tree can't be browsed, it's directly written in the
+        // file or the pipe
+        let html = launch_args.output_format == OutputFormat::Html;
+        let skin = if launch_args.color.keeps_colors(html) {
+            skin
+        } else {
+            skin::Skin::no_term()
+        };
+        stdout_export::print_tree(
+            launch_args.root,
+            launch_args.tree_options,
+            launch_args.height,
+            launch_args.output_format,
+            &skin,
+        )?;
+        return Ok(None);
+    }
+    let mut context = AppContext::from(launch_args, verb_store);
+    session::install_panic_hook();
+    App::new().run(&mut io::writer(), &mut context, skin)
 }
 
 fn main() {
     let res = match run() {
         Ok(res) => res,
+        Err(e) if e.is_broken_pipe() => {
+            // the reader of the output, eg `head`, doesn't want more
+            return;
+        }
         Err(e) => {
             // this usually happens when the passed path isn't of a directory
             warn!("Error: {}", e);
@@ -78,10 +173,13 @@ fn main() {
         }
     };
     if let Some(launchable) = res {
-        if let Err(e) = launchable.execute() {
-            warn!("Failed to launch {:?}", &launchable);
-            warn!("Error: {:?}", e);
-            eprintln!("{}", e);
+        match launchable.execute() {
+            Err(e) if !e.is_broken_pipe() => {
+                warn!("Failed to launch {:?}", &launchable);
+                warn!("Error: {:?}", e);
+                eprintln!("{}", e);
+            }
+            _ => {}
         }
     }
     info!("bye");
@@ -0,0 +1,64 @@
+//! the marked paths: files and directories chosen while browsing,
+//! whatever the tree they were seen in.
+//!
+//! When broot is launched with `--out` and `--out-marks`, they're
+//! written in the output file on quitting, one per line.
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::path_case;
+
+lazy_static! {
+    static ref MARKS_MUTEX: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+}
+
+/// return the marked path designating the same file as the given
+/// one, which may differ by case on case insensitive file systems
+fn find_mark(marks: &BTreeSet<PathBuf>, path: &Path) -> Option<PathBuf> {
+    if marks.contains(path) {
+        Some(path.to_path_buf())
+    } else if path_case::is_case_insensitive() {
+        marks.iter().find(|mark| path_case::same_path(mark, path)).cloned()
+    } else {
+        None
+    }
+}
+
+/// mark the path, or unmark it if it was marked. Return
+/// whether it's now marked
+pub fn toggle(path: &Path) -> bool {
+    let mut marks = MARKS_MUTEX.lock().unwrap();
+    if let Some(mark) = find_mark(&marks, path) {
+        marks.remove(&mark);
+        false
+    } else {
+        marks.insert(path.to_path_buf());
+        true
+    }
+}
+
+pub fn is_marked(path: &Path) -> bool {
+    find_mark(&MARKS_MUTEX.lock().unwrap(), path).is_some()
+}
+
+pub fn count() -> usize {
+    MARKS_MUTEX.lock().unwrap().len()
+}
+
+/// mark all the given paths, for example the ones of a restored session
+pub fn add_all(paths: Vec<PathBuf>) {
+    let mut marks = MARKS_MUTEX.lock().unwrap();
+    for path in paths {
+        if find_mark(&marks, &path).is_none() {
+            marks.insert(path);
+        }
+    }
+}
+
+/// return the marked paths, sorted
+pub fn all() -> Vec<PathBuf> {
+    MARKS_MUTEX.lock().unwrap().iter().cloned().collect()
+}
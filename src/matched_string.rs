@@ -1,8 +1,10 @@
 
 
+use crossterm::style::Color;
 use termimad::CompoundStyle;
 
 use crate::{
+    column_widths,
     patterns::Pattern,
 };
 
@@ -11,6 +13,7 @@ pub struct MatchedString<'a> {
     pub string: &'a str,
     pub base_style: &'a CompoundStyle,
     pub match_style: &'a CompoundStyle,
+    pub colors: Option<&'a [Color]>, // the foreground colors of the chars, if they're highlighted
 }
 
 impl Pattern {
@@ -25,31 +28,199 @@ impl Pattern {
             string,
             base_style,
             match_style,
+            colors: None,
         }
     }
 }
 
 impl<'a> MatchedString<'a> {
+    /// give the chars their own foreground colors, the matching
+    /// chars keeping the color of the match style
+    pub fn with_colors(mut self, colors: &'a [Color]) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
     pub fn write_on<W>(&self, w: &mut W) -> Result<(), termimad::Error>
         where W: std::io::Write
     {
-        if self.pattern.is_some() {
-            if let Some(m) = self.pattern.find(self.string) {
-                let mut pos_idx: usize = 0;
-                let mut combined_style = self.base_style.clone();
-                combined_style.overwrite_with(self.match_style);
-                for (cand_idx, cand_char) in self.string.chars().enumerate() {
-                    if pos_idx < m.pos.len() && m.pos[pos_idx] == cand_idx {
-                        combined_style.queue(w, cand_char)?;
-                        pos_idx += 1;
-                    } else {
-                        self.base_style.queue(w, cand_char)?;
-                    }
+        self.write_fit_on(w, usize::max_value()).map(|_| ())
+    }
+
+    /// write the string, truncated with ellipsis if it's wider than
+    /// the width, and return the number of written columns.
+    ///
+    /// The truncation keeps the matching chars visible when possible:
+    /// the start of the string is cut instead of the end when the
+    /// match is at the end, and both are cut when it's in the middle.
+    /// A char is never separated from its combining marks.
+    pub fn write_fit_on<W>(&self, w: &mut W, width: usize) -> Result<usize, termimad::Error>
+        where W: std::io::Write
+    {
+        let m = if self.pattern.is_some() {
+            self.pattern.find(self.string)
+        } else {
+            None
+        };
+        let pos = m.map_or_else(Vec::new, |m| m.pos);
+        let clusters = column_widths::clusters(self.string);
+        let widths: Vec<usize> = clusters.iter().map(|c| c.width).collect();
+        let mut cluster_of = Vec::new(); // the index of the cluster of each char
+        for (idx, cluster) in clusters.iter().enumerate() {
+            cluster_of.extend((cluster.start..cluster.end).map(|_| idx));
+        }
+        let mut cluster_pos: Vec<usize> = pos
+            .iter()
+            .filter_map(|&p| cluster_of.get(p).copied())
+            .collect();
+        cluster_pos.dedup();
+        let window = Window::of(&widths, &cluster_pos, width);
+        // the window, in chars
+        let start = clusters.get(window.start).map_or(0, |c| c.start);
+        let end = if window.end > window.start {
+            clusters[window.end - 1].end
+        } else {
+            start
+        };
+        if window.leading_ellipsis {
+            self.base_style.queue(w, '…')?;
+        }
+        if pos.is_empty() && self.colors.is_none() {
+            let kept: String = self.string
+                .chars()
+                .skip(start)
+                .take(end - start)
+                .collect();
+            self.base_style.queue_str(w, &kept)?;
+        } else {
+            let mut pos_idx: usize = 0;
+            let mut base_style = self.base_style.clone();
+            let mut combined_style = self.base_style.clone();
+            combined_style.overwrite_with(self.match_style);
+            for (cand_idx, cand_char) in self.string.chars().enumerate() {
+                let matching = pos_idx < pos.len() && pos[pos_idx] == cand_idx;
+                if matching {
+                    pos_idx += 1;
+                }
+                if cand_idx < start || cand_idx >= end {
+                    continue;
+                }
+                if matching {
+                    combined_style.queue(w, cand_char)?;
+                } else if let Some(&color) = self.colors.and_then(|colors| colors.get(cand_idx)) {
+                    base_style.set_fg(color);
+                    base_style.queue(w, cand_char)?;
+                } else {
+                    self.base_style.queue(w, cand_char)?;
                 }
-                return Ok(());
             }
         }
-        self.base_style.queue_str(w, self.string)
+        if window.trailing_ellipsis {
+            self.base_style.queue(w, '…')?;
+        }
+        Ok(window.width(&widths))
+    }
+}
+
+/// the part of a string which is written when it must fit a width
+#[derive(Debug, PartialEq)]
+struct Window {
+    start: usize, // index of the first written cluster
+    end: usize,   // index after the last written cluster
+    leading_ellipsis: bool,
+    trailing_ellipsis: bool,
+}
+
+/// the number of clusters, taken in order, fitting in the width
+fn fitting<'a>(widths: impl Iterator<Item = &'a usize>, width: usize) -> usize {
+    let mut used = 0;
+    widths
+        .take_while(|&&w| {
+            used += w;
+            used <= width
+        })
+        .count()
+}
+
+impl Window {
+    /// compute the visible part of a string whose clusters of chars
+    /// have the given widths, the matching clusters being at the
+    /// given (sorted) positions
+    fn of(widths: &[usize], pos: &[usize], width: usize) -> Window {
+        let len = widths.len();
+        if widths.iter().sum::<usize>() <= width {
+            return Window { start: 0, end: len, leading_ellipsis: false, trailing_ellipsis: false };
+        }
+        if width < 2 {
+            return Window { start: 0, end: 0, leading_ellipsis: false, trailing_ellipsis: width == 1 };
+        }
+        let (first, last) = match (pos.first(), pos.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => (0, 0),
+        };
+        let head = fitting(widths.iter(), width - 1);
+        let tail = fitting(widths.iter().rev(), width - 1);
+        if last < head {
+            // the match is in the visible start
+            Window { start: 0, end: head, leading_ellipsis: false, trailing_ellipsis: true }
+        } else if first >= len - tail {
+            // the match is in the visible end
+            Window { start: len - tail, end: len, leading_ellipsis: true, trailing_ellipsis: false }
+        } else {
+            // the window starts just before the match, as
+            // the chars following it are more telling
+            let start = first.saturating_sub(1).max(1);
+            let end = start + fitting(widths[start..].iter(), width - 2);
+            Window { start, end, leading_ellipsis: true, trailing_ellipsis: true }
+        }
+    }
+    /// the number of written columns, ellipsis included
+    fn width(&self, widths: &[usize]) -> usize {
+        widths[self.start..self.end].iter().sum::<usize>()
+            + if self.leading_ellipsis { 1 } else { 0 }
+            + if self.trailing_ellipsis { 1 } else { 0 }
     }
 }
 
+#[cfg(test)]
+mod window_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_windows() {
+        let (w10, w20, w30) = ([1; 10], [1; 20], [1; 30]);
+        // fitting strings aren't cut
+        assert_eq!(Window::of(&w10, &[8], 10).width(&w10), 10);
+        // without match, or with a match at the start, the end is cut
+        assert_eq!(
+            Window::of(&w20, &[], 10),
+            Window { start: 0, end: 9, leading_ellipsis: false, trailing_ellipsis: true },
+        );
+        assert_eq!(Window::of(&w20, &[2, 8], 10).end, 9);
+        // with a match at the end, the start is cut
+        assert_eq!(
+            Window::of(&w20, &[15, 19], 10),
+            Window { start: 11, end: 20, leading_ellipsis: true, trailing_ellipsis: false },
+        );
+        // with a match in the middle, both are cut
+        let window = Window::of(&w30, &[12, 14], 10);
+        assert_eq!(window, Window { start: 11, end: 19, leading_ellipsis: true, trailing_ellipsis: true });
+        assert_eq!(window.width(&w30), 10);
+        // too narrow
+        assert_eq!(Window::of(&w30, &[12], 1).width(&w30), 1);
+        assert_eq!(Window::of(&w30, &[12], 0).width(&w30), 0);
+    }
+
+    #[test]
+    fn check_wide_windows() {
+        // wide chars take two columns, and are never cut in half
+        let widths = [2; 10];
+        let window = Window::of(&widths, &[], 10);
+        assert_eq!(window.end, 4);
+        assert_eq!(window.width(&widths), 9);
+        let window = Window::of(&widths, &[9], 10);
+        assert_eq!(window.start, 6);
+        assert!(window.width(&widths) <= 10);
+    }
+}
@@ -0,0 +1,83 @@
+//! the handling of file names which aren't valid UTF-8 (on unix a
+//! name is any sequence of bytes).
+//!
+//! Such names are displayed, and matched by the patterns, with their
+//! invalid bytes escaped as `\xNN`, while the paths given to verbs and
+//! written in exports are the raw ones, so that the operations on
+//! those files succeed.
+
+use std::{
+    ffi::OsStr,
+    io::{self, Write},
+};
+
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+
+/// return the name as text, with the bytes which aren't part of a
+/// valid UTF-8 sequence written as `\xNN`
+#[cfg(unix)]
+pub fn escape(name: &OsStr) -> String {
+    if let Some(s) = name.to_str() {
+        return s.to_string();
+    }
+    let mut escaped = String::new();
+    let mut bytes = name.as_bytes();
+    loop {
+        match std::str::from_utf8(bytes) {
+            Ok(valid) => {
+                escaped.push_str(valid);
+                return escaped;
+            }
+            Err(e) => {
+                let (valid, rest) = bytes.split_at(e.valid_up_to());
+                escaped.push_str(std::str::from_utf8(valid).unwrap());
+                let invalid_len = e.error_len().unwrap_or_else(|| rest.len());
+                for b in &rest[..invalid_len] {
+                    escaped.push_str(&format!("\\x{:02X}", b));
+                }
+                bytes = &rest[invalid_len..];
+            }
+        }
+    }
+}
+
+/// return the name as text. Outside of unix the names are made of
+/// characters, so only unpaired surrogates can be lost
+#[cfg(not(unix))]
+pub fn escape(name: &OsStr) -> String {
+    name.to_string_lossy().to_string()
+}
+
+/// write the raw bytes of the string, followed by a newline, so that
+/// the shell or the program reading an export gets the real names
+#[cfg(unix)]
+pub fn write_line<W: Write>(w: &mut W, s: &OsStr) -> io::Result<()> {
+    w.write_all(s.as_bytes())?;
+    w.write_all(b"\n")
+}
+
+#[cfg(not(unix))]
+pub fn write_line<W: Write>(w: &mut W, s: &OsStr) -> io::Result<()> {
+    writeln!(w, "{}", s.to_string_lossy())
+}
+
+#[cfg(all(test, unix))]
+mod os_names_tests {
+
+    use super::*;
+    use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+
+    fn check(bytes: &[u8], escaped: &str) {
+        assert_eq!(escape(&OsString::from_vec(bytes.to_vec())), escaped);
+    }
+
+    #[test]
+    fn check_escapes() {
+        check(b"main.rs", "main.rs");
+        check("héhé".as_bytes(), "héhé");
+        check(b"caf\xe9.txt", r"caf\xE9.txt");
+        check(b"\xff\xfe", r"\xFF\xFE");
+        check(b"ab\xe2\x82", r"ab\xE2\x82"); // truncated sequence
+    }
+}
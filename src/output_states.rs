@@ -0,0 +1,179 @@
+use std::io::Write;
+
+use crossterm::{
+    queue,
+    terminal::{Clear, ClearType},
+};
+use minimad::{Composite, CompositeStyle, Compound, Line, Text};
+use termimad::{Area, FmtText, TextView};
+
+use crate::{
+    app_context::AppContext,
+    app_state::{AppState, AppStateCmdResult},
+    commands::{Action, Command},
+    control_chars,
+    errors::ProgramError,
+    flat_tree::Tree,
+    io::W,
+    screens::Screen,
+    session::Session,
+    state_history::Place,
+    status::Status,
+    task_sync::TaskLifetime,
+    verb_store::PrefixSearchResult,
+    verbs::VerbExecutor,
+};
+
+/// an application state displaying what a program launched by
+/// a verb with `capture = true` wrote.
+///
+/// The colors the program may have written are removed, and the
+/// other control chars escaped, so that they're never executed
+/// by the terminal
+pub struct OutputState {
+    pub scroll: i32, // scroll position
+    pub area: Area,
+    screen_size: (u16, u16), // kept to detect when the background should be cleared
+    command: String, // the executed command, as it would be typed in a shell
+    output: String,  // stdout then stderr of the program
+}
+
+impl OutputState {
+    pub fn new(command: String, output: String) -> OutputState {
+        OutputState {
+            scroll: 0,
+            area: Area::uninitialized(), // will be fixed at drawing time
+            screen_size: (0, 0),
+            command,
+            output: output
+                .replace('\t', "    ")
+                .lines()
+                .map(control_chars::escape_without_sgr)
+                .collect::<Vec<String>>()
+                .join("\n"),
+        }
+    }
+
+    /// return true when the screen area changed
+    fn resize_area(&mut self, screen: &Screen) -> bool {
+        if self.screen_size == (screen.width, screen.height) {
+            return false;
+        }
+        self.screen_size = (screen.width, screen.height);
+        self.area = Area::new(0, 0, screen.width, screen.height - 2);
+        true
+    }
+
+    /// the output as text, without interpreting it as markdown
+    fn text(&self) -> Text<'_> {
+        let lines = self
+            .output
+            .lines()
+            .map(|line| {
+                Line::Normal(Composite {
+                    style: CompositeStyle::Paragraph,
+                    compounds: vec![Compound::raw_str(line)],
+                })
+            })
+            .collect();
+        Text { lines }
+    }
+}
+
+impl AppState for OutputState {
+    fn has_pending_task(&self) -> bool {
+        false
+    }
+
+    fn apply(
+        &mut self,
+        cmd: &mut Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<AppStateCmdResult, ProgramError> {
+        Ok(match &cmd.action {
+            Action::Back | Action::UpTree | Action::UpTreeFromRoot => AppStateCmdResult::PopState,
+            Action::VerbIndex(index) => {
+                let verb = &con.verb_store.verbs[*index];
+                self.execute_verb(verb, &verb.invocation, screen, con)?
+            }
+            Action::VerbInvocate(invocation) => match con.verb_store.search(&invocation.name) {
+                PrefixSearchResult::Match(verb) => {
+                    self.execute_verb(verb, &invocation, screen, con)?
+                }
+                _ => AppStateCmdResult::verb_not_found(&invocation.name),
+            },
+            Action::MoveSelection(dy) => {
+                self.scroll += *dy;
+                AppStateCmdResult::Keep
+            }
+            _ => AppStateCmdResult::Keep,
+        })
+    }
+
+    fn refresh(&mut self, _screen: &Screen, _con: &AppContext) -> Command {
+        Command::new()
+    }
+
+    fn do_pending_task(&mut self, _screen: &mut Screen, _tl: &TaskLifetime) {
+        unreachable!();
+    }
+
+    fn display(
+        &mut self,
+        w: &mut W,
+        screen: &Screen,
+        _con: &AppContext
+    ) -> Result<(), ProgramError> {
+        if self.resize_area(screen) {
+            screen.clear(w)?;
+        }
+        let fmt_text = FmtText::from_text(
+            &screen.help_skin,
+            self.text(),
+            Some((self.area.width - 1) as usize),
+        );
+        let mut text_view = TextView::from(&self.area, &fmt_text);
+        self.scroll = text_view.set_scroll(self.scroll);
+        Ok(text_view.write_on(w)?)
+    }
+
+    fn write_status(
+        &self,
+        w: &mut W,
+        _cmd: &Command,
+        screen: &Screen,
+        _con: &AppContext,
+    ) -> Result<(), ProgramError> {
+        let text = format!(
+            "Output of `{}`, hit *esc* to get back to the tree",
+            &self.command,
+        );
+        Status::from_message(Composite::from_inline(&text)).display(w, screen)
+    }
+
+    /// there's no meaningful flags here
+    fn write_flags(
+        &self,
+        w: &mut W,
+        screen: &mut Screen,
+        _con: &AppContext
+    ) -> Result<(), ProgramError> {
+        screen.skin.default.queue_bg(w)?;
+        queue!(w, Clear(ClearType::UntilNewLine))?;
+        Ok(())
+    }
+
+    /// the output isn't restored on resume
+    fn session(&self) -> Option<Session> {
+        None
+    }
+
+    fn place(&self) -> Option<Place> {
+        None
+    }
+
+    fn tree(&self) -> Option<&Tree> {
+        None
+    }
+}
@@ -0,0 +1,48 @@
+//! this modules defines the execution of verbs on the screen
+//! displaying the output of a program
+
+use crate::{
+    app_context::AppContext,
+    app_state::AppStateCmdResult,
+    errors::ProgramError,
+    output_states::OutputState,
+    screens::Screen,
+    verb_invocation::VerbInvocation,
+    verbs::{Verb, VerbExecutor},
+};
+
+impl VerbExecutor for OutputState {
+    fn execute_verb(
+        &mut self,
+        verb: &Verb,
+        invocation: &VerbInvocation,
+        _screen: &mut Screen,
+        _con: &AppContext,
+    ) -> Result<AppStateCmdResult, ProgramError> {
+        if let Some(err) = verb.match_error(invocation) {
+            return Ok(AppStateCmdResult::DisplayError(err));
+        }
+        Ok(match verb.execution.as_ref() {
+            ":back" => AppStateCmdResult::PopState,
+            ":line_down" => {
+                self.scroll += 1;
+                AppStateCmdResult::Keep
+            }
+            ":line_up" => {
+                self.scroll -= 1;
+                AppStateCmdResult::Keep
+            }
+            ":page_down" => {
+                self.scroll += self.area.height as i32;
+                AppStateCmdResult::Keep
+            }
+            ":page_up" => {
+                self.scroll -= self.area.height as i32;
+                AppStateCmdResult::Keep
+            }
+            ":quit" => AppStateCmdResult::Quit,
+            // the other verbs apply to the tree
+            _ => AppStateCmdResult::PopStateAndReapply,
+        })
+    }
+}
@@ -0,0 +1,80 @@
+//! handling of the file systems which don't distinguish
+//! `Foo` from `foo` (the default on Windows and on macOS)
+
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+// 0 when the platform default applies, 1 when the file system is
+// declared case insensitive in the configuration, 2 when it's not
+static CASE_INSENSITIVITY: AtomicU8 = AtomicU8::new(0);
+
+/// override the platform default, as set with `case_insensitive_paths`
+/// in the configuration (None meaning the default applies)
+pub fn set_case_insensitive(insensitive: Option<bool>) {
+    let value = match insensitive {
+        None => 0,
+        Some(true) => 1,
+        Some(false) => 2,
+    };
+    CASE_INSENSITIVITY.store(value, Ordering::Relaxed);
+}
+
+/// tell whether paths differing only by case designate the same file.
+///
+/// Without configuration, this is only an assumption based on the
+/// platform defaults: a case sensitive volume can be mounted on
+/// macOS, for example.
+pub fn is_case_insensitive() -> bool {
+    match CASE_INSENSITIVITY.load(Ordering::Relaxed) {
+        1 => true,
+        2 => false,
+        _ => cfg!(any(windows, target_os = "macos")),
+    }
+}
+
+/// tell whether both paths designate the same file, ignoring
+/// the case when the file system is assumed to do so
+pub fn same_path(a: &Path, b: &Path) -> bool {
+    if is_case_insensitive() {
+        a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+    } else {
+        a == b
+    }
+}
+
+/// return the path with the case of the names as stored in the file
+/// system, so that the same directory is always displayed the same way
+/// whatever the case typed by the user.
+///
+/// The path is assumed to be absolute and canonicalized (without any
+/// `..` or symlink). Components which can't be found are kept as is.
+pub fn with_real_case(path: &Path) -> PathBuf {
+    if !is_case_insensitive() {
+        return path.to_path_buf();
+    }
+    let mut real = PathBuf::new();
+    for component in path.components() {
+        let name = match component {
+            Component::Normal(name) => name,
+            _ => {
+                real.push(component.as_os_str());
+                continue;
+            }
+        };
+        let lowercase_name = name.to_string_lossy().to_lowercase();
+        let found = fs::read_dir(&real).ok().and_then(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.file_name())
+                .find(|n| n.to_string_lossy().to_lowercase() == lowercase_name)
+        });
+        match found {
+            Some(real_name) => real.push(real_name),
+            None => real.push(name),
+        }
+    }
+    real
+}
@@ -0,0 +1,122 @@
+//! expansion and completion of the paths typed as verb arguments
+
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use directories::UserDirs;
+
+/// replace the environment variables (`$NAME` or `${NAME}`) of the
+/// typed string by their values, the unknown ones being kept as is
+fn expand_env_vars(typed: &str) -> String {
+    regex!(r"\$(?:\{(\w+)\}|(\w+))")
+        .replace_all(typed, |caps: &regex::Captures<'_>| {
+            let name = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+            env::var(name).unwrap_or_else(|_| caps[0].to_string())
+        })
+        .to_string()
+}
+
+/// compute the path designated by what the user typed: the `~` and
+/// the environment variables are expanded and a relative path is
+/// taken from base_dir
+pub fn expand_path(typed: &str, base_dir: &Path) -> PathBuf {
+    let typed = expand_env_vars(typed);
+    if typed == "~" || typed.starts_with("~/") {
+        if let Some(user_dirs) = UserDirs::new() {
+            return user_dirs.home_dir().join(typed[1..].trim_start_matches('/'));
+        }
+    }
+    base_dir.join(typed)
+}
+
+/// return the longest string which starts all the given names
+fn common_prefix(names: &[String]) -> String {
+    let mut prefix: Vec<char> = match names.first() {
+        Some(name) => name.chars().collect(),
+        None => {
+            return String::new();
+        }
+    };
+    for name in &names[1..] {
+        let len = prefix
+            .iter()
+            .zip(name.chars())
+            .take_while(|(a, b)| *a == b)
+            .count();
+        prefix.truncate(len);
+    }
+    prefix.into_iter().collect()
+}
+
+/// try to complete the last token of the raw input, assuming it's
+/// a path, either absolute, starting with `~` or relative to base_dir.
+///
+/// Return the completed input, or None when there's nothing to add
+/// (no matching file or more than one possible continuation)
+pub fn complete_last_token(raw: &str, base_dir: &Path) -> Option<String> {
+    let token_start = raw
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let token = &raw[token_start..];
+    let (dir_part, name_prefix) = match token.rfind('/') {
+        Some(i) => (&token[..=i], &token[i + 1..]),
+        None => ("", token),
+    };
+    let dir = expand_path(dir_part, base_dir);
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().to_str().map(|s| s.to_string()))
+        .filter(|name| name.starts_with(name_prefix))
+        .filter(|name| name_prefix.starts_with('.') || !name.starts_with('.'))
+        .collect();
+    names.sort();
+    let mut completion = common_prefix(&names);
+    if names.len() == 1 && dir.join(&completion).is_dir() {
+        completion.push('/');
+    }
+    if completion.len() <= name_prefix.len() {
+        return None;
+    }
+    Some(format!("{}{}{}", &raw[..token_start], dir_part, completion))
+}
+
+#[cfg(test)]
+mod path_completion_tests {
+
+    use super::*;
+
+    fn check(names: &[&str], prefix: &str) {
+        let names: Vec<String> = names.iter().map(|s| s.to_string()).collect();
+        assert_eq!(common_prefix(&names), prefix.to_string());
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        check(&[], "");
+        check(&["broot"], "broot");
+        check(&["src", "srv"], "sr");
+        check(&["Cargo.toml", "Cargo.lock", "CHANGELOG.md"], "C");
+        check(&["réveil", "réveillon"], "réveil");
+        check(&["a", "b"], "");
+    }
+
+    #[test]
+    fn test_path_expansion() {
+        let base = Path::new("/base");
+        env::set_var("BROOT_TEST_DIR", "/some/dir");
+        assert_eq!(expand_path("src", base), PathBuf::from("/base/src"));
+        assert_eq!(expand_path("/etc", base), PathBuf::from("/etc"));
+        assert_eq!(expand_path("$BROOT_TEST_DIR/a", base), PathBuf::from("/some/dir/a"));
+        assert_eq!(expand_path("${BROOT_TEST_DIR}/a", base), PathBuf::from("/some/dir/a"));
+        assert_eq!(expand_path("$BROOT_NOT_SET", base), PathBuf::from("/base/$BROOT_NOT_SET"));
+        if let Some(user_dirs) = UserDirs::new() {
+            assert_eq!(expand_path("~/a", base), user_dirs.home_dir().join("a"));
+        }
+    }
+}
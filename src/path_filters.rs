@@ -0,0 +1,100 @@
+//! glob based rules telling which paths are shown and
+//! which directories are entered, applied while building
+//! the tree.
+//!
+//! A glob containing a `/` is matched against the whole path,
+//! other ones are matched against the file name. The case is
+//! ignored on case insensitive file systems.
+
+use std::{
+    path::Path,
+    str::FromStr,
+};
+
+use crate::{errors::ConfError, path_case};
+
+/// what broot does with the paths matching a glob of
+/// the `special_paths` configuration section
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpecialHandling {
+    Hide,    // never shown
+    NoEnter, // shown but never entered by the builder
+}
+
+impl FromStr for SpecialHandling {
+    type Err = ConfError;
+    fn from_str(s: &str) -> Result<SpecialHandling, ConfError> {
+        match s {
+            "hide" => Ok(SpecialHandling::Hide),
+            "no-enter" => Ok(SpecialHandling::NoEnter),
+            _ => Err(ConfError::InvalidSpecialHandling {
+                raw: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpecialPath {
+    pub pattern: glob::Pattern,
+    pub handling: SpecialHandling,
+}
+
+impl SpecialPath {
+    pub fn new(glob: &str, handling: SpecialHandling) -> Result<SpecialPath, ConfError> {
+        Ok(SpecialPath {
+            pattern: parse_glob(glob).ok_or_else(|| ConfError::InvalidGlob {
+                raw: glob.to_string(),
+            })?,
+            handling,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PathFilters {
+    pub includes: Vec<glob::Pattern>, // when not empty, only the files matching one are shown
+    pub excludes: Vec<glob::Pattern>, // files and directories never shown
+    pub special_paths: Vec<SpecialPath>,
+}
+
+/// parse a glob, returning None if it's invalid
+pub fn parse_glob(glob: &str) -> Option<glob::Pattern> {
+    glob::Pattern::new(glob).ok()
+}
+
+/// match the glob against the path or the name, ignoring the case
+/// when the file system does
+fn glob_matches(pattern: &glob::Pattern, path: &Path, name: &str) -> bool {
+    let options = glob::MatchOptions {
+        case_sensitive: !path_case::is_case_insensitive(),
+        ..glob::MatchOptions::new()
+    };
+    if pattern.as_str().contains('/') {
+        pattern.matches_path_with(path, options)
+    } else {
+        pattern.matches_with(name, options)
+    }
+}
+
+impl PathFilters {
+    /// tell whether the file or directory must be excluded from the tree
+    pub fn hides(&self, path: &Path, name: &str, is_dir: bool) -> bool {
+        if !is_dir
+            && !self.includes.is_empty()
+            && !self.includes.iter().any(|p| glob_matches(p, path, name))
+        {
+            return true;
+        }
+        self.excludes.iter().any(|p| glob_matches(p, path, name))
+            || self.special_paths.iter().any(|sp| {
+                sp.handling == SpecialHandling::Hide && glob_matches(&sp.pattern, path, name)
+            })
+    }
+    /// tell whether the builder may look into the directory
+    pub fn may_enter(&self, path: &Path, name: &str) -> bool {
+        !self.special_paths.iter().any(|sp| {
+            sp.handling == SpecialHandling::NoEnter && glob_matches(&sp.pattern, path, name)
+        })
+    }
+}
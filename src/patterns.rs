@@ -1,21 +1,51 @@
 //! a pattern for filtering and sorting filenames.
-//! It's backed either by a fuzzy pattern matcher or
+//! It's backed either by a fuzzy pattern matcher,
 //!  by a regular expression (in which case there's no real
-//!  score)
+//!  score), or by a logical combination of those patterns.
+//! It may also carry a filter on the type of the files (see the
+//!  file_filters module)
 
-use std::{fmt, mem};
+use std::{fmt, mem, path::Path};
 
 use crate::{
+    composite_patterns::CompositePattern,
     errors::RegexError,
+    file_filters::{FileFilter, FilteredPattern},
     fuzzy_patterns::FuzzyPattern,
+    fuzzy_scoring::FuzzyScoring,
     regex_patterns::RegexPattern,
+    tree_sources::TreeSource,
 };
 
+/// what any kind of pattern must provide to filter and
+/// sort the candidates
+pub trait Scorer {
+    /// return the match, with the positions of the matching chars
+    fn find(&self, candidate: &str) -> Option<Match>;
+    /// return the score of the best match, which may be faster
+    /// to compute than the match itself
+    fn score_of(&self, candidate: &str) -> Option<i32>;
+    /// the number of results we should find before starting to
+    /// sort them (unless time is runing out)
+    fn optimal_result_number(&self, targeted_size: usize) -> usize;
+    /// tell whether the scores rank the matches, which makes it
+    /// worth looking for better matches once we have enough of
+    /// them (a regex either matches or not)
+    fn has_ranking(&self) -> bool;
+    /// the score no candidate can exceed, when it's known, so that
+    /// the search can stop once it can't find better matches
+    fn best_score(&self) -> Option<i32> {
+        None
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Pattern {
     None,
     Fuzzy(FuzzyPattern),
     Regex(RegexPattern),
+    Composite(CompositePattern),
+    Filtered(FilteredPattern),
 }
 
 impl fmt::Display for Pattern {
@@ -23,6 +53,8 @@ impl fmt::Display for Pattern {
         match self {
             Pattern::Fuzzy(fp) => write!(f, "Fuzzy({})", fp),
             Pattern::Regex(rp) => write!(f, "Regex({})", rp),
+            Pattern::Composite(cp) => write!(f, "Composite({})", cp),
+            Pattern::Filtered(fp) => write!(f, "Filtered({})", fp),
             Pattern::None => write!(f, "None"),
         }
     }
@@ -30,18 +62,59 @@ impl fmt::Display for Pattern {
 
 impl Pattern {
     /// create a new fuzzy pattern
-    pub fn fuzzy(pat: &str) -> Pattern {
-        Pattern::Fuzzy(FuzzyPattern::from(pat))
+    pub fn fuzzy(pat: &str, scoring: FuzzyScoring) -> Pattern {
+        Pattern::Fuzzy(FuzzyPattern::with_scoring(pat, scoring))
     }
     /// try to create a regex pattern
     pub fn regex(pat: &str, flags: &str) -> Result<Pattern, RegexError> {
         Ok(Pattern::Regex(RegexPattern::from(pat, flags)?))
     }
-    pub fn find(&self, candidate: &str) -> Option<Match> {
+    /// try to create a pattern combining other ones with logical operators
+    pub fn composite(pat: &str, scoring: FuzzyScoring) -> Result<Pattern, RegexError> {
+        Ok(match CompositePattern::from(pat, scoring)? {
+            Some(cp) => Pattern::Composite(cp),
+            None => Pattern::None,
+        })
+    }
+    /// add a file filter to the pattern, which may be Pattern::None
+    pub fn filtered(self, filter: FileFilter) -> Pattern {
+        if filter.is_empty() {
+            self
+        } else {
+            Pattern::Filtered(FilteredPattern {
+                filter,
+                inner: Box::new(self),
+            })
+        }
+    }
+    /// return the filter on the type of the files, if any
+    pub fn file_filter(&self) -> Option<&FileFilter> {
+        match self {
+            Pattern::Filtered(fp) => Some(&fp.filter),
+            _ => None,
+        }
+    }
+    /// read what the file filter refers to (the reference files
+    /// of the `newer` filters), relative paths starting from the root
+    pub fn resolve_file_filter(&mut self, root: &Path, source: &dyn TreeSource) {
+        if let Pattern::Filtered(fp) = self {
+            fp.filter.resolve(root, source);
+        }
+    }
+    /// return the scorer backing the pattern, if any
+    fn scorer(&self) -> Option<&dyn Scorer> {
         match self {
-            Pattern::Fuzzy(fp) => fp.find(candidate),
-            Pattern::Regex(rp) => rp.find(candidate),
-            Pattern::None => Some(Match {
+            Pattern::Fuzzy(fp) => Some(fp),
+            Pattern::Regex(rp) => Some(rp),
+            Pattern::Composite(cp) => Some(cp),
+            Pattern::Filtered(fp) => Some(fp),
+            Pattern::None => None,
+        }
+    }
+    pub fn find(&self, candidate: &str) -> Option<Match> {
+        match self.scorer() {
+            Some(scorer) => scorer.find(candidate),
+            None => Some(Match {
                 // this isn't really supposed to be used
                 score: 1,
                 pos: Vec::with_capacity(0),
@@ -49,11 +122,7 @@ impl Pattern {
         }
     }
     pub fn score_of(&self, candidate: &str) -> Option<i32> {
-        match self {
-            Pattern::Fuzzy(fp) => fp.score_of(candidate),
-            Pattern::Regex(rp) => rp.find(candidate).map(|m| m.score),
-            Pattern::None => None,
-        }
+        self.scorer().and_then(|scorer| scorer.score_of(candidate))
     }
     pub fn is_some(&self) -> bool {
         match self {
@@ -61,6 +130,14 @@ impl Pattern {
             _ => true,
         }
     }
+    /// tell whether the scores of the pattern rank the matches
+    pub fn has_ranking(&self) -> bool {
+        self.scorer().map_or(false, |scorer| scorer.has_ranking())
+    }
+    /// the score no candidate can exceed, if known
+    pub fn best_score(&self) -> Option<i32> {
+        self.scorer().and_then(|scorer| scorer.best_score())
+    }
     /// empties the pattern and return it
     /// Similar to Option::take
     pub fn take(&mut self) -> Pattern {
@@ -69,11 +146,8 @@ impl Pattern {
     // return the number of results we should find before starting to
     //  sort them (unless time is runing out).
     pub fn optimal_result_number(&self, targeted_size: usize) -> usize {
-        match self {
-            Pattern::Fuzzy(fp) => fp.optimal_result_number(targeted_size),
-            Pattern::Regex(rp) => rp.optimal_result_number(targeted_size),
-            Pattern::None => targeted_size,
-        }
+        self.scorer()
+            .map_or(targeted_size, |scorer| scorer.optimal_result_number(targeted_size))
     }
 }
 
@@ -1,4 +1,17 @@
-use std::{collections::HashMap, sync::Mutex};
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    fs,
+    io,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{MetadataExt, PermissionsExt},
+    },
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::errors::ProgramError;
 
 #[cfg(unix)]
 use users;
@@ -36,3 +49,240 @@ pub fn group_name(gid: u32) -> String {
         })
         .to_string()
 }
+
+/// compute the mode resulting of the application of `spec` on a
+/// file whose mode is `current`.
+///
+/// `spec` is either octal (eg `755`) or symbolic, as in chmod
+/// (eg `u+x`, `go-w` or `a=r,u+w`).
+#[cfg(unix)]
+pub fn parse_mode(spec: &str, current: u32, is_dir: bool) -> Option<u32> {
+    let spec = spec.trim();
+    if spec.is_empty() {
+        return None;
+    }
+    if spec.chars().all(|c| c.is_digit(8)) {
+        return match u32::from_str_radix(spec, 8) {
+            Ok(mode) if mode <= 0o7777 => Some(mode),
+            _ => None,
+        };
+    }
+    let mut mode = current & 0o7777;
+    for clause in spec.split(',') {
+        let mut chars = clause.chars().peekable();
+        let mut who = 0;
+        while let Some(c) = chars.peek() {
+            who |= match c {
+                'u' => 0o4700,
+                'g' => 0o2070,
+                'o' => 0o1007,
+                'a' => 0o7777,
+                _ => break,
+            };
+            chars.next();
+        }
+        if who == 0 {
+            who = 0o7777; // as with `a`, ignoring the umask
+        }
+        let mut has_op = false;
+        while let Some(op) = chars.next() {
+            if op != '+' && op != '-' && op != '=' {
+                return None;
+            }
+            has_op = true;
+            let mut bits = 0;
+            while let Some(c) = chars.peek() {
+                bits |= match c {
+                    'r' => 0o444,
+                    'w' => 0o222,
+                    'x' => 0o111,
+                    // execute only for directories and already executable files
+                    'X' if is_dir || mode & 0o111 != 0 => 0o111,
+                    'X' => 0,
+                    's' => 0o6000,
+                    't' => 0o1000,
+                    '+' | '-' | '=' => break,
+                    _ => return None,
+                };
+                chars.next();
+            }
+            match op {
+                '+' => mode |= bits & who,
+                '-' => mode &= !(bits & who),
+                _ => mode = (mode & !who) | (bits & who),
+            }
+        }
+        if !has_op {
+            return None;
+        }
+    }
+    Some(mode)
+}
+
+/// parse an owner spec, as given to `chown`: `user`, `user:group`
+/// or `:group`, where names may also be numeric ids.
+/// Return the ids to set, None meaning unchanged
+#[cfg(unix)]
+pub fn parse_owner(spec: &str) -> Result<(Option<u32>, Option<u32>), ProgramError> {
+    let spec = spec.trim();
+    let (user, group) = match spec.find(':') {
+        Some(idx) => (&spec[..idx], Some(&spec[idx + 1..])),
+        None => (spec, None),
+    };
+    let uid = if user.is_empty() {
+        None
+    } else {
+        let uid = user
+            .parse()
+            .ok()
+            .or_else(|| users::get_user_by_name(user).map(|u| u.uid()))
+            .ok_or_else(|| ProgramError::UnknownOwner { name: user.to_string() })?;
+        Some(uid)
+    };
+    let gid = match group {
+        Some(group) if !group.is_empty() => {
+            let gid = group
+                .parse()
+                .ok()
+                .or_else(|| users::get_group_by_name(group).map(|g| g.gid()))
+                .ok_or_else(|| ProgramError::UnknownOwner { name: group.to_string() })?;
+            Some(gid)
+        }
+        _ => None,
+    };
+    if uid.is_none() && gid.is_none() {
+        return Err(ProgramError::UnknownOwner { name: spec.to_string() });
+    }
+    Ok((uid, gid))
+}
+
+/// change the owner and/or the group of the file, as `chown` would
+/// do with the given spec
+#[cfg(unix)]
+pub fn chown(path: &Path, spec: &str) -> Result<(), ProgramError> {
+    let (uid, gid) = parse_owner(spec)?;
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // -1 (as unsigned) means the id isn't changed
+    let uid = uid.unwrap_or(u32::max_value());
+    let gid = gid.unwrap_or(u32::max_value());
+    let res = unsafe { libc::chown(c_path.as_ptr(), uid, gid) };
+    if res != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// the names of the users of the system, sorted, for completion
+#[cfg(unix)]
+pub fn user_names() -> Vec<String> {
+    let mut names: Vec<String> = unsafe { users::all_users() }
+        .map(|u| u.name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+/// the names of the groups of the system, sorted, for completion
+#[cfg(unix)]
+pub fn group_names() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_to_string("/etc/group")
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split(':').next())
+        .filter(|name| !name.is_empty())
+        .map(|name| name.to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+/// complete the owner spec being typed for `:chown`, with the first
+/// user or group name starting with it
+#[cfg(unix)]
+pub fn complete_owner(typed: &str) -> Option<String> {
+    let (prefix, typed_name, names) = match typed.find(':') {
+        Some(idx) => (&typed[..=idx], &typed[idx + 1..], group_names()),
+        None => ("", typed, user_names()),
+    };
+    names
+        .into_iter()
+        .find(|name| name.starts_with(typed_name) && name != typed_name)
+        .map(|name| format!("{}{}", prefix, name))
+}
+
+/// tell whether the error is the system refusing the change, in
+/// which case the operation may be retried with more privileges
+#[cfg(unix)]
+pub fn is_permission_denied(err: &ProgramError) -> bool {
+    match err {
+        ProgramError::Io { source } => source.kind() == io::ErrorKind::PermissionDenied,
+        _ => false,
+    }
+}
+
+/// check the spec given to `chmod` or `chown` (the command), so that
+/// an invalid one is reported once, before any file is changed
+#[cfg(unix)]
+pub fn check_spec(command: &str, spec: &str) -> Result<(), ProgramError> {
+    match command {
+        "chown" => parse_owner(spec).map(|_| ()),
+        _ => parse_mode(spec, 0, false)
+            .map(|_| ())
+            .ok_or_else(|| ProgramError::InvalidMode { raw: spec.to_string() }),
+    }
+}
+
+/// change the mode of the file, as `chmod` would do with the given spec
+#[cfg(unix)]
+pub fn chmod(path: &Path, spec: &str) -> Result<(), ProgramError> {
+    let metadata = fs::metadata(path)?;
+    let mode = parse_mode(spec, metadata.mode(), metadata.is_dir())
+        .ok_or_else(|| ProgramError::InvalidMode { raw: spec.to_string() })?;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod mode_parsing_tests {
+
+    use super::parse_mode;
+
+    #[test]
+    fn test_octal_modes() {
+        assert_eq!(parse_mode("755", 0o644, false), Some(0o755));
+        assert_eq!(parse_mode("0600", 0o644, false), Some(0o600));
+        assert_eq!(parse_mode("789", 0o644, false), None);
+        assert_eq!(parse_mode("17777", 0o644, false), None);
+    }
+
+    #[test]
+    fn test_symbolic_modes() {
+        assert_eq!(parse_mode("u+x", 0o644, false), Some(0o744));
+        assert_eq!(parse_mode("+x", 0o644, false), Some(0o755));
+        assert_eq!(parse_mode("go-rw", 0o666, false), Some(0o600));
+        assert_eq!(parse_mode("a=r,u+w", 0o777, false), Some(0o644));
+        assert_eq!(parse_mode("u=rwx,g=rx,o=", 0o600, false), Some(0o750));
+        assert_eq!(parse_mode("a+X", 0o644, true), Some(0o755));
+        assert_eq!(parse_mode("a+X", 0o644, false), Some(0o644));
+        assert_eq!(parse_mode("u+s", 0o755, false), Some(0o4755));
+        assert_eq!(parse_mode("u+x-w", 0o644, false), Some(0o544));
+    }
+
+    #[test]
+    fn test_numeric_owners() {
+        assert_eq!(super::parse_owner("1000").unwrap(), (Some(1000), None));
+        assert_eq!(super::parse_owner("1000:100").unwrap(), (Some(1000), Some(100)));
+        assert_eq!(super::parse_owner(":100").unwrap(), (None, Some(100)));
+        assert!(super::parse_owner(":").is_err());
+    }
+
+    #[test]
+    fn test_invalid_modes() {
+        assert_eq!(parse_mode("", 0o644, false), None);
+        assert_eq!(parse_mode("u", 0o644, false), None);
+        assert_eq!(parse_mode("u+q", 0o644, false), None);
+        assert_eq!(parse_mode("z+x", 0o644, false), None);
+    }
+}
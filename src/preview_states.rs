@@ -0,0 +1,590 @@
+//! an application state displaying the content of a file, like a
+//! simple pager.
+//!
+//! One line is selected: it's the one `:copy_line` copies and the
+//! `{line}` of the external verbs, so that `$EDITOR +{line} {file}`
+//! opens the file at this line. A pattern typed in the input (a regex
+//! when it starts with a `/`) highlights the matches, *tab* and
+//! *shift-tab* going from a matching line to the next or previous one.
+//!
+//! The control chars of the file are displayed escaped (see the
+//! `control_chars` module), so that they're never executed by the
+//! terminal.
+//!
+//! Text files are highlighted according to their syntax, with the
+//! `syntax_theme` of the configuration, unless they're bigger than
+//! its `max_highlighted_size`.
+//!
+//! An image is described (format, size, EXIF data) and, when the
+//! terminal can display images, shown below its description (see
+//! the `image_preview` module).
+
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crossterm::{
+    cursor,
+    queue,
+    style::{Color, SetBackgroundColor},
+    terminal::{Clear, ClearType},
+};
+use minimad::Composite;
+
+use crate::{
+    app_context::AppContext,
+    app_state::{AppState, AppStateCmdResult},
+    commands::{Action, Command},
+    control_chars,
+    errors::ProgramError,
+    flat_tree::Tree,
+    fuzzy_scoring::FuzzyScoring,
+    image_preview::{self, ImageInfo, ImageProtocol},
+    io::W,
+    patterns::Pattern,
+    screens::Screen,
+    session::Session,
+    state_history::Place,
+    status::Status,
+    syntax_highlighting,
+    task_sync::TaskLifetime,
+    verb_store::PrefixSearchResult,
+    verbs::VerbExecutor,
+};
+
+/// the biggest part of a file which is read for the preview
+const MAX_PREVIEW_SIZE: u64 = 4 * 1024 * 1024;
+
+/// the part of the start of the file which is checked to tell
+/// whether it's a text file
+const BINARY_CHECK_SIZE: usize = 8 * 1024;
+
+/// what's displayed of the file
+enum Content {
+    Text {
+        lines: Vec<String>,
+        len: u64, // the size of the file
+        truncated: bool, // whether the file is bigger than MAX_PREVIEW_SIZE
+    },
+    Image {
+        info: ImageInfo,
+        description: Vec<String>,
+        bytes: Option<Vec<u8>>, // None when the image is too big to be displayed
+    },
+    Binary(u64), // a file which isn't text, with its size
+}
+
+/// an image, as built for the terminal, with the area it was built for
+struct Thumbnail {
+    area: (u16, u16), // the columns and rows available
+    sequence: Option<Vec<u8>>,
+}
+
+impl Content {
+    fn read(path: &Path) -> io::Result<Content> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let mut bytes = Vec::new();
+        file.take(MAX_PREVIEW_SIZE).read_to_end(&mut bytes)?;
+        if let Some(info) = ImageInfo::of(&bytes) {
+            let description = info
+                .description(len)
+                .iter()
+                .map(|line| control_chars::escape(line))
+                .collect();
+            let bytes = if len > MAX_PREVIEW_SIZE { None } else { Some(bytes) };
+            return Ok(Content::Image {
+                info,
+                description,
+                bytes,
+            });
+        }
+        if bytes.iter().take(BINARY_CHECK_SIZE).any(|&b| b == 0) {
+            return Ok(Content::Binary(len));
+        }
+        let lines = String::from_utf8_lossy(&bytes)
+            .lines()
+            .map(|line| control_chars::escape(&line.replace('\t', "    ")))
+            .collect();
+        Ok(Content::Text {
+            lines,
+            len,
+            truncated: len > MAX_PREVIEW_SIZE,
+        })
+    }
+    fn lines(&self) -> &[String] {
+        match self {
+            Content::Text { lines, .. } => lines,
+            Content::Image { description, .. } => description,
+            Content::Binary(_) => &[],
+        }
+    }
+}
+
+/// return the colors of the chars of the lines of a text file, or
+/// nothing when the file mustn't or can't be highlighted
+fn highlight(path: &Path, content: &Content, con: &AppContext) -> Vec<Vec<Color>> {
+    let args = &con.launch_args;
+    match (content, &args.syntax_theme) {
+        (Content::Text { lines, len, .. }, Some(theme)) if *len <= args.max_highlighted_size => {
+            syntax_highlighting::highlight(path, lines, theme, args.color_support)
+                .unwrap_or_default()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// return the index of the first line matching the pattern, starting
+/// from the given line (included) in the given direction and going
+/// round at the end
+fn find_matching_line(
+    lines: &[String],
+    pattern: &Pattern,
+    from: usize,
+    forward: bool,
+) -> Option<usize> {
+    let len = lines.len();
+    (0..len)
+        .map(|i| {
+            if forward {
+                (from + i) % len
+            } else {
+                (from + len - i % len) % len
+            }
+        })
+        .find(|&idx| pattern.score_of(&lines[idx]).is_some())
+}
+
+/// an application state displaying the content of a file
+pub struct PreviewState {
+    path: PathBuf, // the previewed file
+    root: PathBuf, // the root of the tree the preview was opened from
+    content: Content,
+    colors: Vec<Vec<Color>>, // the colors of the chars of the lines, when they're highlighted
+    selection: usize, // the selected line
+    scroll: usize,        // the first displayed line
+    page_height: usize,
+    pattern: Pattern,
+    fuzzy_scoring: FuzzyScoring,
+    thumbnail: Option<Thumbnail>, // built on first display, for images
+}
+
+impl PreviewState {
+    pub fn new(
+        path: PathBuf,
+        root: PathBuf,
+        fuzzy_scoring: FuzzyScoring,
+        screen: &Screen,
+        con: &AppContext,
+    ) -> io::Result<PreviewState> {
+        let content = Content::read(&path)?;
+        let colors = highlight(&path, &content, con);
+        Ok(PreviewState {
+            path,
+            root,
+            content,
+            colors,
+            selection: 0,
+            scroll: 0,
+            page_height: PreviewState::page_height_of(screen),
+            pattern: Pattern::None,
+            fuzzy_scoring,
+            thumbnail: None,
+        })
+    }
+
+    fn page_height_of(screen: &Screen) -> usize {
+        screen.height.max(2) as usize - 2
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// the number, starting at 1, of the selected line
+    pub fn line_number(&self) -> usize {
+        self.selection + 1
+    }
+
+    /// the content of the selected line, if it's a text file
+    pub fn selected_line(&self) -> Option<&str> {
+        self.content.lines().get(self.selection).map(|s| s.as_str())
+    }
+
+    /// move the selection, the displayed lines following it
+    pub fn move_selection(&mut self, dy: i32) {
+        let len = self.content.lines().len();
+        if len == 0 {
+            return;
+        }
+        let selection = self.selection as i32 + dy;
+        self.selection = selection.max(0).min(len as i32 - 1) as usize;
+        self.show_selection();
+    }
+
+    /// the number of displayed lines
+    pub fn page_height(&self) -> i32 {
+        self.page_height as i32
+    }
+
+    /// change the scroll so that the selection is visible
+    fn show_selection(&mut self) {
+        if self.selection < self.scroll {
+            self.scroll = self.selection;
+        } else if self.selection >= self.scroll + self.page_height {
+            self.scroll = self.selection + 1 - self.page_height;
+        }
+    }
+
+    /// select the next (or previous) line matching the pattern
+    fn select_match(&mut self, from: usize, forward: bool) -> AppStateCmdResult {
+        if !self.pattern.is_some() {
+            return AppStateCmdResult::Keep;
+        }
+        match find_matching_line(self.content.lines(), &self.pattern, from, forward) {
+            Some(idx) => {
+                self.selection = idx;
+                self.show_selection();
+                AppStateCmdResult::Keep
+            }
+            None => AppStateCmdResult::DisplayError("no matching line".to_string()),
+        }
+    }
+
+    /// change the pattern, selecting the first matching line
+    /// starting from the selection
+    fn set_pattern(&mut self, pattern: Pattern) -> AppStateCmdResult {
+        self.pattern = pattern;
+        self.select_match(self.selection, true)
+    }
+
+    /// return the bytes displaying the image in the given area, if
+    /// the file is an image and the terminal can display it. They're
+    /// kept as long as the area doesn't change.
+    fn thumbnail(&mut self, cols: u16, rows: u16) -> Option<&[u8]> {
+        let protocol = ImageProtocol::detect()?;
+        let (info, bytes) = match &self.content {
+            Content::Image {
+                info,
+                bytes: Some(bytes),
+                ..
+            } => (info, bytes),
+            _ => return None,
+        };
+        let dimensions = info.dimensions?;
+        let up_to_date = match &self.thumbnail {
+            Some(thumbnail) => thumbnail.area == (cols, rows),
+            None => false,
+        };
+        if !up_to_date {
+            let (image_cols, image_rows) = image_preview::fit(dimensions, cols, rows);
+            let sequence = image_preview::sequence(
+                protocol,
+                info.format,
+                bytes,
+                &self.path,
+                image_cols,
+                image_rows,
+            );
+            self.thumbnail = Some(Thumbnail {
+                area: (cols, rows),
+                sequence,
+            });
+        }
+        self.thumbnail.as_ref().and_then(|t| t.sequence.as_deref())
+    }
+
+    fn count_matching_lines(&self) -> usize {
+        self.content
+            .lines()
+            .iter()
+            .filter(|line| self.pattern.score_of(line).is_some())
+            .count()
+    }
+}
+
+impl AppState for PreviewState {
+    fn has_pending_task(&self) -> bool {
+        false
+    }
+
+    fn apply(
+        &mut self,
+        cmd: &mut Command,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<AppStateCmdResult, ProgramError> {
+        self.page_height = PreviewState::page_height_of(screen);
+        Ok(match &cmd.action {
+            Action::Back | Action::UpTree | Action::UpTreeFromRoot => {
+                if self.pattern.is_some() {
+                    cmd.raw.clear();
+                    self.pattern = Pattern::None;
+                    AppStateCmdResult::Keep
+                } else {
+                    AppStateCmdResult::PopState
+                }
+            }
+            Action::FuzzyPatternEdit(pat) => {
+                if pat.is_empty() {
+                    self.pattern = Pattern::None;
+                    AppStateCmdResult::Keep
+                } else {
+                    self.set_pattern(Pattern::fuzzy(pat, self.fuzzy_scoring))
+                }
+            }
+            Action::RegexEdit(pat, flags) => match Pattern::regex(pat, flags) {
+                Ok(pattern) => self.set_pattern(pattern),
+                Err(e) => AppStateCmdResult::DisplayError(format!("{}", e)),
+            },
+            Action::CompositePatternEdit(pat) => {
+                match Pattern::composite(pat, self.fuzzy_scoring) {
+                    Ok(pattern) => self.set_pattern(pattern),
+                    Err(e) => AppStateCmdResult::DisplayError(format!("{}", e)),
+                }
+            }
+            Action::Next => self.select_match(self.selection + 1, true),
+            Action::Previous => {
+                let len = self.content.lines().len();
+                self.select_match((self.selection + len.max(1) - 1) % len.max(1), false)
+            }
+            Action::MoveSelection(dy) => {
+                self.move_selection(*dy);
+                AppStateCmdResult::Keep
+            }
+            Action::Click(_, y) => {
+                let idx = self.scroll + *y as usize;
+                if (*y as usize) < self.page_height && idx < self.content.lines().len() {
+                    self.selection = idx;
+                }
+                AppStateCmdResult::Keep
+            }
+            Action::VerbIndex(index) => {
+                let verb = &con.verb_store.verbs[*index];
+                self.execute_verb(verb, &verb.invocation, screen, con)?
+            }
+            Action::VerbInvocate(invocation) => match con.verb_store.search(&invocation.name) {
+                PrefixSearchResult::Match(verb) => {
+                    self.execute_verb(verb, &invocation, screen, con)?
+                }
+                _ => AppStateCmdResult::verb_not_found(&invocation.name),
+            },
+            _ => AppStateCmdResult::Keep,
+        })
+    }
+
+    /// read the file again, as a verb may have changed it
+    fn refresh(&mut self, screen: &Screen, con: &AppContext) -> Command {
+        match Content::read(&self.path) {
+            Ok(content) => {
+                self.colors = highlight(&self.path, &content, con);
+                self.content = content;
+                self.thumbnail = None;
+            }
+            Err(e) => {
+                warn!("error while reading {:?} again: {:?}", &self.path, e);
+            }
+        }
+        self.page_height = PreviewState::page_height_of(screen);
+        let len = self.content.lines().len();
+        self.selection = self.selection.min(len.max(1) - 1);
+        self.show_selection();
+        Command::from_pattern(&self.pattern)
+    }
+
+    fn do_pending_task(&mut self, _screen: &mut Screen, _tl: &TaskLifetime) {
+        unreachable!();
+    }
+
+    fn display(
+        &mut self,
+        w: &mut W,
+        screen: &Screen,
+        _con: &AppContext,
+    ) -> Result<(), ProgramError> {
+        self.page_height = PreviewState::page_height_of(screen);
+        self.show_selection();
+        let skin = &screen.skin;
+        let lines = self.content.lines();
+        let number_width = lines.len().to_string().len();
+        let text_width = (screen.width as usize).saturating_sub(number_width + 2);
+        for y in 0..self.page_height {
+            queue!(w, cursor::MoveTo(0, y as u16))?;
+            let idx = self.scroll + y;
+            let selected = idx == self.selection;
+            let mut base_style = skin.default.clone();
+            let mut match_style = skin.char_match.clone();
+            if selected {
+                if let Some(c) = skin.selected_line.get_bg() {
+                    base_style.set_bg(c);
+                    match_style.set_bg(c);
+                }
+            }
+            match (&self.content, lines.get(idx)) {
+                (_, Some(line)) => {
+                    let mut number_style = skin.tree.clone();
+                    if let Some(c) = base_style.get_bg() {
+                        number_style.set_bg(c);
+                    }
+                    number_style.queue(w, format!("{:>1$} ", idx + 1, number_width))?;
+                    let mut matched_string = self.pattern.style(line, &base_style, &match_style);
+                    if let Some(colors) = self.colors.get(idx) {
+                        matched_string = matched_string.with_colors(colors);
+                    }
+                    matched_string.write_fit_on(w, text_width)?;
+                }
+                (Content::Text { truncated: true, .. }, None) if idx == lines.len() => {
+                    skin.file_error.queue_str(w, "… (the rest of the file isn't displayed)")?;
+                }
+                (Content::Binary(len), None) if idx == 0 => {
+                    skin.file_error.queue(
+                        w,
+                        format!("this file isn't a text file ({} bytes)", len),
+                    )?;
+                }
+                _ => {}
+            }
+            if selected {
+                skin.selected_line.queue_bg(w)?;
+            } else {
+                skin.default.queue_bg(w)?;
+            }
+            queue!(w, Clear(ClearType::UntilNewLine))?;
+            queue!(w, SetBackgroundColor(Color::Reset))?;
+        }
+        // the image is drawn below the description, over the empty lines
+        let top = lines.len() + 1;
+        if top + 1 < self.page_height {
+            let rows = (self.page_height - top) as u16;
+            if let Some(sequence) = self.thumbnail(text_width as u16, rows) {
+                queue!(w, cursor::MoveTo((number_width + 1) as u16, top as u16))?;
+                w.write_all(sequence)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_status(
+        &self,
+        w: &mut W,
+        cmd: &Command,
+        screen: &Screen,
+        con: &AppContext,
+    ) -> Result<(), ProgramError> {
+        match &cmd.action {
+            Action::VerbEdit(invocation) => match con.verb_store.search(&invocation.name) {
+                PrefixSearchResult::NoMatch => {
+                    Status::from_error(mad_inline!("No matching verb")).display(w, screen)
+                }
+                PrefixSearchResult::Match(verb) => verb.write_status(
+                    w,
+                    None,
+                    self.path.clone(),
+                    &self.root,
+                    self.line_number(),
+                    invocation,
+                    screen,
+                ),
+                PrefixSearchResult::TooManyMatches => Status::from_message(mad_inline!(
+                    "Type a verb then *enter* to execute it"
+                )).display(w, screen),
+            },
+            Action::PendingChord(first) => {
+                let hint = con.verb_store.chord_hint(*first);
+                Status::from_message(Composite::from_inline(&hint)).display(w, screen)
+            }
+            _ if self.pattern.is_some() => {
+                let text = format!(
+                    "Line {} of {}, {} matching lines. Hit *tab* for the next match, *esc* to clear the pattern",
+                    self.line_number(),
+                    self.content.lines().len(),
+                    self.count_matching_lines(),
+                );
+                Status::from_message(Composite::from_inline(&text)).display(w, screen)
+            }
+            _ => {
+                let text = format!(
+                    "Line {} of {}. Type a pattern to search (a `/` for a regex), a space to start a verb, or *esc* to go back",
+                    self.line_number(),
+                    self.content.lines().len(),
+                );
+                Status::from_message(Composite::from_inline(&text)).display(w, screen)
+            }
+        }
+    }
+
+    /// there's no meaningful flags here
+    fn write_flags(
+        &self,
+        w: &mut W,
+        screen: &mut Screen,
+        _con: &AppContext,
+    ) -> Result<(), ProgramError> {
+        screen.skin.default.queue_bg(w)?;
+        queue!(w, Clear(ClearType::UntilNewLine))?;
+        Ok(())
+    }
+
+    /// the preview isn't restored on resume
+    fn session(&self) -> Option<Session> {
+        None
+    }
+
+    fn place(&self) -> Option<Place> {
+        None
+    }
+
+    fn tree(&self) -> Option<&Tree> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod preview_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_matching_lines() {
+        let lines: Vec<String> = vec!["fn main() {", "    let a = 1;", "}", "fn b() {}"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let pattern = Pattern::regex("^fn", "").unwrap();
+        assert_eq!(find_matching_line(&lines, &pattern, 0, true), Some(0));
+        assert_eq!(find_matching_line(&lines, &pattern, 1, true), Some(3));
+        assert_eq!(find_matching_line(&lines, &pattern, 3, false), Some(3));
+        assert_eq!(find_matching_line(&lines, &pattern, 2, false), Some(0));
+        // the search goes round
+        assert_eq!(find_matching_line(&lines, &pattern, 0, false), Some(0));
+        assert_eq!(find_matching_line(&lines[..3], &pattern, 1, true), Some(0));
+        let pattern = Pattern::regex("nothing", "").unwrap();
+        assert_eq!(find_matching_line(&lines, &pattern, 0, true), None);
+        assert_eq!(find_matching_line(&[], &pattern, 0, true), None);
+    }
+
+    #[test]
+    fn check_control_chars_are_escaped() {
+        use std::{env, fs, process};
+        use termimad::CompoundStyle;
+        let path = env::temp_dir().join(format!("broot-test-preview-{}.txt", process::id()));
+        fs::write(&path, "copied: \x1b]52;c;Y3VybCBldmlsLnNo\x07\n\x1b[2Jcleared\r\n").unwrap();
+        let content = Content::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(content.lines().len(), 2);
+        let style = CompoundStyle::default();
+        let mut written = Vec::new();
+        for line in content.lines() {
+            Pattern::None.style(line, &style, &style).write_fit_on(&mut written, 80).unwrap();
+        }
+        assert!(!written.contains(&0x1b));
+        assert!(!written.contains(&0x07));
+        assert!(String::from_utf8(written).unwrap().contains("^[]52;c;"));
+    }
+}
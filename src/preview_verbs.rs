@@ -0,0 +1,64 @@
+//! this modules defines the execution of verbs on the preview
+//! of a file
+
+use crate::{
+    app_context::AppContext,
+    app_state::AppStateCmdResult,
+    clipboard_copy,
+    errors::ProgramError,
+    preview_states::PreviewState,
+    screens::Screen,
+    verb_invocation::VerbInvocation,
+    verbs::{Verb, VerbExecutor},
+};
+
+impl VerbExecutor for PreviewState {
+    fn execute_verb(
+        &mut self,
+        verb: &Verb,
+        invocation: &VerbInvocation,
+        screen: &mut Screen,
+        con: &AppContext,
+    ) -> Result<AppStateCmdResult, ProgramError> {
+        if let Some(err) = verb.match_error(invocation) {
+            return Ok(AppStateCmdResult::DisplayError(err));
+        }
+        Ok(match verb.execution.as_ref() {
+            ":back" => AppStateCmdResult::PopState,
+            ":copy_line" => match self.selected_line() {
+                Some(line) => clipboard_copy::copy_to_clipboard(line)?,
+                None => AppStateCmdResult::DisplayError("no line to copy".to_string()),
+            },
+            ":copy_path" => clipboard_copy::copy_to_clipboard(&self.path().to_string_lossy())?,
+            ":line_down" => {
+                self.move_selection(1);
+                AppStateCmdResult::Keep
+            }
+            ":line_up" => {
+                self.move_selection(-1);
+                AppStateCmdResult::Keep
+            }
+            ":page_down" => {
+                self.move_selection(self.page_height());
+                AppStateCmdResult::Keep
+            }
+            ":page_up" => {
+                self.move_selection(-self.page_height());
+                AppStateCmdResult::Keep
+            }
+            ":preview" => AppStateCmdResult::Keep,
+            ":quit" => AppStateCmdResult::Quit,
+            // the other built-in verbs apply to the tree
+            _ if verb.execution.starts_with(':') => AppStateCmdResult::PopStateAndReapply,
+            // the external ones apply to the previewed file, at the selected line
+            _ => verb.to_cmd_result(
+                &self.path().to_path_buf(),
+                &self.root().to_path_buf(),
+                self.line_number(),
+                &invocation.args,
+                screen,
+                con,
+            )?,
+        })
+    }
+}
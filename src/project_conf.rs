@@ -0,0 +1,299 @@
+//! the configuration a project may have in a `.broot.toml` file at
+//! its root, merged over the user's one when broot is launched on it:
+//!
+//! ```toml
+//! ignore = ["target", "*.log"] # globs of the paths never shown
+//!
+//! [toggles] # turned on at launch
+//! hidden = true
+//! sizes = true
+//!
+//! [[verbs]]
+//! invocation = "test"
+//! execution = "cargo test"
+//! leave_broot = false
+//! ```
+//!
+//! As its verbs run commands, a project configuration is only used once
+//! the user said it could be trusted. The trusted configurations are
+//! recorded, with their whole content, in a file of the data directory,
+//! so that a modified one must be trusted again.
+//!
+//! Only the configuration of the directory broot is launched on is read:
+//! focusing another project doesn't apply its `.broot.toml`.
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use termimad::MadSkin;
+use toml::Value;
+
+use crate::{
+    cli,
+    conf::{self, Conf},
+    errors::{ConfError, ProgramError},
+    path_filters::{SpecialHandling, SpecialPath},
+    session::Toggles,
+    tree_options::TreeOptions,
+    verb_conf::{self, VerbConf},
+    verbs,
+};
+
+/// the name of the file of a project configuration, in the root
+pub const FILE_NAME: &str = ".broot.toml";
+
+#[derive(Debug)]
+pub struct ProjectConf {
+    pub path: PathBuf,
+    pub toggles: Option<Toggles>,
+    pub ignore: Vec<SpecialPath>,
+    pub verbs: Vec<VerbConf>,
+}
+
+/// return the path to the file listing the trusted project configurations
+pub fn trust_file_path() -> PathBuf {
+    conf::data_dir().join("trusted_projects.toml")
+}
+
+/// the key of the configuration in the table of the trusted ones:
+/// its canonical path, so that two paths of the same file match
+fn trust_key(path: &Path) -> String {
+    fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// read the table of the trusted configurations, whose keys
+/// are their paths and whose values are their contents
+fn read_trusted() -> toml::value::Table {
+    match fs::read_to_string(trust_file_path()).map(|data| data.parse::<Value>()) {
+        Ok(Ok(Value::Table(trusted))) => trusted,
+        _ => toml::value::Table::new(),
+    }
+}
+
+fn is_trusted_in(trusted: &toml::value::Table, key: &str, data: &str) -> bool {
+    trusted.get(key).and_then(|v| v.as_str()) == Some(data)
+}
+
+fn is_trusted(path: &Path, data: &str) -> bool {
+    is_trusted_in(&read_trusted(), &trust_key(path), data)
+}
+
+/// record the configuration as trusted, replacing the trust
+/// given to a previous content of the same file
+fn trust(path: &Path, data: &str) -> Result<(), io::Error> {
+    let mut trusted = read_trusted();
+    trusted.insert(trust_key(path), Value::String(data.to_string()));
+    fs::create_dir_all(conf::data_dir())?;
+    fs::write(trust_file_path(), Value::Table(trusted).to_string())
+}
+
+/// describe a verb with everything deciding what it runs, how and
+/// when, as the user must know all of it to trust the configuration
+fn verb_description(verb: &VerbConf) -> String {
+    let mut triggers = vec![format!("`{}`", verb.invocation)];
+    if let Some(shortcut) = &verb.shortcut {
+        triggers.push(format!("shortcut `{}`", shortcut));
+    }
+    if verb.key.is_some() {
+        triggers.push(format!("key `{}`", verbs::key_event_desc(&verb.key)));
+    }
+    if let Some(chord) = verb.chord {
+        triggers.push(format!("chord `{}`", verbs::chord_desc(chord)));
+    }
+    let mut details = Vec::new();
+    for (name, value) in &verb.env {
+        details.push(format!("`{}={}`", name, value));
+    }
+    let flags = [
+        ("from_shell", verb.from_shell),
+        ("leave_broot", verb.leave_broot),
+        ("confirm", verb.confirm),
+        ("dry_run", verb.dry_run),
+        ("capture", verb.capture),
+        ("async", verb.run_async),
+    ];
+    for (name, value) in flags.iter() {
+        if let Some(value) = value {
+            details.push(format!("`{} = {}`", name, value));
+        }
+    }
+    if let Some(apply_to) = verb.apply_to {
+        details.push(format!("on {}", apply_to.description()));
+    }
+    for extension in &verb.name_condition.extensions {
+        details.push(format!("on `*.{}`", extension));
+    }
+    for glob in &verb.name_condition.globs {
+        details.push(format!("on `{}`", glob.as_str()));
+    }
+    let mut description = format!("* {} runs `{}`", triggers.join(", "), verb.execution);
+    if !details.is_empty() {
+        description.push_str(" with ");
+        description.push_str(&details.join(", "));
+    }
+    description
+}
+
+/// ask the user, on the terminal, whether the configuration can be trusted
+fn ask_trust(project_conf: &ProjectConf, skin: &MadSkin) -> Result<bool, ProgramError> {
+    let mut md = format!(
+        "This directory has a project configuration: `{}`\n",
+        project_conf.path.to_string_lossy(),
+    );
+    if !project_conf.verbs.is_empty() {
+        md.push_str("It defines these verbs:\n");
+        for verb in &project_conf.verbs {
+            md.push_str(&verb_description(verb));
+            md.push('\n');
+        }
+    }
+    md.push_str("Do you trust it ? `[y N]`");
+    skin.print_text(&md);
+    cli::ask_explicit_authorization()
+}
+
+impl ProjectConf {
+    /// read the project configuration at the root of the tree, if any,
+    /// with the content of its file
+    fn read(root: &Path) -> Result<Option<(ProjectConf, String)>, ConfError> {
+        let path = root.join(FILE_NAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let data = fs::read_to_string(&path)?;
+        let project_conf = ProjectConf::from_toml(path, &data)?;
+        Ok(Some((project_conf, data)))
+    }
+
+    fn from_toml(path: PathBuf, data: &str) -> Result<ProjectConf, ConfError> {
+        let root: Value = data.parse::<Value>()?;
+        let toggles = root.get("toggles").map(|v| Toggles::read(Some(v)));
+        let mut ignore = Vec::new();
+        if let Some(Value::Array(globs)) = root.get("ignore") {
+            for glob in globs.iter().filter_map(|v| v.as_str()) {
+                ignore.push(SpecialPath::new(glob, SpecialHandling::Hide)?);
+            }
+        }
+        let verbs = conf::read_verbs(&root)?;
+        Ok(ProjectConf {
+            path,
+            toggles,
+            ignore,
+            verbs,
+        })
+    }
+
+    /// return the configuration of the project at the root if the
+    /// user trusts it, asking when it's unknown and `interactive` is
+    /// true (the terminal isn't yet given to the application)
+    pub fn load(root: &Path, interactive: bool) -> Result<Option<ProjectConf>, ProgramError> {
+        let (project_conf, data) = match ProjectConf::read(root)? {
+            Some(read) => read,
+            None => return Ok(None),
+        };
+        if is_trusted(&project_conf.path, &data) {
+            return Ok(Some(project_conf));
+        }
+        if !interactive {
+            warn!("untrusted project configuration {:?} ignored", &project_conf.path);
+            return Ok(None);
+        }
+        if ask_trust(&project_conf, &cli::mad_skin())? {
+            trust(&project_conf.path, &data)?;
+            Ok(Some(project_conf))
+        } else {
+            cli::mad_skin().print_text("The project configuration is **ignored** for now.");
+            Ok(None)
+        }
+    }
+
+    /// turn on the toggles of the project in the tree options, then
+    /// merge the project configuration over the user's one
+    pub fn apply(mut self, conf: &mut Conf, tree_options: &mut TreeOptions) -> Result<(), ConfError> {
+        if let Some(toggles) = self.toggles.take() {
+            toggles.add_to(tree_options);
+        }
+        self.merge_into(conf)
+    }
+
+    /// merge the ignored paths and the verbs over the user configuration,
+    /// the verbs of the project taking precedence
+    pub fn merge_into(self, conf: &mut Conf) -> Result<(), ConfError> {
+        conf.special_paths.extend(self.ignore);
+        let mut verbs = self.verbs;
+        verbs.append(&mut conf.verbs);
+        verb_conf::check_chord_conflicts(&verbs)?;
+        conf.verbs = verbs;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod project_conf_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_parsing() {
+        let data = r#"
+            ignore = ["target", "*.log"]
+            [toggles]
+            hidden = true
+            [[verbs]]
+            invocation = "test"
+            execution = "cargo test"
+            leave_broot = false
+        "#;
+        let project_conf = ProjectConf::from_toml(PathBuf::from("p/.broot.toml"), data).unwrap();
+        assert_eq!(project_conf.ignore.len(), 2);
+        assert_eq!(project_conf.verbs.len(), 1);
+        let toggles = project_conf.toggles.unwrap();
+        assert!(toggles.show_hidden);
+        assert!(!toggles.show_sizes);
+        assert!(ProjectConf::from_toml(PathBuf::from("p/.broot.toml"), "ignore = [\"[\"]").is_err());
+    }
+
+    #[test]
+    fn check_verb_description() {
+        let data = r#"
+            [[verbs]]
+            invocation = "open"
+            shortcut = "o"
+            key = "ctrl-o"
+            execution = "xdg-open {file}"
+            leave_broot = false
+            confirm = false
+            extensions = ["pdf"]
+            env = { LD_PRELOAD = "/tmp/hook.so" }
+        "#;
+        let project_conf = ProjectConf::from_toml(PathBuf::from("p/.broot.toml"), data).unwrap();
+        assert_eq!(
+            verb_description(&project_conf.verbs[0]),
+            "* `open`, shortcut `o`, key `^o` runs `xdg-open {file}` with \
+            `LD_PRELOAD=/tmp/hook.so`, `leave_broot = false`, `confirm = false`, on `*.pdf`",
+        );
+    }
+
+    #[test]
+    fn check_trust() {
+        let mut trusted = toml::value::Table::new();
+        let key = trust_key(Path::new("/no/such/project/.broot.toml"));
+        trusted.insert(key.clone(), Value::String("a = 1".to_string()));
+        // the trust survives writing and reading the table
+        let trusted = match Value::Table(trusted).to_string().parse::<Value>() {
+            Ok(Value::Table(trusted)) => trusted,
+            _ => panic!("invalid trust table"),
+        };
+        assert!(is_trusted_in(&trusted, &key, "a = 1"));
+        assert!(!is_trusted_in(&trusted, &key, "a = 2"));
+        // a path sharing a suffix with the trusted one isn't trusted
+        let other_key = trust_key(Path::new("/other/no/such/project/.broot.toml"));
+        assert!(!is_trusted_in(&trusted, &other_key, "a = 1"));
+    }
+}
@@ -4,7 +4,10 @@ use std::fmt;
 
 use regex;
 
-use crate::{errors::RegexError, patterns};
+use crate::{
+    errors::RegexError,
+    patterns::{self, Scorer},
+};
 
 #[derive(Debug, Clone)]
 pub struct RegexPattern {
@@ -45,10 +48,11 @@ impl RegexPattern {
         //  find over is_match
         match self.rex.find(candidate) {
             Some(rm) => {
-                let mut pos = Vec::with_capacity(rm.end() - rm.start());
-                for i in rm.start()..rm.end() {
-                    pos.push(i);
-                }
+                // the regex gives byte offsets, while the positions
+                // of a match are char indexes
+                let start = candidate[..rm.start()].chars().count();
+                let len = rm.as_str().chars().count();
+                let pos = (start..start + len).collect();
                 Some(patterns::Match { score: 1, pos })
             }
             None => None,
@@ -62,3 +66,18 @@ impl RegexPattern {
         targeted_size
     }
 }
+
+impl Scorer for RegexPattern {
+    fn find(&self, candidate: &str) -> Option<patterns::Match> {
+        self.find(candidate)
+    }
+    fn score_of(&self, candidate: &str) -> Option<i32> {
+        self.find(candidate).map(|m| m.score)
+    }
+    fn optimal_result_number(&self, targeted_size: usize) -> usize {
+        self.optimal_result_number(targeted_size)
+    }
+    fn has_ranking(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,344 @@
+//! a tree source reading a remote server, given as `sftp://user@host/path`.
+//!
+//! The server is read with the `ssh` command, which must be able to
+//! connect without asking for a password (with a key or an agent), and
+//! GNU `find` on the server. The connection is shared between the
+//! commands thanks to the ControlMaster option of ssh, its socket
+//! being in a private directory of broot's data directory.
+//!
+//! The content of the remote files isn't read: only browsing and
+//! searching the names are possible.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Mutex,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::{
+    conf,
+    errors::ProgramError,
+    tree_sources::{EntryMetadata, EntryType, SourceEntry, TreeSource},
+};
+
+/// the format of the lines written by `find -printf`, the path
+/// being last as it's the field the most likely to contain a tab
+const FIND_FORMAT: &str = r"%y\t%s\t%T@\t%m\t%U\t%G\t%l\t%p\0";
+
+/// a parsed `sftp://[user@]host[:port][/path]` url
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteUrl {
+    pub destination: String, // [user@]host, as given to ssh
+    pub port: Option<u16>,
+    pub path: Option<PathBuf>, // None for the home directory
+}
+
+impl RemoteUrl {
+    pub fn parse(raw: &str) -> Option<RemoteUrl> {
+        let rest = raw.strip_prefix("sftp://")?;
+        let (authority, path) = match rest.find('/') {
+            Some(idx) => (&rest[..idx], &rest[idx..]),
+            None => (rest, ""),
+        };
+        let (destination, port) = match authority.rfind(':') {
+            Some(idx) => (&authority[..idx], Some(authority[idx + 1..].parse().ok()?)),
+            None => (authority, None),
+        };
+        // a destination starting with a dash would be read by ssh as an option
+        if destination.is_empty() || destination.ends_with('@') || destination.starts_with('-') {
+            return None;
+        }
+        let path = match path.trim_end_matches('/') {
+            "" => None,
+            path => Some(PathBuf::from(path)),
+        };
+        Some(RemoteUrl {
+            destination: destination.to_string(),
+            port,
+            path,
+        })
+    }
+}
+
+/// what's known of a remote entry
+#[derive(Debug, Clone)]
+struct RemoteEntry {
+    metadata: EntryMetadata,
+    target: Option<PathBuf>, // for links
+}
+
+/// a tree source reading a server through ssh
+#[derive(Debug)]
+pub struct RemoteSource {
+    url: RemoteUrl,
+    root: PathBuf,
+    entries: Mutex<HashMap<PathBuf, RemoteEntry>>,
+    children: Mutex<HashMap<PathBuf, Vec<SourceEntry>>>,
+}
+
+/// wrap the string in single quotes for the remote shell
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// return the directory of the sockets of the shared ssh connections,
+/// creating it if needed. It's readable only by the user, so that no
+/// other user can create or use those sockets.
+fn control_dir() -> io::Result<PathBuf> {
+    let dir = conf::data_dir().join("ssh");
+    fs::create_dir_all(&dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(dir)
+}
+
+/// parse a record written by find with FIND_FORMAT (without the
+/// terminating NUL char)
+fn parse_record(record: &str) -> Option<(PathBuf, RemoteEntry)> {
+    let mut fields = record.splitn(8, '\t');
+    let entry_type = match fields.next()? {
+        "d" => EntryType::Dir,
+        "l" => EntryType::Symlink,
+        _ => EntryType::File,
+    };
+    let len = fields.next()?.parse().ok()?;
+    let modified = fields
+        .next()?
+        .parse::<f64>()
+        .ok()
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs as u64));
+    let mode = u32::from_str_radix(fields.next()?, 8).ok()?;
+    let uid = fields.next()?.parse().ok()?;
+    let gid = fields.next()?.parse().ok()?;
+    let target = match fields.next()? {
+        "" => None,
+        target => Some(PathBuf::from(target)),
+    };
+    let path = PathBuf::from(fields.next()?);
+    let metadata = EntryMetadata {
+        entry_type,
+        len,
+        modified,
+        created: None,
+        accessed: None,
+        mode,
+        uid,
+        gid,
+        dev: 0,
+        ino: 0,
+        nlink: 0,
+        in_archive: false,
+    };
+    Some((path, RemoteEntry { metadata, target }))
+}
+
+impl RemoteSource {
+    /// check the connection and the root, and return the source
+    pub fn connect(url: RemoteUrl) -> Result<RemoteSource, ProgramError> {
+        let mut source = RemoteSource {
+            url,
+            root: PathBuf::from("/"),
+            entries: Mutex::new(HashMap::new()),
+            children: Mutex::new(HashMap::new()),
+        };
+        source.root = match &source.url.path {
+            Some(path) => path.clone(),
+            None => {
+                let home = source.run("pwd").map_err(|e| ProgramError::Remote {
+                    details: e.to_string(),
+                })?;
+                PathBuf::from(home.trim())
+            }
+        };
+        let root = source.root.clone();
+        match source.symlink_metadata(&root) {
+            Ok(md) if md.entry_type.is_dir() => Ok(source),
+            Ok(_) => Err(ProgramError::Remote {
+                details: format!("{:?} isn't a directory", &root),
+            }),
+            Err(e) => Err(ProgramError::Remote {
+                details: e.to_string(),
+            }),
+        }
+    }
+
+    /// the remote directory to open first
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// run a command on the server and return its standard output
+    fn run(&self, remote_command: &str) -> io::Result<String> {
+        // %C is a hash of the destination, short enough for a socket path
+        let control_path = control_dir()?.join("%C");
+        let mut command = Command::new("ssh");
+        command
+            .arg("-o")
+            .arg("BatchMode=yes") // there's no way to type a password
+            .arg("-o")
+            .arg("ControlMaster=auto")
+            .arg("-o")
+            .arg(format!("ControlPath=\"{}\"", control_path.to_string_lossy()))
+            .arg("-o")
+            .arg("ControlPersist=60");
+        if let Some(port) = self.url.port {
+            command.arg("-p").arg(port.to_string());
+        }
+        debug!("remote command: {:?}", remote_command);
+        let output = command
+            .arg("--")
+            .arg(&self.url.destination)
+            .arg(remote_command)
+            .stdin(Stdio::null())
+            .output()?;
+        if !output.status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// run find with the given options before and after the path,
+    /// and remember the found entries
+    fn find(
+        &self,
+        before: &str,
+        path: &Path,
+        after: &str,
+    ) -> io::Result<Vec<(PathBuf, RemoteEntry)>> {
+        let output = self.run(&format!(
+            "find {} {} {} -printf {}",
+            before,
+            quote(&path.to_string_lossy()),
+            after,
+            quote(FIND_FORMAT),
+        ))?;
+        let found: Vec<(PathBuf, RemoteEntry)> = output
+            .split('\0')
+            .filter(|record| !record.is_empty())
+            .filter_map(parse_record)
+            .collect();
+        let mut entries = self.entries.lock().unwrap();
+        for (path, entry) in &found {
+            entries.insert(path.clone(), entry.clone());
+        }
+        Ok(found)
+    }
+
+    fn entry(&self, path: &Path) -> io::Result<RemoteEntry> {
+        if let Some(entry) = self.entries.lock().unwrap().get(path) {
+            return Ok(entry.clone());
+        }
+        self.find("", path, "-maxdepth 0")?
+            .into_iter()
+            .next()
+            .map(|(_, entry)| entry)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not found"))
+    }
+}
+
+impl TreeSource for RemoteSource {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<SourceEntry>> {
+        if let Some(children) = self.children.lock().unwrap().get(dir) {
+            return Ok(children.clone());
+        }
+        // -H so that a link to a directory, when focused, is entered
+        let children: Vec<SourceEntry> = self
+            .find("-H", dir, "-mindepth 1 -maxdepth 1")?
+            .into_iter()
+            .map(|(path, entry)| SourceEntry {
+                path,
+                entry_type: entry.metadata.entry_type,
+            })
+            .collect();
+        self.children.lock().unwrap().insert(dir.to_path_buf(), children.clone());
+        Ok(children)
+    }
+    fn metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        let entry = self.entry(path)?;
+        if !entry.metadata.entry_type.is_symlink() {
+            return Ok(entry.metadata);
+        }
+        // the link is followed by the server, the result isn't cached
+        // as it would replace the entry of the link
+        let output = self.run(&format!(
+            "find -L {} -maxdepth 0 -printf {}",
+            quote(&path.to_string_lossy()),
+            quote(FIND_FORMAT),
+        ))?;
+        output
+            .split('\0')
+            .filter_map(parse_record)
+            .next()
+            .map(|(_, entry)| entry.metadata)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "broken link"))
+    }
+    fn symlink_metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        self.entry(path).map(|entry| entry.metadata)
+    }
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.entry(path)?
+            .target
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "not a link"))
+    }
+    fn is_local(&self) -> bool {
+        false
+    }
+    fn clear_cache(&self) {
+        self.entries.lock().unwrap().clear();
+        self.children.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod remote_parsing_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_url_parsing() {
+        assert_eq!(
+            RemoteUrl::parse("sftp://me@server.org/var/log/"),
+            Some(RemoteUrl {
+                destination: "me@server.org".to_string(),
+                port: None,
+                path: Some(PathBuf::from("/var/log")),
+            }),
+        );
+        assert_eq!(
+            RemoteUrl::parse("sftp://server:2222"),
+            Some(RemoteUrl {
+                destination: "server".to_string(),
+                port: Some(2222),
+                path: None,
+            }),
+        );
+        assert_eq!(RemoteUrl::parse("sftp://"), None);
+        assert_eq!(RemoteUrl::parse("sftp://me@"), None);
+        assert_eq!(RemoteUrl::parse("sftp://-oProxyCommand=x/"), None);
+        assert_eq!(RemoteUrl::parse("sftp://server:port/"), None);
+        assert_eq!(RemoteUrl::parse("/home/me"), None);
+    }
+
+    #[test]
+    fn check_record_parsing() {
+        let (path, entry) = parse_record("l\t7\t1573140000.5\t777\t1000\t100\tlib/a\t/srv/with\ttab").unwrap();
+        assert_eq!(path, PathBuf::from("/srv/with\ttab"));
+        assert!(entry.metadata.entry_type.is_symlink());
+        assert_eq!(entry.metadata.len, 7);
+        assert_eq!(entry.metadata.mode, 0o777);
+        assert_eq!(entry.target, Some(PathBuf::from("lib/a")));
+        let (_, entry) = parse_record("d\t4096\t1573140000.0\t755\t0\t0\t\t/srv").unwrap();
+        assert!(entry.metadata.entry_type.is_dir());
+        assert_eq!(entry.target, None);
+        assert!(parse_record("d\t4096").is_none());
+    }
+}
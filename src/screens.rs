@@ -1,4 +1,4 @@
-use std::io::Write;
+use std::{io::Write, time::Duration};
 
 use crossterm::{
     cursor,
@@ -20,6 +20,9 @@ use crate::{
 
 pub static FLAGS_AREA_WIDTH: u16 = 10;
 
+/// the period at which the size of the terminal is checked
+pub static SIZE_POLL_PERIOD: Duration = Duration::from_millis(200);
+
 pub struct Screen {
     pub width: u16,
     pub height: u16,
@@ -31,20 +34,32 @@ pub struct Screen {
 
 impl Screen {
     pub fn new(con: &AppContext, skin: Skin) -> Result<Screen, ProgramError> {
+        let mut screen = Screen::with_size(skin, 0, 0);
+        screen.read_size(con)?;
+        Ok(screen)
+    }
+    /// build a screen of the given size, without querying the terminal
+    pub fn with_size(skin: Skin, width: u16, height: u16) -> Screen {
         let mut input_field = InputField::new(Area::new(0, 0, 10, 1));
         input_field.set_normal_style(CompoundStyle::from(skin.input.clone()));
         let status_skin = StatusMadSkinSet::from_skin(&skin);
         let help_skin = mad_skin::make_help_mad_skin(&skin);
-        let mut screen = Screen {
-            width: 0,
-            height: 0,
+        Screen {
+            width,
+            height,
             skin,
             input_field,
             status_skin,
             help_skin,
-        };
-        screen.read_size(con)?;
-        Ok(screen)
+        }
+    }
+    /// replace the skin and all the styles which are derived from it
+    /// (the screen must be redrawn afterwards)
+    pub fn set_skin(&mut self, skin: Skin) {
+        self.input_field.set_normal_style(CompoundStyle::from(skin.input.clone()));
+        self.status_skin = StatusMadSkinSet::from_skin(&skin);
+        self.help_skin = mad_skin::make_help_mad_skin(&skin);
+        self.skin = skin;
     }
     pub fn read_size(&mut self, con: &AppContext) -> Result<(), ProgramError> {
         let (w, h) = termimad::terminal_size();
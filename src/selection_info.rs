@@ -0,0 +1,143 @@
+//! the information about the selected line displayed at the
+//! start of the status line: size, modification date, permissions,
+//! owner (when it's pruned from the tree), and the git branch of
+//! the tree root
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[cfg(unix)]
+use crate::{displayable_tree, permissions};
+
+use crate::{
+    column_widths,
+    errors::ProgramError,
+    file_sizes::Size,
+    flat_tree::{LineType, Tree},
+    io::W,
+    screens::Screen,
+};
+
+pub struct SelectionInfo {
+    size: Option<String>,
+    date: Option<String>,
+    permissions: Option<String>,
+    owner: Option<String>,
+    git_branch: Option<String>,
+}
+
+impl SelectionInfo {
+    pub fn of(tree: &Tree, git_branch: Option<&String>) -> SelectionInfo {
+        let line = tree.selected_line();
+        let size = match line.size {
+            Some(size) => Some(size),
+            None if line.line_type == LineType::File => Some(Size::from(line.metadata.len())),
+            None => None,
+        };
+        let date = line
+            .metadata
+            .date(tree.options.displayed_date_kind())
+            .ok()
+            .map(|system_time| tree.options.date_format.format(system_time));
+        #[cfg(unix)]
+        let permissions = Some(line.mode().to_string());
+        #[cfg(not(unix))]
+        let permissions = None;
+        #[cfg(unix)]
+        let owner = if tree.options.show_permissions {
+            // the columns pruned from the tree because uniform are given here
+            let (user_width, group_width) = displayable_tree::user_group_max_lengths(tree);
+            let mut owner = Vec::new();
+            if user_width == 0 {
+                owner.push(permissions::user_name(line.metadata.uid()));
+            }
+            if group_width == 0 {
+                owner.push(permissions::group_name(line.metadata.gid()));
+            }
+            if owner.is_empty() {
+                None
+            } else {
+                Some(owner.join(":"))
+            }
+        } else {
+            None
+        };
+        #[cfg(not(unix))]
+        let owner = None;
+        SelectionInfo {
+            size: size.map(|s| s.format(tree.options.size_format)),
+            date,
+            permissions,
+            owner,
+            git_branch: git_branch.cloned(),
+        }
+    }
+
+    /// write the info at the current position, return the number
+    /// of chars written
+    pub fn display(&self, w: &mut W, screen: &Screen) -> Result<usize, ProgramError> {
+        let mut width = 0;
+        for field in [&self.size, &self.date, &self.permissions, &self.owner].iter() {
+            if let Some(field) = field {
+                let s = format!(" {} ", field);
+                width += column_widths::str_width(&s);
+                screen.skin.status_info.queue(w, s)?;
+            }
+        }
+        if let Some(branch) = &self.git_branch {
+            let s = format!(" ⎇ {} ", branch);
+            width += column_widths::str_width(&s);
+            screen.skin.status_git_branch.queue(w, s)?;
+        }
+        Ok(width)
+    }
+}
+
+/// find the .git directory of the repository containing
+/// the given directory, if any
+fn git_dir(dir: &Path) -> Option<PathBuf> {
+    let mut dir = Some(dir);
+    while let Some(d) = dir {
+        let git = d.join(".git");
+        if git.is_dir() {
+            return Some(git);
+        }
+        if git.is_file() {
+            // in a worktree or a submodule, .git is a file
+            // containing the path to the real git directory
+            let content = fs::read_to_string(&git).ok()?;
+            let path = content.trim().trim_start_matches("gitdir:").trim();
+            return Some(d.join(path));
+        }
+        dir = d.parent();
+    }
+    None
+}
+
+/// return the root directory of the git repository containing
+/// the given directory, if any
+pub fn git_root(dir: &Path) -> Option<PathBuf> {
+    let mut dir = Some(dir);
+    while let Some(d) = dir {
+        if d.join(".git").exists() {
+            return Some(d.to_path_buf());
+        }
+        dir = d.parent();
+    }
+    None
+}
+
+/// return the name of the current branch of the git repository
+/// containing the directory (or the start of the commit hash
+/// when the HEAD is detached)
+pub fn git_branch(dir: &Path) -> Option<String> {
+    let head = fs::read_to_string(git_dir(dir)?.join("HEAD")).ok()?;
+    let head = head.trim();
+    Some(if head.starts_with("ref: refs/heads/") {
+        head["ref: refs/heads/".len()..].to_string()
+    } else {
+        head.chars().take(7).collect()
+    })
+}
@@ -0,0 +1,164 @@
+//! the kind of selection a verb may require, and the names
+//! of the files it may apply to
+
+use std::str::FromStr;
+
+use crate::{
+    errors::ConfError,
+    flat_tree::TreeLine,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SelectionType {
+    File,
+    Directory,
+    Link, // a symbolic link, to a file or to a directory
+    Any,
+}
+
+impl SelectionType {
+    /// return the type of the selected line (a link is considered
+    /// as its target)
+    pub fn of(line: &TreeLine) -> SelectionType {
+        if line.is_dir() {
+            SelectionType::Directory
+        } else {
+            SelectionType::File
+        }
+    }
+    /// a description for the user, for example in the help screen
+    pub fn description(self) -> &'static str {
+        match self {
+            SelectionType::File => "files",
+            SelectionType::Directory => "directories",
+            SelectionType::Link => "links",
+            SelectionType::Any => "anything",
+        }
+    }
+}
+
+impl FromStr for SelectionType {
+    type Err = ConfError;
+    fn from_str(s: &str) -> Result<SelectionType, ConfError> {
+        match s {
+            "file" => Ok(SelectionType::File),
+            "directory" => Ok(SelectionType::Directory),
+            "link" => Ok(SelectionType::Link),
+            "any" => Ok(SelectionType::Any),
+            _ => Err(ConfError::InvalidSelectionType {
+                raw: s.to_string(),
+            }),
+        }
+    }
+}
+
+/// the names of the files a verb applies to, either by their
+/// extension or by a glob. A verb having neither applies to
+/// all names.
+#[derive(Debug, Clone, Default)]
+pub struct NameCondition {
+    pub extensions: Vec<String>, // without the dot, eg "rs"
+    pub globs: Vec<glob::Pattern>,
+}
+
+impl NameCondition {
+    pub fn is_empty(&self) -> bool {
+        self.extensions.is_empty() && self.globs.is_empty()
+    }
+    pub fn accepts(&self, name: &str) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let extension = name.rfind('.').filter(|&i| i > 0).map(|i| &name[i + 1..]);
+        if let Some(extension) = extension {
+            if self.extensions.iter().any(|e| e.eq_ignore_ascii_case(extension)) {
+                return true;
+            }
+        }
+        self.globs.iter().any(|glob| glob.matches(name))
+    }
+}
+
+/// what the conditions of the verbs are checked against
+#[derive(Debug, Clone)]
+pub struct Selection {
+    pub stype: SelectionType, // a link having the type of its target
+    pub is_link: bool,
+    pub name: Option<String>, // None when the conditions on names don't apply
+}
+
+impl Selection {
+    pub fn of(line: &TreeLine) -> Selection {
+        Selection {
+            stype: SelectionType::of(line),
+            is_link: line.is_symlink(),
+            name: line.path.file_name().map(|n| n.to_string_lossy().to_string()),
+        }
+    }
+    /// a selection of which only the type is known
+    pub fn of_type(stype: SelectionType) -> Selection {
+        Selection {
+            stype,
+            is_link: false,
+            name: None,
+        }
+    }
+    /// tell whether the selection is accepted by a verb
+    /// with the given conditions
+    pub fn respects(&self, condition: SelectionType, name_condition: &NameCondition) -> bool {
+        let type_ok = match condition {
+            SelectionType::Any => true,
+            SelectionType::Link => self.is_link,
+            _ => self.stype == condition,
+        };
+        type_ok && self.name.as_ref().map_or(true, |name| name_condition.accepts(name))
+    }
+    /// a description for the user, for example in the help screen
+    pub fn description(&self) -> String {
+        match &self.name {
+            Some(name) => {
+                let kind = if self.is_link {
+                    "the link"
+                } else if self.stype == SelectionType::Directory {
+                    "the directory"
+                } else {
+                    "the file"
+                };
+                format!("{} `{}`", kind, name)
+            }
+            None => self.stype.description().to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod selection_type_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_conditions() {
+        let condition = NameCondition {
+            extensions: vec!["rs".to_string(), "toml".to_string()],
+            globs: vec![glob::Pattern::new("README*").unwrap()],
+        };
+        assert!(condition.accepts("main.rs"));
+        assert!(condition.accepts("Cargo.TOML"));
+        assert!(condition.accepts("README.md"));
+        assert!(!condition.accepts("rs"));
+        assert!(!condition.accepts(".rs"));
+        assert!(!condition.accepts("notes.txt"));
+        let file = Selection {
+            stype: SelectionType::File,
+            is_link: true,
+            name: Some("notes.txt".to_string()),
+        };
+        assert!(file.respects(SelectionType::Link, &NameCondition::default()));
+        assert!(file.respects(SelectionType::File, &NameCondition::default()));
+        assert!(!file.respects(SelectionType::Directory, &NameCondition::default()));
+        assert!(!file.respects(SelectionType::Any, &condition));
+        let any = Selection::of_type(SelectionType::Any);
+        assert!(any.respects(SelectionType::Any, &condition));
+        assert!(!any.respects(SelectionType::File, &condition));
+    }
+}
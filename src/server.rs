@@ -0,0 +1,190 @@
+//! the server mode, in which a running broot listens on a unix socket
+//! so that other programs (for example editor plugins) can drive it.
+//!
+//! The protocol is made of lines: the client sends a request, and
+//! broot answers with exactly one line. The requests are
+//! - `get_selection`: the answer is the path of the selected line
+//! - `command <commands>`: the commands are executed as if typed (the
+//!   syntax is the one of `--cmd`), the answer is `ok` or an error
+//! - `root <path>`: the tree is replaced with the one of the given
+//!   directory, the answer is `ok` or an error
+
+use std::path::PathBuf;
+
+#[cfg(unix)]
+use {
+    std::{
+        fs,
+        io::{self, BufRead, BufReader, Write},
+        os::unix::{
+            fs::{FileTypeExt, PermissionsExt},
+            net::{UnixListener, UnixStream},
+        },
+        path::Path,
+        thread,
+    },
+    crossbeam::channel::{unbounded, Sender},
+    crate::app_events::AppEvent,
+};
+
+/// a request received by a listening broot
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    GetSelection,
+    Command(String),
+    Root(PathBuf),
+}
+
+impl Request {
+    /// parse a line of the protocol
+    pub fn parse(line: &str) -> Option<Request> {
+        let line = line.trim();
+        let (name, arg) = match line.find(' ') {
+            Some(idx) => (&line[..idx], line[idx + 1..].trim()),
+            None => (line, ""),
+        };
+        match (name, arg) {
+            ("get_selection", "") => Some(Request::GetSelection),
+            ("command", arg) if !arg.is_empty() => Some(Request::Command(arg.to_string())),
+            ("root", arg) if !arg.is_empty() => Some(Request::Root(PathBuf::from(arg))),
+            _ => None,
+        }
+    }
+
+    /// the line sent by a client for this request
+    pub fn to_line(&self) -> String {
+        match self {
+            Request::GetSelection => "get_selection".to_string(),
+            Request::Command(commands) => format!("command {}", commands),
+            Request::Root(path) => format!("root {}", path.to_string_lossy()),
+        }
+    }
+}
+
+/// listen on the socket, in a separate thread, and forward the requests
+/// to the application, each one with a sender for the reply.
+///
+/// A previous socket at the same path is replaced, but any other
+/// file is kept and the server isn't started. As the clients can
+/// execute verbs, the socket is only accessible to the user.
+#[cfg(unix)]
+pub fn listen(socket: &Path, tx: Sender<AppEvent>) -> io::Result<()> {
+    if let Ok(md) = fs::symlink_metadata(socket) {
+        if !md.file_type().is_socket() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{:?} already exists and isn't a socket", socket),
+            ));
+        }
+        fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)?;
+    fs::set_permissions(socket, fs::Permissions::from_mode(0o600))?;
+    info!("listening on {:?}", socket);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let tx = tx.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = serve(stream, tx) {
+                            warn!("error while serving a client: {:?}", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    warn!("failed to accept a connection: {:?}", e);
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// answer the requests of a client, until it closes the connection
+/// or the application stops
+#[cfg(unix)]
+fn serve(stream: UnixStream, tx: Sender<AppEvent>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        debug!("received request {:?}", &line);
+        let reply = match Request::parse(&line) {
+            Some(request) => {
+                let (reply_tx, reply_rx) = unbounded();
+                if tx.send(AppEvent::Remote(request, reply_tx)).is_err() {
+                    return Ok(()); // the application is quitting
+                }
+                match reply_rx.recv() {
+                    Ok(reply) => reply,
+                    Err(_) => return Ok(()),
+                }
+            }
+            None => format!("error: not a valid request: {:?}", line),
+        };
+        writeln!(writer, "{}", reply)?;
+    }
+    Ok(())
+}
+
+/// send a request to the broot listening on the socket, and
+/// return its reply
+#[cfg(unix)]
+pub fn send(socket: &Path, request: &Request) -> io::Result<String> {
+    let mut stream = UnixStream::connect(socket)?;
+    writeln!(stream, "{}", request.to_line())?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(reply.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod request_parsing_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_request_parsing() {
+        assert_eq!(Request::parse("get_selection"), Some(Request::GetSelection));
+        assert_eq!(
+            Request::parse("command /txt :focus"),
+            Some(Request::Command("/txt :focus".to_string())),
+        );
+        assert_eq!(
+            Request::parse("root /home/my dir"),
+            Some(Request::Root(PathBuf::from("/home/my dir"))),
+        );
+        assert_eq!(Request::parse("root"), None);
+        assert_eq!(Request::parse("unknown"), None);
+        let request = Request::Root(PathBuf::from("/tmp"));
+        assert_eq!(Request::parse(&request.to_line()), Some(request));
+    }
+}
+
+#[cfg(all(test, unix))]
+mod listen_tests {
+
+    use {
+        super::*,
+        std::{env, process},
+    };
+
+    #[test]
+    fn check_socket_path() {
+        let dir = env::temp_dir().join(format!("broot-test-listen-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // a file which isn't a socket is never replaced
+        let file = dir.join("notes.txt");
+        fs::write(&file, "precious").unwrap();
+        assert!(listen(&file, unbounded().0).is_err());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "precious");
+        // the socket is private, and a previous one is replaced
+        let socket = dir.join("broot.sock");
+        listen(&socket, unbounded().0).unwrap();
+        listen(&socket, unbounded().0).unwrap();
+        let md = fs::symlink_metadata(&socket).unwrap();
+        assert!(md.file_type().is_socket());
+        assert_eq!(md.permissions().mode() & 0o777, 0o600);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
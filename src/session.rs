@@ -0,0 +1,241 @@
+//! the session is what's needed to restore broot where the user
+//! was (with `broot --restore`), even after a crash or the loss
+//! of the terminal: the root, the pattern, the selection, the
+//! toggled options and the marked paths.
+//!
+//! The current session is kept in memory and regularly written
+//! in a file of the data directory. A panic hook writes it a last time.
+
+use std::{
+    fs, panic,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use toml::{self, value::Table, Value};
+
+use crate::{
+    conf,
+    tree_options::{OptionBool, TreeOptions},
+};
+
+/// how long a modified session may stay unsaved
+pub static AUTOSAVE_PERIOD: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    static ref CURRENT: Mutex<Option<Session>> = Mutex::new(None);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub root: PathBuf,
+    pub pattern: String, // the raw pattern, as typed by the user (may be empty)
+    pub selection: Option<PathBuf>,
+    pub toggles: Toggles,
+    pub marked: Vec<PathBuf>, // the marked paths, whatever the tree they were seen in
+}
+
+/// the tree options the user can toggle with verbs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Toggles {
+    pub show_hidden: bool,
+    pub only_folders: bool,
+    pub show_sizes: bool,
+    pub show_dates: bool,
+    pub show_counts: bool,
+    pub show_permissions: bool,
+    pub respect_git_ignore: OptionBool,
+}
+
+impl Toggles {
+    pub fn of(options: &TreeOptions) -> Toggles {
+        Toggles {
+            show_hidden: options.show_hidden,
+            only_folders: options.only_folders,
+            show_sizes: options.show_sizes,
+            show_dates: options.show_dates,
+            show_counts: options.show_counts,
+            show_permissions: options.show_permissions,
+            respect_git_ignore: options.respect_git_ignore,
+        }
+    }
+    /// turn on in the options the toggles which are on here, the
+    /// other ones being left as they are, and use the gitignore
+    /// handling when the options keep the default one
+    pub fn add_to(self, options: &mut TreeOptions) {
+        options.show_hidden |= self.show_hidden;
+        options.only_folders |= self.only_folders;
+        options.show_sizes |= self.show_sizes;
+        options.show_dates |= self.show_dates;
+        options.show_counts |= self.show_counts;
+        options.show_permissions |= self.show_permissions;
+        if options.respect_git_ignore == OptionBool::Auto {
+            options.respect_git_ignore = self.respect_git_ignore;
+        }
+    }
+    /// read the toggles of a TOML table, the missing ones
+    /// having their default value
+    pub fn read(value: Option<&Value>) -> Toggles {
+        let mut toggles = Toggles::of(&TreeOptions::default());
+        let tbl = match value.and_then(|v| v.as_table()) {
+            Some(tbl) => tbl,
+            None => return toggles,
+        };
+        let flag = |name: &str, default: bool| {
+            tbl.get(name).and_then(|v| v.as_bool()).unwrap_or(default)
+        };
+        toggles.show_hidden = flag("hidden", toggles.show_hidden);
+        toggles.only_folders = flag("only_folders", toggles.only_folders);
+        toggles.show_sizes = flag("sizes", toggles.show_sizes);
+        toggles.show_dates = flag("dates", toggles.show_dates);
+        toggles.show_counts = flag("counts", toggles.show_counts);
+        toggles.show_permissions = flag("permissions", toggles.show_permissions);
+        if let Some(Ok(gi)) = tbl.get("gitignore").and_then(|v| v.as_str()).map(|s| s.parse()) {
+            toggles.respect_git_ignore = gi;
+        }
+        toggles
+    }
+    fn write(self) -> Value {
+        let mut tbl = Table::new();
+        tbl.insert("hidden".to_string(), Value::Boolean(self.show_hidden));
+        tbl.insert("only_folders".to_string(), Value::Boolean(self.only_folders));
+        tbl.insert("sizes".to_string(), Value::Boolean(self.show_sizes));
+        tbl.insert("dates".to_string(), Value::Boolean(self.show_dates));
+        tbl.insert("counts".to_string(), Value::Boolean(self.show_counts));
+        tbl.insert("permissions".to_string(), Value::Boolean(self.show_permissions));
+        let gi = match self.respect_git_ignore {
+            OptionBool::Auto => "auto",
+            OptionBool::Yes => "yes",
+            OptionBool::No => "no",
+        };
+        tbl.insert("gitignore".to_string(), Value::String(gi.to_string()));
+        Value::Table(tbl)
+    }
+}
+
+/// return the path to the file in which the session is saved
+pub fn file_path() -> PathBuf {
+    conf::data_dir().join("session.toml")
+}
+
+impl Session {
+    /// read the last saved session, if any
+    pub fn load() -> Option<Session> {
+        let data = fs::read_to_string(file_path()).ok()?;
+        let root: Value = data.parse().ok()?;
+        let session_root = PathBuf::from(root.get("root")?.as_str()?);
+        if !session_root.is_dir() {
+            return None;
+        }
+        let pattern = root
+            .get("pattern")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        let selection = root
+            .get("selection")
+            .and_then(|v| v.as_str())
+            .map(PathBuf::from)
+            .filter(|p| p.exists());
+        let marked = root
+            .get("marked")
+            .and_then(|v| v.as_array())
+            .map(|paths| {
+                paths
+                    .iter()
+                    .filter_map(|v| v.as_str())
+                    .map(PathBuf::from)
+                    .filter(|p| p.exists())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Some(Session {
+            root: session_root,
+            pattern,
+            selection,
+            toggles: Toggles::read(root.get("toggles")),
+            marked,
+        })
+    }
+    fn save(&self) {
+        let mut tbl = Table::new();
+        tbl.insert(
+            "root".to_string(),
+            Value::String(self.root.to_string_lossy().to_string()),
+        );
+        tbl.insert("pattern".to_string(), Value::String(self.pattern.clone()));
+        if let Some(selection) = &self.selection {
+            tbl.insert(
+                "selection".to_string(),
+                Value::String(selection.to_string_lossy().to_string()),
+            );
+        }
+        tbl.insert("toggles".to_string(), self.toggles.write());
+        tbl.insert(
+            "marked".to_string(),
+            Value::Array(
+                self.marked
+                    .iter()
+                    .map(|p| Value::String(p.to_string_lossy().to_string()))
+                    .collect(),
+            ),
+        );
+        let path = file_path();
+        let res = fs::create_dir_all(conf::data_dir())
+            .and_then(|_| fs::write(&path, Value::Table(tbl).to_string()));
+        if let Err(e) = res {
+            warn!("failed to save the session in {:?}: {:?}", path, e);
+        }
+    }
+}
+
+/// keeps the current session and regularly saves it
+pub struct Autosaver {
+    saved: Option<Session>,
+    last_save: Instant,
+}
+
+impl Autosaver {
+    pub fn new() -> Autosaver {
+        Autosaver {
+            saved: None,
+            last_save: Instant::now(),
+        }
+    }
+    /// record the current session, which will be saved when
+    /// the autosave period is elapsed
+    pub fn update(&mut self, session: Option<Session>) {
+        if session.is_some() {
+            *CURRENT.lock().unwrap() = session;
+        }
+        if self.last_save.elapsed() >= AUTOSAVE_PERIOD {
+            self.save();
+        }
+    }
+    /// write the current session if it changed since the last save
+    pub fn save(&mut self) {
+        let current = CURRENT.lock().unwrap().clone();
+        if current.is_some() && current != self.saved {
+            if let Some(session) = &current {
+                session.save();
+            }
+            self.saved = current;
+        }
+        self.last_save = Instant::now();
+    }
+}
+
+/// make broot write the current session when it panics
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // the mutex may be held by the panicking thread
+        if let Ok(current) = CURRENT.try_lock() {
+            if let Some(session) = &*current {
+                session.save();
+            }
+        }
+        default_hook(info);
+    }));
+}
@@ -2,7 +2,10 @@ use crate::shell_install::ShellFamily;
 
 pub const BASH: ShellFamily<'static> = ShellFamily {
     name: "bash",
+    shells: &["bash", "zsh"],
     sourcing_files: &[".bashrc", ".zshrc"],
+    source_command: "source",
+    script_extension: "",
     version: 1,
     script: BASH_FUNC,
 };
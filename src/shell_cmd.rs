@@ -0,0 +1,53 @@
+use crate::shell_install::ShellFamily;
+
+/// cmd.exe has no configuration file we could patch: the br.cmd
+/// script must be found in the PATH
+pub const CMD: ShellFamily<'static> = ShellFamily {
+    name: "cmd",
+    shells: &["cmd"],
+    sourcing_files: &[],
+    source_command: "",
+    script_extension: ".cmd",
+    version: 1,
+    script: CMD_FUNC,
+};
+
+// The code page is switched to UTF-8 while reading the command
+// file, as broot writes it in UTF-8.
+// A `cd` needs the /d switch to also change the current drive.
+// BROOT_SHELL tells broot to quote the paths for cmd. It's cleared
+// after the test of errorlevel, which a `set` would reset.
+const CMD_FUNC: &str = r#"@echo off
+rem This script was automatically generated by the broot function
+rem More information can be found in https://github.com/Canop/broot
+
+rem This script starts broot and executes the command
+rem it produces, if any.
+rem It's needed because some shell commands, like `cd`,
+rem have no useful effect if executed in a subshell.
+set "_broot_cmd_file=%TEMP%\broot-%RANDOM%%RANDOM%.txt"
+set "BROOT_SHELL=cmd"
+broot --outcmd "%_broot_cmd_file%" %*
+if errorlevel 1 (
+    set "BROOT_SHELL="
+    del /q "%_broot_cmd_file%" 2>nul
+    set "_broot_cmd_file="
+    exit /b 1
+)
+set "BROOT_SHELL="
+set "_broot_cmd="
+for /f "tokens=2 delims=:." %%c in ('chcp') do set "_broot_code_page=%%c"
+chcp 65001 >nul
+if exist "%_broot_cmd_file%" set /p _broot_cmd=<"%_broot_cmd_file%"
+chcp %_broot_code_page% >nul
+del /q "%_broot_cmd_file%" 2>nul
+set "_broot_cmd_file="
+set "_broot_code_page="
+if not defined _broot_cmd goto :eof
+if /i "%_broot_cmd:~0,3%"=="cd " (
+    cd /d %_broot_cmd:~3%
+) else (
+    %_broot_cmd%
+)
+set "_broot_cmd="
+"#;
@@ -2,7 +2,10 @@ use crate::shell_install::ShellFamily;
 
 pub const FISH: ShellFamily<'static> = ShellFamily {
     name: "fish",
+    shells: &["fish"],
     sourcing_files: &[".config/fish/config.fish"], // idealy we should probably use XDG here...
+    source_command: "source",
+    script_extension: "",
     version: 1,
     script: FISH_FUNC,
 };
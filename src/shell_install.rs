@@ -9,23 +9,34 @@
 //!    ├──conf.toml
 //!    └──launcher
 //!       ├──bash
-//!       │  ├──1
-//!       │  └──br -> /home/dys/.config/broot/launcher/bash/1
-//!       └──installed
+//!       │   ├──1
+//!       │   ├──br -> /home/dys/.config/broot/launcher/bash/1
+//!       │   └──installed
+//!       └──fish
+//!          ├──1
+//!          ├──br -> /home/dys/.config/broot/launcher/fish/1
+//!          └──installed
 //!
 //! and a "source .config/broot/launcher/bash/br" line is written in
 //! the .bashrc file (and the .zshrc file if found)
 //!
 //!
-//! If the user refused the installation, a "refused" file takes the
-//! place of the "installed" one.
+//! If the user refused the installation for a shell, a "refused" file
+//! takes the place of the "installed" one.
+//!
+//! Only the shells found in /etc/shells or in $SHELL are considered.
+//! Older versions of broot kept a single "installed" or "refused" file
+//! directly in the launcher directory: it still applies to all shells
+//! which don't have their own file.
 //!
 
 use std::{
+    env,
     fs::{self, OpenOptions},
     io::{self, BufRead, BufReader, Write},
+    #[cfg(unix)]
     os,
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use directories::UserDirs;
@@ -36,9 +47,20 @@ use crate::{
     errors::ProgramError,
     shell_bash::BASH,
     shell_fish::FISH,
+    shell_nushell::NUSHELL,
+};
+
+#[cfg(windows)]
+use crate::{
+    shell_cmd::CMD,
+    shell_powershell::POWERSHELL,
 };
 
-const SHELL_FAMILIES: &[ShellFamily<'static>] = &[BASH, FISH];
+#[cfg(not(windows))]
+const SHELL_FAMILIES: &[ShellFamily<'static>] = &[BASH, FISH, NUSHELL];
+
+#[cfg(windows)]
+const SHELL_FAMILIES: &[ShellFamily<'static>] = &[BASH, FISH, NUSHELL, POWERSHELL, CMD];
 
 const MD_INSTALL_REQUEST: & str = r#"
 **Broot** should be launched using a shell function (see *https://github.com/Canop/broot* for explanations).
@@ -47,7 +69,10 @@ The function is either missing, old or badly installed.
 
 pub struct ShellFamily<'a> {
     pub name: &'a str,
-    pub sourcing_files: &'a [&'a str],
+    pub shells: &'a [&'a str], // names of the shell executables of this family
+    pub sourcing_files: &'a [&'a str], // when empty, the script must be found in the PATH
+    pub source_command: &'a str, // the command sourcing a script, eg "source"
+    pub script_extension: &'a str, // eg ".ps1", needed by some shells
     pub version: usize,
     pub script: &'a str,
 }
@@ -58,9 +83,9 @@ impl ShellFamily<'static> {
     // (i.e. this isn't enough to make the function available)
     fn ensure_script_installed(&self, launcher_dir: &Path) -> Result<(), ProgramError> {
         let dir = launcher_dir.join(self.name);
-        let link_path = dir.join("br");
+        let link_path = self.br_path(launcher_dir);
         let link_present = link_path.exists();
-        let script_path = dir.join(self.version.to_string());
+        let script_path = dir.join(format!("{}{}", self.version, self.script_extension));
         let func_present = script_path.exists();
         if !func_present {
             info!("script_path not present: writing it");
@@ -74,12 +99,50 @@ impl ShellFamily<'static> {
             info!("creating link from {:?} to {:?}", &link_path, &script_path);
             #[cfg(unix)]
             os::unix::fs::symlink(&script_path, &link_path)?;
+            // creating a symlink needs special rights on windows,
+            // so we just make a copy
             #[cfg(windows)]
-            os::windows::fs::symlink_file(&script_path, &link_path)?;
+            fs::copy(&script_path, &link_path)?;
         }
         Ok(())
     }
 
+    /// return the path to the script the shell must source
+    fn br_path(&self, launcher_dir: &Path) -> PathBuf {
+        launcher_dir
+            .join(self.name)
+            .join(format!("br{}", self.script_extension))
+    }
+
+    /// build the line which must be added to a shell config file
+    fn source_line(&self, path: &Path) -> String {
+        let path = path.to_string_lossy();
+        if path.contains(char::is_whitespace) {
+            format!("{} \"{}\"", self.source_command, path)
+        } else {
+            format!("{} {}", self.source_command, path)
+        }
+    }
+
+    /// tell whether one of the shells of the family is known on this system
+    fn is_used(&self, shell_names: &[String]) -> bool {
+        self.shells.iter().any(|s| shell_names.iter().any(|n| n == s))
+    }
+
+    /// return the path to a state file ("installed" or "refused") of this family,
+    /// falling back to the one common to all families, which was written
+    /// by older versions of broot
+    fn state_path(&self, launcher_dir: &Path, state: &str) -> PathBuf {
+        let path = launcher_dir.join(self.name).join(state);
+        if !path.exists() {
+            let legacy_path = launcher_dir.join(state);
+            if legacy_path.exists() {
+                return legacy_path;
+            }
+        }
+        path
+    }
+
     /// return true if the application should quit
     fn maybe_patch_all_sourcing_files(
         &self,
@@ -87,9 +150,9 @@ impl ShellFamily<'static> {
         installation_required: bool,
         motivation_already_explained: bool,
     ) -> Result<bool, ProgramError> {
-        let installed_path = launcher_dir.join("installed");
+        let installed_path = self.state_path(launcher_dir, "installed");
         if installed_path.exists() {
-            debug!("*installed* file found");
+            debug!("*installed* file found for {}", self.name);
             // everything seems OK
             // Note that if a new shell has been installed, we don't
             // look again at all the .shellrc files, by design.
@@ -99,9 +162,9 @@ impl ShellFamily<'static> {
                 return Ok(false);
             }
         }
-        let refused_path = launcher_dir.join("refused");
+        let refused_path = self.state_path(launcher_dir, "refused");
         if refused_path.exists() {
-            debug!("*refused* file found :(");
+            debug!("*refused* file found for {} :(", self.name);
             if installation_required {
                 fs::remove_file(&refused_path)?;
             } else {
@@ -111,6 +174,17 @@ impl ShellFamily<'static> {
         }
         // it looks like the shell function is neither installed nor refused
         let ms = cli::mad_skin();
+        if self.sourcing_files.is_empty() {
+            // there's no file to patch, the script must be in the PATH
+            if installation_required {
+                ms.print_text(&format!(
+                    "To use **br** in {}, add `{}` to your PATH.\n",
+                    self.name,
+                    launcher_dir.join(self.name).to_string_lossy(),
+                ));
+            }
+            return Ok(installation_required);
+        }
         let homedir_path = match UserDirs::new() {
             Some(user_dirs) => user_dirs.home_dir().to_path_buf(),
             None => {
@@ -157,6 +231,7 @@ impl ShellFamily<'static> {
             debug!("proceed: {:?}", proceed);
             if !proceed {
                 // user doesn't want the shell function, let's remember it
+                let refused_path = launcher_dir.join(self.name).join("refused");
                 fs::write(
                     &refused_path,
                     "to install the br function, run broot --install\n",
@@ -166,8 +241,8 @@ impl ShellFamily<'static> {
             }
         }
 
-        let br_path = launcher_dir.join(self.name).join("br");
-        let source_line = format!("source {}", br_path.to_string_lossy());
+        let br_path = self.br_path(launcher_dir);
+        let source_line = self.source_line(&br_path);
         let mut changes_made = false;
         for rc_file in rc_files {
             if file_contains_line(&rc_file.1, &source_line)? {
@@ -184,7 +259,7 @@ impl ShellFamily<'static> {
                     "{} successfully patched, you should now refresh it with",
                     rc_file.0
                 );
-                println!("  source {}", rc_file.1.to_string_lossy());
+                println!("  {}", self.source_line(&rc_file.1));
                 changes_made = true;
             }
             // signal if there's an old br function declared in the shellrc file
@@ -201,6 +276,7 @@ impl ShellFamily<'static> {
             ms.print_text("You should afterwards start broot with just **br**.\n");
         }
         // and remember we did it
+        let installed_path = launcher_dir.join(self.name).join("installed");
         fs::write(
             &installed_path,
             "to reinstall the br function, run broot --install\n",
@@ -218,14 +294,53 @@ fn file_contains_line(path: &Path, searched_line: &str) -> io::Result<bool> {
     Ok(false)
 }
 
+/// return the names of the shells available on the system, read
+/// from /etc/shells and from the $SHELL environment variable.
+///
+/// On windows there's no reliable way to know the installed shells,
+/// so the returned list is empty, which means all families are considered.
+#[cfg(windows)]
+fn known_shell_names() -> Vec<String> {
+    Vec::new()
+}
+
+/// return the names of the shells available on the system, read
+/// from /etc/shells and from the $SHELL environment variable
+#[cfg(not(windows))]
+fn known_shell_names() -> Vec<String> {
+    let mut paths: Vec<String> = match fs::read_to_string("/etc/shells") {
+        Ok(content) => content
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.to_string())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    if let Ok(shell) = env::var("SHELL") {
+        paths.push(shell);
+    }
+    paths
+        .iter()
+        .filter_map(|path| Path::new(path).file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .collect()
+}
+
 /// check whether the shell function is installed, install
 /// it if it wasn't refused before or if broot is launched
 /// with --install.
 /// returns true if the app should quit
 pub fn init(launch_args: &AppLaunchArgs) -> Result<bool, ProgramError> {
     let launcher_dir = conf::dir().join("launcher");
+    let shell_names = known_shell_names();
+    debug!("known shells: {:?}", &shell_names);
     let mut should_quit = false;
     for family in SHELL_FAMILIES {
+        if !shell_names.is_empty() && !family.is_used(&shell_names) {
+            debug!("no {} shell found", family.name);
+            continue;
+        }
         family.ensure_script_installed(&launcher_dir)?;
         let done = family.maybe_patch_all_sourcing_files(
             &launcher_dir,
@@ -0,0 +1,32 @@
+use crate::shell_install::ShellFamily;
+
+pub const NUSHELL: ShellFamily<'static> = ShellFamily {
+    name: "nushell",
+    shells: &["nu"],
+    sourcing_files: &[".config/nushell/config.nu"],
+    source_command: "source",
+    script_extension: "",
+    version: 1,
+    script: NUSHELL_FUNC,
+};
+
+// Nushell can't evaluate a command given as a string, so only
+// the `cd` commands written by broot are executed
+const NUSHELL_FUNC: &str = r#"
+# This script was automatically generated by the broot function
+# More information can be found in https://github.com/Canop/broot
+
+# This function starts broot and executes the command
+# it produces, if any.
+# It's needed because some shell commands, like `cd`,
+# have no useful effect if executed in a subshell.
+def --env br [...args] {
+    let cmd_file = (mktemp -t broot.XXXXXXXXXX)
+    broot --outcmd $cmd_file ...$args
+    let cmd = (open --raw $cmd_file | str trim)
+    rm -f $cmd_file
+    if ($cmd | str starts-with "cd ") {
+        cd ($cmd | str substring 3.. | str trim --char "'")
+    }
+}
+"#;
@@ -0,0 +1,41 @@
+use crate::shell_install::ShellFamily;
+
+pub const POWERSHELL: ShellFamily<'static> = ShellFamily {
+    name: "powershell",
+    shells: &["powershell", "pwsh"],
+    sourcing_files: &[
+        "Documents/WindowsPowerShell/Microsoft.PowerShell_profile.ps1",
+        "Documents/PowerShell/Microsoft.PowerShell_profile.ps1",
+    ],
+    source_command: ".",
+    script_extension: ".ps1",
+    version: 1,
+    script: POWERSHELL_FUNC,
+};
+
+// The command file is written by broot in UTF-8, which
+// isn't the default encoding of Get-Content in Windows PowerShell
+const POWERSHELL_FUNC: &str = r#"
+# This script was automatically generated by the broot function
+# More information can be found in https://github.com/Canop/broot
+
+# This function starts broot and executes the command
+# it produces, if any.
+# It's needed because some shell commands, like `cd`,
+# have no useful effect if executed in a subshell.
+Function br {
+    $cmd_file = New-TemporaryFile
+    broot --outcmd $cmd_file.FullName $args
+    $exit_code = $LASTEXITCODE
+    If ($exit_code -eq 0) {
+        $cmd = Get-Content -Encoding UTF8 -Raw $cmd_file
+    }
+    Remove-Item -Force $cmd_file
+    If ($exit_code -ne 0) {
+        Return
+    }
+    If ($cmd) {
+        Invoke-Expression $cmd
+    }
+}
+"#;
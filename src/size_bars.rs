@@ -0,0 +1,75 @@
+//! the visualizations of the size of a file relative to the
+//! total size, displayed after the size in size mode
+
+use std::str::FromStr;
+
+use termimad::ProgressBar;
+
+use crate::errors::ConfError;
+
+/// the braille chars of a bar, each one adding a column of dots
+const BRAILLE_STEPS: &[char] = &['⠀', '⡀', '⡄', '⡆', '⡇', '⣇', '⣧', '⣷', '⣿'];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeBarStyle {
+    Wide,    // a 10 chars progress bar
+    Narrow,  // a 5 chars progress bar
+    Braille, // a 2 chars bar made of braille dots
+    None,    // only the numbers
+}
+
+impl Default for SizeBarStyle {
+    fn default() -> Self {
+        SizeBarStyle::Wide
+    }
+}
+
+impl FromStr for SizeBarStyle {
+    type Err = ConfError;
+    fn from_str(s: &str) -> Result<SizeBarStyle, ConfError> {
+        match s {
+            "wide" => Ok(SizeBarStyle::Wide),
+            "narrow" => Ok(SizeBarStyle::Narrow),
+            "braille" => Ok(SizeBarStyle::Braille),
+            "none" => Ok(SizeBarStyle::None),
+            _ => Err(ConfError::InvalidSizeBar {
+                raw: s.to_string(),
+            }),
+        }
+    }
+}
+
+impl SizeBarStyle {
+    /// the number of chars taken by the bar
+    pub fn width(self) -> usize {
+        match self {
+            SizeBarStyle::Wide => 10,
+            SizeBarStyle::Narrow => 5,
+            SizeBarStyle::Braille => 2,
+            SizeBarStyle::None => 0,
+        }
+    }
+    /// build the bar of a size which is the given part of the total,
+    /// padded to the width of the style
+    pub fn render(self, part: f32) -> String {
+        let width = self.width();
+        match self {
+            SizeBarStyle::Wide | SizeBarStyle::Narrow => {
+                format!("{:<w$}", ProgressBar::new(part, width), w = width)
+            }
+            SizeBarStyle::Braille => {
+                let steps_per_char = BRAILLE_STEPS.len() - 1;
+                let part = part.max(0.0).min(1.0);
+                let mut steps = (part * (width * steps_per_char) as f32).round() as usize;
+                let mut bar = String::new();
+                for _ in 0..width {
+                    let char_steps = steps.min(steps_per_char);
+                    bar.push(BRAILLE_STEPS[char_steps]);
+                    steps -= char_steps;
+                }
+                bar
+            }
+            SizeBarStyle::None => String::new(),
+        }
+    }
+}
@@ -15,47 +15,91 @@ use crossterm::{
 };
 use termimad::CompoundStyle;
 
+use crate::color_support::{self, ColorSupport};
+
 macro_rules! Skin {
     (
         $($name:ident: $fg:expr, $bg:expr; $({$a:expr})*)*
     ) => {
         pub struct Skin {
             $(pub $name: CompoundStyle,)*
+            pub file_styles: Vec<(glob::Pattern, CompoundStyle)>, // for the files whose name matches the glob
         }
         impl Skin {
             /// build a skin without any terminal control character (for file output)
             pub fn no_term() -> Skin {
                 Skin {
                     $($name: CompoundStyle::default(),)*
+                    file_styles: Vec::new(),
                 }
             }
-            /// build a skin with some entry overloaded by configuration
-            pub fn create(mut skin_conf: HashMap<String, CompoundStyle>) -> Skin {
+            /// build a skin with some entry overloaded by configuration,
+            /// and the styles of files depending on their names
+            pub fn create(
+                mut skin_conf: HashMap<String, CompoundStyle>,
+                file_styles: Vec<(glob::Pattern, CompoundStyle)>,
+            ) -> Skin {
                 let mut skin = Skin {
                     $($name: skin_conf.remove(stringify!($name)).unwrap_or(CompoundStyle::new(
                         $fg,
                         $bg,
                         [$($a),*].to_vec(),
                     )),)*
+                    file_styles: Vec::new(),
                 };
                 $(
                     let mut base = skin.default.clone();
                     base.overwrite_with(&skin.$name);
                     skin.$name = base;
                 )*
+                // the file styles only change what they define in the file style
+                skin.file_styles = file_styles
+                    .into_iter()
+                    .map(|(glob, style)| {
+                        let mut base = skin.file.clone();
+                        base.overwrite_with(&style);
+                        (glob, base)
+                    })
+                    .collect();
                 skin
             }
+            /// replace the colors the terminal can't display with
+            /// the nearest ones it can display
+            pub fn degrade_colors(&mut self, support: ColorSupport) {
+                $(degrade_style(&mut self.$name, support);)*
+                for (_, style) in &mut self.file_styles {
+                    degrade_style(style, support);
+                }
+            }
+            /// return the style of the files with this name, if
+            /// one was defined for them
+            pub fn file_style(&self, name: &str) -> Option<&CompoundStyle> {
+                self.file_styles
+                    .iter()
+                    .find(|(glob, _)| glob.matches(name))
+                    .map(|(_, style)| style)
+            }
         }
         impl Clone for Skin {
             fn clone(&self) -> Self {
                 Skin {
                     $($name: self.$name.clone(),)*
+                    file_styles: self.file_styles.clone(),
                 }
             }
         }
     }
 }
 
+fn degrade_style(style: &mut CompoundStyle, support: ColorSupport) {
+    if let Some(fg) = style.get_fg() {
+        style.set_fg(color_support::degrade(fg, support));
+    }
+    if let Some(bg) = style.get_bg() {
+        style.set_bg(color_support::degrade(bg, support));
+    }
+}
+
 pub fn gray(level: u8) -> Option<Color> {
     Some(AnsiValue(0xE8 + level))
 }
@@ -79,8 +123,10 @@ Skin! {
     exe: Some(Cyan), None;
     link: Some(Magenta), None;
     pruning: gray(12), None; {Italic}
+    dimmed: gray(11), None;
     permissions: gray(15), None;
     dates: ansi(109), None;
+    counts: gray(14), None;
     selected_line: None, gray(4);
     char_match: Some(Green), None;
     file_error: Some(Red), None;
@@ -94,8 +140,13 @@ Skin! {
     status_bold: ansi(178), gray(3); {Bold}
     status_code: ansi(229), gray(3);
     status_ellipsis: gray(19), gray(1);
+    status_info: gray(17), gray(5);
+    status_git_branch: ansi(178), gray(5);
     scrollbar_track: gray(7), None;
     scrollbar_thumb: gray(22), None;
+    diff_added: Some(Green), None;
+    diff_removed: Some(Red), None;
+    diff_header: ansi(110), None; {Bold}
     help_paragraph: gray(20), None;
     help_bold: ansi(178), None; {Bold}
     help_italic: ansi(229), None; {Italic}
@@ -0,0 +1,287 @@
+//! A snapshot is a JSON file recording a tree of files, written
+//! with `--write-snapshot` and browsed offline with `--snapshot`.
+//!
+//! Its format is
+//!
+//! ```json
+//! {
+//!     "root": "/home/dys/dev",
+//!     "entries": [
+//!         {"path": "/home/dys/dev/broot", "type": "dir", "len": 4096, "modified": 1575000000},
+//!         {"path": "/home/dys/dev/last", "type": "link", "target": "broot"}
+//!     ]
+//! }
+//! ```
+//!
+//! where `type` is one of `dir`, `file` or `link`, and the dates
+//! (`modified`, `created` and `accessed`) are in seconds since the
+//! epoch. Only `path` and `type` are mandatory.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    errors::SnapshotError,
+    tree_sources::{EntryMetadata, EntryType, SourceEntry, TreeSource},
+};
+
+/// the number of links followed before giving up, as the system does
+const MAX_LINK_HOPS: usize = 40;
+
+#[derive(Debug)]
+struct SnapshotEntry {
+    metadata: EntryMetadata,
+    target: Option<PathBuf>, // for links
+}
+
+/// a tree source reading a snapshot file
+#[derive(Debug)]
+pub struct SnapshotSource {
+    root: PathBuf,
+    entries: HashMap<PathBuf, SnapshotEntry>,
+    children: HashMap<PathBuf, Vec<SourceEntry>>,
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{:?} isn't in the snapshot", path),
+    )
+}
+
+fn parse_entry_type(raw: &str) -> Result<EntryType, SnapshotError> {
+    match raw {
+        "dir" => Ok(EntryType::Dir),
+        "file" => Ok(EntryType::File),
+        "link" => Ok(EntryType::Symlink),
+        _ => Err(SnapshotError::InvalidEntryType {
+            raw: raw.to_string(),
+        }),
+    }
+}
+
+fn entry_type_name(entry_type: EntryType) -> &'static str {
+    match entry_type {
+        EntryType::Dir => "dir",
+        EntryType::File => "file",
+        EntryType::Symlink => "link",
+    }
+}
+
+impl SnapshotSource {
+    pub fn load(file_path: &Path) -> Result<SnapshotSource, SnapshotError> {
+        let file = File::open(file_path)?;
+        let value: Value = serde_json::from_reader(BufReader::new(file))?;
+        let root = value
+            .get("root")
+            .and_then(Value::as_str)
+            .map(PathBuf::from)
+            .ok_or_else(|| SnapshotError::MissingField {
+                txt: "root".to_string(),
+            })?;
+        let mut source = SnapshotSource {
+            root,
+            entries: HashMap::new(),
+            children: HashMap::new(),
+        };
+        let raw_entries = value
+            .get("entries")
+            .and_then(Value::as_array)
+            .ok_or_else(|| SnapshotError::MissingField {
+                txt: "entries".to_string(),
+            })?;
+        for raw_entry in raw_entries {
+            let path = raw_entry
+                .get("path")
+                .and_then(Value::as_str)
+                .map(PathBuf::from)
+                .ok_or_else(|| SnapshotError::MissingField {
+                    txt: "path".to_string(),
+                })?;
+            let entry_type = raw_entry
+                .get("type")
+                .and_then(Value::as_str)
+                .ok_or_else(|| SnapshotError::MissingField {
+                    txt: "type".to_string(),
+                })
+                .and_then(parse_entry_type)?;
+            let int = |name: &str| raw_entry.get(name).and_then(Value::as_u64);
+            let date = |name: &str| int(name).map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+            let metadata = EntryMetadata {
+                entry_type,
+                len: int("len").unwrap_or(0),
+                modified: date("modified"),
+                created: date("created"),
+                accessed: date("accessed"),
+                mode: int("mode").unwrap_or(0) as u32,
+                uid: int("uid").unwrap_or(0) as u32,
+                gid: int("gid").unwrap_or(0) as u32,
+                dev: 0,
+                ino: 0,
+                nlink: 0,
+                in_archive: false,
+            };
+            let target = raw_entry
+                .get("target")
+                .and_then(Value::as_str)
+                .map(PathBuf::from);
+            source.add(path, metadata, target);
+        }
+        if !source.entries.contains_key(&source.root) {
+            // the root may not have been recorded as an entry
+            let root = source.root.clone();
+            source.add(
+                root,
+                EntryMetadata {
+                    entry_type: EntryType::Dir,
+                    len: 0,
+                    modified: None,
+                    created: None,
+                    accessed: None,
+                    mode: 0,
+                    uid: 0,
+                    gid: 0,
+                    dev: 0,
+                    ino: 0,
+                    nlink: 0,
+                    in_archive: false,
+                },
+                None,
+            );
+        }
+        Ok(source)
+    }
+
+    fn add(&mut self, path: PathBuf, metadata: EntryMetadata, target: Option<PathBuf>) {
+        if let Some(parent) = path.parent() {
+            self.children
+                .entry(parent.to_path_buf())
+                .or_insert_with(Vec::new)
+                .push(SourceEntry {
+                    path: path.clone(),
+                    entry_type: metadata.entry_type,
+                });
+        }
+        self.entries.insert(path, SnapshotEntry { metadata, target });
+    }
+
+    /// the directory which was the root when the snapshot was written
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl TreeSource for SnapshotSource {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<SourceEntry>> {
+        match self.entries.get(dir) {
+            Some(entry) if entry.metadata.entry_type.is_dir() => {
+                Ok(self.children.get(dir).cloned().unwrap_or_default())
+            }
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("{:?} isn't a directory", dir),
+            )),
+            None => Err(not_found(dir)),
+        }
+    }
+    fn metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        let mut path = path.to_path_buf();
+        for _ in 0..MAX_LINK_HOPS {
+            let entry = self.entries.get(&path).ok_or_else(|| not_found(&path))?;
+            match &entry.target {
+                Some(target) if entry.metadata.entry_type.is_symlink() => {
+                    path = match path.parent() {
+                        Some(parent) => parent.join(target),
+                        None => target.clone(),
+                    };
+                }
+                _ => {
+                    return Ok(entry.metadata.clone());
+                }
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("too many levels of links at {:?}", path),
+        ))
+    }
+    fn symlink_metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        self.entries
+            .get(path)
+            .map(|entry| entry.metadata.clone())
+            .ok_or_else(|| not_found(path))
+    }
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.entries
+            .get(path)
+            .and_then(|entry| entry.target.clone())
+            .ok_or_else(|| not_found(path))
+    }
+}
+
+/// record the tree under root, without following links, into a
+/// snapshot file
+pub fn write(root: &Path, file_path: &Path) -> Result<(), SnapshotError> {
+    let mut entries = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let read_dir = match fs::read_dir(&dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) => {
+                warn!("can't read {:?}: {:?}", &dir, e);
+                continue;
+            }
+        };
+        for e in read_dir {
+            let path = match e {
+                Ok(e) => e.path(),
+                Err(_) => continue,
+            };
+            let md = match fs::symlink_metadata(&path) {
+                Ok(md) => EntryMetadata::from_fs(&md),
+                Err(_) => continue,
+            };
+            let mut raw_entry = Map::new();
+            raw_entry.insert("path".to_string(), json!(path.to_string_lossy()));
+            raw_entry.insert("type".to_string(), json!(entry_type_name(md.entry_type)));
+            raw_entry.insert("len".to_string(), json!(md.len));
+            for (name, date) in &[
+                ("modified", md.modified),
+                ("created", md.created),
+                ("accessed", md.accessed),
+            ] {
+                if let Some(d) = date.and_then(|date| date.duration_since(UNIX_EPOCH).ok()) {
+                    raw_entry.insert(name.to_string(), json!(d.as_secs()));
+                }
+            }
+            if md.mode != 0 {
+                raw_entry.insert("mode".to_string(), json!(md.mode));
+                raw_entry.insert("uid".to_string(), json!(md.uid));
+                raw_entry.insert("gid".to_string(), json!(md.gid));
+            }
+            if md.entry_type.is_symlink() {
+                if let Ok(target) = fs::read_link(&path) {
+                    raw_entry.insert("target".to_string(), json!(target.to_string_lossy()));
+                }
+            }
+            entries.push(Value::Object(raw_entry));
+            if md.entry_type.is_dir() {
+                dirs.push(path);
+            }
+        }
+    }
+    let snapshot = json!({
+        "root": root.to_string_lossy(),
+        "entries": entries,
+    });
+    let writer = BufWriter::new(File::create(file_path)?);
+    serde_json::to_writer(writer, &snapshot)?;
+    Ok(())
+}
@@ -0,0 +1,147 @@
+//! the history of the places the user went through, for `:undo`
+//! and `:redo`.
+//!
+//! A place is a root displayed with some options (toggles, pattern) and
+//! a selection. A new place is recorded each time the user changes the
+//! root, toggles an option, goes back (for example with esc) or edits
+//! the pattern, the keys typed in a row in the pattern making only one
+//! change.
+//!
+//! Both stacks are bounded: the oldest places are forgotten.
+//!
+//! This history is distinct from the stack of states, which `:back`
+//! and `:forward` walk: a state (a tree, the help, etc.) is only
+//! stacked when the user goes to another screen, while a place is
+//! also recorded for the changes made in the same state.
+
+use std::{collections::VecDeque, path::PathBuf};
+
+use crate::{commands::Command, tree_options::TreeOptions};
+
+/// the number of places which can be undone
+const MAX_DEPTH: usize = 50;
+
+/// what's needed to display again a tree
+#[derive(Debug, Clone)]
+pub struct Place {
+    pub root: PathBuf,
+    pub options: TreeOptions, // with the pattern
+    pub selection: Option<PathBuf>,
+}
+
+impl Place {
+    /// the pattern as typed by the user
+    pub fn raw_pattern(&self) -> String {
+        Command::from_pattern(&self.options.pattern).raw
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct StateHistory {
+    undos: VecDeque<Place>, // the most recent place last
+    redos: Vec<Place>,      // the places left by undoing, the last undone one last
+    editing_pattern: bool,  // whether the last recorded change was a pattern edit
+}
+
+impl StateHistory {
+    pub fn new() -> StateHistory {
+        StateHistory::default()
+    }
+
+    fn push_undo(&mut self, place: Place) {
+        if self.undos.len() >= MAX_DEPTH {
+            self.undos.pop_front();
+        }
+        self.undos.push_back(place);
+    }
+
+    /// record the place the user just left. As in an editor,
+    /// a new change forgets the places which were undone
+    pub fn record(&mut self, left: Place) {
+        self.push_undo(left);
+        self.redos.clear();
+        self.editing_pattern = false;
+    }
+
+    /// record the place left by editing the pattern, unless it's
+    /// the continuation of the previous pattern edit
+    pub fn record_pattern_edit(&mut self, left: Place) {
+        if !self.editing_pattern {
+            self.record(left);
+            self.editing_pattern = true;
+        }
+    }
+
+    /// tell the history the user did something else than editing
+    /// the pattern, so that the next pattern edit is a new change
+    pub fn end_pattern_edit(&mut self) {
+        self.editing_pattern = false;
+    }
+
+    /// return the place to go back to, if any, the current one
+    /// being kept for redo
+    pub fn undo(&mut self, current: Place) -> Option<Place> {
+        self.editing_pattern = false;
+        let place = self.undos.pop_back()?;
+        self.redos.push(current);
+        Some(place)
+    }
+
+    /// return the place the last undo left, if any, the current
+    /// one being kept for undo
+    pub fn redo(&mut self, current: Place) -> Option<Place> {
+        self.editing_pattern = false;
+        let place = self.redos.pop()?;
+        self.push_undo(current);
+        Some(place)
+    }
+}
+
+#[cfg(test)]
+mod state_history_tests {
+
+    use super::*;
+
+    fn place(root: &str) -> Place {
+        Place {
+            root: PathBuf::from(root),
+            options: TreeOptions::default(),
+            selection: None,
+        }
+    }
+
+    fn root(place: Option<Place>) -> Option<PathBuf> {
+        place.map(|p| p.root)
+    }
+
+    #[test]
+    fn check_undo_redo() {
+        let mut history = StateHistory::new();
+        history.record(place("a"));
+        history.record(place("b"));
+        assert_eq!(root(history.undo(place("c"))), Some(PathBuf::from("b")));
+        assert_eq!(root(history.undo(place("b"))), Some(PathBuf::from("a")));
+        assert!(history.undo(place("a")).is_none());
+        assert_eq!(root(history.redo(place("a"))), Some(PathBuf::from("b")));
+        // a new change forgets what was undone
+        history.record(place("b"));
+        assert!(history.redo(place("d")).is_none());
+        assert_eq!(root(history.undo(place("d"))), Some(PathBuf::from("b")));
+    }
+
+    #[test]
+    fn check_pattern_edits_and_depth() {
+        let mut history = StateHistory::new();
+        history.record_pattern_edit(place("a"));
+        history.record_pattern_edit(place("a1"));
+        history.end_pattern_edit();
+        history.record_pattern_edit(place("a2"));
+        assert_eq!(root(history.undo(place("a3"))), Some(PathBuf::from("a2")));
+        assert_eq!(root(history.undo(place("a2"))), Some(PathBuf::from("a")));
+        for i in 0..MAX_DEPTH + 10 {
+            history.record(place(&i.to_string()));
+        }
+        assert_eq!(history.undos.len(), MAX_DEPTH);
+        assert_eq!(history.undos[0].root, PathBuf::from("10"));
+    }
+}
@@ -7,7 +7,9 @@ use minimad::{
 use crate::{
     errors::ProgramError,
     io::W,
+    jobs,
     screens::Screen,
+    selection_info::SelectionInfo,
 };
 
 /// the status contains information written on the grey line
@@ -16,6 +18,7 @@ pub struct Status<'a> {
     pending_task: Option<&'static str>, // current pending_task
     message: Composite<'a>,
     error: bool, // is the current message an error?
+    info: Option<SelectionInfo>, // information about the selection
 }
 
 impl<'a> Status<'a> {
@@ -29,14 +32,22 @@ impl<'a> Status<'a> {
             pending_task,
             message,
             error,
+            info: None,
         }
     }
 
+    /// add information about the selection, written before the message
+    pub fn with_info(mut self, info: SelectionInfo) -> Status<'a> {
+        self.info = Some(info);
+        self
+    }
+
     pub fn from_message(message: Composite<'a>) -> Status<'a> {
         Self {
             pending_task: None,
             message,
             error: false,
+            info: None,
         }
     }
 
@@ -45,6 +56,7 @@ impl<'a> Status<'a> {
             pending_task: None,
             message,
             error: true,
+            info: None,
         }
     }
 
@@ -61,14 +73,24 @@ impl<'a> Status<'a> {
             x += pending_task.chars().count();
             screen.skin.status_job.queue(w, pending_task)?;
         }
+        let running_jobs = jobs::running_count();
+        if running_jobs > 0 {
+            let s = if running_jobs == 1 { "" } else { "s" };
+            let jobs_indicator = format!(" {} job{} ", running_jobs, s);
+            x += jobs_indicator.chars().count();
+            screen.skin.status_job.queue(w, jobs_indicator)?;
+        }
         screen.goto(w, x as u16, y)?;
+        if let Some(info) = &self.info {
+            x += info.display(w, screen)?;
+        }
         let skin = if self.error {
             &screen.status_skin.error
         } else {
             &screen.status_skin.normal
         };
         skin.write_inline_on(w, " ")?;
-        let remaining_width = screen.width as usize - x - 1;
+        let remaining_width = (screen.width as usize).saturating_sub(x + 1);
         skin.write_composite_fill(w, self.message, remaining_width, Alignment::Left)?;
         screen.clear_line(w)
     }
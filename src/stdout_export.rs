@@ -0,0 +1,74 @@
+//! the export mode used with `--print`, or when neither stdin nor stdout
+//! is a terminal (for example in a script): the tree is built without
+//! entering the application, then written on stdout, without style
+//! unless `--color always` was given.
+//!
+//! When only stdout is redirected, as in `my_command "$(broot)"`, the
+//! application runs as usual, rendered on stderr.
+
+use std::{io, path::PathBuf};
+
+use crate::{
+    displayable_tree::DisplayableTree,
+    errors::ProgramError,
+    html_export::{OutputFormat, TreeHtmlRenderer},
+    skin::Skin,
+    task_sync::TaskLifetime,
+    tree_build::TreeBuilder,
+    tree_options::TreeOptions,
+};
+
+/// the maximal number of lines of a printed tree, when no
+/// height is given (unless `--show-all` is explicitly given)
+const MAX_PRINTED_LINES: usize = 10_000;
+
+/// tell whether stdout is redirected to a file or a pipe
+pub fn is_redirected() -> bool {
+    !atty::is(atty::Stream::Stdout)
+}
+
+/// tell whether there's no terminal at all to run the application on
+pub fn is_headless() -> bool {
+    is_redirected() && !atty::is(atty::Stream::Stdin)
+}
+
+/// build the tree and write it on stdout. With a height, the tree is
+/// balanced to fill that many rows, like on a screen. Without, it's
+/// expanded up to a bound.
+pub fn print_tree(
+    root: PathBuf,
+    options: TreeOptions,
+    height: Option<u16>,
+    format: OutputFormat,
+    skin: &Skin,
+) -> Result<(), ProgramError> {
+    let targeted_size = match height {
+        Some(height) => (height / options.rows_per_line()).max(1) as usize,
+        None => MAX_PRINTED_LINES,
+    };
+    let builder = TreeBuilder::from(root, options, targeted_size)?;
+    let tl = TaskLifetime::unlimited();
+    let mut tree = match builder.build(&tl) {
+        Some(tree) => tree,
+        None => {
+            return Ok(());
+        }
+    };
+    // the sizes and counts computed in background in the application
+    // must all be known before writing
+    while tree.has_dir_missing_size() {
+        tree.fetch_some_missing_dir_size(&tl);
+    }
+    while tree.has_dir_missing_count() {
+        tree.fetch_some_missing_dir_count(&tl);
+    }
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    match format {
+        OutputFormat::Text => {
+            // the width doesn't matter as nothing is truncated out of the app
+            DisplayableTree::out_of_app(&tree, skin, u16::max_value()).write_on(&mut stdout)
+        }
+        OutputFormat::Html => TreeHtmlRenderer::new(&tree, skin).write_on(&mut stdout),
+    }
+}
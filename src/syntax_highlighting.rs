@@ -0,0 +1,147 @@
+//! syntax highlighting of the files displayed in the preview, with
+//! the syntaxes and themes embedded in syntect.
+//!
+//! Only the foreground colors of the theme are used, so that the
+//! background of the skin and of the selected line are kept, and
+//! they're degraded to the colors the terminal can display, as
+//! are the ones of the skin.
+
+use std::path::Path;
+
+use crossterm::style::Color;
+use syntect::{
+    easy::HighlightLines,
+    highlighting::ThemeSet,
+    parsing::{SyntaxReference, SyntaxSet},
+};
+
+use crate::{
+    color_support::{self, Background, ColorSupport},
+    errors::ConfError,
+};
+
+/// the names of the themes embedded in syntect
+pub const THEMES: &[&str] = &[
+    "base16-eighties.dark",
+    "base16-mocha.dark",
+    "base16-ocean.dark",
+    "base16-ocean.light",
+    "InspiredGitHub",
+    "Solarized (dark)",
+    "Solarized (light)",
+];
+
+lazy_static! {
+    static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_nonewlines();
+    static ref THEME_SET: ThemeSet = ThemeSet::load_defaults();
+}
+
+/// the theme used when none is configured
+pub fn default_theme(background: Background) -> &'static str {
+    match background {
+        Background::Dark => "base16-ocean.dark",
+        Background::Light => "InspiredGitHub",
+    }
+}
+
+/// read the `syntax_theme` setting: the name of a theme, or "none"
+/// when the preview mustn't be highlighted
+pub fn parse_theme(raw: &str) -> Result<Option<String>, ConfError> {
+    if raw == "none" {
+        Ok(None)
+    } else if THEMES.contains(&raw) {
+        Ok(Some(raw.to_string()))
+    } else {
+        Err(ConfError::InvalidSyntaxTheme {
+            raw: raw.to_string(),
+        })
+    }
+}
+
+/// find the syntax of the file from its name, its extension or,
+/// for scripts, its first line
+fn find_syntax(path: &Path, first_line: Option<&str>) -> Option<&'static SyntaxReference> {
+    let by_name = |name: Option<&std::ffi::OsStr>| {
+        name.and_then(|name| name.to_str())
+            .and_then(|name| SYNTAX_SET.find_syntax_by_extension(name))
+    };
+    by_name(path.file_name())
+        .or_else(|| by_name(path.extension()))
+        .or_else(|| first_line.and_then(|line| SYNTAX_SET.find_syntax_by_first_line(line)))
+}
+
+/// the colors of the chars of the lines of a file, or None when the
+/// syntax of the file isn't known
+pub fn highlight(
+    path: &Path,
+    lines: &[String],
+    theme: &str,
+    support: ColorSupport,
+) -> Option<Vec<Vec<Color>>> {
+    let theme = THEME_SET.themes.get(theme)?;
+    let syntax = find_syntax(path, lines.first().map(|line| line.as_str()))?;
+    debug!("highlighting {:?} as {}", path, &syntax.name);
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let mut colored_lines = Vec::with_capacity(lines.len());
+    for line in lines {
+        let ranges = match highlighter.highlight_line(line, &SYNTAX_SET) {
+            Ok(ranges) => ranges,
+            Err(e) => {
+                warn!("error while highlighting {:?}: {:?}", path, e);
+                return None;
+            }
+        };
+        let mut colors = Vec::with_capacity(line.len());
+        for (style, text) in ranges {
+            let fg = style.foreground;
+            let color = color_support::degrade(
+                Color::Rgb {
+                    r: fg.r,
+                    g: fg.g,
+                    b: fg.b,
+                },
+                support,
+            );
+            colors.extend(text.chars().map(|_| color));
+        }
+        colored_lines.push(colors);
+    }
+    Some(colored_lines)
+}
+
+#[cfg(test)]
+mod syntax_highlighting_tests {
+
+    use super::*;
+
+    #[test]
+    fn check_embedded_themes() {
+        for name in THEMES {
+            assert!(THEME_SET.themes.contains_key(*name), "missing theme {}", name);
+        }
+        assert_eq!(parse_theme("none").unwrap(), None);
+        assert!(parse_theme("Monokai").is_err());
+    }
+
+    #[test]
+    fn check_highlighting() {
+        let lines: Vec<String> = vec!["fn main() {", "    let a = \"é\";", "}"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let colors = highlight(
+            Path::new("src/main.rs"),
+            &lines,
+            "base16-ocean.dark",
+            ColorSupport::TrueColor,
+        )
+        .unwrap();
+        assert_eq!(colors.len(), 3);
+        assert_eq!(colors[1].len(), lines[1].chars().count());
+        // the keyword and the name of the function have different colors
+        assert_ne!(colors[0][0], colors[0][3]);
+        let script = vec!["#!/bin/sh".to_string(), "echo a".to_string()];
+        assert!(highlight(Path::new("run"), &script, "InspiredGitHub", ColorSupport::Ansi16).is_some());
+        assert!(highlight(Path::new("notes.unknown"), &lines, "InspiredGitHub", ColorSupport::Ansi16).is_none());
+    }
+}
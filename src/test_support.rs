@@ -0,0 +1,404 @@
+//! Support for end-to-end tests, available with the `test-support`
+//! feature (and in broot's own tests, at the end of this file).
+//!
+//! A `Harness` plays a script of key events on the application states
+//! as the application would, but without a terminal, and renders the
+//! displayed tree as text frames:
+//!
+//! ```ignore
+//! let mut harness = Harness::new(root, TreeOptions::default(), Conf::default_conf(), 80, 30)?;
+//! harness.type_str("main")?;
+//! assert!(harness.frame()?.unwrap().contains("main.rs"));
+//! harness.type_str(" edit")?;
+//! harness.key(KeyEvent::Enter)?;
+//! ```
+
+use std::path::PathBuf;
+
+use crossterm::input::KeyEvent;
+use termimad::Event;
+
+use crate::{
+    app::{App, CommandOutcome},
+    app_context::AppContext,
+    browser_states::BrowserState,
+    cli::AppLaunchArgs,
+    color_support::{ColorChoice, ColorSupport},
+    commands::Command,
+    conf::Conf,
+    displayable_tree::DisplayableTree,
+    errors::{ProgramError, TreeBuildError},
+    external::Launchable,
+    html_export::OutputFormat,
+    screens::{Screen, FLAGS_AREA_WIDTH},
+    skin::Skin,
+    task_sync::TaskLifetime,
+    tree_options::TreeOptions,
+    verb_store::VerbStore,
+};
+
+/// build the context of an application launched on the given root,
+/// without any launch argument, with the verbs of the configuration
+pub fn app_context(
+    root: PathBuf,
+    tree_options: TreeOptions,
+    conf: &Conf,
+    height: u16,
+) -> AppContext {
+    let mut verb_store = VerbStore::new();
+    verb_store.init(conf);
+    let launch_args = AppLaunchArgs {
+        root,
+        root_given: true,
+        file_export_path: None,
+        out_marks: false,
+        confirm_quit: false,
+        dry_run: false,
+        auto_refresh: false,
+        elevation_command: None,
+        syntax_theme: None,
+        max_highlighted_size: 0,
+        color_support: ColorSupport::TrueColor,
+        cmd_export_path: None,
+        tree_options,
+        commands: None,
+        cmd_separator: None,
+        install: false,
+        height: Some(height),
+        color: ColorChoice::Auto,
+        no_mouse: false,
+        output_format: OutputFormat::Text,
+        size_format: None,
+        fuzzy_case: None,
+        full_permissions: false,
+        two_rows: false,
+        get_path: None,
+        limit: 1,
+        print: false,
+        quick_start: false,
+        restore: false,
+        selection: None,
+        snapshot: None,
+        write_snapshot: None,
+        listen: None,
+        send: None,
+    };
+    AppContext::from(launch_args, verb_store)
+}
+
+/// an application driven by a script instead of a terminal. The
+/// commands are applied by the application itself, only the drawing
+/// is replaced by the rendering of frames
+pub struct Harness {
+    con: AppContext,
+    screen: Screen,
+    app: App,
+    cmd: Command,
+    error: Option<String>,
+    message: Option<String>,
+}
+
+impl Harness {
+    /// build the first state, on the given root, with the verbs and
+    /// the skin of the given configuration
+    pub fn new(
+        root: PathBuf,
+        tree_options: TreeOptions,
+        conf: Conf,
+        width: u16,
+        height: u16,
+    ) -> Result<Harness, ProgramError> {
+        let con = app_context(root.clone(), tree_options.clone(), &conf, height);
+        let mut screen = Screen::with_size(Skin::create(conf.skin, conf.file_styles), width, height);
+        screen.input_field.change_area(0, height - 1, width - FLAGS_AREA_WIDTH);
+        let state = BrowserState::new(root.clone(), tree_options, &screen, &TaskLifetime::unlimited())?
+            .ok_or_else(|| TreeBuildError::NotADirectory {
+                path: format!("{:?}", root),
+            })?;
+        let mut app = App::new();
+        app.push(Box::new(state));
+        let mut harness = Harness {
+            con,
+            screen,
+            app,
+            cmd: Command::new(),
+            error: None,
+            message: None,
+        };
+        harness.do_pending_tasks();
+        Ok(harness)
+    }
+
+    /// do all the pending tasks (searches, sizes, counts), as there's
+    /// no user to interrupt them
+    fn do_pending_tasks(&mut self) {
+        let tl = TaskLifetime::unlimited();
+        while self.app.state().has_pending_task() {
+            self.app.mut_state().do_pending_task(&mut self.screen, &tl);
+        }
+    }
+
+    /// apply the current command, the way the application does
+    fn apply_command(&mut self) -> Result<(), ProgramError> {
+        let cmd = self.cmd.clone();
+        let CommandOutcome { cmd, error, message } =
+            self.app.apply_command_to_states(cmd, &mut self.screen, &self.con)?;
+        self.error = error;
+        self.message = message;
+        self.screen.input_field.set_content(&cmd.raw);
+        self.cmd = cmd;
+        self.do_pending_tasks();
+        Ok(())
+    }
+
+    /// play a terminal event, as if it came from the user
+    pub fn event(&mut self, event: Event) -> Result<(), ProgramError> {
+        if self.app.is_quitting() {
+            return Ok(());
+        }
+        self.cmd.add_event(&event, &mut self.screen.input_field, &self.con);
+        self.apply_command()
+    }
+
+    pub fn key(&mut self, key: KeyEvent) -> Result<(), ProgramError> {
+        self.event(Event::Key(key))
+    }
+
+    /// type the chars of the string, one key event per char
+    pub fn type_str(&mut self, s: &str) -> Result<(), ProgramError> {
+        for c in s.chars() {
+            self.key(KeyEvent::Char(c))?;
+        }
+        Ok(())
+    }
+
+    /// render the displayed tree as it would be printed by `:print_tree`,
+    /// with the skin of the configuration when `styled` is true
+    fn render(&self, styled: bool) -> Result<Option<String>, ProgramError> {
+        let tree = match self.app.state().tree() {
+            Some(tree) => tree,
+            None => return Ok(None),
+        };
+        let no_style_skin;
+        let skin = if styled {
+            &self.screen.skin
+        } else {
+            no_style_skin = Skin::no_term();
+            &no_style_skin
+        };
+        let dp = DisplayableTree::out_of_app(tree, skin, self.screen.width);
+        let mut frame = Vec::new();
+        dp.write_on(&mut frame)?;
+        Ok(Some(String::from_utf8_lossy(&frame).to_string()))
+    }
+
+    /// the displayed tree, as plain text, or None if the current state
+    /// doesn't display a tree (eg the help)
+    pub fn frame(&self) -> Result<Option<String>, ProgramError> {
+        self.render(false)
+    }
+
+    /// the displayed tree with the styles of the skin, as ANSI escape codes
+    pub fn styled_frame(&self) -> Result<Option<String>, ProgramError> {
+        self.render(true)
+    }
+
+    /// the path of the selected line, if a tree is displayed
+    pub fn selection(&self) -> Option<PathBuf> {
+        self.app.state().tree().map(|tree| tree.selected_line().path.to_path_buf())
+    }
+
+    /// what's visible in the input
+    pub fn input(&self) -> &str {
+        &self.cmd.raw
+    }
+
+    /// the error the application would display in the status line
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_ref().map(|s| s.as_str())
+    }
+
+    /// the message the application would display in the status line
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_ref().map(|s| s.as_str())
+    }
+
+    /// the command of the verb waiting for the user to confirm
+    /// its execution, if any
+    pub fn confirmation(&self) -> Option<String> {
+        self.app.confirmation().map(|execution| execution.command())
+    }
+
+    /// the program the application would launch on quitting, if any
+    pub fn launchable(&self) -> Option<&Launchable> {
+        self.app.launchable()
+    }
+
+    pub fn is_quitting(&self) -> bool {
+        self.app.is_quitting()
+    }
+
+    /// the number of stacked states (the help is a state)
+    pub fn depth(&self) -> usize {
+        self.app.depth()
+    }
+}
+
+#[cfg(test)]
+mod harness_tests {
+
+    use {
+        super::*,
+        std::{env, fs, process, thread, time::Duration},
+    };
+
+    /// create, for one test, a directory with a few files
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("broot-test-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(dir.join("trash.txt"), "").unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    fn harness(root: &PathBuf) -> Harness {
+        Harness::new(root.clone(), TreeOptions::default(), Conf::default_conf(), 80, 20).unwrap()
+    }
+
+    /// select trash.txt and ask for its removal
+    fn ask_rm(harness: &mut Harness, root: &PathBuf) {
+        harness.type_str("trash").unwrap();
+        assert_eq!(harness.selection(), Some(root.join("trash.txt")));
+        harness.type_str(" rm").unwrap();
+        harness.key(KeyEvent::Enter).unwrap();
+        let command = harness.confirmation().expect("a confirmation should be asked");
+        assert!(command.contains("trash.txt"), "unexpected command: {:?}", command);
+        assert!(root.join("trash.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_confirmed_execution() {
+        let root = test_dir("confirm");
+        let mut harness = harness(&root);
+        ask_rm(&mut harness, &root);
+        // the wheel doesn't answer the question
+        harness.event(Event::Wheel(1)).unwrap();
+        assert!(harness.confirmation().is_some());
+        harness.key(KeyEvent::Char('y')).unwrap();
+        assert_eq!(harness.confirmation(), None);
+        assert_eq!(harness.error(), None);
+        assert!(!root.join("trash.txt").exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn check_cancelled_execution() {
+        let root = test_dir("cancel");
+        let mut harness = harness(&root);
+        ask_rm(&mut harness, &root);
+        harness.key(KeyEvent::Char('n')).unwrap();
+        assert_eq!(harness.confirmation(), None);
+        assert_eq!(harness.message(), Some("Cancelled"));
+        assert!(root.join("trash.txt").exists());
+        // the next key is handled normally
+        harness.key(KeyEvent::Esc).unwrap();
+        assert_eq!(harness.message(), None);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn check_search_frames() {
+        let root = test_dir("search");
+        let mut harness = harness(&root);
+        let frame = harness.frame().unwrap().unwrap();
+        assert!(frame.contains("trash.txt"));
+        assert!(harness.styled_frame().unwrap().unwrap().contains('\x1b'));
+        harness.type_str("main").unwrap();
+        assert_eq!(harness.input(), "main");
+        assert_eq!(harness.selection(), Some(root.join("src/main.rs")));
+        let frame = harness.frame().unwrap().unwrap();
+        assert!(frame.contains("main.rs"));
+        assert!(!frame.contains("trash.txt"));
+        // esc clears the pattern, and :undo brings it back
+        harness.key(KeyEvent::Esc).unwrap();
+        assert_eq!(harness.input(), "");
+        assert!(harness.frame().unwrap().unwrap().contains("trash.txt"));
+        harness.type_str(":undo").unwrap();
+        harness.key(KeyEvent::Enter).unwrap();
+        assert_eq!(harness.error(), None);
+        assert_eq!(harness.input(), "main");
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn check_newer_filter() {
+        let root = test_dir("newer");
+        // some file systems only record the second
+        thread::sleep(Duration::from_millis(1100));
+        fs::write(root.join("src/lib.rs"), "").unwrap();
+        let mut harness = harness(&root);
+        harness.type_str("newer:trash.txt").unwrap();
+        let frame = harness.frame().unwrap().unwrap();
+        assert!(frame.contains("lib.rs"));
+        assert!(!frame.contains("main.rs"));
+        assert!(!frame.contains("trash.txt"));
+        // the filter combines with a pattern
+        harness.type_str(":main").unwrap();
+        assert_eq!(harness.input(), "newer:trash.txt:main");
+        let frame = harness.frame().unwrap().unwrap();
+        assert!(!frame.contains("lib.rs"));
+        assert!(!frame.contains("main.rs"));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn check_help_state() {
+        let root = test_dir("help");
+        let mut harness = harness(&root);
+        harness.key(KeyEvent::Char('?')).unwrap();
+        assert_eq!(harness.depth(), 2);
+        assert_eq!(harness.frame().unwrap(), None);
+        harness.key(KeyEvent::Esc).unwrap();
+        assert_eq!(harness.depth(), 1);
+        assert!(harness.frame().unwrap().is_some());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_custom_verb() {
+        let root = test_dir("verb");
+        let conf = Conf::from_toml(
+            r#"
+            [[verbs]]
+            invocation = "mk {name}"
+            execution = "touch {directory}/{name}"
+            leave_broot = false
+            "#,
+        )
+        .unwrap();
+        let mut harness = Harness::new(root.clone(), TreeOptions::default(), conf, 80, 20).unwrap();
+        harness.type_str(":mk new.txt").unwrap();
+        harness.key(KeyEvent::Enter).unwrap();
+        assert_eq!(harness.error(), None);
+        assert!(root.join("new.txt").exists());
+        assert!(harness.frame().unwrap().unwrap().contains("new.txt"));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn check_dry_run() {
+        let root = test_dir("dry-run");
+        let mut harness = harness(&root);
+        harness.con.launch_args.dry_run = true;
+        harness.type_str("trash rm").unwrap();
+        harness.key(KeyEvent::Enter).unwrap();
+        assert_eq!(harness.confirmation(), None);
+        let message = harness.message().expect("the command should be displayed");
+        assert!(message.starts_with("Dry run, not executed:"), "unexpected message: {:?}", message);
+        assert!(root.join("trash.txt").exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -1,43 +1,113 @@
 use std::{
     cmp::{self, Ordering},
     collections::{BinaryHeap, VecDeque},
-    fs,
-    path::PathBuf,
+    fs, io,
+    path::{Path, PathBuf},
     result::Result,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 
 use id_arena::{Arena, Id};
+use rayon::{
+    iter::{IntoParallelRefIterator, ParallelIterator},
+    ThreadPool, ThreadPoolBuilder,
+};
 
 use crate::{
+    archives::ArchiveKind,
     errors::TreeBuildError,
-    flat_tree::{LineType, Tree, TreeLine},
+    file_sizes::with_thousands_separators,
+    flat_tree::{LinePath, LineType, Tree, TreeLine},
     git_ignore::GitIgnoreFilter,
+    os_names,
+    path_case,
+    selection_info,
     task_sync::TaskLifetime,
-    tree_options::{OptionBool, TreeOptions},
+    tree_options::{FilterStrictness, FollowSymlinks, OptionBool, TreeOptions},
+    tree_sources::{EntryType, SourceEntry, TreeSource},
 };
 
 type BId = Id<BLine>;
 
+/// where a build stands after a step of gathering
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuildProgress {
+    Complete,    // the lines are gathered, the tree can be finished
+    Pending,     // the deadline was reached before the end of the gathering
+    Interrupted, // the task lifetime expired
+}
+
+/// what the builder tells of the search it made
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchStats {
+    pub nb_searched: usize, // number of entries read and checked against the filters
+    pub nb_matches: usize,  // number of entries whose name matched the pattern
+    pub complete: bool,     // whether everything was searched (not stopped by the time budget)
+}
+
+impl SearchStats {
+    /// a human readable summary, eg "37 matches among 12,502 files searched"
+    pub fn summary(&self) -> String {
+        let s = if self.nb_matches == 1 { "" } else { "es" };
+        format!(
+            "{} match{} among {} files searched{}",
+            with_thousands_separators(self.nb_matches as u64),
+            s,
+            with_thousands_separators(self.nb_searched as u64),
+            if self.complete { "" } else { " (the search stopped before the end)" },
+        )
+    }
+}
+
 /// If a search found enough results to fill the screen but didn't scan
 /// everything, we search a little more in case we find better matches
 /// but not after the NOT_LONG duration.
 static NOT_LONG: Duration = Duration::from_millis(1300);
 
+/// the number of threads reading directories in parallel. Reading
+/// is mostly waiting for the disk or the network, so there may be
+/// more threads than cores
+const READER_THREADS: usize = 8;
+
+/// the number of directories read in parallel before their children
+/// are added to the tree and the task lifetime is checked again
+const READ_CHUNK_SIZE: usize = 64;
+
+lazy_static! {
+    // None when the threads can't be created, the directories
+    // being then read one after the other
+    static ref READER_POOL: Option<ThreadPool> = ThreadPoolBuilder::new()
+        .num_threads(READER_THREADS)
+        .build()
+        .ok();
+}
+
+/// the base of the score of the lines, from which their depth is
+/// subtracted so that less deep entries come first
+const DEPTH_DOPING: i32 = 10_000;
+
+/// added to the score of the lines of the forced open directory
+/// so that they're the last ones to be trimmed
+const FORCED_OPEN_BONUS: i32 = 100_000;
+
 /// like a tree line, but with the info needed during the build
 /// This structure isn't usable independantly from the tree builder
 struct BLine {
     parent_id: Option<BId>,
-    path: PathBuf,
+    path: LinePath,
     depth: u16,
     name: String,
-    file_type: fs::FileType,
+    file_type: EntryType,
     children: Option<Vec<BId>>, // sorted and filtered
     next_child_idx: usize,      // index for iteration, among the children
     has_error: bool,
     has_match: bool,
+    direct_match: bool, // whether the line itself matches, not only some of its children
+    nb_matching_children: usize, // used during the gathering step, when matches are evicted
     score: i32,
     ignore_filter: Option<GitIgnoreFilter>,
+    followed_link: bool, // a symlink to a directory the builder may enter
+    opened_archive: bool, // an archive the builder may enter, as a directory
     nb_kept_children: i32, // used during the trimming step
 }
 
@@ -47,8 +117,8 @@ enum BLineResult {
     FilteredOutAsHidden,
     FilteredOutByPattern,
     FilteredOutAsNonFolder,
+    FilteredOutByGlob,
     GitIgnored,
-    Invalid,
 }
 
 impl BLine {
@@ -56,28 +126,30 @@ impl BLine {
     fn from_root(
         blines: &mut Arena<BLine>,
         path: PathBuf,
+        source: &dyn TreeSource,
         respect_ignore: OptionBool,
     ) -> Result<BId, TreeBuildError> {
         let name = match path.file_name() {
-            Some(name) => name.to_string_lossy().to_string(),
+            Some(name) => os_names::escape(name),
             None => String::from("???"), // should not happen
         };
-        let ignore_filter = if respect_ignore == OptionBool::No {
+        let ignore_filter = if respect_ignore == OptionBool::No || !source.is_local() {
+            // the .gitignore files of a remote tree aren't read
             None
         } else {
-            let gif = GitIgnoreFilter::applicable_to(&path);
-            // if auto, we don't look for other gif if we're not in a git dir
-            if respect_ignore == OptionBool::Auto && gif.files.is_empty() {
+            // if auto, the rules only apply when we're in a git repository
+            // (the repositories found deeper will be detected by make_line)
+            if respect_ignore == OptionBool::Auto && selection_info::git_root(&path).is_none() {
                 None
             } else {
-                Some(gif)
+                Some(GitIgnoreFilter::applicable_to(&path))
             }
         };
-        if let Ok(md) = fs::metadata(&path) {
-            let file_type = md.file_type();
+        if let Ok(md) = source.metadata(&path) {
+            let file_type = md.entry_type;
             Ok(blines.alloc(BLine {
                 parent_id: None,
-                path,
+                path: LinePath::root(&path),
                 depth: 0,
                 name,
                 children: None,
@@ -85,8 +157,12 @@ impl BLine {
                 file_type,
                 has_error: false,
                 has_match: true,
+                direct_match: true,
+                nb_matching_children: 0,
                 score: 0,
                 ignore_filter,
+                followed_link: false,
+                opened_archive: false,
                 nb_kept_children: 0,
             }))
         } else {
@@ -95,19 +171,20 @@ impl BLine {
             })
         }
     }
-    fn to_tree_line(&self) -> std::io::Result<TreeLine> {
+    fn to_tree_line(&self, source: &dyn TreeSource) -> std::io::Result<TreeLine> {
+        let path = self.path.to_path_buf();
+        let metadata = source.symlink_metadata(&path)?;
         let mut has_error = self.has_error;
         let line_type = if self.file_type.is_dir() {
             LineType::Dir
         } else if self.file_type.is_symlink() {
-            if let Ok(target) = fs::read_link(&self.path) {
-                let target = target.to_string_lossy().into_owned();
-                let mut target_path = PathBuf::from(&target);
+            if let Ok(mut target_path) = source.read_link(&path) {
+                let target = os_names::escape(target_path.as_os_str());
                 if target_path.is_relative() {
-                    target_path = self.path.parent().unwrap().join(target_path)
+                    target_path = path.parent().unwrap().join(target_path)
                 }
-                if let Ok(target_metadata) = fs::symlink_metadata(&target_path) {
-                    if target_metadata.file_type().is_dir() {
+                if let Ok(target_metadata) = source.symlink_metadata(&target_path) {
+                    if target_metadata.entry_type.is_dir() {
                         LineType::SymLinkToDir(target)
                     } else {
                         LineType::SymLinkToFile(target)
@@ -120,6 +197,11 @@ impl BLine {
                 has_error = true;
                 LineType::SymLinkToFile(String::from("????"))
             }
+        } else if !metadata.in_archive
+            && source.is_local()
+            && ArchiveKind::of(&path).is_some()
+        {
+            LineType::Archive
         } else {
             LineType::File
         };
@@ -129,7 +211,6 @@ impl BLine {
         } else {
             0
         };
-        let metadata = fs::symlink_metadata(&self.path)?;
         Ok(TreeLine {
             left_branchs: vec![false; self.depth as usize].into_boxed_slice(),
             depth: self.depth,
@@ -141,6 +222,8 @@ impl BLine {
             unlisted,
             score: self.score,
             size: None,
+            children_count: None,
+            group_count: None,
             metadata,
         })
     }
@@ -176,6 +259,22 @@ impl PartialOrd for SortableBId {
     }
 }
 
+/// the state of the exploration of the directories, kept between
+/// the steps of gathering
+struct Gathering {
+    out_blines: Vec<BId>, // the blines we want to display
+    nb_lines_ok: usize,   // in out_blines
+    // breadth first: the directories of the current level, those of the next
+    // level, and those whose children must be read before going on
+    open_dirs: VecDeque<BId>,
+    next_level_dirs: Vec<BId>,
+    unread_dirs: VecDeque<BId>,
+    // depth first: the directories being explored, with the number
+    // of their children which matched
+    dir_stack: Vec<(BId, usize)>,
+    start: Instant,
+}
+
 /// The TreeBuilder builds a Tree according to options (including an optional search pattern)
 /// Instead of the final TreeLine, the builder uses an internal structure: BLine.
 /// All BLines used during build are stored in the blines vector and kept until the end.
@@ -185,8 +284,11 @@ pub struct TreeBuilder {
     options: TreeOptions,
     targeted_size: usize, // the number of lines we should fill (height of the screen)
     nb_gitignored: u32,   // number of times a gitignore pattern excluded a file
+    search_stats: SearchStats,
     blines: Arena<BLine>,
     root_id: BId,
+    best_matches: BinaryHeap<SortableBId>, // the retained matching files, the worst one on top
+    gathering: Option<Gathering>,          // None until the first step of gathering
 }
 impl TreeBuilder {
     pub fn from(
@@ -194,30 +296,39 @@ impl TreeBuilder {
         options: TreeOptions,
         targeted_size: usize,
     ) -> Result<TreeBuilder, TreeBuildError> {
+        let mut options = options;
+        options.pattern.resolve_file_filter(&path, options.source.as_ref());
         let mut blines = Arena::new();
-        let root_id = BLine::from_root(&mut blines, path, options.respect_git_ignore)?;
+        let root_id = BLine::from_root(
+            &mut blines,
+            path,
+            options.source.as_ref(),
+            options.respect_git_ignore,
+        )?;
         Ok(TreeBuilder {
             options,
             targeted_size,
             nb_gitignored: 0,
+            search_stats: SearchStats::default(),
             blines,
             root_id,
+            best_matches: BinaryHeap::new(),
+            gathering: None,
         })
     }
     /// return a bline if the direntry directly matches the options and there's no error
-    fn make_line(&mut self, parent_id: BId, e: fs::DirEntry, depth: u16) -> BLineResult {
-        let name = e.file_name();
-        let name = match name.to_str() {
-            Some(name) => name,
-            None => {
-                return BLineResult::Invalid;
-            }
+    fn make_line(&mut self, parent_id: BId, e: SourceEntry, depth: u16) -> BLineResult {
+        // a name which isn't valid UTF-8 is matched with its invalid
+        // bytes escaped, the same way it's displayed
+        let name = match e.path.file_name() {
+            Some(name) => os_names::escape(name),
+            None => String::from("???"), // should not happen
         };
         if !self.options.show_hidden && name.starts_with('.') {
             return BLineResult::FilteredOutAsHidden;
         }
         let mut has_match = true;
-        let mut score = 10000 - i32::from(depth); // we dope less deep entries
+        let mut score = DEPTH_DOPING - i32::from(depth); // we dope less deep entries
         if self.options.pattern.is_some() {
             if let Some(pattern_score) = self.options.pattern.score_of(&name) {
                 score += pattern_score;
@@ -225,13 +336,34 @@ impl TreeBuilder {
                 has_match = false;
             }
         }
-        let file_type = match e.file_type() {
-            Ok(ft) => ft,
-            Err(_) => {
-                return BLineResult::Invalid;
+        let file_type = e.entry_type;
+        let path = e.path;
+        if has_match {
+            if let Some(filter) = self.options.pattern.file_filter() {
+                has_match = filter.accepts(&path, file_type, &*self.options.source);
             }
-        };
-        if file_type.is_file() || file_type.is_symlink() {
+        }
+        if has_match {
+            if let Some(reference) = self.options.newer_than {
+                has_match = self.is_newer(&path, reference);
+            }
+        }
+        if has_match {
+            if let Some(inode) = self.options.same_inode {
+                has_match = self
+                    .options
+                    .source
+                    .symlink_metadata(&path)
+                    .map_or(false, |md| (md.dev(), md.ino()) == inode);
+            }
+        }
+        let followed_link = file_type.is_symlink()
+            && self.follows_links()
+            && self.options.source.metadata(&path).map_or(false, |md| md.entry_type.is_dir())
+            && !self.is_link_cycle(parent_id, &path);
+        let opened_archive = file_type.is_file()
+            && self.options.opened_archives.iter().any(|p| path_case::same_path(p, &path));
+        if (file_type.is_file() || file_type.is_symlink()) && !followed_link && !opened_archive {
             if !has_match {
                 return BLineResult::FilteredOutByPattern;
             }
@@ -239,7 +371,9 @@ impl TreeBuilder {
                 return BLineResult::FilteredOutAsNonFolder;
             }
         }
-        let path = e.path();
+        if self.options.path_filters.hides(&path, &name, file_type.is_dir()) {
+            return BLineResult::FilteredOutByGlob;
+        }
         let mut ignore_filter = None;
         if let Some(gif) = &self.blines[parent_id].ignore_filter {
             if !gif.accepts(&path, &name, file_type.is_dir()) {
@@ -248,48 +382,175 @@ impl TreeBuilder {
             if file_type.is_dir() {
                 ignore_filter = Some(gif.extended_to(&path));
             }
+        } else if file_type.is_dir()
+            && self.options.respect_git_ignore == OptionBool::Auto
+            && self.options.source.is_local()
+            && path.join(".git").exists()
+        {
+            // in auto mode, entering a repository switches its rules on
+            ignore_filter = Some(GitIgnoreFilter::applicable_to(&path));
+        }
+        self.search_stats.nb_searched += 1;
+        if has_match && self.options.pattern.is_some() {
+            self.search_stats.nb_matches += 1;
         }
+        // the path is stored as a child of the parent's one
+        let path = match path.file_name() {
+            Some(file_name) => self.blines[parent_id].path.child(file_name),
+            None => LinePath::root(&path), // should not happen
+        };
         BLineResult::Some(self.blines.alloc(BLine {
             parent_id: Some(parent_id),
             path,
             depth,
-            name: name.to_string(),
+            name,
             file_type,
             children: None,
             next_child_idx: 0,
             has_error: false,
             has_match,
+            direct_match: has_match,
+            nb_matching_children: 0,
             score,
             ignore_filter,
+            followed_link,
+            opened_archive,
             nb_kept_children: 0,
         }))
     }
 
-    /// returns true when there are direct matches among children
+    /// tell whether the entry was modified after the reference time.
+    /// An entry whose date can't be read isn't newer.
+    fn is_newer(&self, path: &Path, reference: SystemTime) -> bool {
+        self.options
+            .source
+            .metadata(path)
+            .and_then(|md| md.modified())
+            .map_or(false, |modified| modified > reference)
+    }
+
+    /// tell whether symlinks to directories may currently be entered
+    fn follows_links(&self) -> bool {
+        match self.options.follow_symlinks {
+            FollowSymlinks::Never => false,
+            FollowSymlinks::WhenExplicit => !self.options.pattern.is_some(),
+            FollowSymlinks::Always => true,
+        }
+    }
+
+    /// tell whether the symlink at path targets the parent directory
+    /// or one of its ancestors, which would make the builder loop
+    fn is_link_cycle(&self, parent_id: BId, path: &Path) -> bool {
+        let target = match fs::canonicalize(path) {
+            Ok(target) => target,
+            Err(_) => {
+                return true; // we don't enter what we don't understand
+            }
+        };
+        let mut ancestor_id = Some(parent_id);
+        while let Some(id) = ancestor_id {
+            if let Ok(ancestor) = fs::canonicalize(&self.blines[id].path.to_path_buf()) {
+                if ancestor == target {
+                    return true;
+                }
+            }
+            ancestor_id = self.blines[id].parent_id;
+        }
+        false
+    }
+
+    /// tell whether the builder may look into the directory to
+    /// search or to count its children.
+    /// The root is always entered, even if it's in the do_not_enter list.
+    fn may_enter(&self, bid: BId) -> bool {
+        self.may_list_children(bid) && !self.is_at_max_depth(bid)
+    }
+
+    /// tell whether the line is a directory the builder doesn't enter
+    /// because it's at the maximal depth (the root is never cut)
+    fn is_at_max_depth(&self, bid: BId) -> bool {
+        let depth = self.blines[bid].depth;
+        depth > 0 && self.options.max_depth.map_or(false, |max| depth >= max)
+    }
+
+    /// tell whether the children of the directory may be read, either
+    /// to explore them or only to count them.
+    fn may_list_children(&self, bid: BId) -> bool {
+        let bline = &self.blines[bid];
+        if !(bline.file_type.is_dir() || bline.followed_link || bline.opened_archive) {
+            return false;
+        }
+        if bline.parent_id.is_none() {
+            return true;
+        }
+        let path = bline.path.to_path_buf();
+        !self
+            .options
+            .do_not_enter
+            .iter()
+            .any(|p| path_case::same_path(p, &path))
+            && self.options.path_filters.may_enter(&path, &bline.name)
+    }
+
+    /// read the children of the directory and make their lines.
+    /// Returns true when there are direct matches among children
     fn load_children(&mut self, bid: BId) -> bool {
+        let listing = self.options.source.read_dir(&self.blines[bid].path.to_path_buf());
+        self.add_children(bid, listing)
+    }
+
+    /// read the directories in parallel. The listings are returned in
+    /// the order of the ids, so that the tree doesn't depend on which
+    /// read ends first. Return None if the task expired
+    fn read_dirs(
+        &self,
+        ids: &[BId],
+        task_lifetime: &TaskLifetime,
+    ) -> Option<Vec<io::Result<Vec<SourceEntry>>>> {
+        let source = self.options.source.as_ref();
+        let paths: Vec<PathBuf> = ids.iter().map(|&id| self.blines[id].path.to_path_buf()).collect();
+        let read = |path: &PathBuf| {
+            if task_lifetime.is_expired() {
+                Err(io::Error::new(io::ErrorKind::Interrupted, "task expired"))
+            } else {
+                source.read_dir(path)
+            }
+        };
+        let listings: Vec<io::Result<Vec<SourceEntry>>> = match &*READER_POOL {
+            Some(pool) => pool.install(|| paths.par_iter().map(read).collect()),
+            None => paths.iter().map(read).collect(),
+        };
+        if task_lifetime.is_expired() {
+            None
+        } else {
+            Some(listings)
+        }
+    }
+
+    /// make the lines of the children of the directory from its listing.
+    /// Returns true when there are direct matches among children
+    fn add_children(&mut self, bid: BId, listing: io::Result<Vec<SourceEntry>>) -> bool {
         let mut has_child_match = false;
-        match fs::read_dir(&self.blines[bid].path) {
+        match listing {
             Ok(entries) => {
                 let mut children: Vec<BId> = Vec::new();
                 let child_depth = self.blines[bid].depth + 1;
                 for e in entries {
-                    if let Ok(e) = e {
-                        let bl = self.make_line(bid, e, child_depth);
-                        match bl {
-                            BLineResult::Some(child_id) => {
-                                if self.blines[child_id].has_match {
-                                    // direct match
-                                    self.blines[bid].has_match = true;
-                                    has_child_match = true;
-                                }
-                                children.push(child_id);
-                            }
-                            BLineResult::GitIgnored => {
-                                self.nb_gitignored += 1;
-                            }
-                            _ => {
-                                // other reason, we don't care
+                    let bl = self.make_line(bid, e, child_depth);
+                    match bl {
+                        BLineResult::Some(child_id) => {
+                            if self.blines[child_id].has_match {
+                                // direct match, the caller marks the directory
+                                self.blines[bid].nb_matching_children += 1;
+                                has_child_match = true;
                             }
+                            children.push(child_id);
+                        }
+                        BLineResult::GitIgnored => {
+                            self.nb_gitignored += 1;
+                        }
+                        _ => {
+                            // other reason, we don't care
                         }
                     }
                 }
@@ -324,87 +585,392 @@ impl TreeBuilder {
         }
     }
 
+    /// the number of matching lines to gather before stopping (or,
+    /// with a ranking pattern, before looking only a little more for
+    /// better ones)
+    fn optimal_size(&self) -> usize {
+        if self.options.show_all {
+            usize::max_value()
+        } else {
+            self.options.pattern.optimal_result_number(self.targeted_size)
+        }
+    }
+
+    /// tell whether the gathering is done exploring the directories
+    /// one after the other, instead of level by level
+    fn is_depth_first(&self) -> bool {
+        self.options.depth_first && self.options.pattern.is_some()
+    }
+
+    /// the smallest depth of the lines which may still be gathered
+    fn min_next_depth(&self, g: &Gathering) -> u16 {
+        if self.is_depth_first() {
+            return 1;
+        }
+        g.open_dirs
+            .front()
+            .or_else(|| g.next_level_dirs.first())
+            .map_or(1, |&id| self.blines[id].depth + 1)
+    }
+
+    /// tell whether no line which may still be gathered can get a
+    /// better score than the worst of the kept matches
+    fn is_worst_match_unbeatable(&self, g: &Gathering) -> bool {
+        match (self.best_matches.peek(), self.options.pattern.best_score()) {
+            (Some(worst), Some(best_score)) => {
+                let best_line_score = DEPTH_DOPING - i32::from(self.min_next_depth(g)) + best_score;
+                worst.score >= best_line_score
+            }
+            _ => false,
+        }
+    }
+
+    /// tell whether the gathering can stop.
+    ///
+    /// When the pattern has a meaningful score, we don't stop when we have
+    /// enough matches but we go on looking (not too long) for better ones,
+    /// unless the kept ones can't be beaten.
+    fn enough_lines(&self, g: &Gathering, optimal_size: usize) -> bool {
+        if self.options.show_all {
+            return false;
+        }
+        if g.nb_lines_ok > optimal_size {
+            if !self.options.pattern.has_ranking() {
+                return true;
+            }
+            if self.best_matches.len() >= optimal_size && self.is_worst_match_unbeatable(g) {
+                return true;
+            }
+        }
+        g.nb_lines_ok >= self.targeted_size && g.start.elapsed() > NOT_LONG
+    }
+
+    /// handle a new line which may be a match, and update the number
+    /// of matching lines.
+    ///
+    /// Only the best `cap` matching files are kept: when there are already
+    /// enough of them, the new one either replaces the worst one or is
+    /// dropped. Directories are always kept as they may contain other matches.
+    fn count_match(&mut self, bid: BId, cap: usize, nb_lines_ok: &mut usize) {
+        let bline = &self.blines[bid];
+        if !bline.has_match {
+            return;
+        }
+        if bline.file_type.is_dir()
+            || bline.followed_link
+            || bline.opened_archive
+            || !self.options.pattern.has_ranking()
+        {
+            *nb_lines_ok += 1;
+            return;
+        }
+        let score = bline.score;
+        if self.best_matches.len() < cap {
+            self.best_matches.push(SortableBId { id: bid, score });
+            *nb_lines_ok += 1;
+            return;
+        }
+        let worst_score = match self.best_matches.peek() {
+            Some(worst) => worst.score,
+            None => {
+                // a cap of 0 means no retention
+                *nb_lines_ok += 1;
+                return;
+            }
+        };
+        if score > worst_score {
+            if let Some(worst) = self.best_matches.pop() {
+                let nb_unmarked = self.unmark_evicted_match(worst.id);
+                *nb_lines_ok = nb_lines_ok.saturating_sub(nb_unmarked);
+            }
+            self.best_matches.push(SortableBId { id: bid, score });
+            *nb_lines_ok += 1;
+        } else {
+            // the line itself wasn't counted yet
+            let nb_unmarked = self.unmark_evicted_match(bid);
+            *nb_lines_ok = nb_lines_ok.saturating_sub(nb_unmarked - 1);
+        }
+    }
+
+    /// unmark a matching file evicted by better ones, and the
+    /// directories which were only matching because of it.
+    /// Return the number of lines which aren't matching anymore.
+    fn unmark_evicted_match(&mut self, bid: BId) -> usize {
+        let mut nb_unmarked = 0;
+        let mut id = bid;
+        loop {
+            let bline = &mut self.blines[id];
+            if !bline.has_match {
+                break;
+            }
+            bline.has_match = false;
+            nb_unmarked += 1;
+            let parent_id = match bline.parent_id {
+                Some(parent_id) => parent_id,
+                None => break,
+            };
+            let parent = &mut self.blines[parent_id];
+            parent.nb_matching_children = parent.nb_matching_children.saturating_sub(1);
+            if parent.nb_matching_children > 0 || parent.direct_match || parent.parent_id.is_none() {
+                break;
+            }
+            id = parent_id;
+        }
+        nb_unmarked
+    }
+
+    /// mark the line and its ancestors as matching, because one of
+    /// the children of the line has a match.
+    /// Return the number of lines which weren't yet matching.
+    fn mark_as_matching_with_ancestors(&mut self, bid: BId) -> usize {
+        let mut nb_new_matches = 0;
+        let mut id = bid;
+        loop {
+            let bline = &mut self.blines[id];
+            let newly_matching = !bline.has_match;
+            if newly_matching {
+                bline.has_match = true;
+                nb_new_matches += 1;
+            }
+            if let Some(pid) = bline.parent_id {
+                if newly_matching {
+                    self.blines[pid].nb_matching_children += 1;
+                }
+                id = pid;
+            } else {
+                break;
+            }
+        }
+        nb_new_matches
+    }
+
+    /// start the gathering: the root is the first line and its
+    /// children are read
+    fn start_gathering(&mut self) -> Gathering {
+        self.load_children(self.root_id);
+        let mut open_dirs = VecDeque::new();
+        open_dirs.push_back(self.root_id);
+        Gathering {
+            out_blines: vec![self.root_id],
+            nb_lines_ok: 1,
+            open_dirs,
+            next_level_dirs: Vec::new(),
+            unread_dirs: VecDeque::new(),
+            dir_stack: vec![(self.root_id, 0)],
+            start: Instant::now(),
+        }
+    }
+
     /// first step of the build: we explore the directories and gather lines.
     /// If there's no search pattern we stop when we have enough lines to fill the screen.
     /// If there's a pattern, we try to gather more lines that will be sorted afterwards.
-    fn gather_lines(&mut self, task_lifetime: &TaskLifetime) -> Option<Vec<BId>> {
-        let start = Instant::now();
-        let mut out_blines: Vec<BId> = Vec::new(); // the blines we want to display
-        let optimal_size = self
-            .options
-            .pattern
-            .optimal_result_number(self.targeted_size);
-        out_blines.push(self.root_id);
-        let mut nb_lines_ok = 1; // in out_blines
-        let mut open_dirs: VecDeque<BId> = VecDeque::new();
-        let mut next_level_dirs: Vec<BId> = Vec::new();
-        self.load_children(self.root_id);
-        open_dirs.push_back(self.root_id);
+    ///
+    /// The gathering also stops at the deadline, if any, or when the task
+    /// lifetime expires. In both cases it goes on where it stopped when
+    /// this function is called again.
+    pub fn gather(
+        &mut self,
+        task_lifetime: &TaskLifetime,
+        deadline: Option<Instant>,
+    ) -> BuildProgress {
+        let mut gathering = match self.gathering.take() {
+            Some(gathering) => gathering,
+            None => self.start_gathering(),
+        };
+        let progress = if self.is_depth_first() {
+            self.gather_lines_depth_first(&mut gathering, task_lifetime, deadline)
+        } else {
+            self.gather_lines_breadth_first(&mut gathering, task_lifetime, deadline)
+        };
+        self.gathering = Some(gathering);
+        progress
+    }
+
+    /// add the line to the gathered ones, if it's not already there,
+    /// and make sure it survives the trimming
+    fn force_line(&mut self, bid: BId, out_blines: &mut Vec<BId>) {
+        self.blines[bid].score += FORCED_OPEN_BONUS;
+        self.mark_as_matching_with_ancestors(bid);
+        if !out_blines.contains(&bid) {
+            out_blines.push(bid);
+        }
+    }
+
+    /// make sure the forced open directory, the directories leading
+    /// to it and its children (the matching ones when there's a
+    /// pattern) are in the gathered lines.
+    /// Nothing is done if the directory isn't in the tree.
+    fn gather_forced_open(&mut self, path: &Path, out_blines: &mut Vec<BId>) {
+        let relative = match path.strip_prefix(&self.blines[self.root_id].path.to_path_buf()) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => {
+                return;
+            }
+        };
+        let mut dir_id = self.root_id;
+        for component in relative.components() {
+            if self.blines[dir_id].children.is_none() {
+                self.load_children(dir_id);
+            }
+            let name = component.as_os_str();
+            let child_id = self.blines[dir_id].children.as_ref().and_then(|children| {
+                children
+                    .iter()
+                    .copied()
+                    .find(|&id| self.blines[id].path.file_name() == Some(name))
+            });
+            match child_id {
+                Some(child_id) => {
+                    self.force_line(child_id, out_blines);
+                    dir_id = child_id;
+                }
+                None => {
+                    return; // hidden or filtered out
+                }
+            }
+        }
+        if self.blines[dir_id].children.is_none() {
+            self.load_children(dir_id);
+        }
+        let children = self.blines[dir_id].children.clone().unwrap_or_default();
+        for &child_id in &children {
+            if self.blines[child_id].has_match {
+                self.force_line(child_id, out_blines);
+            }
+        }
+        self.blines[dir_id].next_child_idx = children.len();
+    }
+
+    /// tell whether the step must stop, returning why
+    fn interruption(
+        &self,
+        task_lifetime: &TaskLifetime,
+        deadline: Option<Instant>,
+    ) -> Option<BuildProgress> {
+        if task_lifetime.is_expired() {
+            info!("task expired (core build)");
+            Some(BuildProgress::Interrupted)
+        } else if deadline.map_or(false, |deadline| Instant::now() > deadline) {
+            Some(BuildProgress::Pending)
+        } else {
+            None
+        }
+    }
+
+    /// gather lines by exploring a directory completely (or until it
+    /// gave max_matches_per_dir direct matches) before its next siblings.
+    /// This brings faster the deep files matching a precise pattern.
+    fn gather_lines_depth_first(
+        &mut self,
+        g: &mut Gathering,
+        task_lifetime: &TaskLifetime,
+        deadline: Option<Instant>,
+    ) -> BuildProgress {
+        let optimal_size = self.optimal_size();
+        let max_matches_per_dir = self.options.max_matches_per_dir;
         loop {
-            if (nb_lines_ok > optimal_size)
-                || (nb_lines_ok >= self.targeted_size && start.elapsed() > NOT_LONG)
-            {
-                break;
+            if self.enough_lines(g, optimal_size) {
+                return BuildProgress::Complete;
             }
-            if let Some(open_dir_id) = open_dirs.pop_front() {
-                if let Some(child_id) = self.next_child(open_dir_id) {
-                    open_dirs.push_back(open_dir_id);
-                    let child = &self.blines[child_id];
-                    if child.has_match {
-                        nb_lines_ok += 1;
+            if let Some(progress) = self.interruption(task_lifetime, deadline) {
+                return progress;
+            }
+            let (open_dir_id, nb_dir_matches) = match g.dir_stack.last() {
+                Some(&open_dir) => open_dir,
+                None => {
+                    self.search_stats.complete = true; // everything was explored
+                    return BuildProgress::Complete;
+                }
+            };
+            if max_matches_per_dir > 0 && nb_dir_matches >= max_matches_per_dir {
+                g.dir_stack.pop();
+                continue;
+            }
+            if let Some(child_id) = self.next_child(open_dir_id) {
+                g.out_blines.push(child_id);
+                if self.blines[child_id].has_match {
+                    self.count_match(child_id, optimal_size, &mut g.nb_lines_ok);
+                    if let Some(open_dir) = g.dir_stack.last_mut() {
+                        open_dir.1 += 1;
                     }
-                    if child.file_type.is_dir() {
-                        next_level_dirs.push(child_id);
+                }
+                if self.may_enter(child_id) {
+                    if self.load_children(child_id) {
+                        g.nb_lines_ok += self.mark_as_matching_with_ancestors(child_id);
                     }
-                    out_blines.push(child_id);
+                    g.dir_stack.push((child_id, 0));
                 }
             } else {
-                // this depth is finished, we must go deeper
-                if self.options.show_sizes {
-                    // both for technical reasons (bad sort) and ergonomics
-                    //  ones (it proved to be hard to read), we don't want
-                    //  a deep tree when looking at sizes.
-                    break;
-                }
-                if next_level_dirs.is_empty() {
-                    // except there's nothing deeper
-                    break;
-                }
-                for next_level_dir_id in &next_level_dirs {
-                    if task_lifetime.is_expired() {
+                g.dir_stack.pop();
+            }
+        }
+    }
+
+    /// gather lines level by level, which gives a balanced tree
+    fn gather_lines_breadth_first(
+        &mut self,
+        g: &mut Gathering,
+        task_lifetime: &TaskLifetime,
+        deadline: Option<Instant>,
+    ) -> BuildProgress {
+        let optimal_size = self.optimal_size();
+        loop {
+            if let Some(progress) = self.interruption(task_lifetime, deadline) {
+                return progress;
+            }
+            if !g.unread_dirs.is_empty() {
+                // the directories of the next level are read in parallel, by
+                // chunks so that an expired task is quickly noticed. A level
+                // is always completely read, so that the matching directories
+                // are known whatever the speed of the reads
+                let chunk: Vec<BId> = g.unread_dirs.iter().take(READ_CHUNK_SIZE).copied().collect();
+                let listings = match self.read_dirs(&chunk, task_lifetime) {
+                    Some(listings) => listings,
+                    None => {
                         info!("task expired (core build - inner loop)");
-                        return None;
+                        return BuildProgress::Interrupted;
                     }
-                    let has_child_match = self.load_children(*next_level_dir_id);
+                };
+                for (dir_id, listing) in chunk.into_iter().zip(listings) {
+                    g.unread_dirs.pop_front();
+                    let has_child_match = self.add_children(dir_id, listing);
                     if has_child_match {
                         // we must ensure the ancestors are made Ok
-                        let mut id = *next_level_dir_id;
-                        loop {
-                            let mut bline = &mut self.blines[id];
-                            if !bline.has_match {
-                                bline.has_match = true;
-                                nb_lines_ok += 1;
-                            }
-                            if let Some(pid) = bline.parent_id {
-                                id = pid;
-                            } else {
-                                break;
-                            }
-                        }
+                        g.nb_lines_ok += self.mark_as_matching_with_ancestors(dir_id);
                     }
-                    open_dirs.push_back(*next_level_dir_id);
+                    g.open_dirs.push_back(dir_id);
                 }
-                next_level_dirs.clear();
+                continue;
             }
-        }
-        if self.options.show_sizes || !self.options.trim_root {
-            // if the root directory isn't totally read, we finished it even
-            // it it goes past the bottom of the screen
-            while let Some(child_id) = self.next_child(self.root_id) {
-                out_blines.push(child_id);
+            if self.enough_lines(g, optimal_size) {
+                return BuildProgress::Complete;
+            }
+            if let Some(open_dir_id) = g.open_dirs.pop_front() {
+                if let Some(child_id) = self.next_child(open_dir_id) {
+                    g.open_dirs.push_back(open_dir_id);
+                    self.count_match(child_id, optimal_size, &mut g.nb_lines_ok);
+                    if self.may_enter(child_id) {
+                        g.next_level_dirs.push(child_id);
+                    }
+                    g.out_blines.push(child_id);
+                }
+            } else {
+                // this depth is finished, we must go deeper
+                if self.options.show_sizes && !self.options.whale_spotting {
+                    // both for technical reasons (bad sort) and ergonomics
+                    //  ones (it proved to be hard to read), we don't want
+                    //  a deep tree when looking at sizes, except in whale
+                    //  spotting mode where the tree is sorted level by level.
+                    return BuildProgress::Complete;
+                }
+                if g.next_level_dirs.is_empty() {
+                    // except there's nothing deeper
+                    self.search_stats.complete = true;
+                    return BuildProgress::Complete;
+                }
+                g.unread_dirs.extend(g.next_level_dirs.drain(..));
             }
         }
-        Some(out_blines)
     }
 
     /// Post search trimming
@@ -441,14 +1007,14 @@ impl TreeBuilder {
             "Trimming: we have {} lines for a goal of {}",
             count, self.targeted_size
         );
-        while count > self.targeted_size {
+        while count > self.targeted_size && !self.options.show_all {
             if let Some(sli) = remove_queue.pop() {
                 //debug!("removing {:?}", &self.blines[sli.idx].path);
                 self.blines[sli.id].has_match = false;
                 let parent_id = self.blines[sli.id].parent_id.unwrap();
                 let mut parent = &mut self.blines[parent_id];
                 parent.nb_kept_children -= 1;
-                parent.next_child_idx -= 1; // to fix the number of "unlisted"
+                parent.next_child_idx = parent.next_child_idx.saturating_sub(1); // to fix the number of "unlisted"
                 if parent.nb_kept_children == 0 {
                     remove_queue.push(SortableBId {
                         id: parent_id,
@@ -463,16 +1029,31 @@ impl TreeBuilder {
         }
     }
 
-    // makes a tree from the builder's specific structure
-    fn take(&mut self, out_blines: &[BId]) -> Tree {
+    // makes a tree from the builder's specific structure.
+    // When read_children is false, the directories whose children weren't
+    // read yet are displayed without count of unlisted children
+    fn take(&mut self, out_blines: &[BId], read_children: bool) -> Tree {
+        // we need to count the children, so we load them (this is
+        // also how the directories cut by max_depth get their marker)
+        let unread: Vec<BId> = out_blines
+            .iter()
+            .copied()
+            .filter(|&id| self.blines[id].has_match)
+            .filter(|&id| read_children && self.may_list_children(id))
+            .filter(|&id| self.blines[id].children.is_none())
+            .collect();
+        let tl = TaskLifetime::unlimited();
+        for chunk in unread.chunks(READ_CHUNK_SIZE) {
+            if let Some(listings) = self.read_dirs(chunk, &tl) {
+                for (&dir_id, listing) in chunk.iter().zip(listings) {
+                    self.add_children(dir_id, listing);
+                }
+            }
+        }
         let mut lines: Vec<TreeLine> = Vec::new();
         for id in out_blines.iter() {
             if self.blines[*id].has_match {
-                // we need to count the children, so we load them
-                if self.blines[*id].file_type.is_dir() && self.blines[*id].children.is_none() {
-                    self.load_children(*id);
-                }
-                if let Ok(tree_line) = self.blines[*id].to_tree_line() {
+                if let Ok(tree_line) = self.blines[*id].to_tree_line(self.options.source.as_ref()) {
                     lines.push(tree_line);
                 } else {
                     // I guess the file went missing during tree computation
@@ -484,13 +1065,22 @@ impl TreeBuilder {
             }
         }
         let mut tree = Tree {
+            root: self.blines[self.root_id].path.to_path_buf(),
             lines: lines.into_boxed_slice(),
             selection: 0,
             options: self.options.clone(),
             scroll: 0,
             nb_gitignored: self.nb_gitignored,
+            search_stats: self.search_stats,
         };
         tree.after_lines_changed();
+        if self.options.grouping && self.options.pattern.is_some() {
+            tree.group_matches();
+        } else if self.options.filter_strictness == FilterStrictness::Strict
+            && self.options.pattern.is_some()
+        {
+            tree.keep_only_matches();
+        }
 
         if self.options.show_sizes {
             tree.fetch_file_sizes(); // not the dirs, only simple files
@@ -498,18 +1088,63 @@ impl TreeBuilder {
         tree
     }
 
+    /// make the tree of the lines gathered until now, while the
+    /// gathering goes on. The builder is left as it was, so that
+    /// this can be done after every step.
+    pub fn partial_tree(&mut self) -> Option<Tree> {
+        let out_blines = self.gathering.as_ref()?.out_blines.clone();
+        // trimming changes the lines, which must be restored after
+        let saved: Vec<(bool, i32, usize)> = out_blines
+            .iter()
+            .map(|&id| {
+                let bline = &self.blines[id];
+                (bline.has_match, bline.nb_kept_children, bline.next_child_idx)
+            })
+            .collect();
+        self.trim_excess(&out_blines);
+        let tree = self.take(&out_blines, false);
+        for (&id, (has_match, nb_kept_children, next_child_idx)) in out_blines.iter().zip(saved) {
+            let bline = &mut self.blines[id];
+            bline.has_match = has_match;
+            bline.nb_kept_children = nb_kept_children;
+            bline.next_child_idx = next_child_idx;
+        }
+        Some(tree)
+    }
+
+    pub fn options(&self) -> &TreeOptions {
+        &self.options
+    }
+
+    /// make the tree once the gathering is complete
+    pub fn finish(mut self) -> Tree {
+        let mut out_blines = match self.gathering.take() {
+            Some(gathering) => gathering.out_blines,
+            None => vec![self.root_id],
+        };
+        if self.options.show_sizes || !self.options.trim_root {
+            // if the root directory isn't totally read, we finished it even
+            // it it goes past the bottom of the screen
+            while let Some(child_id) = self.next_child(self.root_id) {
+                out_blines.push(child_id);
+            }
+        }
+        if let Some(path) = self.options.forced_open.clone() {
+            self.gather_forced_open(&path, &mut out_blines);
+        }
+        self.trim_excess(&out_blines);
+        self.take(&out_blines, true)
+    }
+
     /// build a tree. Can be called only once per builder.
     ///
     /// Return None if the lifetime expires before end of computation
     /// (usually because the user hit a key)
     pub fn build(mut self, task_lifetime: &TaskLifetime) -> Option<Tree> {
         debug!("start building with pattern {}", self.options.pattern);
-        match self.gather_lines(task_lifetime) {
-            Some(out_blines) => {
-                self.trim_excess(&out_blines);
-                Some(self.take(&out_blines))
-            }
-            None => None, // interrupted
+        match self.gather(task_lifetime, None) {
+            BuildProgress::Complete => Some(self.finish()),
+            _ => None, // interrupted
         }
     }
 }
@@ -0,0 +1,98 @@
+//! A summary of what changed in the displayed lines of a tree, shown
+//! after a verb modified files so that the user can check it did
+//! what was expected.
+
+use std::collections::HashMap;
+
+use crate::{
+    file_sizes::{Size, SizeFormat},
+    flat_tree::TreeLine,
+};
+
+/// the maximal number of changes detailed in the summary
+const MAX_DETAILED_CHANGES: usize = 3;
+
+/// tell whether two lines are probably the same file under two
+/// names (a move or a rename keeps the metadata)
+fn same_file(a: &TreeLine, b: &TreeLine) -> bool {
+    a.metadata.entry_type == b.metadata.entry_type
+        && a.metadata.len == b.metadata.len
+        && a.metadata.mode == b.metadata.mode
+        && a.metadata.modified == b.metadata.modified
+}
+
+/// describe what changed in the metadata of a line kept in the tree,
+/// or return None if nothing changed
+fn describe_change(before: &TreeLine, after: &TreeLine, size_format: SizeFormat) -> Option<String> {
+    let mut changes = Vec::new();
+    if before.is_file() && before.metadata.len != after.metadata.len {
+        changes.push(format!(
+            "size {} → {}",
+            Size::from(before.metadata.len).format(size_format),
+            Size::from(after.metadata.len).format(size_format),
+        ));
+    }
+    #[cfg(unix)]
+    {
+        if before.metadata.mode != after.metadata.mode {
+            changes.push(format!("mode {} → {}", before.mode(), after.mode()));
+        }
+    }
+    if changes.is_empty() && before.metadata.modified != after.metadata.modified {
+        changes.push("modified".to_string());
+    }
+    if changes.is_empty() {
+        None
+    } else {
+        Some(format!("`{}` {}", after.name, changes.join(", ")))
+    }
+}
+
+/// compare the lines displayed before and after a verb and return a
+/// summary of the changes fitting in the status line, or None if no
+/// displayed line changed
+pub fn summarize(before: &[TreeLine], after: &[TreeLine], size_format: SizeFormat) -> Option<String> {
+    let before_lines: HashMap<_, _> = before.iter().skip(1)
+        .filter(|line| line.is_selectable())
+        .map(|line| (&line.path, line))
+        .collect();
+    let after_lines: HashMap<_, _> = after.iter().skip(1)
+        .filter(|line| line.is_selectable())
+        .map(|line| (&line.path, line))
+        .collect();
+    let mut changes = Vec::new();
+    let mut added: Vec<&TreeLine> = after.iter().skip(1)
+        .filter(|line| line.is_selectable() && !before_lines.contains_key(&line.path))
+        .collect();
+    for line in before.iter().skip(1).filter(|line| line.is_selectable()) {
+        match after_lines.get(&line.path) {
+            Some(after_line) => {
+                changes.extend(describe_change(line, after_line, size_format));
+            }
+            None => {
+                if let Some(idx) = added.iter().position(|a| same_file(line, a)) {
+                    let moved = added.remove(idx);
+                    changes.push(format!("`{}` → `{}`", line.name, moved.name));
+                } else {
+                    changes.push(format!("`{}` removed", line.name));
+                }
+            }
+        }
+    }
+    for line in added {
+        changes.push(format!("`{}` added", line.name));
+    }
+    if changes.is_empty() {
+        return None;
+    }
+    let nb_changes = changes.len();
+    let mut summary = changes
+        .into_iter()
+        .take(MAX_DETAILED_CHANGES)
+        .collect::<Vec<String>>()
+        .join(", ");
+    if nb_changes > MAX_DETAILED_CHANGES {
+        summary.push_str(&format!(" and {} more changes", nb_changes - MAX_DETAILED_CHANGES));
+    }
+    Some(summary)
+}
@@ -1,6 +1,16 @@
-use std::str::FromStr;
+use std::{path::PathBuf, str::FromStr, sync::Arc, time::SystemTime};
 
-use crate::{errors::ProgramError, patterns::Pattern};
+use crate::{
+    column_widths::ColumnWidths,
+    date_format::DateFormat,
+    errors::ProgramError,
+    file_sizes::SizeFormat,
+    fuzzy_scoring::FuzzyScoring,
+    path_filters::PathFilters,
+    patterns::Pattern,
+    size_bars::SizeBarStyle,
+    tree_sources::{DateKind, FsSource, TreeSource},
+};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum OptionBool {
@@ -24,29 +34,155 @@ impl FromStr for OptionBool {
     }
 }
 
+/// when the tree builder goes through symbolic links to directories
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FollowSymlinks {
+    Never,        // links are listed but never entered
+    WhenExplicit, // links are entered when listing the tree, not when searching
+    Always,       // links are entered like directories, even when searching
+}
+
+impl FromStr for FollowSymlinks {
+    type Err = ProgramError;
+    fn from_str(s: &str) -> Result<FollowSymlinks, ProgramError> {
+        match s {
+            "never" => Ok(FollowSymlinks::Never),
+            "when-explicit" => Ok(FollowSymlinks::WhenExplicit),
+            "always" => Ok(FollowSymlinks::Always),
+            _ => Err(ProgramError::ArgParse {
+                bad: s.to_string(),
+                valid: "never, when-explicit, always".to_string(),
+            }),
+        }
+    }
+}
+
+/// how the directories leading to the matches of a search are shown
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FilterStrictness {
+    Mixed,  // they look like the matches
+    Dimmed, // they're dimmed, so that the matches stand out
+    Strict, // they're removed: the matches are listed with their path relative to the root
+}
+
 #[derive(Debug, Clone)]
 pub struct TreeOptions {
     pub show_hidden: bool, // whether files whose name starts with a dot should be shown
     pub only_folders: bool, // whether to hide normal files and links
     pub show_sizes: bool,  // whether to compute and show sizes of files and dirs
+    pub whale_spotting: bool, // sizes relative to the parent, deep tree sorted by size, small lines pruned
+    pub link_target_sizes: bool, // whether the size of a symlink is the one of its target
+    pub size_format: SizeFormat, // the unit system of the displayed sizes
+    pub size_bar: SizeBarStyle, // how the part of each size in the total is drawn
+    pub column_widths: ColumnWidths, // bounds of the widths of the columns before names
     pub show_dates: bool,  // whether to show the last modified date
+    pub date_format: DateFormat, // how the dates are written
+    pub date_kind: DateKind, // which date is displayed: modified, created or accessed
+    pub show_counts: bool, // whether to count and show the children of directories
     pub trim_root: bool,   // whether to cut out direct children of root
     pub show_permissions: bool, // show classic rwx unix permissions
+    pub show_xattrs: bool, // whether to show the extended attributes column
+    pub show_inodes: bool, // whether to show the inode and hard links column
+    pub two_rows: bool, // whether sizes, dates and permissions are on a second row under the name
+    pub max_lines: Option<usize>, // when set, the tree never has more lines, whatever the screen height
+    pub show_all: bool, // whether the tree is fully expanded instead of balanced to the screen height
+    pub show_icons: bool, // whether an icon is written before the names
+    pub full_permissions: bool, // whether owner and group columns are shown even when uniform
     pub respect_git_ignore: OptionBool, // hide files as requested by .gitignore ?
     pub pattern: Pattern,  // an optional filtering/scoring pattern
+    pub fuzzy_scoring: FuzzyScoring, // the weights and case handling of fuzzy patterns
+    pub do_not_enter: Vec<PathBuf>, // directories which are listed but never automatically explored
+    pub path_filters: PathFilters, // glob rules telling which paths are shown or entered
+    pub follow_symlinks: FollowSymlinks, // whether symlinks to directories are entered
+    pub depth_first: bool, // whether a search explores each directory before its siblings
+    pub grouping: bool, // whether search matches are listed under a header line for their directory
+    pub filter_strictness: FilterStrictness, // how the directories leading to the matches are shown
+    pub max_matches_per_dir: usize, // when searching depth first, a directory is left after that many matches
+    pub max_depth: Option<u16>, // when set, the directories at that depth aren't entered
+    pub newer_than: Option<SystemTime>, // when set, only the entries modified after that time match
+    pub same_inode: Option<(u64, u64)>, // when set, only the hard links of this (device, inode) match
+    pub forced_open: Option<PathBuf>, // a directory whose children are listed, even if it's deep in the tree
+    pub opened_archives: Vec<PathBuf>, // the archives the builder enters, as if they were directories
+    pub source: Arc<dyn TreeSource>, // where the tree is read, usually the file system
 }
 
 impl TreeOptions {
+    /// the number of screen rows taken by each line of the tree
+    pub fn rows_per_line(&self) -> u16 {
+        if self.two_rows {
+            2
+        } else {
+            1
+        }
+    }
+    /// tell whether the date column is displayed, which is also the
+    /// case when the search filters the entries on their dates
+    pub fn shows_dates(&self) -> bool {
+        self.show_dates
+            || self
+                .pattern
+                .file_filter()
+                .map_or(false, |filter| filter.has_date_filter())
+    }
+    /// return the kind of the displayed dates: the one the search
+    /// filters on, if any, so that the filtered dates are visible
+    pub fn displayed_date_kind(&self) -> DateKind {
+        self.pattern
+            .file_filter()
+            .and_then(|filter| filter.date_kind())
+            .unwrap_or(self.date_kind)
+    }
+    /// return options for a first tree which can be built and displayed
+    /// quickly: the features needing to read more than the listed
+    /// directories (sizes, counts, .gitignore files) are disabled.
+    pub fn without_expensive_features(&self) -> TreeOptions {
+        let mut options = self.clone();
+        options.show_sizes = false;
+        options.show_counts = false;
+        if options.respect_git_ignore == OptionBool::Auto {
+            options.respect_git_ignore = OptionBool::No;
+        }
+        options
+    }
     pub fn without_pattern(&self) -> TreeOptions {
         TreeOptions {
             show_hidden: self.show_hidden,
             only_folders: self.only_folders,
             show_sizes: self.show_sizes,
+            whale_spotting: self.whale_spotting,
+            link_target_sizes: self.link_target_sizes,
+            size_format: self.size_format,
+            size_bar: self.size_bar,
+            column_widths: self.column_widths.clone(),
             show_dates: self.show_dates,
+            date_format: self.date_format.clone(),
+            date_kind: self.date_kind,
+            show_counts: self.show_counts,
             trim_root: self.trim_root,
             show_permissions: self.show_permissions,
+            show_xattrs: self.show_xattrs,
+            show_inodes: self.show_inodes,
+            two_rows: self.two_rows,
+            max_lines: self.max_lines,
+            show_all: self.show_all,
+            show_icons: self.show_icons,
+            full_permissions: self.full_permissions,
             respect_git_ignore: self.respect_git_ignore,
             pattern: Pattern::None,
+            fuzzy_scoring: self.fuzzy_scoring,
+            do_not_enter: self.do_not_enter.clone(),
+            path_filters: self.path_filters.clone(),
+            follow_symlinks: self.follow_symlinks,
+            depth_first: self.depth_first,
+            grouping: self.grouping,
+            filter_strictness: self.filter_strictness,
+            max_matches_per_dir: self.max_matches_per_dir,
+            max_depth: self.max_depth,
+            newer_than: self.newer_than,
+            same_inode: self.same_inode,
+            forced_open: None, // it only makes sense for the tree it was set for
+            opened_archives: self.opened_archives.clone(),
+            source: Arc::clone(&self.source),
         }
     }
 }
@@ -57,11 +193,40 @@ impl Default for TreeOptions {
             show_hidden: false,
             only_folders: false,
             show_sizes: false,
+            whale_spotting: false,
+            link_target_sizes: false,
+            size_format: SizeFormat::default(),
+            size_bar: SizeBarStyle::default(),
+            column_widths: ColumnWidths::default(),
             show_dates: false,
+            date_format: DateFormat::default(),
+            date_kind: DateKind::default(),
+            show_counts: false,
             trim_root: true,
             show_permissions: false,
+            show_xattrs: false,
+            show_inodes: false,
+            two_rows: false,
+            max_lines: None,
+            show_all: false,
+            show_icons: false,
+            full_permissions: false,
             respect_git_ignore: OptionBool::Auto,
             pattern: Pattern::None,
+            fuzzy_scoring: FuzzyScoring::default(),
+            do_not_enter: Vec::new(),
+            path_filters: PathFilters::default(),
+            follow_symlinks: FollowSymlinks::Never,
+            depth_first: false,
+            grouping: false,
+            filter_strictness: FilterStrictness::Mixed,
+            max_matches_per_dir: 5,
+            max_depth: None,
+            newer_than: None,
+            same_inode: None,
+            forced_open: None,
+            opened_archives: Vec::new(),
+            source: Arc::new(FsSource),
         }
     }
 }
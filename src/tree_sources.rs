@@ -0,0 +1,246 @@
+//! the tree builder doesn't read the file system directly but goes
+//! through a TreeSource, so that other backends can be browsed
+//! (a snapshot of a tree, see the snapshots module, or a remote
+//! server, see the remote module).
+//!
+//! The file system source also reads the content of the archives
+//! (see the archives module) the builder enters.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
+};
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+use crate::{archives, dir_prelisting, errors::ConfError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryType {
+    Dir,
+    File,
+    Symlink,
+}
+
+impl EntryType {
+    pub fn of(ft: fs::FileType) -> EntryType {
+        if ft.is_dir() {
+            EntryType::Dir
+        } else if ft.is_symlink() {
+            EntryType::Symlink
+        } else {
+            EntryType::File
+        }
+    }
+    pub fn is_dir(self) -> bool {
+        self == EntryType::Dir
+    }
+    pub fn is_file(self) -> bool {
+        self == EntryType::File
+    }
+    pub fn is_symlink(self) -> bool {
+        self == EntryType::Symlink
+    }
+}
+
+/// which of the dates of an entry is displayed or filtered on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateKind {
+    Modified, // the last modification of the content (mtime)
+    Created,  // the creation of the file (btime), not known by all file systems
+    Accessed, // the last read (atime), often not updated by the system
+}
+
+impl Default for DateKind {
+    fn default() -> Self {
+        DateKind::Modified
+    }
+}
+
+impl FromStr for DateKind {
+    type Err = ConfError;
+    fn from_str(s: &str) -> Result<DateKind, ConfError> {
+        match s {
+            "modified" | "mtime" => Ok(DateKind::Modified),
+            "created" | "btime" => Ok(DateKind::Created),
+            "accessed" | "atime" => Ok(DateKind::Accessed),
+            _ => Err(ConfError::InvalidDateKind { raw: s.to_string() }),
+        }
+    }
+}
+
+impl DateKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            DateKind::Modified => "modified",
+            DateKind::Created => "created",
+            DateKind::Accessed => "accessed",
+        }
+    }
+}
+
+/// the part of the metadata of an entry broot uses, whatever the source.
+/// The accessors are named like the ones of std::fs::Metadata
+#[derive(Debug, Clone)]
+pub struct EntryMetadata {
+    pub entry_type: EntryType,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    pub created: Option<SystemTime>,  // None when the system or the source doesn't know it
+    pub accessed: Option<SystemTime>,
+    pub mode: u32, // unix permissions (0 when unknown)
+    pub uid: u32,
+    pub gid: u32,
+    pub dev: u64,   // the device and the inode identify a file (0 when unknown)
+    pub ino: u64,
+    pub nlink: u64, // the number of hard links (0 when unknown)
+    pub in_archive: bool, // whether the entry is read from an archive (and is thus read-only)
+}
+
+impl EntryMetadata {
+    pub fn from_fs(md: &fs::Metadata) -> EntryMetadata {
+        #[cfg(unix)]
+        let (mode, uid, gid) = (md.mode(), md.uid(), md.gid());
+        #[cfg(not(unix))]
+        let (mode, uid, gid) = (0, 0, 0);
+        #[cfg(unix)]
+        let (dev, ino, nlink) = (md.dev(), md.ino(), md.nlink());
+        #[cfg(not(unix))]
+        let (dev, ino, nlink) = (0, 0, 0);
+        EntryMetadata {
+            entry_type: EntryType::of(md.file_type()),
+            len: md.len(),
+            modified: md.modified().ok(),
+            created: md.created().ok(),
+            accessed: md.accessed().ok(),
+            mode,
+            uid,
+            gid,
+            dev,
+            ino,
+            nlink,
+            in_archive: false,
+        }
+    }
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+    pub fn modified(&self) -> io::Result<SystemTime> {
+        self.modified
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "unknown modification date"))
+    }
+    pub fn created(&self) -> io::Result<SystemTime> {
+        self.created
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "unknown creation date"))
+    }
+    pub fn accessed(&self) -> io::Result<SystemTime> {
+        self.accessed
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "unknown access date"))
+    }
+    /// return the date of the given kind
+    pub fn date(&self, kind: DateKind) -> io::Result<SystemTime> {
+        match kind {
+            DateKind::Modified => self.modified(),
+            DateKind::Created => self.created(),
+            DateKind::Accessed => self.accessed(),
+        }
+    }
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+    pub fn dev(&self) -> u64 {
+        self.dev
+    }
+    pub fn ino(&self) -> u64 {
+        self.ino
+    }
+    pub fn nlink(&self) -> u64 {
+        self.nlink
+    }
+}
+
+/// an entry listed in a directory
+#[derive(Debug, Clone)]
+pub struct SourceEntry {
+    pub path: PathBuf,
+    pub entry_type: EntryType, // not following symlinks
+}
+
+/// what the tree builder needs to read a hierarchy of files
+pub trait TreeSource: fmt::Debug + Send + Sync {
+    /// list the entries of a directory, in no specific order
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<SourceEntry>>;
+    /// return the metadata of the entry, following symlinks
+    fn metadata(&self, path: &Path) -> io::Result<EntryMetadata>;
+    /// return the metadata of the entry, not following symlinks
+    fn symlink_metadata(&self, path: &Path) -> io::Result<EntryMetadata>;
+    /// return the target of a symlink, as written in the link
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+    /// tell whether the paths are the ones of the local file system,
+    /// so that file contents can be read and verbs executed on them
+    fn is_local(&self) -> bool {
+        true
+    }
+    /// forget what was read, so that the next build sees the changes
+    fn clear_cache(&self) {}
+}
+
+/// list a directory of the local file system, the entries which
+/// can't be read being skipped
+pub fn read_fs_dir(dir: &Path) -> io::Result<Vec<SourceEntry>> {
+    let mut entries = Vec::new();
+    for e in fs::read_dir(dir)? {
+        if let Ok(e) = e {
+            if let Ok(ft) = e.file_type() {
+                entries.push(SourceEntry {
+                    path: e.path(),
+                    entry_type: EntryType::of(ft),
+                });
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// the normal source: the local file system
+#[derive(Debug, Default)]
+pub struct FsSource;
+
+impl TreeSource for FsSource {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<SourceEntry>> {
+        if let Some(entries) = dir_prelisting::cached(dir) {
+            return Ok(entries);
+        }
+        match read_fs_dir(dir) {
+            Ok(entries) => Ok(entries),
+            Err(e) => archives::read_dir(dir).unwrap_or(Err(e)),
+        }
+    }
+    fn metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        match fs::metadata(path) {
+            Ok(md) => Ok(EntryMetadata::from_fs(&md)),
+            Err(e) => archives::metadata(path).unwrap_or(Err(e)),
+        }
+    }
+    fn symlink_metadata(&self, path: &Path) -> io::Result<EntryMetadata> {
+        match fs::symlink_metadata(path) {
+            Ok(md) => Ok(EntryMetadata::from_fs(&md)),
+            Err(e) => archives::metadata(path).unwrap_or(Err(e)),
+        }
+    }
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+    fn clear_cache(&self) {
+        dir_prelisting::clear_cache();
+    }
+}
@@ -0,0 +1,109 @@
+//! the completion of the verb name being typed in the input: the
+//! matching verbs are listed in a popup over the bottom of the tree,
+//! and *tab* (or *shift-tab*) cycles through them.
+
+use crossterm::{
+    cursor,
+    queue,
+    style::{Color, SetBackgroundColor},
+};
+
+use crate::{
+    app_context::AppContext,
+    commands::{Action, Command},
+    errors::ProgramError,
+    io::W,
+    screens::Screen,
+    verbs::Verb,
+};
+
+/// the maximal number of candidates visible at once in the popup
+const MAX_POPUP_ROWS: usize = 8;
+
+/// the state of a completion started with *tab*
+#[derive(Debug, Clone)]
+pub struct VerbCompletion {
+    pub typed: String,    // the part of the name the user typed before hitting tab
+    pub selection: usize, // the index of the candidate written in the input
+}
+
+/// return the name of the verb being typed, when the input ends
+/// with it (no argument, no trailing space)
+pub fn typed_name(cmd: &Command) -> Option<&str> {
+    match &cmd.action {
+        Action::VerbEdit(invocation)
+            if invocation.args.is_none() && !cmd.raw.ends_with(char::is_whitespace) =>
+        {
+            Some(&invocation.name)
+        }
+        _ => None,
+    }
+}
+
+/// return the verbs proposed for the name being typed, and the
+/// index of the one currently written in the input, if any
+fn candidates<'v>(cmd: &Command, con: &'v AppContext) -> Option<(Vec<&'v Verb>, Option<usize>)> {
+    let name = typed_name(cmd)?;
+    Some(match &cmd.verb_completion {
+        Some(completion) => (
+            con.verb_store.completions(&completion.typed),
+            Some(completion.selection),
+        ),
+        None => {
+            let verbs = con.verb_store.completions(name);
+            if verbs.len() == 1 && verbs[0].invocation.name == name {
+                return None; // the name is complete, there's nothing to propose
+            }
+            (verbs, None)
+        }
+    })
+}
+
+/// draw the popup of the candidates, if a verb name is being typed.
+/// It's drawn just above the status line, at the left.
+pub fn draw_popup(
+    w: &mut W,
+    cmd: &Command,
+    screen: &Screen,
+    con: &AppContext,
+) -> Result<(), ProgramError> {
+    let (verbs, selection) = match candidates(cmd, con) {
+        Some((verbs, selection)) if !verbs.is_empty() => (verbs, selection),
+        _ => {
+            return Ok(());
+        }
+    };
+    let rows = verbs.len().min(MAX_POPUP_ROWS);
+    let available_rows = (screen.height as usize).saturating_sub(3);
+    if rows > available_rows {
+        return Ok(());
+    }
+    // the first candidate shown is chosen so that the selected one is visible
+    let scroll = match selection {
+        Some(idx) if idx >= rows => idx + 1 - rows,
+        _ => 0,
+    };
+    let name_width = verbs.iter().map(|v| v.invocation.name.chars().count()).max().unwrap_or(0);
+    let shortcut_width = verbs
+        .iter()
+        .map(|v| v.shortcut.as_ref().map_or(0, |s| s.chars().count()))
+        .max()
+        .unwrap_or(0);
+    let top = screen.height as usize - 2 - rows;
+    for (row, idx) in (scroll..scroll + rows).enumerate() {
+        let verb = verbs[idx];
+        let (name_style, shortcut_style) = if selection == Some(idx) {
+            (&screen.skin.status_bold, &screen.skin.status_bold)
+        } else {
+            (&screen.skin.status_normal, &screen.skin.status_code)
+        };
+        queue!(w, cursor::MoveTo(0, (top + row) as u16))?;
+        name_style.queue(w, format!(" {:<w$} ", verb.invocation.name, w = name_width))?;
+        if shortcut_width > 0 {
+            let shortcut = verb.shortcut.as_ref().map_or("", |s| s.as_str());
+            shortcut_style.queue(w, format!("{:<w$} ", shortcut, w = shortcut_width))?;
+        }
+    }
+    queue!(w, SetBackgroundColor(Color::Reset))?;
+    Ok(())
+}
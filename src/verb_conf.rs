@@ -1,7 +1,11 @@
 use crossterm::input::KeyEvent;
 use regex::Regex;
 
-use crate::errors::ConfError;
+use crate::{
+    errors::ConfError,
+    selection_type::{NameCondition, SelectionType},
+    verbs,
+};
 
 /// what's needed to handle a verb
 #[derive(Debug)]
@@ -9,11 +13,18 @@ pub struct VerbConf {
     pub shortcut: Option<String>,
     pub invocation: String,
     pub key: Option<KeyEvent>,
+    pub chord: Option<(KeyEvent, KeyEvent)>,
     pub execution: String,
     pub description: Option<String>,
     pub from_shell: Option<bool>,
     pub leave_broot: Option<bool>,
-    pub confirm: Option<bool>,
+    pub confirm: Option<bool>, // whether the execution must be confirmed with `y`
+    pub dry_run: Option<bool>, // whether the command is only displayed instead of being executed
+    pub capture: Option<bool>, // whether the output is displayed in broot (when not leaving it)
+    pub run_async: Option<bool>, // whether the program runs in the background (when not leaving broot)
+    pub apply_to: Option<SelectionType>,
+    pub name_condition: NameCondition, // the extensions or globs of the names of the files it applies to
+    pub env: Vec<(String, String)>, // variables added to the environment of the program
 }
 
 fn bad_key(raw: &str) -> Result<KeyEvent, ConfError> {
@@ -75,6 +86,56 @@ pub fn parse_key(raw: &str) -> Result<KeyEvent, ConfError> {
     }
 }
 
+/// parse a sequence of two keys separated by a space, eg "g s"
+/// or "ctrl-g b". Contrary to single keys, chords may use
+/// plain chars, but their keys can't contain spaces.
+pub fn parse_chord(raw: &str) -> Result<(KeyEvent, KeyEvent), ConfError> {
+    let keys = raw
+        .split_whitespace()
+        .map(|part| {
+            let mut chars = part.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(KeyEvent::Char(c)),
+                _ => parse_key(part),
+            }
+        })
+        .collect::<Result<Vec<KeyEvent>, ConfError>>()?;
+    match keys.as_slice() {
+        [first, second] => Ok((*first, *second)),
+        _ => Err(ConfError::InvalidChord {
+            raw: raw.to_owned(),
+        }),
+    }
+}
+
+impl VerbConf {
+    /// tell whether the verb applies to some selections only
+    pub fn is_conditional(&self) -> bool {
+        self.apply_to.map_or(false, |t| t != SelectionType::Any) || !self.name_condition.is_empty()
+    }
+}
+
+/// check that no chord of the verbs is unreachable: a chord can't be
+/// defined twice (unless the first verb only applies to some selections),
+/// and its first key can't be the key of a verb, as this key would then
+/// always start the chord
+pub fn check_chord_conflicts(verbs: &[VerbConf]) -> Result<(), ConfError> {
+    for (idx, verb) in verbs.iter().enumerate() {
+        if let Some(chord) = verb.chord {
+            let conflicting = verbs.iter().enumerate().any(|(other_idx, other)| {
+                (other_idx < idx && other.chord == Some(chord) && !other.is_conditional())
+                    || other.key == Some(chord.0)
+            });
+            if conflicting {
+                return Err(ConfError::ConflictingChord {
+                    raw: verbs::chord_desc(chord),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod key_parsing_tests {
 
@@ -110,4 +171,59 @@ mod key_parsing_tests {
         check_ok("ctrl Q", Ctrl('q'));
         check_ok("^Q", Ctrl('q'));
     }
+
+    #[test]
+    fn check_chord_parsing() {
+        assert_eq!(parse_chord("g s").unwrap(), (Char('g'), Char('s')));
+        assert_eq!(parse_chord("ctrl-g B").unwrap(), (Ctrl('g'), Char('B')));
+        assert_eq!(parse_chord("F2  alt-x").unwrap(), (F(2), Alt('x')));
+        assert!(parse_chord("g").is_err(), "should not have parsed a single key");
+        assert!(parse_chord("g s b").is_err(), "should not have parsed three keys");
+    }
+
+    fn verb_conf(key: Option<KeyEvent>, chord: Option<(KeyEvent, KeyEvent)>) -> VerbConf {
+        VerbConf {
+            shortcut: None,
+            invocation: "test".to_string(),
+            key,
+            chord,
+            execution: "echo {file}".to_string(),
+            description: None,
+            from_shell: None,
+            leave_broot: None,
+            confirm: None,
+            dry_run: None,
+            capture: None,
+            run_async: None,
+            apply_to: None,
+            name_condition: NameCondition::default(),
+            env: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn check_chord_conflict_detection() {
+        let verbs = vec![
+            verb_conf(Some(F(2)), None),
+            verb_conf(None, Some((Char('g'), Char('g')))),
+            verb_conf(None, Some((Char('g'), Char('s')))),
+        ];
+        assert!(check_chord_conflicts(&verbs).is_ok());
+        let verbs = vec![
+            verb_conf(None, Some((Char('d'), Char('d')))),
+            verb_conf(None, Some((Char('d'), Char('d')))),
+        ];
+        assert!(check_chord_conflicts(&verbs).is_err(), "a chord was defined twice");
+        let mut verbs = vec![
+            verb_conf(None, Some((Char('d'), Char('d')))),
+            verb_conf(None, Some((Char('d'), Char('d')))),
+        ];
+        verbs[0].apply_to = Some(SelectionType::File);
+        assert!(check_chord_conflicts(&verbs).is_ok(), "the second verb applies to directories");
+        let verbs = vec![
+            verb_conf(Some(F(2)), None),
+            verb_conf(None, Some((F(2), Char('x')))),
+        ];
+        assert!(check_chord_conflicts(&verbs).is_err(), "F2 can't start a chord");
+    }
 }
@@ -1,6 +1,13 @@
 use crossterm::input::KeyEvent;
+use regex::Regex;
 
-use crate::{conf::Conf, permissions, verbs::Verb};
+use crate::{
+    conf::Conf,
+    permissions,
+    selection_type::Selection,
+    verbs::{self, Verb},
+    xattrs,
+};
 
 /// Provide access to the verbs:
 /// - the built-in ones
@@ -26,13 +33,13 @@ impl VerbStore {
     }
     fn add_builtin(
         &mut self,
-        name: &str,
+        invocation: &str,
         key: Option<KeyEvent>,
         shortcut: Option<String>,
         description: &str,
     ) {
         self.verbs
-            .push(Verb::create_builtin(name, key, shortcut, description));
+            .push(Verb::create_builtin(invocation, key, shortcut, description));
     }
     pub fn init(&mut self, conf: &Conf) {
         // we first add the verbs coming from configuration, as
@@ -49,7 +56,18 @@ impl VerbStore {
                 verb_conf.leave_broot.unwrap_or(true),
                 verb_conf.confirm.unwrap_or(false),
             ) {
-                Ok(v) => {
+                Ok(mut v) => {
+                    if let Some(selection_condition) = verb_conf.apply_to {
+                        v.selection_condition = selection_condition;
+                    }
+                    v.name_condition = verb_conf.name_condition.clone();
+                    if let Some(chord) = verb_conf.chord {
+                        v.set_chord(chord);
+                    }
+                    v.env = verb_conf.env.clone();
+                    v.capture = verb_conf.capture.unwrap_or(false);
+                    v.run_async = verb_conf.run_async.unwrap_or(false);
+                    v.dry_run = verb_conf.dry_run.unwrap_or(false);
                     self.verbs.push(v);
                 }
                 Err(e) => {
@@ -59,10 +77,16 @@ impl VerbStore {
         }
         self.add_builtin(
             "back",
-            None, // esc is mapped in commands.rs
+            Some(KeyEvent::CtrlLeft), // esc is also mapped in commands.rs
             None,
             "revert to the previous state (mapped to *esc*)",
         );
+        self.add_builtin(
+            "bookmark {name}",
+            None,
+            Some("bm".to_string()),
+            "save the selected directory as a bookmark",
+        );
         self.verbs.push(
             Verb::create_external(
                 "cd",
@@ -76,6 +100,38 @@ impl VerbStore {
             )
             .unwrap(),
         );
+        #[cfg(unix)]
+        self.add_builtin(
+            "chmod {mode}",
+            None,
+            None,
+            "change the permissions of the marked files, or of the selection (eg `755` or `u+x`)",
+        );
+        #[cfg(unix)]
+        self.add_builtin(
+            "chown {owner}",
+            None,
+            None,
+            "change the owner of the marked files, or of the selection (eg `john` or `john:staff`)",
+        );
+        self.add_builtin(
+            "copy_line",
+            None,
+            None,
+            "copy the selected line of the preview to the clipboard",
+        );
+        self.add_builtin(
+            "copy_name",
+            None,
+            None,
+            "copy the name of the selection to the clipboard",
+        );
+        self.add_builtin(
+            "copy_path",
+            None,
+            Some("cpp".to_string()),
+            "copy the path of the selection to the clipboard",
+        );
         self.verbs.push(
             Verb::create_external(
                 "cp {newpath}",
@@ -90,23 +146,107 @@ impl VerbStore {
             .unwrap(),
         );
         self.add_builtin(
-            "focus",
+            "dates_format {format}",
+            None,
+            None,
+            "set how dates are written: `relative` or a strftime format (eg `%d/%m %H:%M`)",
+        );
+        self.add_builtin(
+            "dates_kind {kind}",
+            None,
+            None,
+            "set which date is displayed: `modified`, `created` or `accessed`",
+        );
+        #[cfg(unix)]
+        self.add_builtin(
+            "elevate",
+            None,
+            None,
+            "quit and retry the refused `:chmod` or `:chown` with the `elevation_command`",
+        );
+        self.add_builtin(
+            "export_html {path}",
+            None,
+            None,
+            "write the displayed tree as a HTML page (*tab* completes the path)",
+        );
+        self.add_builtin(
+            "extract",
+            None,
+            None,
+            "extract the selected archive, or entry of an archive, next to the archive",
+        );
+        self.add_builtin(
+            "filesystems",
+            None,
+            Some("fs".to_string()),
+            "list the mounted filesystems with their used and free space",
+        );
+        self.add_builtin(
+            "filter_strictness",
+            None,
+            Some("strict".to_string()),
+            "toggle how the directories leading to the matches are shown (mixed, dimmed, strict)",
+        );
+        let mut focus = Verb::create_builtin(
+            "focus {path}",
             None, // enter
-            Some("goto".to_string()),
-            "display the directory (mapped to *enter* in tree)",
+            None,
+            "display the selected directory (mapped to *enter* in tree) or the given one",
         );
+        // the path is optional: without it, the selected directory is focused
+        focus.args_parser = Some(Regex::new(r"^(?P<path>.*)$").unwrap());
+        self.verbs.push(focus);
         self.add_builtin(
             "focus_root",
             None,
             None,
             "focus `/`",
         );
+        self.add_builtin(
+            "forward",
+            Some(KeyEvent::CtrlRight),
+            None,
+            "go back to the state left with `:back`",
+        );
+        self.add_builtin(
+            "git_diff",
+            None,
+            Some("diff".to_string()),
+            "display the changes of the selection against the HEAD of its git repository",
+        );
+        self.add_builtin(
+            "goto {name}",
+            None,
+            None,
+            "display the bookmarked directory (*tab* completes the name)",
+        );
+        if cfg!(unix) {
+            self.add_builtin(
+                "hard_links",
+                None,
+                Some("hl".to_string()),
+                "search the tree for the other hard links of the selected file",
+            );
+        }
         self.add_builtin(
             "help",
             Some(KeyEvent::F(1)), // note: some terminals intercept the F1 key
             Some("?".to_string()),
             "display broot's help",
         );
+        self.add_builtin(
+            "jobs",
+            None,
+            None,
+            "list the programs running in the background, launched by async verbs",
+        );
+        self.add_builtin(
+            "kill_job",
+            None,
+            None,
+            "kill the job selected in the jobs screen",
+        );
         self.add_builtin(
             "line_down",
             Some(KeyEvent::Down),
@@ -145,6 +285,12 @@ impl VerbStore {
             )
             .unwrap(),
         );
+        self.add_builtin(
+            "newer {path}",
+            None,
+            None,
+            "show only the files modified after the given one (*esc* to remove)",
+        );
         self.add_builtin(
             "open_stay",
             None, // default mapping directly handled in commands#add_event
@@ -157,6 +303,12 @@ impl VerbStore {
             None,
             "open file or directory according to OS settings (quit broot)",
         );
+        self.add_builtin(
+            "open_in_fm",
+            None,
+            Some("fm".to_string()),
+            "show the selection in the system file manager",
+        );
         self.add_builtin(
             "page_down",
             Some(KeyEvent::PageDown),
@@ -175,6 +327,12 @@ impl VerbStore {
             Some("p".to_string()),
             "move to the parent directory",
         );
+        self.add_builtin(
+            "preview",
+            None,
+            Some("pv".to_string()),
+            "display the content of the selected file, whose lines can be searched",
+        );
         self.add_builtin(
             "print_path",
             None,
@@ -193,11 +351,17 @@ impl VerbStore {
             Some("q".to_string()),
             "quit the application",
         );
+        self.add_builtin(
+            "redo",
+            None,
+            None,
+            "go again where `:undo` came from",
+        );
         self.add_builtin(
             "refresh",
             Some(KeyEvent::F(5)),
             None,
-            "refresh tree and clear size cache",
+            "refresh tree and clear the size and .gitignore caches",
         );
         self.verbs.push(
             Verb::create_external(
@@ -208,34 +372,90 @@ impl VerbStore {
                 None,
                 false,
                 false, // doesn't leave broot
-                false,
+                true,  // must be confirmed
             )
             .unwrap(),
         );
+        self.add_builtin(
+            "reveal",
+            None,
+            Some("rv".to_string()),
+            "open the parent directory in the system file manager, with the selection selected (same as open_in_fm)",
+        );
+        self.add_builtin(
+            "tar {path}",
+            None,
+            None,
+            "pack the marked files, or the selection, in a tar archive (eg `backup.tar.gz`)",
+        );
+        self.add_builtin(
+            "toggle_counts",
+            None,
+            Some("counts".to_string()),
+            "toggle showing the numbers of children of directories",
+        );
         self.add_builtin(
             "toggle_dates",
             None,
             Some("dates".to_string()),
             "toggle showing last modified dates",
         );
+        self.add_builtin(
+            "toggle_depth_first",
+            None,
+            Some("dfs".to_string()),
+            "toggle searching depth first",
+        );
         self.add_builtin(
             "toggle_files",
             None,
             Some("files".to_string()),
             "toggle showing files (or just folders)",
         );
+        self.add_builtin(
+            "toggle_follow_symlinks",
+            None,
+            Some("links".to_string()),
+            "toggle entering symlinks to directories (never, when not searching, always)",
+        );
         self.add_builtin(
             "toggle_git_ignore",
             None,
             Some("gi".to_string()),
             "toggle use of .gitignore",
         );
+        self.add_builtin(
+            "toggle_grouping",
+            None,
+            Some("group".to_string()),
+            "toggle listing the matches of a search under their directory",
+        );
         self.add_builtin(
             "toggle_hidden",
             None,
             Some("h".to_string()),
             "toggle showing hidden files",
         );
+        if cfg!(unix) {
+            self.add_builtin(
+                "toggle_inodes",
+                None,
+                Some("inodes".to_string()),
+                "toggle showing inode numbers and hard link counts",
+            );
+        }
+        self.add_builtin(
+            "toggle_link_target_sizes",
+            None,
+            Some("lts".to_string()),
+            "toggle showing the sizes of symlinks' targets",
+        );
+        self.add_builtin(
+            "toggle_mark",
+            None,
+            Some("mark".to_string()),
+            "mark or unmark the selected file",
+        );
         if permissions::supported() {
             self.add_builtin(
                 "toggle_perm",
@@ -244,6 +464,12 @@ impl VerbStore {
                 "toggle showing file permissions",
             );
         }
+        self.add_builtin(
+            "toggle_show_all",
+            None,
+            Some("all".to_string()),
+            "toggle fully expanding the tree instead of fitting it to the screen",
+        );
         self.add_builtin(
             "toggle_sizes",
             None,
@@ -256,17 +482,63 @@ impl VerbStore {
             Some("t".to_string()),
             "toggle removing nodes at first level too (default)",
         );
+        self.add_builtin(
+            "toggle_two_rows",
+            None,
+            Some("rows".to_string()),
+            "toggle writing sizes, dates and permissions on a second row",
+        );
+        if xattrs::supported() {
+            self.add_builtin(
+                "toggle_xattr",
+                None,
+                Some("xattr".to_string()),
+                "toggle showing extended attributes and security contexts",
+            );
+        }
+        self.add_builtin(
+            "undo",
+            None,
+            None,
+            "go back to the previous root, pattern or options",
+        );
         self.add_builtin(
             "up_tree",
             None,
             Some("up".to_string()),
             "focus the parent of the current root",
         );
+        self.add_builtin(
+            "write_tree {path}",
+            None,
+            None,
+            "write the displayed tree in a file (*tab* completes the path)",
+        );
+        self.add_builtin(
+            "zip {path}",
+            None,
+            None,
+            "pack the marked files, or the selection, in a zip archive",
+        );
     }
     pub fn search(&self, prefix: &str) -> PrefixSearchResult<&Verb> {
+        self.search_among(prefix, |_| true)
+    }
+    /// search the verb among the ones applying to the selection
+    pub fn search_for(&self, prefix: &str, selection: &Selection) -> PrefixSearchResult<&Verb> {
+        self.search_among(prefix, |verb| verb.applies_to(selection))
+    }
+    fn search_among(
+        &self,
+        prefix: &str,
+        filter: impl Fn(&Verb) -> bool,
+    ) -> PrefixSearchResult<&Verb> {
         let mut found_index = 0;
         let mut nb_found = 0;
         for (index, verb) in self.verbs.iter().enumerate() {
+            if !filter(verb) {
+                continue;
+            }
             if let Some(shortcut) = &verb.shortcut {
                 if shortcut.starts_with(prefix) {
                     if shortcut == prefix {
@@ -291,6 +563,25 @@ impl VerbStore {
             _ => PrefixSearchResult::TooManyMatches,
         }
     }
+    /// return the verbs whose name or shortcut starts with the prefix,
+    /// in the order of the store, for the completion of a verb name
+    pub fn completions(&self, prefix: &str) -> Vec<&Verb> {
+        let mut completions: Vec<&Verb> = Vec::new();
+        for verb in &self.verbs {
+            let name = &verb.invocation.name;
+            if name.is_empty() || completions.iter().any(|v| &v.invocation.name == name) {
+                continue; // a verb overriding another one is proposed once
+            }
+            let shortcut_matches = verb
+                .shortcut
+                .as_ref()
+                .map_or(false, |shortcut| shortcut.starts_with(prefix));
+            if shortcut_matches || name.starts_with(prefix) {
+                completions.push(verb);
+            }
+        }
+        completions
+    }
     /// return the index of the verb having the long name. This function is meant
     /// for internal access when it's sure it can't failed (i.e. for a builtin)
     /// It looks for verbs by name, starting from the builtins, to
@@ -314,4 +605,48 @@ impl VerbStore {
         }
         None
     }
+    /// return the verb to execute on the selection when the one at the
+    /// index was triggered by its key or chord: the first one having
+    /// the same key or chord and applying to the selection, if any
+    pub fn triggered_for(&self, index: usize, selection: &Selection) -> Option<&Verb> {
+        let triggered = &self.verbs[index];
+        self.verbs[index..].iter().find(|verb| {
+            let same_trigger = (triggered.key.is_some() && verb.key == triggered.key)
+                || (triggered.chord.is_some() && verb.chord == triggered.chord);
+            same_trigger && verb.applies_to(selection)
+        })
+    }
+    /// return the index of the verb which is triggered by the given chord, if any
+    pub fn index_of_chord(&self, first: KeyEvent, second: KeyEvent) -> Option<usize> {
+        self.verbs
+            .iter()
+            .position(|verb| verb.chord == Some((first, second)))
+    }
+    /// tell whether some verb is triggered by a chord starting with the key
+    pub fn starts_chord(&self, key: KeyEvent) -> bool {
+        self.verbs
+            .iter()
+            .any(|verb| verb.chord.map_or(false, |chord| chord.0 == key))
+    }
+    /// build the hint displayed while a chord is pending, listing the
+    /// keys which may complete it
+    pub fn chord_hint(&self, first: KeyEvent) -> String {
+        let completions: Vec<String> = self
+            .verbs
+            .iter()
+            .filter_map(|verb| match verb.chord {
+                Some((f, second)) if f == first => Some(format!(
+                    "*{}*: {}",
+                    verbs::chord_desc((f, second)),
+                    if verb.invocation.name.is_empty() {
+                        &verb.execution
+                    } else {
+                        &verb.invocation.name
+                    },
+                )),
+                _ => None,
+            })
+            .collect();
+        format!("{} (*esc* to cancel)", completions.join(", "))
+    }
 }
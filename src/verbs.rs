@@ -1,10 +1,11 @@
 /// Verbs are the engines of broot commands, and apply
 /// - to the selected file (if user-defined, then must contain {file}, {parent} or {directory})
+///   or to the tree ({root}, {git-root})
 /// - to the current app state
 use std::{
     collections::HashMap,
+    ffi::OsString,
     fs::OpenOptions,
-    io::Write,
     path::{Path, PathBuf},
 };
 
@@ -15,10 +16,16 @@ use regex::{self, Captures, Regex};
 use crate::{
     app_context::AppContext,
     app_state::AppStateCmdResult,
+    commands::Command,
     errors::{ConfError, ProgramError},
     external,
     io::W,
+    jobs,
+    os_names,
+    output_states::OutputState,
     screens::Screen,
+    selection_info,
+    selection_type::{NameCondition, Selection, SelectionType},
     status::Status,
     verb_invocation::VerbInvocation,
 };
@@ -33,6 +40,7 @@ use crate::{
 pub struct Verb {
     pub invocation: VerbInvocation, // how the verb is supposed to be called, may be empty
     pub key: Option<KeyEvent>,
+    pub chord: Option<(KeyEvent, KeyEvent)>, // two keys which, typed in sequence, trigger the verb
     pub key_desc: String, // a description of the optional keyboard key triggering that verb
     pub args_parser: Option<Regex>,
     pub shortcut: Option<String>,    // a shortcut, eg "c"
@@ -40,7 +48,13 @@ pub struct Verb {
     pub description: Option<String>, // a description for the user
     pub from_shell: bool, // whether it must be launched from the parent shell (eg because it's a shell function)
     pub leave_broot: bool, // only defined for external
-    pub confirm: bool,
+    pub confirm: bool, // whether the command must be confirmed by the user before execution
+    pub dry_run: bool, // whether the command is only displayed, not executed
+    pub capture: bool, // whether the output of the program is displayed in broot
+    pub run_async: bool, // whether the program runs in the background, as a job
+    pub selection_condition: SelectionType, // the kind of selection the verb applies to
+    pub name_condition: NameCondition, // the names of the files the verb applies to
+    pub env: Vec<(String, String)>, // variables, maybe with {placeholders}, added to the environment of the program
 }
 
 lazy_static! {
@@ -48,18 +62,100 @@ lazy_static! {
 }
 
 /// build a human description of a key event
-fn key_event_desc(key: &Option<KeyEvent>) -> String {
+pub fn key_event_desc(key: &Option<KeyEvent>) -> String {
     match key {
         Some(key) => match key {
             KeyEvent::F(d) => format!("F{}", d),
             KeyEvent::Ctrl(c) => format!("^{}", c),
             KeyEvent::Alt(c) => format!("alt-{}", c),
+            KeyEvent::Char(c) => c.to_string(),
             _ => format!("{:?}", key),
         }
         None => "".to_owned(),
     }
 }
 
+/// build a human description of a chord, eg "g s"
+pub fn chord_desc(chord: (KeyEvent, KeyEvent)) -> String {
+    format!(
+        "{} {}",
+        key_event_desc(&Some(chord.0)),
+        key_event_desc(&Some(chord.1)),
+    )
+}
+
+/// a verb with the selection and the arguments it applies to,
+/// waiting for the user to confirm its execution.
+/// An external verb is executed by the execution itself. A built-in
+/// verb (eg `:chmod` on the marked files) is executed by the state
+/// which asked for the confirmation
+#[derive(Debug, Clone)]
+pub struct VerbExecution {
+    verb: Verb,
+    file: PathBuf,
+    root: PathBuf,
+    line: usize,
+    args: Option<String>,
+    paths: Vec<PathBuf>, // the marked paths a built-in verb applies to
+}
+
+impl VerbExecution {
+    /// prepare the confirmation of a built-in verb applied to
+    /// several paths
+    pub fn builtin(verb: &Verb, args: &Option<String>, paths: Vec<PathBuf>) -> VerbExecution {
+        VerbExecution {
+            verb: verb.clone(),
+            file: PathBuf::new(),
+            root: PathBuf::new(),
+            line: 1,
+            args: args.clone(),
+            paths,
+        }
+    }
+    fn is_builtin(&self) -> bool {
+        self.verb.execution.starts_with(':')
+    }
+    /// the command which would be executed, as displayed to the user
+    pub fn command(&self) -> String {
+        if self.is_builtin() {
+            let marked = match self.paths.len() {
+                1 => "the marked file".to_string(),
+                n => format!("the {} marked files", n),
+            };
+            format!("{} {} on {}", self.verb.execution, self.args(), marked)
+        } else {
+            self.verb.shell_exec_string(&self.file, &self.root, self.line, &self.args)
+        }
+    }
+    /// the execution of the built-in verb, eg ":chmod" (or the
+    /// exec pattern of an external one)
+    pub fn execution(&self) -> &str {
+        &self.verb.execution
+    }
+    pub fn args(&self) -> &str {
+        self.args.as_ref().map_or("", |s| s.trim())
+    }
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+    /// tell whether the execution may change files while broot
+    /// goes on, so that the tree must be refreshed after it
+    pub fn may_change_files(&self) -> bool {
+        self.is_builtin() || (!self.verb.from_shell && !self.verb.leave_broot)
+    }
+    /// execute an external verb. The built-in ones are executed
+    /// by the application states
+    pub fn execute(&self, con: &AppContext) -> Result<AppStateCmdResult, ProgramError> {
+        if self.is_builtin() {
+            return Ok(AppStateCmdResult::DisplayError(format!(
+                "{} can't be executed here",
+                self.verb.execution,
+            )));
+        }
+        self.verb.execute(&self.file, &self.root, self.line, &self.args, con)
+    }
+}
+
 pub trait VerbExecutor {
     fn execute_verb(
         &mut self,
@@ -81,10 +177,25 @@ fn path_to_string(path: &Path, for_shell: bool) -> String {
     if for_shell {
         external::escape_for_shell(path)
     } else {
-        path.to_string_lossy().to_string()
+        os_names::escape(path.as_os_str())
     }
 }
 
+/// build the map of the braced parts which are replaced by a path
+fn path_map(file: &Path, root: &Path) -> HashMap<String, PathBuf> {
+    let mut map = HashMap::new();
+    let parent = file.parent().unwrap_or(file); // when there's no parent... we take file
+    let dir = if file.is_dir() { file } else { parent };
+    map.insert("file".to_string(), file.to_path_buf());
+    map.insert("parent".to_string(), parent.to_path_buf());
+    map.insert("directory".to_string(), dir.to_path_buf());
+    map.insert("root".to_string(), root.to_path_buf());
+    if let Some(git_root) = selection_info::git_root(dir) {
+        map.insert("git-root".to_string(), git_root);
+    }
+    map
+}
+
 impl Verb {
     /// build a verb using standard configurable behavior.
     /// "external" means not "built-in".
@@ -108,6 +219,7 @@ impl Verb {
             invocation,
             key_desc: key_event_desc(&key),
             key,
+            chord: None,
             args_parser,
             shortcut,
             execution,
@@ -115,34 +227,65 @@ impl Verb {
             from_shell,
             leave_broot,
             confirm,
+            dry_run: false,
+            capture: false,
+            run_async: false,
+            selection_condition: SelectionType::Any,
+            name_condition: NameCondition::default(),
+            env: Vec::new(),
         })
     }
 
     /// built-ins are verbs offering a logic other than the execution
-    ///  based on exec_pattern. They mostly modify the appstate
+    ///  based on exec_pattern. They mostly modify the appstate.
+    /// The invocation may declare arguments (eg "goto {name}")
     pub fn create_builtin(
-        name: &str,
+        invocation_str: &str,
         key: Option<KeyEvent>,
         shortcut: Option<String>,
         description: &str,
     ) -> Verb {
+        let invocation = VerbInvocation::from(invocation_str);
+        let args_parser = invocation
+            .args
+            .as_ref()
+            .map(|args| make_invocation_args_regex(&args).expect("invalid built-in invocation"));
+        let execution = format!(":{}", &invocation.name);
         Verb {
-            invocation: VerbInvocation {
-                name: name.to_string(),
-                args: None,
-            },
+            invocation,
             key_desc: key_event_desc(&key),
             key,
-            args_parser: None,
+            chord: None,
+            args_parser,
             shortcut,
-            execution: format!(":{}", name),
+            execution,
             description: Some(description.to_string()),
             from_shell: false,
             leave_broot: true, // ignored
             confirm: false,    // ignored
+            dry_run: false,    // ignored
+            capture: false,    // ignored
+            run_async: false,  // ignored
+            selection_condition: SelectionType::Any,
+            name_condition: NameCondition::default(),
+            env: Vec::new(),
         }
     }
 
+    /// make the verb also triggered by a sequence of two keys
+    pub fn set_chord(&mut self, chord: (KeyEvent, KeyEvent)) {
+        if !self.key_desc.is_empty() {
+            self.key_desc.push_str(", ");
+        }
+        self.key_desc.push_str(&chord_desc(chord));
+        self.chord = Some(chord);
+    }
+
+    /// tell whether the verb may be executed on the selection
+    pub fn applies_to(&self, selection: &Selection) -> bool {
+        selection.respects(self.selection_condition, &self.name_condition)
+    }
+
     /// Assuming the verb has been matched, check whether the arguments
     /// are OK according to the regex. Return none when there's no problem
     /// and return the error to display if arguments don't match
@@ -172,19 +315,23 @@ impl Verb {
     fn replacement_map(
         &self,
         file: &Path,
+        root: &Path,
+        line: usize,
         args: &Option<String>,
         for_shell: bool,
     ) -> HashMap<String, String> {
-        let mut map = HashMap::new();
-        // first we add the replacements computed from the given path
-        let parent = file.parent().unwrap_or(file); // when there's no parent... we take file
-        let file_str = path_to_string(file, for_shell);
-        let parent_str = path_to_string(parent, for_shell);
-        map.insert("file".to_string(), file_str.to_string());
-        map.insert("parent".to_string(), parent_str.to_string());
-        let dir_str = if file.is_dir() { file_str } else { parent_str };
-        map.insert("directory".to_string(), dir_str.to_string());
-        // then the ones computed from the user input
+        // first we add the replacements computed from the given path and the tree
+        let mut map: HashMap<String, String> = path_map(file, root)
+            .into_iter()
+            .map(|(name, path)| {
+                let replacement = path_to_string(&path, for_shell);
+                (name, replacement)
+            })
+            .collect();
+        // the line selected in the preview, the first one elsewhere
+        map.insert("line".to_string(), line.to_string());
+        // then the ones computed from the user input, which may
+        // replace the previous ones
         if let Some(args) = args {
             if let Some(r) = &self.args_parser {
                 if let Some(input_cap) = r.captures(&args) {
@@ -207,6 +354,8 @@ impl Verb {
         w: &mut W,
         task: Option<&'static str>,
         path: PathBuf,
+        root: &Path,
+        line: usize,
         invocation: &VerbInvocation,
         screen: &Screen,
     ) -> Result<(), ProgramError> {
@@ -223,7 +372,7 @@ impl Verb {
                 );
                 Composite::from_inline(&markdown)
             } else {
-                verb_description = self.shell_exec_string(&path, &invocation.args);
+                verb_description = self.shell_exec_string(&path, root, line, &invocation.args);
                 mad_inline!(
                     "Hit *enter* to **$0**: `$1`",
                     &self.invocation.name,
@@ -239,26 +388,58 @@ impl Verb {
     }
 
     /// build the cmd result for a verb defined with an exec pattern.
-    /// Calling this function on a built-in doesn't make sense
+    /// Calling this function on a built-in doesn't make sense.
+    /// A dry run only displays the command, and a verb requiring
+    /// a confirmation is only executed once the user gave it
     pub fn to_cmd_result(
         &self,
         file: &Path,
+        root: &Path,
+        line: usize,
         args: &Option<String>,
         _screen: &mut Screen,
         con: &AppContext,
+    ) -> Result<AppStateCmdResult, ProgramError> {
+        if self.dry_run || con.launch_args.dry_run {
+            return Ok(AppStateCmdResult::DisplayMessage(format!(
+                "Dry run, not executed: `{}`",
+                self.shell_exec_string(file, root, line, args),
+            )));
+        }
+        if self.confirm {
+            return Ok(AppStateCmdResult::AskConfirmation(Box::new(VerbExecution {
+                verb: self.clone(),
+                file: file.to_path_buf(),
+                root: root.to_path_buf(),
+                line,
+                args: args.clone(),
+                paths: Vec::new(),
+            })));
+        }
+        self.execute(file, root, line, args, con)
+    }
+
+    /// execute the verb, without asking for a confirmation
+    fn execute(
+        &self,
+        file: &Path,
+        root: &Path,
+        line: usize,
+        args: &Option<String>,
+        con: &AppContext,
     ) -> Result<AppStateCmdResult, ProgramError> {
         Ok(if self.from_shell {
             if let Some(ref export_path) = con.launch_args.cmd_export_path {
                 // Broot was probably launched as br.
                 // the whole command is exported in the passed file
                 let f = OpenOptions::new().append(true).open(export_path)?;
-                writeln!(&f, "{}", self.shell_exec_string(file, args))?;
+                os_names::write_line(&mut &f, &self.shell_exec_os_string(file, root, line, args))?;
                 AppStateCmdResult::Quit
             } else if let Some(ref export_path) = con.launch_args.file_export_path {
                 // old version of the br function: only the file is exported
                 // in the passed file
                 let f = OpenOptions::new().append(true).open(export_path)?;
-                writeln!(&f, "{}", file.to_string_lossy())?;
+                os_names::write_line(&mut &f, file.as_os_str())?;
                 AppStateCmdResult::Quit
             } else {
                 AppStateCmdResult::DisplayError(
@@ -266,9 +447,38 @@ impl Verb {
                 )
             }
         } else {
-            let launchable = external::Launchable::program(self.exec_token(file, args))?;
+            let launchable = external::Launchable::program_with_env(
+                self.exec_token(file, root, line, args),
+                self.exec_env(file, root, line, args),
+            )?;
             if self.leave_broot {
                 AppStateCmdResult::from(launchable)
+            } else if self.run_async {
+                info!("Executing as a job, launchable {:?}", launchable);
+                match launchable.spawn() {
+                    Ok(Some(child)) => {
+                        let id = jobs::add(self.shell_exec_string(file, root, line, args), child);
+                        debug!("job {} started", id);
+                        AppStateCmdResult::Keep
+                    }
+                    Ok(None) => AppStateCmdResult::RefreshState,
+                    Err(e) => {
+                        warn!("launchable failed : {:?}", e);
+                        AppStateCmdResult::DisplayError(e.to_string())
+                    }
+                }
+            } else if self.capture {
+                info!("Executing with capture, launchable {:?}", launchable);
+                match launchable.output() {
+                    Ok(output) => AppStateCmdResult::NewState(
+                        Box::new(OutputState::new(self.shell_exec_string(file, root, line, args), output)),
+                        Command::new(),
+                    ),
+                    Err(e) => {
+                        warn!("launchable failed : {:?}", e);
+                        AppStateCmdResult::DisplayError(e.to_string())
+                    }
+                }
             } else {
                 info!("Executing not leaving, launchable {:?}", launchable);
                 let execution = launchable.execute();
@@ -288,41 +498,118 @@ impl Verb {
 
     /// build the token which can be used to launch en executable.
     /// This doesn't make sense for a built-in.
-    pub fn exec_token(&self, file: &Path, args: &Option<String>) -> Vec<String> {
-        let map = self.replacement_map(file, args, false);
+    pub fn exec_token(
+        &self,
+        file: &Path,
+        root: &Path,
+        line: usize,
+        args: &Option<String>,
+    ) -> Vec<OsString> {
+        let map = self.replacement_map(file, root, line, args, false);
+        let paths = path_map(file, root);
         self.execution
             .split_whitespace()
-            .map(|token| {
-                GROUP
-                    .replace_all(token, |ec: &Captures<'_>| do_exec_replacement(ec, &map))
-                    .to_string()
+            .map(|token| replace_groups(token, &map, &paths, false))
+            .collect()
+    }
+
+    /// build the shell command written in the export file of `br`.
+    /// Unlike shell_exec_string, it contains the raw paths.
+    fn shell_exec_os_string(
+        &self,
+        file: &Path,
+        root: &Path,
+        line: usize,
+        args: &Option<String>,
+    ) -> OsString {
+        let map = self.replacement_map(file, root, line, args, true);
+        let paths = path_map(file, root);
+        replace_groups(&self.execution, &map, &paths, true)
+    }
+
+    /// build the variables added to the environment of the launched
+    /// executable, with their placeholders replaced
+    pub fn exec_env(
+        &self,
+        file: &Path,
+        root: &Path,
+        line: usize,
+        args: &Option<String>,
+    ) -> Vec<(String, String)> {
+        if self.env.is_empty() {
+            return Vec::new();
+        }
+        let map = self.replacement_map(file, root, line, args, false);
+        self.env
+            .iter()
+            .map(|(name, value)| {
+                let value = GROUP
+                    .replace_all(value, |ec: &Captures<'_>| do_exec_replacement(ec, &map))
+                    .to_string();
+                (name.clone(), value)
             })
             .collect()
     }
 
-    /// build a shell compatible command, with escapings
-    pub fn shell_exec_string(&self, file: &Path, args: &Option<String>) -> String {
-        let map = self.replacement_map(file, args, true);
+    /// build a shell compatible command, with escapings, to be displayed.
+    /// The command written for the shell is built with the raw paths.
+    pub fn shell_exec_string(
+        &self,
+        file: &Path,
+        root: &Path,
+        line: usize,
+        args: &Option<String>,
+    ) -> String {
+        let map = self.replacement_map(file, root, line, args, true);
         GROUP
             .replace_all(&self.execution, |ec: &Captures<'_>| {
                 do_exec_replacement(ec, &map)
             })
             .to_string()
             .split_whitespace()
-            .map(|token| {
-                let path = Path::new(token);
-                if path.exists() {
-                    if let Some(path) = path.to_str() {
-                        return path.to_string();
-                    }
-                }
-                token.to_string()
-            })
-            .collect::<Vec<String>>()
+            .collect::<Vec<&str>>()
             .join(" ")
     }
 }
 
+/// replace the braced parts of a part of the execution pattern.
+/// A part which is just a path is replaced by the raw path (escaped
+/// for the shell if required), so that names which aren't valid
+/// UTF-8 reach the program unchanged
+fn replace_groups(
+    pattern: &str,
+    replacement_map: &HashMap<String, String>,
+    paths: &HashMap<String, PathBuf>,
+    for_shell: bool,
+) -> OsString {
+    let mut replaced = OsString::new();
+    let mut end = 0;
+    for ec in GROUP.captures_iter(pattern) {
+        let group = ec.get(0).unwrap();
+        replaced.push(&pattern[end..group.start()]);
+        let name = ec.get(1).unwrap().as_str();
+        match paths.get(name) {
+            // the path may have been replaced by an invocation argument
+            Some(path)
+                if ec.get(2).is_none()
+                    && replacement_map.get(name) == Some(&path_to_string(path, for_shell)) =>
+            {
+                if for_shell {
+                    replaced.push(external::escape_os_for_shell(path));
+                } else {
+                    replaced.push(path);
+                }
+            }
+            _ => {
+                replaced.push(do_exec_replacement(&ec, replacement_map));
+            }
+        }
+        end = group.end();
+    }
+    replaced.push(&pattern[end..]);
+    replaced
+}
+
 /// replace a group in the execution string, using
 ///  data from the user input and from the selected line
 fn do_exec_replacement(ec: &Captures<'_>, replacement_map: &HashMap<String, String>) -> String {
@@ -405,3 +692,84 @@ mod path_normalize_tests {
         );
     }
 }
+
+#[cfg(test)]
+mod exec_env_tests {
+
+    use std::path::Path;
+
+    use crate::verbs::Verb;
+
+    #[test]
+    fn test_env_placeholders() {
+        let mut verb = Verb::create_external(
+            "edit {line}",
+            None,
+            None,
+            "vi {file}".to_string(),
+            None,
+            false,
+            true,
+            false,
+        ).unwrap();
+        verb.env = vec![
+            ("BROOT_SELECTED".to_string(), "{file}".to_string()),
+            ("BROOT_CONTEXT".to_string(), "{parent}:{line}".to_string()),
+        ];
+        let env = verb.exec_env(
+            Path::new("/no/such/dir/file.txt"),
+            Path::new("/no/such"),
+            1,
+            &Some("42".to_string()),
+        );
+        assert_eq!(env, vec![
+            ("BROOT_SELECTED".to_string(), "/no/such/dir/file.txt".to_string()),
+            ("BROOT_CONTEXT".to_string(), "/no/such/dir:42".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_tree_placeholders() {
+        let verb = Verb::create_external(
+            "edit",
+            None,
+            None,
+            "vi +{line} {file} --root {root}".to_string(),
+            None,
+            false,
+            true,
+            false,
+        ).unwrap();
+        let tokens = verb.exec_token(
+            Path::new("/no/such/dir/file.txt"),
+            Path::new("/no/such"),
+            12,
+            &None,
+        );
+        assert_eq!(tokens, vec!["vi", "+12", "/no/such/dir/file.txt", "--root", "/no/such"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_paths() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+        let verb = Verb::create_external(
+            "backup",
+            None,
+            None,
+            "cp {file} {file}.bak".to_string(),
+            None,
+            false,
+            true,
+            false,
+        ).unwrap();
+        let file = Path::new(OsStr::from_bytes(b"/no/such/caf\xe9"));
+        let tokens = verb.exec_token(file, Path::new("/no/such"), 1, &None);
+        assert_eq!(tokens[1].as_os_str(), file.as_os_str());
+        assert_eq!(tokens[2].as_os_str(), OsStr::from_bytes(b"/no/such/caf\xe9.bak"));
+        assert_eq!(
+            verb.shell_exec_string(file, Path::new("/no/such"), 1, &None),
+            r"cp '/no/such/caf\xE9' '/no/such/caf\xE9'.bak",
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! reading of the extended attributes of files, for the optional
+//! xattr column: whether there are some, and the security context
+//! they carry (the SELinux context on linux, the quarantine flag
+//! on mac).
+//! They're read only for the lines being displayed, and cached.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::{ffi::CString, io, os::unix::ffi::OsStrExt, ptr};
+
+lazy_static! {
+    static ref XATTR_CACHE_MUTEX: Mutex<HashMap<PathBuf, Xattrs>> = Mutex::new(HashMap::new());
+}
+
+/// what the xattr column tells of a file
+#[derive(Debug, Clone, Default)]
+pub struct Xattrs {
+    pub count: usize,            // the number of extended attributes
+    pub context: Option<String>, // the SELinux context, or "quarantine"
+}
+
+pub fn clear_cache() {
+    let mut cache = XATTR_CACHE_MUTEX.lock().unwrap();
+    cache.clear();
+}
+
+/// tell whether extended attributes can be read on this system
+pub fn supported() -> bool {
+    cfg!(any(target_os = "linux", target_os = "macos"))
+}
+
+/// return the extended attributes of the file (not following
+/// symlinks), reading them if they're not in cache
+pub fn of(path: &Path) -> Xattrs {
+    if let Some(xattrs) = XATTR_CACHE_MUTEX.lock().unwrap().get(path) {
+        return xattrs.clone();
+    }
+    let xattrs = read(path).unwrap_or_default();
+    let mut cache = XATTR_CACHE_MUTEX.lock().unwrap();
+    cache.insert(path.to_path_buf(), xattrs.clone());
+    xattrs
+}
+
+#[cfg(target_os = "linux")]
+const CONTEXT_ATTRIBUTE: &str = "security.selinux";
+
+#[cfg(target_os = "macos")]
+const CONTEXT_ATTRIBUTE: &str = "com.apple.quarantine";
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn read(path: &Path) -> io::Result<Xattrs> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let names = list_names(&c_path)?;
+    let context = if names.iter().any(|name| name == CONTEXT_ATTRIBUTE) {
+        if cfg!(target_os = "macos") {
+            Some("quarantine".to_string())
+        } else {
+            value(&c_path, CONTEXT_ATTRIBUTE)
+        }
+    } else {
+        None
+    };
+    Ok(Xattrs {
+        count: names.len(),
+        context,
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn read(_path: &Path) -> Option<Xattrs> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn list(path: &CString, buf: *mut libc::c_char, size: usize) -> isize {
+    libc::llistxattr(path.as_ptr(), buf, size)
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn list(path: &CString, buf: *mut libc::c_char, size: usize) -> isize {
+    libc::listxattr(path.as_ptr(), buf, size, libc::XATTR_NOFOLLOW)
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn get(path: &CString, name: &CString, buf: *mut libc::c_void, size: usize) -> isize {
+    libc::lgetxattr(path.as_ptr(), name.as_ptr(), buf, size)
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn get(path: &CString, name: &CString, buf: *mut libc::c_void, size: usize) -> isize {
+    libc::getxattr(path.as_ptr(), name.as_ptr(), buf, size, 0, libc::XATTR_NOFOLLOW)
+}
+
+/// return the names of the extended attributes of the file. The
+/// system gives them as a list of nul terminated strings.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn list_names(path: &CString) -> io::Result<Vec<String>> {
+    // the first call only gives the size of the list
+    let size = unsafe { list(path, ptr::null_mut(), 0) };
+    if size < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut buf = vec![0u8; size as usize];
+    let size = unsafe { list(path, buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if size < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(buf[..size as usize]
+        .split(|&b| b == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).to_string())
+        .collect())
+}
+
+/// return the value of an attribute, as text
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn value(path: &CString, name: &str) -> Option<String> {
+    let name = CString::new(name).ok()?;
+    let size = unsafe { get(path, &name, ptr::null_mut(), 0) };
+    if size < 0 {
+        return None;
+    }
+    let mut buf = vec![0u8; size as usize];
+    let size = unsafe { get(path, &name, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+    if size < 0 {
+        return None;
+    }
+    let value = String::from_utf8_lossy(&buf[..size as usize]);
+    Some(value.trim_end_matches('\0').to_string())
+}